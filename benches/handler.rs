@@ -0,0 +1,84 @@
+use graphql_router::{
+    GraphqlRequest, GraphqlRouterHandler, HandleError, HttpRequest, RouterRequest, RouterResponse, RouterService,
+};
+
+use core::future::{ready, Ready};
+use core::task;
+use core::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use hyper::StatusCode;
+
+#[derive(Clone)]
+struct Respond;
+
+impl tower_service::Service<RouterRequest> for Respond {
+    type Response = RouterResponse;
+    type Error = HandleError;
+    type Future = Ready<Result<RouterResponse, HandleError>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    #[inline(always)]
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let response = graphql_router::error::router_error(StatusCode::OK, "done", "DONE", req.context);
+        ready(Ok(response))
+    }
+}
+
+fn request() -> RouterRequest {
+    RouterRequest::fake_builder().build().expect("to build request")
+}
+
+fn handler(criterion: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("to build runtime");
+    let boxed = RouterService::new(Respond);
+
+    let mut group = criterion.benchmark_group("handler");
+    group.bench_function("boxed", |bench| {
+        bench.iter(|| runtime.block_on(GraphqlRouterHandler::new(boxed.clone(), request())))
+    });
+    group.bench_function("unboxed", |bench| {
+        bench.iter(|| runtime.block_on(GraphqlRouterHandler::new(Respond, request())))
+    });
+    group.bench_function("unboxed_timeout", |bench| {
+        bench.iter(|| {
+            let handler = GraphqlRouterHandler::new(Respond, request()).with_timeout(Duration::from_secs(1));
+            runtime.block_on(handler)
+        })
+    });
+    group.finish();
+}
+
+fn body() -> bytes::Bytes {
+    let ids = (0..512).map(|id| format!("\"{}\"", id)).collect::<Vec<_>>().join(",");
+    let query = "query Users($ids: [ID!]!) { users(ids: $ids) { id username } }";
+    format!(r#"{{"query":"{}","variables":{{"ids":[{}]}}}}"#, query, ids).into()
+}
+
+fn parse(criterion: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().expect("to build runtime");
+    let body = body();
+
+    //With `simd-json` feature, router parses body using simd-json.
+    let mut group = criterion.benchmark_group("parse");
+    group.bench_function("serde_json", |bench| {
+        bench.iter(|| serde_json::from_slice::<GraphqlRequest>(&body).expect("to parse"))
+    });
+    group.bench_function("router", |bench| {
+        bench.iter(|| {
+            let req = HttpRequest::post("/").body(hyper::Body::from(body.clone())).expect("to build request");
+            runtime.block_on(graphql_router::parse_http_request(req)).expect("to parse")
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, handler, parse);
+criterion_main!(benches);
@@ -0,0 +1,602 @@
+//! Administration of running router
+
+use hyper::http::header::CONTENT_TYPE;
+use hyper::http::HeaderValue;
+use hyper::{Method, StatusCode};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::config::ConfigWatcher;
+use crate::log::{LogFilter, LogPatch};
+use crate::plugins::MaintenanceMode;
+use crate::remote::{CircuitBreakerSettings, CircuitState, HealthStatus, RemoteSettingsHandle};
+use crate::server::{error_response, is_authorized, IpFilter, ServerError};
+use crate::{GraphqlRouter, HttpRequest, RebuildError, Schema, WarmupOperation};
+
+use core::convert::Infallible;
+use core::future::Future;
+use core::time::Duration;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const APPLICATION_JSON: HeaderValue = HeaderValue::from_static("application/json");
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+///Circuit breaker settings update
+pub struct CircuitBreakerPatch {
+    ///Number of consecutive failures to open circuit, `0` disables circuit breaker.
+    pub failure_threshold: u32,
+    ///Time during which requests are rejected, in milliseconds.
+    pub reset_timeout_ms: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Partial update of remote subgraph settings
+///
+///Unspecified fields are left unchanged.
+pub struct SettingsPatch {
+    #[serde(default)]
+    ///Retry number.
+    pub max_retry_num: Option<usize>,
+    #[serde(default)]
+    ///Delay before first retry in milliseconds, `0` disables backoff.
+    pub retry_backoff_ms: Option<u64>,
+    #[serde(default)]
+    ///Redirect number.
+    pub max_redirect_num: Option<usize>,
+    #[serde(default)]
+    ///Time limit in milliseconds, `0` disables limit.
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    ///Circuit breaker settings.
+    pub circuit_breaker: Option<CircuitBreakerPatch>,
+}
+
+impl SettingsPatch {
+    ///Applies update to subgraph settings, taking effect for subsequent requests.
+    pub fn apply(&self, handle: &RemoteSettingsHandle) {
+        handle.update(|settings| {
+            if let Some(max_retry_num) = self.max_retry_num {
+                settings.max_retry_num = max_retry_num;
+            }
+            if let Some(retry_backoff_ms) = self.retry_backoff_ms {
+                settings.retry_backoff = match retry_backoff_ms {
+                    0 => None,
+                    retry_backoff_ms => Some(Duration::from_millis(retry_backoff_ms)),
+                };
+            }
+            if let Some(max_redirect_num) = self.max_redirect_num {
+                settings.max_redirect_num = max_redirect_num;
+            }
+            if let Some(timeout_ms) = self.timeout_ms {
+                settings.timeout = match timeout_ms {
+                    0 => None,
+                    timeout_ms => Some(Duration::from_millis(timeout_ms)),
+                };
+            }
+            if let Some(circuit_breaker) = self.circuit_breaker {
+                settings.circuit_breaker = match circuit_breaker.failure_threshold {
+                    0 => None,
+                    failure_threshold => Some(CircuitBreakerSettings {
+                        failure_threshold,
+                        reset_timeout: Duration::from_millis(circuit_breaker.reset_timeout_ms),
+                    }),
+                };
+            }
+        })
+    }
+}
+
+///Returns JSON representation of subgraph settings.
+pub fn settings_json(handle: &RemoteSettingsHandle) -> serde_json::Value {
+    let settings = handle.get();
+    let circuit_breaker = match settings.circuit_breaker.as_ref() {
+        Some(circuit_breaker) => {
+            let state = match handle.circuit_breaker().state(circuit_breaker) {
+                CircuitState::Closed => "closed",
+                CircuitState::Open => "open",
+                CircuitState::HalfOpen => "half-open",
+            };
+            serde_json::json!({
+                "failure_threshold": circuit_breaker.failure_threshold,
+                "reset_timeout_ms": circuit_breaker.reset_timeout.as_millis() as u64,
+                "state": state,
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+
+    let variants = handle
+        .variant_metrics()
+        .into_iter()
+        .map(|(name, metrics)| (name, metrics.to_json()))
+        .collect::<serde_json::Map<_, _>>();
+
+    serde_json::json!({
+        "max_retry_num": settings.max_retry_num,
+        "retry_backoff_ms": settings.retry_backoff.map(|backoff| backoff.as_millis() as u64),
+        "max_redirect_num": settings.max_redirect_num,
+        "timeout_ms": settings.timeout.map(|timeout| timeout.as_millis() as u64),
+        "circuit_breaker": circuit_breaker,
+        "variants": variants,
+        "draining": handle.draining(),
+        "in_flight": handle.in_flight(),
+    })
+}
+
+pub(crate) fn rebuild_error_response(error: RebuildError) -> hyper::Response<hyper::Body> {
+    let status = match error {
+        RebuildError::NoStandby | RebuildError::NoPrevious | RebuildError::SmokeTest { .. } => StatusCode::CONFLICT,
+        RebuildError::PluginPinned(_) => StatusCode::FORBIDDEN,
+        RebuildError::UnknownPlugin(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    error_response(status, &error.to_string())
+}
+
+pub(crate) fn json_response(body: &serde_json::Value) -> hyper::Response<hyper::Body> {
+    let body = serde_json::to_vec(body).expect("JSON serialization should not fail");
+    let mut response = hyper::Response::new(body.into());
+    response.headers_mut().insert(CONTENT_TYPE, APPLICATION_JSON);
+    response
+}
+
+///Returns JSON health of router's subgraphs, with `ready` set unless any of them is down or router is paused.
+pub fn health_json(router: &GraphqlRouter) -> serde_json::Value {
+    let health = router.subgraphs_health();
+    let is_paused = router.pause_switch().is_enabled();
+    let is_ready = !is_paused && health.values().all(|health| health.status != HealthStatus::Down);
+    let subgraphs = health
+        .into_iter()
+        .map(|(name, health)| (name, health.to_json()))
+        .collect::<serde_json::Map<_, _>>();
+    serde_json::json!({
+        "ready": is_ready,
+        "paused": is_paused,
+        "subgraphs": subgraphs,
+    })
+}
+
+///Returns JSON summary of router state.
+fn state_json(router: &GraphqlRouter) -> serde_json::Value {
+    let schema = router.schema();
+    let subgraphs = schema.subgraphs().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+    let maintenance = router.maintenance().and_then(|maintenance| maintenance.current());
+    serde_json::json!({
+        "subgraphs": subgraphs,
+        "maintenance": serde_json::to_value(maintenance).expect("JSON serialization should not fail"),
+        "paused": router.pause_switch().is_enabled(),
+        "metrics": router.metrics().is_some(),
+        "persisted_queries": router.persisted_queries().map(|persisted| persisted.len()),
+        "plan_manifest": router.plan_manifest().map(|manifest| manifest.len()),
+    })
+}
+
+///Returns JSON summary of router's current schema.
+fn schema_json(router: &GraphqlRouter) -> serde_json::Value {
+    let schema = router.schema();
+    let hash = crate::hash::sha256_hex(schema.as_str().as_bytes());
+    let subgraphs = schema
+        .subgraphs()
+        .map(|(name, url)| (name.clone(), serde_json::Value::String(url.to_string())))
+        .collect::<serde_json::Map<_, _>>();
+    serde_json::json!({
+        "sha256": hash,
+        "size": schema.as_str().len(),
+        "subgraphs": subgraphs,
+    })
+}
+
+///Handles admin request, with `path` relative to admin prefix.
+///
+///Routes:
+///- `GET /subgraphs` - settings of all subgraphs;
+///- `PATCH /subgraphs` - updates settings of all subgraphs with [SettingsPatch];
+///- `GET /subgraphs/{name}` - settings of subgraph;
+///- `PATCH /subgraphs/{name}` - updates settings with [SettingsPatch];
+///- `POST /subgraphs/{name}/circuit/reset` - closes subgraph's circuit;
+///- `PUT /subgraphs/{name}/drain/{endpoint}` - drains endpoint, see [RemoteSettingsHandle::drain];
+///- `DELETE /subgraphs/{name}/drain/{endpoint}` - resumes sending requests to endpoint;
+///- `GET /buffers` - statistics of serialization [BufferPool](crate::pool::BufferPool);
+///- `GET /maintenance` - current maintenance mode, `null` if disabled;
+///- `PUT /maintenance` - enables maintenance with [MaintenanceMode];
+///- `DELETE /maintenance` - disables maintenance;
+///- `GET /log` - current log filter, see [LogFilter];
+///- `PATCH /log` - updates log levels, subgraph debug and trace sampling with [LogPatch];
+///- `GET /plugins` - plugins of router with whether they are enabled;
+///- `PUT /plugins/{name}` - enables plugin, see [GraphqlRouter::set_plugin_enabled];
+///- `DELETE /plugins/{name}` - disables plugin;
+///- `GET /pause` - current pause mode, `null` if not paused;
+///- `PUT /pause` - pauses built-in server with [MaintenanceMode], see [pause_switch](GraphqlRouter::pause_switch);
+///- `DELETE /pause` - resumes request processing;
+///- `GET /state` - summary of router state;
+///- `GET /health` - health of subgraphs, see [health_json];
+///- `GET /schema` - hash, size and subgraphs of current schema;
+///- `POST /schema/diff` - changes from current schema to SDL in body, see [GraphqlRouter::diff_schema];
+///- `PUT /schema/standby` - stages SDL in body, see [GraphqlRouter::stage_schema];
+///- `POST /schema/standby/smoke` - executes JSON array of [WarmupOperation] against staged schema;
+///- `POST /schema/promote` - promotes staged schema, see [GraphqlRouter::promote_standby];
+///- `POST /schema/rollback` - restores schema replaced by promotion, see [GraphqlRouter::rollback];
+///- `POST /cache/purge` - drops cached query plans, see [GraphqlRouter::purge_plan_cache];
+///- `POST /config/reload` - reloads config, only served by [AdminServer] with [ConfigWatcher].
+pub async fn handle(router: &GraphqlRouter, path: &str, req: HttpRequest) -> hyper::Response<hyper::Body> {
+    route(router, None, path, req).await
+}
+
+async fn route(
+    router: &GraphqlRouter,
+    watcher: Option<&Mutex<ConfigWatcher>>,
+    path: &str,
+    req: HttpRequest,
+) -> hyper::Response<hyper::Body> {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+
+    match (req.method(), segments.as_slice()) {
+        (&Method::GET, ["state"]) => json_response(&state_json(router)),
+        (&Method::GET, ["health"]) => json_response(&health_json(router)),
+        (&Method::GET, ["schema"]) => json_response(&schema_json(router)),
+        (&Method::POST, ["schema", "diff"]) => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            let candidate = match core::str::from_utf8(&body) {
+                Ok(candidate) => candidate,
+                Err(_) => return error_response(StatusCode::BAD_REQUEST, "Schema is not valid UTF-8"),
+            };
+            match router.diff_schema(candidate) {
+                Ok(diff) => json_response(&diff.to_json()),
+                Err(error) => error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            }
+        }
+        (&Method::PUT, ["schema", "standby"]) => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            let schema = match core::str::from_utf8(&body).map(str::parse::<Schema>) {
+                Ok(Ok(schema)) => Arc::new(schema),
+                Ok(Err(error)) => return error_response(StatusCode::BAD_REQUEST, &format!("Invalid schema: {}", error)),
+                Err(_) => return error_response(StatusCode::BAD_REQUEST, "Schema is not valid UTF-8"),
+            };
+            match router.stage_schema(schema).await {
+                Ok(()) => json_response(&serde_json::json!({ "staged": true })),
+                Err(error) => rebuild_error_response(error),
+            }
+        }
+        (&Method::POST, ["schema", "standby", "smoke"]) => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            let operations = match serde_json::from_slice::<Vec<WarmupOperation>>(&body) {
+                Ok(operations) => operations,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            match router.smoke_test_standby(&operations).await {
+                Ok(()) => json_response(&serde_json::json!({ "passed": operations.len() })),
+                Err(error) => rebuild_error_response(error),
+            }
+        }
+        (&Method::POST, ["schema", "promote"]) => match router.clone().promote_standby().await {
+            Ok(()) => json_response(&schema_json(router)),
+            Err(error) => rebuild_error_response(error),
+        },
+        (&Method::POST, ["schema", "rollback"]) => match router.clone().rollback().await {
+            Ok(()) => json_response(&schema_json(router)),
+            Err(error) => rebuild_error_response(error),
+        },
+        (&Method::POST, ["cache", "purge"]) => match router.clone().purge_plan_cache().await {
+            Ok(()) => json_response(&serde_json::json!({ "purged": true })),
+            Err(error) => {
+                tracing::warn!("Failed to purge query plan cache: {}", error);
+                error_response(StatusCode::INTERNAL_SERVER_ERROR, &error.to_string())
+            }
+        },
+        (&Method::POST, ["config", "reload"]) => {
+            let watcher = match watcher {
+                Some(watcher) => watcher,
+                None => return error_response(StatusCode::NOT_FOUND, "Config reload is not configured"),
+            };
+            let mut watcher = watcher.lock().await;
+            match watcher.reload().await {
+                Ok(report) => {
+                    tracing::info!("Config reloaded via admin. {}", report);
+                    json_response(&serde_json::json!({
+                        "applied": report.applied,
+                        "rejected": report.rejected,
+                    }))
+                }
+                Err(error) => error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            }
+        }
+        (&Method::GET, ["subgraphs"]) => {
+            let mut result = serde_json::Map::new();
+            for (name, handle) in router.subgraphs_settings() {
+                result.insert(name, settings_json(&handle));
+            }
+            json_response(&serde_json::Value::Object(result))
+        }
+        (&Method::PATCH, ["subgraphs"]) => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            let patch = match serde_json::from_slice::<SettingsPatch>(&body) {
+                Ok(patch) => patch,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            let mut result = serde_json::Map::new();
+            for (name, handle) in router.subgraphs_settings() {
+                patch.apply(&handle);
+                tracing::info!("{}: Settings updated: {:?}", name, handle.get());
+                result.insert(name, settings_json(&handle));
+            }
+            json_response(&serde_json::Value::Object(result))
+        }
+        (&Method::GET, ["subgraphs", name]) => match router.subgraph_settings(name) {
+            Some(handle) => json_response(&settings_json(&handle)),
+            None => error_response(StatusCode::NOT_FOUND, "Unknown subgraph"),
+        },
+        (&Method::PATCH, ["subgraphs", name]) => {
+            let handle = match router.subgraph_settings(name) {
+                Some(handle) => handle,
+                None => return error_response(StatusCode::NOT_FOUND, "Unknown subgraph"),
+            };
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            match serde_json::from_slice::<SettingsPatch>(&body) {
+                Ok(patch) => {
+                    patch.apply(&handle);
+                    tracing::info!("{}: Settings updated: {:?}", name, handle.get());
+                    json_response(&settings_json(&handle))
+                }
+                Err(error) => error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            }
+        }
+        (&Method::POST, ["subgraphs", name, "circuit", "reset"]) => match router.subgraph_settings(name) {
+            Some(handle) => {
+                handle.circuit_breaker().reset();
+                json_response(&settings_json(&handle))
+            }
+            None => error_response(StatusCode::NOT_FOUND, "Unknown subgraph"),
+        },
+        (&Method::PUT, ["subgraphs", name, "drain", endpoint]) => match router.subgraph_settings(name) {
+            Some(handle) if handle.drain(endpoint) => {
+                tracing::info!("{}: Endpoint '{}' is draining", name, endpoint);
+                json_response(&settings_json(&handle))
+            }
+            Some(_) => error_response(StatusCode::NOT_FOUND, "Unknown endpoint"),
+            None => error_response(StatusCode::NOT_FOUND, "Unknown subgraph"),
+        },
+        (&Method::DELETE, ["subgraphs", name, "drain", endpoint]) => match router.subgraph_settings(name) {
+            Some(handle) => {
+                if handle.resume(endpoint) {
+                    tracing::info!("{}: Endpoint '{}' resumed", name, endpoint);
+                }
+                json_response(&settings_json(&handle))
+            }
+            None => error_response(StatusCode::NOT_FOUND, "Unknown subgraph"),
+        },
+        (&Method::GET, ["buffers"]) => {
+            let metrics = crate::pool::BufferPool::global().metrics();
+            json_response(&serde_json::to_value(metrics).expect("JSON serialization should not fail"))
+        }
+        (_, ["maintenance"]) => {
+            let maintenance = match router.maintenance() {
+                Some(maintenance) => maintenance,
+                None => return error_response(StatusCode::NOT_FOUND, "Maintenance switch is not configured"),
+            };
+            let method = req.method().clone();
+            match method {
+                Method::GET => (),
+                Method::PUT => {
+                    let body = match hyper::body::to_bytes(req.into_body()).await {
+                        Ok(body) => body,
+                        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+                    };
+                    match serde_json::from_slice::<MaintenanceMode>(&body) {
+                        Ok(mode) => {
+                            tracing::info!("Maintenance enabled: {:?}", mode);
+                            maintenance.enable(mode);
+                        }
+                        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+                    }
+                }
+                Method::DELETE => {
+                    tracing::info!("Maintenance disabled");
+                    maintenance.disable();
+                }
+                _ => return error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+            }
+            let current = serde_json::to_value(maintenance.current()).expect("JSON serialization should not fail");
+            json_response(&current)
+        }
+        (&Method::GET, ["plugins"]) => {
+            let plugins = router
+                .plugins()
+                .await
+                .into_iter()
+                .map(|(name, is_enabled)| serde_json::json!({ "name": name, "enabled": is_enabled }))
+                .collect::<Vec<_>>();
+            json_response(&serde_json::Value::Array(plugins))
+        }
+        (method @ (&Method::PUT | &Method::DELETE), ["plugins", name]) => {
+            let is_enabled = *method == Method::PUT;
+            match router.clone().set_plugin_enabled(name, is_enabled).await {
+                Ok(()) => json_response(&serde_json::json!({ "name": name, "enabled": is_enabled })),
+                Err(error) => rebuild_error_response(error),
+            }
+        }
+        (&Method::GET, ["log"]) => json_response(&LogFilter::global().to_json()),
+        (&Method::PATCH, ["log"]) => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            let patch = match serde_json::from_slice::<LogPatch>(&body) {
+                Ok(patch) => patch,
+                Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+            };
+            match patch.apply(LogFilter::global()) {
+                Ok(()) => {
+                    tracing::warn!("Log filter updated: {:?}", patch);
+                    json_response(&LogFilter::global().to_json())
+                }
+                Err(error) => error_response(StatusCode::BAD_REQUEST, &error),
+            }
+        }
+        (_, ["pause"]) => {
+            let pause = router.pause_switch();
+            let method = req.method().clone();
+            match method {
+                Method::GET => (),
+                Method::PUT => {
+                    let body = match hyper::body::to_bytes(req.into_body()).await {
+                        Ok(body) => body,
+                        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+                    };
+                    let mode = match body.is_empty() {
+                        true => Ok(MaintenanceMode::default()),
+                        false => serde_json::from_slice::<MaintenanceMode>(&body),
+                    };
+                    match mode {
+                        Ok(mode) => {
+                            tracing::warn!("Request processing paused: {:?}", mode);
+                            pause.enable(mode);
+                        }
+                        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+                    }
+                }
+                Method::DELETE => {
+                    tracing::info!("Request processing resumed");
+                    pause.disable();
+                }
+                _ => return error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+            }
+            let current = serde_json::to_value(pause.current()).expect("JSON serialization should not fail");
+            json_response(&current)
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+struct AdminShared {
+    token: Option<String>,
+    ip_filter: Option<IpFilter>,
+    watcher: Option<Mutex<ConfigWatcher>>,
+}
+
+///Admin server, listening on its own address, so that management traffic is separate from GraphQL endpoint.
+///
+///Serves the same routes as [handle], at root path.
+pub struct AdminServer {
+    addr: SocketAddr,
+    shared: AdminShared,
+}
+
+impl AdminServer {
+    #[inline(always)]
+    pub(crate) fn with_token(addr: SocketAddr, token: Option<String>) -> Self {
+        Self {
+            addr,
+            shared: AdminShared {
+                token,
+                ip_filter: None,
+                watcher: None,
+            },
+        }
+    }
+
+    #[inline(always)]
+    ///Starts building admin server listening on `addr`, requiring requests to have `Authorization: Bearer <token>`.
+    pub fn new(addr: SocketAddr, token: String) -> Self {
+        Self::with_token(addr, Some(token))
+    }
+
+    #[inline(always)]
+    ///Starts building admin server listening on `127.0.0.1:<port>` without token, so that only clients on the same
+    ///host can reach it.
+    pub fn localhost(port: u16) -> Self {
+        Self::with_token(SocketAddr::from(([127, 0, 0, 1], port)), None)
+    }
+
+    #[inline(always)]
+    ///Rejects clients not allowed by `filter` with `FORBIDDEN`.
+    pub fn ip_filter(mut self, filter: IpFilter) -> Self {
+        self.shared.ip_filter = Some(filter);
+        self
+    }
+
+    #[inline(always)]
+    ///Enables `POST /config/reload`, which reloads config using `watcher`.
+    pub fn config_watcher(mut self, watcher: ConfigWatcher) -> Self {
+        self.shared.watcher = Some(Mutex::new(watcher));
+        self
+    }
+
+    ///Serves admin endpoints of `router` until `shutdown` completes.
+    ///
+    ///Each connection is handled in its own task, so it must be called within tokio runtime.
+    pub async fn serve<F: Future<Output = ()>>(self, router: GraphqlRouter, shutdown: F) -> Result<(), ServerError> {
+        let listener = tokio::net::TcpListener::bind(self.addr)
+            .await
+            .map_err(ServerError::Bind)?;
+        tracing::info!("Admin listening on {}", self.addr);
+        let shared = Arc::new(self.shared);
+
+        tokio::pin!(shutdown);
+        loop {
+            let (stream, remote) = tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(error) => {
+                        tracing::warn!("Admin: Failed to accept connection: {}", error);
+                        continue;
+                    }
+                },
+            };
+
+            let router = router.clone();
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req| respond(router.clone(), shared.clone(), remote, req));
+                let result = hyper::server::conn::Http::new().serve_connection(stream, service).await;
+                if let Err(error) = result {
+                    tracing::info!("Admin: {}: Connection error: {}", remote, error);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+async fn respond(
+    router: GraphqlRouter,
+    shared: Arc<AdminShared>,
+    remote: SocketAddr,
+    req: HttpRequest,
+) -> Result<hyper::Response<hyper::Body>, Infallible> {
+    if let Some(filter) = shared.ip_filter.as_ref() {
+        let client = filter.client_addr(remote, req.headers());
+        if !filter.is_allowed(&client) {
+            tracing::info!("Admin: {}: Rejected client {}", remote, client);
+            return Ok(error_response(StatusCode::FORBIDDEN, "Forbidden"));
+        }
+    }
+    if let Some(token) = shared.token.as_deref() {
+        if !is_authorized(&req, token) {
+            return Ok(error_response(StatusCode::UNAUTHORIZED, "Unauthorized"));
+        }
+    }
+    let path = req.uri().path().to_owned();
+    Ok(route(&router, shared.watcher.as_ref(), &path, req).await)
+}
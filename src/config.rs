@@ -0,0 +1,785 @@
+//! Declarative router configuration
+
+use serde::Deserialize;
+
+use crate::admin::AdminServer;
+use crate::encoding::Encoding;
+use crate::log::{LogFilter, LogPatch};
+use crate::plugins::PluginRegistry;
+use crate::server::{IpFilter, SecurityHeaders, ServerBuilder, TlsConfig};
+use crate::secret::{Secret, SecretSource};
+use crate::tls::{ClientTlsConfig, PemSource};
+use crate::{GraphqlRouter, GraphqlRouterBuilder, RemoteGraphBuilder, RemoteHeaders, Schema, WarmupOperation};
+use hyper::header::{HeaderName, HeaderValue};
+use ipnet::IpNet;
+
+use core::fmt;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod lenient;
+pub(crate) use lenient::from_value;
+mod reload;
+pub use reload::{ConfigWatcher, ReloadReport};
+mod validate;
+pub use validate::ValidationError;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ///Unable to read config file.
+    Io(io::Error),
+    ///Config file has invalid format.
+    Parse(serde_yaml::Error),
+    ///Supergraph schema cannot be loaded.
+    Schema(apollo_router_core::SchemaError),
+    ///Subgraph's URL is not valid.
+    InvalidUrl {
+        subgraph: String,
+        error: hyper::http::uri::InvalidUri,
+    },
+    ///Config references environment variable which is not set and has no default.
+    MissingEnv(String),
+    ///Config contains malformed `${...}` expression.
+    InvalidInterpolation(String),
+    ///Subgraph's header is not valid.
+    InvalidHeader { subgraph: String, header: String },
+    ///Subgraph's TLS settings cannot be loaded.
+    Tls { subgraph: String, error: io::Error },
+    ///Plugin is not present in registry.
+    UnknownPlugin(String),
+    ///Plugin cannot be created with provided config.
+    Plugin { name: String, error: tower::BoxError },
+    ///Config is inconsistent with itself or supergraph.
+    Validation(Vec<ValidationError>),
+}
+
+impl From<io::Error> for ConfigError {
+    #[inline(always)]
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<serde_yaml::Error> for ConfigError {
+    #[inline(always)]
+    fn from(error: serde_yaml::Error) -> Self {
+        ConfigError::Parse(error)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => fmt.write_fmt(format_args!("Failed to read config: {}", error)),
+            ConfigError::Parse(error) => fmt.write_fmt(format_args!("Invalid config: {}", error)),
+            ConfigError::Schema(error) => fmt.write_fmt(format_args!("Failed to load supergraph: {}", error)),
+            ConfigError::InvalidUrl { subgraph, error } => {
+                fmt.write_fmt(format_args!("Subgraph '{}' has invalid url: {}", subgraph, error))
+            }
+            ConfigError::MissingEnv(name) => {
+                fmt.write_fmt(format_args!("Environment variable '{}' is required, but not set", name))
+            }
+            ConfigError::InvalidInterpolation(text) => {
+                fmt.write_fmt(format_args!("Invalid variable interpolation in '{}'", text))
+            }
+            ConfigError::InvalidHeader { subgraph, header } => {
+                fmt.write_fmt(format_args!("Subgraph '{}' has invalid header '{}'", subgraph, header))
+            }
+            ConfigError::Tls { subgraph, error } => {
+                fmt.write_fmt(format_args!("Subgraph '{}' has invalid TLS settings: {}", subgraph, error))
+            }
+            ConfigError::UnknownPlugin(name) => fmt.write_fmt(format_args!("Unknown plugin '{}'", name)),
+            ConfigError::Plugin { name, error } => {
+                fmt.write_fmt(format_args!("Failed to create plugin '{}': {}", name, error))
+            }
+            ConfigError::Validation(errors) => {
+                fmt.write_str("Invalid config:")?;
+                for error in errors.iter() {
+                    fmt.write_fmt(format_args!("\n- {}", error))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Router configuration
+pub struct RouterConfig {
+    ///Path to supergraph schema.
+    ///
+    ///When loaded from file, relative path is resolved against config's directory.
+    pub supergraph: PathBuf,
+    #[serde(default)]
+    ///Options applied to every remote subgraph, unless overridden.
+    pub subgraph_defaults: SubgraphOptions,
+    #[serde(default)]
+    ///Remote subgraphs endpoints.
+    pub subgraphs: BTreeMap<String, SubgraphConfig>,
+    #[serde(default)]
+    ///Plugins to instantiate from registry with their configs, in order they are listed.
+    pub plugins: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    ///Built-in server settings.
+    pub server: Option<ServerConfig>,
+    #[serde(default)]
+    ///Operations executed on startup, before router takes traffic.
+    pub warmup: Vec<WarmupOperation>,
+    #[serde(default)]
+    ///Log levels, applied to [LogFilter::global].
+    pub log: LogPatch,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Remote subgraph configuration
+///
+///Options override values from `subgraph_defaults`.
+pub struct SubgraphConfig {
+    ///URL to send subgraph requests to.
+    pub url: String,
+    #[serde(default)]
+    ///Time limit for subgraph fetch in milliseconds.
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    ///Retry number.
+    pub max_retry_num: Option<usize>,
+    #[serde(default)]
+    ///Delay before first retry in milliseconds, doubled on each subsequent retry.
+    pub retry_backoff_ms: Option<u64>,
+    #[serde(default)]
+    ///Circuit breaker settings.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    #[serde(default)]
+    ///Headers added to every request, merged with default headers.
+    pub headers: BTreeMap<String, SecretValue>,
+    #[serde(default)]
+    ///Token sent as `Authorization: Bearer <token>`.
+    pub auth_token: Option<SecretValue>,
+    #[serde(default)]
+    ///TLS settings.
+    pub tls: Option<SubgraphTlsConfig>,
+    #[serde(default)]
+    ///Limit of concurrent requests.
+    pub max_concurrency: Option<usize>,
+    #[serde(default)]
+    ///Encoding of requests, JSON by default.
+    pub encoding: Option<Encoding>,
+}
+
+impl SubgraphConfig {
+    ///Returns effective options, with `defaults` applied.
+    pub fn options(&self, defaults: &SubgraphOptions) -> SubgraphOptions {
+        let mut headers = defaults.headers.clone();
+        headers.extend(self.headers.iter().map(|(key, value)| (key.clone(), value.clone())));
+
+        SubgraphOptions {
+            timeout_ms: self.timeout_ms.or(defaults.timeout_ms),
+            max_retry_num: self.max_retry_num.or(defaults.max_retry_num),
+            retry_backoff_ms: self.retry_backoff_ms.or(defaults.retry_backoff_ms),
+            circuit_breaker: self.circuit_breaker.or(defaults.circuit_breaker),
+            headers,
+            auth_token: self.auth_token.clone().or_else(|| defaults.auth_token.clone()),
+            tls: self.tls.clone().or_else(|| defaults.tls.clone()),
+            max_concurrency: self.max_concurrency.or(defaults.max_concurrency),
+            encoding: self.encoding.or(defaults.encoding),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Remote subgraph options
+///
+///Unspecified options use `RemoteGraphBuilder`'s defaults.
+pub struct SubgraphOptions {
+    #[serde(default)]
+    ///Time limit for subgraph fetch in milliseconds.
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    ///Retry number.
+    pub max_retry_num: Option<usize>,
+    #[serde(default)]
+    ///Delay before first retry in milliseconds, doubled on each subsequent retry.
+    pub retry_backoff_ms: Option<u64>,
+    #[serde(default)]
+    ///Circuit breaker settings.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    #[serde(default)]
+    ///Headers added to every request.
+    pub headers: BTreeMap<String, SecretValue>,
+    #[serde(default)]
+    ///Token sent as `Authorization: Bearer <token>`.
+    pub auth_token: Option<SecretValue>,
+    #[serde(default)]
+    ///TLS settings.
+    pub tls: Option<SubgraphTlsConfig>,
+    #[serde(default)]
+    ///Limit of concurrent requests.
+    pub max_concurrency: Option<usize>,
+    #[serde(default)]
+    ///Encoding of requests, JSON by default.
+    pub encoding: Option<Encoding>,
+}
+
+impl SubgraphOptions {
+    ///Returns headers and auth token to be added to requests towards subgraph `name`.
+    pub fn remote_headers(&self, name: &str) -> Result<RemoteHeaders, ConfigError> {
+        let mut result = RemoteHeaders::new();
+        for (header, value) in self.headers.iter() {
+            let invalid_header = || ConfigError::InvalidHeader {
+                subgraph: name.to_owned(),
+                header: header.clone(),
+            };
+            let header_name = HeaderName::from_bytes(header.as_bytes()).map_err(|_| invalid_header())?;
+            result = match value {
+                SecretValue::Inline(value) => {
+                    let value = HeaderValue::from_str(value).map_err(|_| invalid_header())?;
+                    result.header(header_name, value)
+                }
+                value => result.secret_header(header_name, Secret::new(value.source())),
+            };
+        }
+        if let Some(auth_token) = self.auth_token.as_ref() {
+            result = result.bearer_token(Secret::new(auth_token.source()));
+        }
+        Ok(result)
+    }
+
+    ///Applies options to `builder`.
+    pub fn apply(&self, name: &str, mut builder: RemoteGraphBuilder) -> Result<RemoteGraphBuilder, ConfigError> {
+        if let Some(timeout_ms) = self.timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout_ms));
+        }
+        if let Some(max_retry_num) = self.max_retry_num {
+            builder = builder.max_retry_num(max_retry_num);
+        }
+        if let Some(retry_backoff_ms) = self.retry_backoff_ms {
+            builder = builder.retry_backoff(Duration::from_millis(retry_backoff_ms));
+        }
+        if let Some(circuit_breaker) = self.circuit_breaker {
+            builder = builder.circuit_breaker(circuit_breaker.failure_threshold, circuit_breaker.reset_timeout());
+        }
+        builder = builder.headers(self.remote_headers(name)?);
+        if let Some(tls) = self.tls.as_ref() {
+            let mut config = ClientTlsConfig::new();
+            if let Some(ca) = tls.ca.as_ref() {
+                config = config.roots(PemSource::File(ca.clone()));
+            }
+            match (tls.cert.as_ref(), tls.key.as_ref()) {
+                (Some(cert), Some(key)) => config = config.identity(PemSource::File(cert.clone()), key.source()),
+                (None, None) => (),
+                _ => {
+                    return Err(ConfigError::Tls {
+                        subgraph: name.to_owned(),
+                        error: io::Error::new(io::ErrorKind::InvalidInput, "Both cert and key must be specified"),
+                    })
+                }
+            }
+            builder = builder.tls(config).map_err(|error| ConfigError::Tls {
+                subgraph: name.to_owned(),
+                error,
+            })?;
+        }
+        if let Some(max_concurrency) = self.max_concurrency {
+            builder = builder.max_concurrency(max_concurrency);
+        }
+        if let Some(encoding) = self.encoding {
+            builder = builder.encoding(encoding);
+        }
+
+        Ok(builder)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+///Circuit breaker settings of remote subgraph
+pub struct CircuitBreakerConfig {
+    ///Number of consecutive failures to open circuit.
+    pub failure_threshold: u32,
+    ///Time during which requests are rejected, before probe request is allowed, in milliseconds.
+    pub reset_timeout_ms: u64,
+}
+
+impl CircuitBreakerConfig {
+    #[inline(always)]
+    ///Returns time during which requests are rejected.
+    pub fn reset_timeout(&self) -> Duration {
+        Duration::from_millis(self.reset_timeout_ms)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///TLS settings of remote subgraph
+pub struct SubgraphTlsConfig {
+    #[serde(default)]
+    ///Path to PEM CA certificates, native roots are used if not specified.
+    pub ca: Option<PathBuf>,
+    #[serde(default)]
+    ///Path to PEM client certificate chain.
+    pub cert: Option<PathBuf>,
+    #[serde(default)]
+    ///PEM client private key.
+    pub key: Option<KeyValue>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+///Secret value, which can be specified inline or referenced.
+///
+///```yaml
+///inline: "value"
+///from_env: { env: TOKEN }
+///from_file: { file: /run/secrets/token }
+///```
+pub enum SecretValue {
+    ///Value as it is.
+    Inline(String),
+    ///Environment variable, read on every access.
+    Env { env: String },
+    ///File, re-read when modified.
+    File { file: PathBuf },
+}
+
+impl SecretValue {
+    #[inline]
+    ///Returns source of secret.
+    pub fn source(&self) -> SecretSource {
+        match self {
+            SecretValue::Inline(value) => SecretSource::Inline(value.clone()),
+            SecretValue::Env { env } => SecretSource::Env(env.clone()),
+            SecretValue::File { file } => SecretSource::File(file.clone()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+///Private key reference.
+///
+///Plain string is a path to PEM file, which is re-loaded when modified.
+///Alternatively `{ env: NAME }` reads PEM content from environment variable.
+pub enum KeyValue {
+    ///PEM content from environment variable.
+    Env { env: String },
+    ///Path to PEM file.
+    File(PathBuf),
+}
+
+impl KeyValue {
+    #[inline]
+    ///Returns PEM source of key.
+    pub fn source(&self) -> PemSource {
+        match self {
+            KeyValue::Env { env } => PemSource::Env(env.clone()),
+            KeyValue::File(path) => PemSource::File(path.clone()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Built-in server configuration
+pub struct ServerConfig {
+    ///Address to listen on.
+    pub listen: SocketAddr,
+    #[serde(default)]
+    ///TLS termination settings.
+    pub tls: Option<ServerTlsConfig>,
+    #[serde(default)]
+    ///Admin endpoints settings.
+    pub admin: Option<ServerAdminConfig>,
+    #[serde(default)]
+    ///Admin server settings, serving admin endpoints on separate address.
+    pub admin_listener: Option<ServerAdminListenerConfig>,
+    #[serde(default)]
+    ///Path of readiness check, reporting health of subgraphs.
+    pub ready_path: Option<String>,
+    #[serde(default)]
+    ///Maximum number of operations in batched request, enabling batching.
+    pub max_batch_size: Option<usize>,
+    #[serde(default)]
+    ///Persisted queries registration endpoint settings.
+    pub persisted_query_registration: Option<ServerRegistrationConfig>,
+    #[serde(default)]
+    ///Specifies whether to set `ETag` on responses to `GET` requests.
+    pub etag: bool,
+    #[serde(default)]
+    ///Minimum size of response in bytes to compress, enabling compression.
+    pub compression_min_size: Option<usize>,
+    #[serde(default)]
+    ///Client IP filter settings.
+    pub ip_filter: Option<ServerIpFilterConfig>,
+    #[serde(default)]
+    ///Security headers settings, enabling them.
+    pub security_headers: Option<ServerSecurityHeadersConfig>,
+}
+
+#[inline(always)]
+fn default_mutation_no_store() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Security headers of built-in server responses
+pub struct ServerSecurityHeadersConfig {
+    #[serde(default)]
+    ///Headers overriding defaults, with empty value removing default header.
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    ///`max-age` of `Strict-Transport-Security`, which is not set by default.
+    pub hsts_max_age: Option<u64>,
+    #[serde(default = "default_mutation_no_store")]
+    ///Specifies whether mutation responses have `Cache-Control: no-store`.
+    pub mutation_no_store: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Client IP filter of built-in server
+pub struct ServerIpFilterConfig {
+    #[serde(default)]
+    ///Allowed networks, all clients are allowed if empty.
+    pub allow: Vec<IpNet>,
+    #[serde(default)]
+    ///Denied networks.
+    pub deny: Vec<IpNet>,
+    #[serde(default)]
+    ///Networks of proxies, whose `Forwarded` and `X-Forwarded-For` headers are trusted.
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Admin endpoints of built-in server
+pub struct ServerAdminConfig {
+    ///Path prefix of admin endpoints.
+    pub prefix: String,
+    ///Bearer token required to access admin endpoints.
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Admin server, separate from built-in server
+pub struct ServerAdminListenerConfig {
+    ///Address to listen on.
+    pub listen: SocketAddr,
+    #[serde(default)]
+    ///Bearer token required to access admin endpoints.
+    ///
+    ///Can be omitted only if `listen` is loopback address.
+    pub token: Option<String>,
+    #[serde(default)]
+    ///Client IP filter settings.
+    pub ip_filter: Option<ServerIpFilterConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Persisted queries registration endpoint of built-in server
+pub struct ServerRegistrationConfig {
+    ///Path of endpoint.
+    pub path: String,
+    ///Bearer token required to register operations.
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///TLS settings of built-in server
+pub struct ServerTlsConfig {
+    ///Path to PEM certificate chain.
+    pub cert: PathBuf,
+    ///PEM private key.
+    pub key: KeyValue,
+    #[serde(default)]
+    ///Path to PEM CA certificates to verify client certificates.
+    pub client_ca: Option<PathBuf>,
+    #[serde(default)]
+    ///Specifies whether client certificate is mandatory when `client_ca` is set.
+    pub client_cert_required: bool,
+}
+
+///Substitutes environment variables within `text`.
+///
+///Supported syntax:
+///- `${NAME}` - replaced with value of `NAME`, error if it is not set;
+///- `${NAME:-default}` - replaced with value of `NAME` or `default` if it is not set;
+///- `$${` - escape producing literal `${`.
+pub fn interpolate_env(text: &str) -> Result<String, ConfigError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(idx) = rest.find('$') {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if rest.starts_with("$${") {
+            result.push_str("${");
+            rest = &rest[3..];
+        } else if let Some(expr) = rest.strip_prefix("${") {
+            let end = match expr.find('}') {
+                Some(end) => end,
+                None => return Err(ConfigError::InvalidInterpolation(text.to_owned())),
+            };
+            let (name, default) = match expr[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&expr[..end], None),
+            };
+            if name.is_empty() {
+                return Err(ConfigError::InvalidInterpolation(text.to_owned()));
+            }
+
+            match (std::env::var(name), default) {
+                (Ok(value), _) => result.push_str(&value),
+                (Err(_), Some(default)) => result.push_str(default),
+                (Err(_), None) => return Err(ConfigError::MissingEnv(name.to_owned())),
+            }
+            rest = &expr[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn interpolate_value(value: &mut serde_yaml::Value) -> Result<(), ConfigError> {
+    match value {
+        serde_yaml::Value::String(text) => {
+            if text.contains('$') {
+                *text = interpolate_env(text)?;
+            }
+        }
+        serde_yaml::Value::Sequence(values) => {
+            for value in values.iter_mut() {
+                interpolate_value(value)?;
+            }
+        }
+        serde_yaml::Value::Mapping(values) => {
+            for (_, value) in values.iter_mut() {
+                interpolate_value(value)?;
+            }
+        }
+        serde_yaml::Value::Null | serde_yaml::Value::Bool(_) | serde_yaml::Value::Number(_) => (),
+    }
+
+    Ok(())
+}
+
+impl RouterConfig {
+    ///Parses config from YAML text.
+    ///
+    ///Environment variables are substituted within string values using [interpolate_env].
+    ///Substituted values stay strings, unless field expects number or boolean.
+    pub fn from_yaml(text: &str) -> Result<Self, ConfigError> {
+        let mut value = serde_yaml::from_str::<serde_yaml::Value>(text)?;
+        interpolate_value(&mut value)?;
+        let value = serde_json::to_value(value).map_err(<serde_yaml::Error as serde::de::Error>::custom)?;
+        lenient::from_value(value).map_err(ConfigError::Parse)
+    }
+
+    ///Reads config from YAML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let mut config = Self::from_yaml(&text)?;
+
+        if config.supergraph.is_relative() {
+            if let Some(dir) = path.parent() {
+                config.supergraph = dir.join(&config.supergraph);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl ServerIpFilterConfig {
+    ///Creates IP filter according to config.
+    pub fn filter(&self) -> IpFilter {
+        let mut filter = IpFilter::new();
+        for net in self.allow.iter() {
+            filter = filter.allow(*net);
+        }
+        for net in self.deny.iter() {
+            filter = filter.deny(*net);
+        }
+        for net in self.trusted_proxies.iter() {
+            filter = filter.trusted_proxy(*net);
+        }
+        filter
+    }
+}
+
+impl ServerConfig {
+    ///Creates admin server according to config, if it is enabled.
+    ///
+    ///Admin server without token is not created, unless it listens on loopback address.
+    ///Config reload is enabled by passing [ConfigWatcher] to [AdminServer::config_watcher].
+    pub fn admin_server(&self) -> Option<AdminServer> {
+        let config = self.admin_listener.as_ref()?;
+        if config.token.is_none() && !config.listen.ip().is_loopback() {
+            tracing::error!("Admin server on {} requires token, unless it listens on loopback", config.listen);
+            return None;
+        }
+        let mut server = AdminServer::with_token(config.listen, config.token.clone());
+        if let Some(ip_filter) = config.ip_filter.as_ref() {
+            server = server.ip_filter(ip_filter.filter());
+        }
+        Some(server)
+    }
+
+    ///Creates server builder according to config.
+    pub fn builder(&self) -> ServerBuilder {
+        let mut builder = ServerBuilder::new(self.listen);
+        if let Some(tls) = self.tls.as_ref() {
+            let mut config = TlsConfig::new(PemSource::File(tls.cert.clone()), tls.key.source());
+            if let Some(client_ca) = tls.client_ca.as_ref() {
+                config = config.client_auth(PemSource::File(client_ca.clone()), tls.client_cert_required);
+            }
+            builder = builder.tls(config);
+        }
+        if let Some(admin) = self.admin.as_ref() {
+            builder = builder.admin(&admin.prefix, admin.token.clone());
+        }
+        if let Some(path) = self.ready_path.as_ref() {
+            builder = builder.readiness(path);
+        }
+        if let Some(limit) = self.max_batch_size {
+            builder = builder.batching(limit);
+        }
+        if let Some(registration) = self.persisted_query_registration.as_ref() {
+            builder = builder.persisted_query_registration(&registration.path, registration.token.clone());
+        }
+        if self.etag {
+            builder = builder.etag();
+        }
+        if let Some(min_size) = self.compression_min_size {
+            builder = builder.compression(min_size);
+        }
+        if let Some(config) = self.ip_filter.as_ref() {
+            builder = builder.ip_filter(config.filter());
+        }
+        if let Some(config) = self.security_headers.as_ref() {
+            let mut headers = SecurityHeaders::new();
+            for (name, value) in config.headers.iter() {
+                let name = match HeaderName::from_bytes(name.as_bytes()) {
+                    Ok(name) => name,
+                    Err(_) => {
+                        tracing::warn!("Ignoring invalid security header name '{}'", name);
+                        continue;
+                    }
+                };
+                headers = match value.is_empty() {
+                    true => headers.without(&name),
+                    false => match HeaderValue::from_str(value) {
+                        Ok(value) => headers.header(name, value),
+                        Err(_) => {
+                            tracing::warn!("Ignoring invalid value of security header '{}'", name);
+                            continue;
+                        }
+                    },
+                };
+            }
+            if let Some(max_age) = config.hsts_max_age {
+                headers = headers.hsts(max_age);
+            }
+            if !config.mutation_no_store {
+                headers = headers.mutation_cache_control(None);
+            }
+            builder = builder.security_headers(headers);
+        }
+        builder
+    }
+}
+
+impl GraphqlRouter {
+    #[inline(always)]
+    ///Starts building router according to config, using only built-in plugins.
+    ///
+    ///Config is validated beforehand, reporting all problems as [ConfigError::Validation].
+    ///
+    ///All subgraphs listed in config are added as remote subgraphs, while local subgraphs can be
+    ///added to returned builder.
+    pub async fn from_config(config: &RouterConfig) -> Result<GraphqlRouterBuilder, ConfigError> {
+        Self::from_config_with_registry(config, &PluginRegistry::new()).await
+    }
+
+    ///Starts building router according to config, creating plugins using `registry`.
+    ///
+    ///Log levels of config are applied to [LogFilter::global] right away.
+    pub async fn from_config_with_registry(
+        config: &RouterConfig,
+        registry: &PluginRegistry,
+    ) -> Result<GraphqlRouterBuilder, ConfigError> {
+        let schema = Schema::read(&config.supergraph).map_err(ConfigError::Schema)?;
+        let errors = config.validate(&schema, registry);
+        if !errors.is_empty() {
+            return Err(ConfigError::Validation(errors));
+        }
+        //Levels are checked by validation.
+        let _ = config.log.apply(LogFilter::global());
+
+        let mut builder = GraphqlRouter::build(Arc::new(schema));
+        let client = Arc::new(crate::remote::http_client());
+
+        for (name, subgraph) in config.subgraphs.iter() {
+            let url = subgraph.url.parse::<hyper::Uri>().map_err(|error| ConfigError::InvalidUrl {
+                subgraph: name.clone(),
+                error,
+            })?;
+            let remote = RemoteGraphBuilder::new(name.as_str(), url).client(client.clone());
+            let remote = subgraph.options(&config.subgraph_defaults).apply(name, remote)?;
+            builder = builder.add_subgraph(remote);
+        }
+
+        for (name, plugin_config) in config.plugins.iter() {
+            //Plugins with builder hook are added by it, so that router can reach them.
+            builder = match registry.build(name, plugin_config.clone(), builder) {
+                Ok(Ok(built)) => {
+                    builder = built;
+                    continue;
+                }
+                Ok(Err(error)) => {
+                    return Err(ConfigError::Plugin {
+                        name: name.clone(),
+                        error,
+                    })
+                }
+                Err(unchanged) => unchanged,
+            };
+            let plugin = match registry.create(name, plugin_config.clone()).await {
+                Some(Ok(plugin)) => plugin,
+                Some(Err(error)) => {
+                    return Err(ConfigError::Plugin {
+                        name: name.clone(),
+                        error,
+                    })
+                }
+                None => return Err(ConfigError::UnknownPlugin(name.clone())),
+            };
+            builder = builder.with_dyn_plugin(name.clone(), plugin);
+        }
+
+        for operation in config.warmup.iter() {
+            builder = builder.warmup(operation.clone());
+        }
+
+        Ok(builder)
+    }
+}
@@ -0,0 +1,167 @@
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, Visitor};
+use serde_json::Value;
+
+use core::marker::PhantomData;
+
+///Deserializes `T` from `value`, parsing strings where numbers or booleans are expected.
+///
+///Values substituted from environment are always strings, so this allows them to be used for any scalar field,
+///while leaving string fields intact whatever their content is.
+pub(crate) fn from_value<T: DeserializeOwned, E: de::Error>(value: Value) -> Result<T, E> {
+    T::deserialize(Lenient::new(value))
+}
+
+struct Lenient<E> {
+    value: Value,
+    error: PhantomData<E>,
+}
+
+impl<E> Lenient<E> {
+    #[inline(always)]
+    fn new(value: Value) -> Self {
+        Self {
+            value,
+            error: PhantomData,
+        }
+    }
+}
+
+impl<'de, E: de::Error> IntoDeserializer<'de, E> for Lenient<E> {
+    type Deserializer = Self;
+
+    #[inline(always)]
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_scalar {
+    ($($method:ident => $ty:ty, $visit:ident;)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+            match self.value {
+                Value::String(text) => match text.trim().parse::<$ty>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&text), &visitor)),
+                },
+                value => Lenient::new(value).deserialize_any(visitor),
+            }
+        }
+    )*};
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for Lenient<E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(value) => visitor.visit_bool(value),
+            Value::Number(value) => value.deserialize_any(visitor).map_err(de::Error::custom),
+            Value::String(value) => visitor.visit_string(value),
+            Value::Array(values) => {
+                let mut seq = SeqDeserializer::<_, E>::new(values.into_iter().map(Lenient::new));
+                let result = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(result)
+            }
+            Value::Object(values) => {
+                let values = values.into_iter().map(|(key, value)| (key, Lenient::new(value)));
+                let mut map = MapDeserializer::<_, E>::new(values);
+                let result = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(result)
+            }
+        }
+    }
+
+    parse_scalar! {
+        deserialize_bool => bool, visit_bool;
+        deserialize_i8 => i64, visit_i64;
+        deserialize_i16 => i64, visit_i64;
+        deserialize_i32 => i64, visit_i64;
+        deserialize_i64 => i64, visit_i64;
+        deserialize_u8 => u64, visit_u64;
+        deserialize_u16 => u64, visit_u64;
+        deserialize_u32 => u64, visit_u64;
+        deserialize_u64 => u64, visit_u64;
+        deserialize_f32 => f64, visit_f64;
+        deserialize_f64 => f64, visit_f64;
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    #[inline(always)]
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        match self.value {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Object(values) if values.len() == 1 => match values.into_iter().next() {
+                Some((variant, value)) => visitor.visit_enum(Enum {
+                    variant,
+                    value: Lenient::new(value),
+                }),
+                None => Err(de::Error::custom("expected enum")),
+            },
+            _ => Err(de::Error::custom("expected enum variant name or map with single key")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct Enum<E> {
+    variant: String,
+    value: Lenient<E>,
+}
+
+impl<'de, E: de::Error> de::EnumAccess<'de> for Enum<E> {
+    type Error = E;
+    type Variant = Lenient<E>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant), E> {
+        let variant = seed.deserialize(IntoDeserializer::<'de, E>::into_deserializer(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de, E: de::Error> de::VariantAccess<'de> for Lenient<E> {
+    type Error = E;
+
+    fn unit_variant(self) -> Result<(), E> {
+        match self.value {
+            Value::Null => Ok(()),
+            _ => Err(de::Error::custom("expected unit variant")),
+        }
+    }
+
+    #[inline(always)]
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, E> {
+        seed.deserialize(self)
+    }
+
+    #[inline(always)]
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_any(visitor)
+    }
+
+    #[inline(always)]
+    fn struct_variant<V: Visitor<'de>>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, E> {
+        self.deserialize_any(visitor)
+    }
+}
@@ -0,0 +1,358 @@
+use super::{ConfigError, RouterConfig};
+use crate::log::LogFilter;
+use crate::plugins::PluginRegistry;
+use crate::remote::CircuitBreakerSettings;
+use crate::{GraphqlRouter, RemoteSettings};
+
+use core::fmt;
+use core::future::Future;
+use core::time::Duration;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+//Plugins depending on schema, which router creates on its own, so they cannot be created by registry.
+const HELD_PLUGINS: [&str; 2] = ["persisted_queries", "nullability"];
+
+#[derive(Debug, Default)]
+///Outcome of config reload
+pub struct ReloadReport {
+    ///Changes applied to running router.
+    pub applied: Vec<String>,
+    ///Changes that cannot be applied without restart.
+    pub rejected: Vec<String>,
+}
+
+impl ReloadReport {
+    #[inline(always)]
+    ///Returns whether config had no changes.
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.rejected.is_empty()
+    }
+}
+
+impl fmt::Display for ReloadReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Applied: ")?;
+        fmt.write_str(&self.applied.join(", "))?;
+        fmt.write_str("; Rejected (requires restart): ")?;
+        fmt.write_str(&self.rejected.join(", "))
+    }
+}
+
+//Runs file system operation on blocking pool, so that it does not stall runtime.
+async fn blocking<T: Send + 'static, F: FnOnce() -> Result<T, ConfigError> + Send + 'static>(
+    op: F,
+) -> Result<T, ConfigError> {
+    match tokio::task::spawn_blocking(op).await {
+        Ok(result) => result,
+        Err(error) => Err(ConfigError::Io(io::Error::new(io::ErrorKind::Other, error))),
+    }
+}
+
+#[inline]
+fn modified(path: &Path) -> Result<SystemTime, ConfigError> {
+    Ok(std::fs::metadata(path).and_then(|meta| meta.modified())?)
+}
+
+//Change of subgraph option, tracking whether it was applied to every subgraph, so that default can be updated.
+struct OptionChange {
+    field: &'static str,
+    is_applied: bool,
+}
+
+impl OptionChange {
+    #[inline(always)]
+    fn new(field: &'static str) -> Self {
+        Self { field, is_applied: true }
+    }
+
+    //Applies change of subgraph `name` via `apply`, returning whether subgraph's own value is in effect now.
+    fn apply<F>(&mut self, report: &mut ReloadReport, name: &str, is_changed: bool, apply: F) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        if !is_changed {
+            return true;
+        }
+        let path = format!("subgraphs.{}.{}", name, self.field);
+        let is_applied = apply();
+        match is_applied {
+            true => report.applied.push(path),
+            false => report.rejected.push(path),
+        }
+        self.is_applied &= is_applied;
+        is_applied
+    }
+}
+
+///Watches config file and applies changes which are safe to change at runtime.
+///
+///Applied at runtime are:
+///
+///- subgraph timeouts, retries, retry backoffs, circuit breakers, headers and auth tokens;
+///- configs of plugins created by registry, e.g. request or rate limits and header propagation, which rebuild
+///router with new plugin;
+///- log levels.
+///
+///Other changes are reported as rejected, leaving current config in effect.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    current: RouterConfig,
+    modified: Option<SystemTime>,
+    router: GraphqlRouter,
+    registry: PluginRegistry,
+}
+
+impl ConfigWatcher {
+    ///Creates watcher over `path`, which was used to create `router` with `current` config.
+    pub fn new<P: Into<PathBuf>>(path: P, current: RouterConfig, router: &GraphqlRouter) -> Self {
+        let path = path.into();
+        let modified = modified(&path).ok();
+        Self {
+            path,
+            current,
+            modified,
+            router: router.clone(),
+            registry: PluginRegistry::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Sets `registry`, which was used to create router, so that plugins can be created with new configs.
+    ///
+    ///Default is registry of built-in plugins.
+    pub fn registry(mut self, registry: PluginRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    #[inline(always)]
+    ///Returns config currently in effect.
+    pub fn current(&self) -> &RouterConfig {
+        &self.current
+    }
+
+    ///Re-reads config file and applies changes.
+    ///
+    ///New config is validated first, and nothing is applied if it is invalid.
+    pub async fn reload(&mut self) -> Result<ReloadReport, ConfigError> {
+        let path = self.path.clone();
+        let new = blocking(move || RouterConfig::from_file(path)).await?;
+        let errors = new.validate(&self.router.schema(), &self.registry);
+        if !errors.is_empty() {
+            return Err(ConfigError::Validation(errors));
+        }
+
+        let mut report = ReloadReport::default();
+        if new.supergraph != self.current.supergraph {
+            report.rejected.push("supergraph".to_owned());
+        }
+        if new.server != self.current.server {
+            report.rejected.push("server".to_owned());
+        }
+        self.reload_log(&new, &mut report);
+        self.reload_subgraphs(&new, &mut report);
+        self.reload_plugins(&new, &mut report).await;
+
+        Ok(report)
+    }
+
+    fn reload_log(&mut self, new: &RouterConfig, report: &mut ReloadReport) {
+        if new.log == self.current.log {
+            return;
+        }
+        match self.current.log.diff(&new.log).apply(LogFilter::global()) {
+            Ok(()) => {
+                self.current.log = new.log.clone();
+                report.applied.push("log".to_owned());
+            }
+            Err(error) => report.rejected.push(format!("log: {}", error)),
+        }
+    }
+
+    fn reload_subgraphs(&mut self, new: &RouterConfig, report: &mut ReloadReport) {
+        for name in self.current.subgraphs.keys() {
+            if !new.subgraphs.contains_key(name) {
+                report.rejected.push(format!("subgraphs.{}: removed", name));
+            }
+        }
+
+        let mut timeout = OptionChange::new("timeout_ms");
+        let mut retry = OptionChange::new("max_retry_num");
+        let mut backoff = OptionChange::new("retry_backoff_ms");
+        let mut circuit_breaker = OptionChange::new("circuit_breaker");
+        let mut headers = OptionChange::new("headers");
+        for (name, subgraph) in new.subgraphs.iter() {
+            let defaults = &self.current.subgraph_defaults;
+            let current = match self.current.subgraphs.get_mut(name) {
+                Some(current) => current,
+                None => {
+                    report.rejected.push(format!("subgraphs.{}: added", name));
+                    continue;
+                }
+            };
+
+            if current.url != subgraph.url {
+                report.rejected.push(format!("subgraphs.{}.url", name));
+            }
+
+            let current_options = current.options(defaults);
+            let new_options = subgraph.options(&new.subgraph_defaults);
+            if current_options.tls != new_options.tls {
+                report.rejected.push(format!("subgraphs.{}.tls", name));
+            }
+            if current_options.max_concurrency != new_options.max_concurrency {
+                report.rejected.push(format!("subgraphs.{}.max_concurrency", name));
+            }
+            if current_options.encoding != new_options.encoding {
+                report.rejected.push(format!("subgraphs.{}.encoding", name));
+            }
+
+            //Own values of subgraph are kept apart from defaults, so that current config matches the file.
+            let settings = self.router.subgraph_settings(name);
+
+            let is_changed = current_options.timeout_ms != new_options.timeout_ms;
+            if timeout.apply(report, name, is_changed, || match settings.as_ref() {
+                Some(settings) => {
+                    let timeout = new_options.timeout_ms.map(Duration::from_millis);
+                    settings.update(|settings| settings.timeout = timeout);
+                    true
+                }
+                None => false,
+            }) {
+                current.timeout_ms = subgraph.timeout_ms;
+            }
+
+            let is_changed = current_options.max_retry_num != new_options.max_retry_num;
+            if retry.apply(report, name, is_changed, || match settings.as_ref() {
+                Some(settings) => {
+                    let max_retry_num = new_options
+                        .max_retry_num
+                        .unwrap_or_else(|| RemoteSettings::default().max_retry_num);
+                    settings.update(|settings| settings.max_retry_num = max_retry_num);
+                    true
+                }
+                None => false,
+            }) {
+                current.max_retry_num = subgraph.max_retry_num;
+            }
+
+            let is_changed = current_options.retry_backoff_ms != new_options.retry_backoff_ms;
+            if backoff.apply(report, name, is_changed, || match settings.as_ref() {
+                Some(settings) => {
+                    let retry_backoff = new_options.retry_backoff_ms.map(Duration::from_millis);
+                    settings.update(|settings| settings.retry_backoff = retry_backoff);
+                    true
+                }
+                None => false,
+            }) {
+                current.retry_backoff_ms = subgraph.retry_backoff_ms;
+            }
+
+            let is_changed = current_options.circuit_breaker != new_options.circuit_breaker;
+            if circuit_breaker.apply(report, name, is_changed, || match settings.as_ref() {
+                Some(settings) => {
+                    let breaker = new_options.circuit_breaker.map(|config| CircuitBreakerSettings {
+                        failure_threshold: config.failure_threshold,
+                        reset_timeout: config.reset_timeout(),
+                    });
+                    settings.update(|settings| settings.circuit_breaker = breaker);
+                    true
+                }
+                None => false,
+            }) {
+                current.circuit_breaker = subgraph.circuit_breaker;
+            }
+
+            let is_changed =
+                current_options.headers != new_options.headers || current_options.auth_token != new_options.auth_token;
+            if headers.apply(report, name, is_changed, || match (settings.as_ref(), new_options.remote_headers(name)) {
+                (Some(settings), Ok(headers)) => {
+                    settings.set_headers(headers);
+                    true
+                }
+                _ => false,
+            }) {
+                current.headers = subgraph.headers.clone();
+                current.auth_token = subgraph.auth_token.clone();
+            }
+        }
+
+        //Defaults are in effect only once every subgraph, using them, accepted change.
+        let defaults = &mut self.current.subgraph_defaults;
+        if timeout.is_applied {
+            defaults.timeout_ms = new.subgraph_defaults.timeout_ms;
+        }
+        if retry.is_applied {
+            defaults.max_retry_num = new.subgraph_defaults.max_retry_num;
+        }
+        if backoff.is_applied {
+            defaults.retry_backoff_ms = new.subgraph_defaults.retry_backoff_ms;
+        }
+        if circuit_breaker.is_applied {
+            defaults.circuit_breaker = new.subgraph_defaults.circuit_breaker;
+        }
+        if headers.is_applied {
+            defaults.headers = new.subgraph_defaults.headers.clone();
+            defaults.auth_token = new.subgraph_defaults.auth_token.clone();
+        }
+    }
+
+    async fn reload_plugins(&mut self, new: &RouterConfig, report: &mut ReloadReport) {
+        if !new.plugins.keys().eq(self.current.plugins.keys()) {
+            report.rejected.push("plugins: added, removed or reordered".to_owned());
+            return;
+        }
+
+        for (name, config) in new.plugins.iter() {
+            if self.current.plugins.get(name) == Some(config) {
+                continue;
+            } else if HELD_PLUGINS.contains(&name.as_str()) {
+                report.rejected.push(format!("plugins.{}", name));
+                continue;
+            }
+            match self.router.reconfigure_plugin(name, &self.registry, config.clone()).await {
+                Ok(()) => {
+                    self.current.plugins.insert(name.clone(), config.clone());
+                    report.applied.push(format!("plugins.{}", name));
+                }
+                Err(error) => report.rejected.push(format!("plugins.{}: {}", name, error)),
+            }
+        }
+    }
+
+    ///Reloads config if file modification time changed since last check.
+    pub async fn poll(&mut self) -> Result<Option<ReloadReport>, ConfigError> {
+        let path = self.path.clone();
+        let modified = blocking(move || modified(&path)).await?;
+        if self.modified == Some(modified) {
+            return Ok(None);
+        }
+
+        self.modified = Some(modified);
+        self.reload().await.map(Some)
+    }
+
+    ///Polls config file every `interval` until `shutdown` completes, logging outcome of each reload.
+    pub async fn watch<F: Future<Output = ()>>(mut self, interval: Duration, shutdown: F) {
+        let mut interval = tokio::time::interval(interval);
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = interval.tick() => match self.poll().await {
+                    Ok(Some(report)) if !report.rejected.is_empty() => {
+                        tracing::warn!("{}: Config reloaded partially. {}", self.path.display(), report);
+                    }
+                    Ok(Some(report)) if !report.is_empty() => {
+                        tracing::info!("{}: Config reloaded. {}", self.path.display(), report);
+                    }
+                    Ok(_) => (),
+                    Err(error) => tracing::warn!("{}: Config reload failed: {}", self.path.display(), error),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use super::{RouterConfig, SecretValue, SubgraphOptions};
+use crate::plugins::PluginRegistry;
+use crate::{RemoteSettings, Schema};
+use hyper::header::{HeaderName, HeaderValue};
+
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Single problem found in config
+pub struct ValidationError {
+    ///Path to offending field, e.g. `subgraphs.product.timeout_ms`.
+    pub path: String,
+    ///Description of problem.
+    pub message: String,
+}
+
+impl ValidationError {
+    #[inline(always)]
+    fn new<P: Into<String>, M: Into<String>>(path: P, message: M) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{}: {}", self.path, self.message))
+    }
+}
+
+//Checks options, which are effective for subgraph, so retries can come from defaults.
+fn validate_retries(path: &str, options: &SubgraphOptions, errors: &mut Vec<ValidationError>) {
+    let max_retry_num = options
+        .max_retry_num
+        .unwrap_or_else(|| RemoteSettings::default().max_retry_num);
+    if options.timeout_ms == Some(0) && max_retry_num > 0 {
+        errors.push(ValidationError::new(
+            format!("{}.timeout_ms", path),
+            "Zero timeout makes retries impossible",
+        ));
+    }
+}
+
+//Checks values, which are specified at `path` itself.
+fn validate_options(path: &str, options: &SubgraphOptions, errors: &mut Vec<ValidationError>) {
+    if options.max_retry_num == Some(0) {
+        errors.push(ValidationError::new(
+            format!("{}.max_retry_num", path),
+            "Number of attempts must be at least 1, as it includes the first one",
+        ));
+    }
+    if options.max_concurrency == Some(0) {
+        errors.push(ValidationError::new(
+            format!("{}.max_concurrency", path),
+            "Zero concurrency would block all requests",
+        ));
+    }
+
+    for (header, value) in options.headers.iter() {
+        if HeaderName::from_bytes(header.as_bytes()).is_err() {
+            errors.push(ValidationError::new(
+                format!("{}.headers.{}", path, header),
+                "Invalid header name",
+            ));
+        }
+        if let SecretValue::Inline(value) = value {
+            if HeaderValue::from_str(value).is_err() {
+                errors.push(ValidationError::new(
+                    format!("{}.headers.{}", path, header),
+                    "Invalid header value",
+                ));
+            }
+        }
+    }
+
+    if let Some(circuit_breaker) = options.circuit_breaker.as_ref() {
+        if circuit_breaker.failure_threshold == 0 {
+            errors.push(ValidationError::new(
+                format!("{}.circuit_breaker.failure_threshold", path),
+                "Zero threshold would open circuit without failures",
+            ));
+        }
+        if circuit_breaker.reset_timeout_ms == 0 {
+            errors.push(ValidationError::new(
+                format!("{}.circuit_breaker.reset_timeout_ms", path),
+                "Zero reset timeout would never reject requests",
+            ));
+        }
+    }
+
+    if let Some(tls) = options.tls.as_ref() {
+        if tls.cert.is_some() != tls.key.is_some() {
+            errors.push(ValidationError::new(
+                format!("{}.tls", path),
+                "Both cert and key must be specified",
+            ));
+        }
+    }
+}
+
+impl RouterConfig {
+    ///Validates config against `schema` and `registry`, returning all found problems.
+    pub fn validate(&self, schema: &Schema, registry: &PluginRegistry) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        validate_retries("subgraph_defaults", &self.subgraph_defaults, &mut errors);
+        validate_options("subgraph_defaults", &self.subgraph_defaults, &mut errors);
+
+        for (name, subgraph) in self.subgraphs.iter() {
+            let path = format!("subgraphs.{}", name);
+
+            if !schema.subgraphs().any(|(service_name, _)| service_name == name) {
+                errors.push(ValidationError::new(path.as_str(), "Subgraph is not present in supergraph"));
+            }
+
+            if let Err(error) = subgraph.url.parse::<hyper::Uri>() {
+                errors.push(ValidationError::new(format!("{}.url", path), error.to_string()));
+            }
+
+            //Problems of defaults are reported once, at their own path.
+            if subgraph.timeout_ms.is_some() || subgraph.max_retry_num.is_some() {
+                validate_retries(&path, &subgraph.options(&self.subgraph_defaults), &mut errors);
+            }
+            validate_options(&path, &subgraph.options(&SubgraphOptions::default()), &mut errors);
+        }
+
+        for name in self.plugins.keys() {
+            if !registry.contains(name) {
+                errors.push(ValidationError::new(format!("plugins.{}", name), "Unknown plugin"));
+            }
+        }
+
+        //Operations sent by id are resolved by persisted queries, so they must be known before audit.
+        let position = |name: &str| self.plugins.keys().position(|key| key == name);
+        if let (Some(audit), Some(persisted)) = (position("audit"), position("persisted_queries")) {
+            if audit < persisted {
+                errors.push(ValidationError::new("plugins.audit", "Must be listed after persisted_queries"));
+            }
+        }
+
+        for (idx, operation) in self.warmup.iter().enumerate() {
+            if operation.is_mutation() {
+                errors.push(ValidationError::new(format!("warmup.{}", idx), "Mutations cannot be used for warmup"));
+            }
+        }
+
+        if let Err(error) = self.log.validate() {
+            errors.push(ValidationError::new("log", error));
+        }
+
+        if let Some(admin) = self.server.as_ref().and_then(|server| server.admin_listener.as_ref()) {
+            if admin.token.is_none() && !admin.listen.ip().is_loopback() {
+                errors.push(ValidationError::new(
+                    "server.admin_listener.token",
+                    "Token is required, unless admin server listens on loopback address",
+                ));
+            }
+        }
+
+        if let Some(tls) = self.server.as_ref().and_then(|server| server.tls.as_ref()) {
+            if tls.client_cert_required && tls.client_ca.is_none() {
+                errors.push(ValidationError::new(
+                    "server.tls.client_cert_required",
+                    "Client certificate cannot be required without client_ca",
+                ));
+            }
+        }
+
+        errors
+    }
+}
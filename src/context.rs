@@ -0,0 +1,145 @@
+//! Typed request context, shared by plugins
+//!
+//! Router [Context] is map of JSON values by string keys, so plugins need to agree on key and
+//! type of value. [ContextEntry] binds both to a type, so that value is stored and read by its type:
+//!
+//! ```no_run
+//! use graphql_router::context::{ClientIdentity, TypedContext};
+//!
+//! fn client(context: &apollo_router_core::Context) -> Option<String> {
+//!     match TypedContext::new(context).get::<ClientIdentity>() {
+//!         Ok(Some(ClientIdentity(client))) => Some(client),
+//!         _ => None,
+//!     }
+//! }
+//! ```
+
+use apollo_router_core::{Context, RouterRequest};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tower::BoxError;
+
+///Value, stored in request context under its own key.
+pub trait ContextEntry: Serialize + DeserializeOwned {
+    ///Key within context, expected to be prefixed with name of plugin or crate.
+    const KEY: &'static str;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+///Id of request, set by [DebugCapture](crate::plugins::DebugCapture) unless already present.
+pub struct RequestId(pub String);
+
+impl ContextEntry for RequestId {
+    const KEY: &'static str = "graphql_router::request_id";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+///Identity of authenticated client, set by [ApiKeys](crate::plugins::ApiKeys).
+pub struct ClientIdentity(pub String);
+
+impl ContextEntry for ClientIdentity {
+    const KEY: &'static str = "graphql_router::api_client";
+}
+
+impl ClientIdentity {
+    ///Returns identity of client of `req`, falling back to value of `header` when client is not authenticated.
+    pub(crate) fn of(req: &RouterRequest, header: Option<&str>) -> Option<String> {
+        if let Ok(Some(ClientIdentity(client))) = TypedContext::new(&req.context).get() {
+            return Some(client);
+        }
+        let value = req.originating_request.headers().get(header?)?;
+        Some(String::from_utf8_lossy(value.as_bytes()).into_owned())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(transparent)]
+///Claims of verified JWT, to be set by authentication plugin.
+pub struct JwtClaims(pub serde_json::Map<String, serde_json::Value>);
+
+impl ContextEntry for JwtClaims {
+    const KEY: &'static str = "graphql_router::jwt_claims";
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+///Priority of request, set by [OperationPolicies](crate::plugins::OperationPolicies).
+///
+///Requests without priority are treated as [Priority::Normal].
+pub enum Priority {
+    ///Bulk traffic, which may wait for others.
+    Low,
+    ///Regular traffic.
+    Normal,
+    ///Critical traffic, which is not limited by shared fetch parallelism.
+    High,
+}
+
+impl Default for Priority {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl ContextEntry for Priority {
+    const KEY: &'static str = "graphql_router::priority";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+///Deployment variant of subgraphs, requested for request by [OperationPolicies](crate::plugins::OperationPolicies).
+///
+///Subgraphs without such variant use their own selection.
+pub struct SubgraphVariant(pub String);
+
+impl ContextEntry for SubgraphVariant {
+    const KEY: &'static str = "graphql_router::subgraph_variant";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(transparent)]
+///Sampling decision of trace, whose client deferred it, made by [TracePropagation](crate::plugins::TracePropagation)
+///according to [LogFilter](crate::log::LogFilter) sampling ratio.
+pub struct TraceSampled(pub bool);
+
+impl ContextEntry for TraceSampled {
+    const KEY: &'static str = "graphql_router::trace_sampled";
+}
+
+#[derive(Clone, Copy)]
+///Typed view of request [Context].
+///
+///Values are request-scoped, as context is created per request and shared by all its services.
+pub struct TypedContext<'a> {
+    context: &'a Context,
+}
+
+impl<'a> TypedContext<'a> {
+    #[inline(always)]
+    ///Creates view of `context`.
+    pub fn new(context: &'a Context) -> Self {
+        Self { context }
+    }
+
+    #[inline]
+    ///Stores `value`, replacing previous value of the same type.
+    pub fn insert<T: ContextEntry>(&self, value: T) -> Result<(), BoxError> {
+        self.context.insert(T::KEY, value).map(|_| ()).map_err(Into::into)
+    }
+
+    #[inline]
+    ///Returns value of type `T`, if it is stored.
+    pub fn get<T: ContextEntry>(&self) -> Result<Option<T>, BoxError> {
+        self.context.get::<_, T>(T::KEY).map_err(Into::into)
+    }
+
+    #[inline]
+    ///Returns whether value of type `T` is stored.
+    pub fn contains<T: ContextEntry>(&self) -> bool {
+        matches!(self.get::<T>(), Ok(Some(_)))
+    }
+}
@@ -0,0 +1,386 @@
+//! Diff of supergraph schemas
+//!
+//! Compares types exposed to clients, classifying each change by its impact on existing operations.
+//! Federation types and directives (e.g. `join__Graph`) are internal to router, so they are not compared.
+
+use async_graphql::parser::types::{
+    BaseType, FieldDefinition, InputValueDefinition, Type, TypeKind, TypeSystemDefinition,
+};
+
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug)]
+///Failure to parse schema for diff
+pub enum SchemaDiffError {
+    ///Current schema is not valid SDL.
+    Current(String),
+    ///Candidate schema is not valid SDL.
+    Candidate(String),
+}
+
+impl fmt::Display for SchemaDiffError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaDiffError::Current(error) => fmt.write_fmt(format_args!("Invalid current schema: {}", error)),
+            SchemaDiffError::Candidate(error) => fmt.write_fmt(format_args!("Invalid candidate schema: {}", error)),
+        }
+    }
+}
+
+impl std::error::Error for SchemaDiffError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+///Impact of change on existing operations
+pub enum Severity {
+    ///Existing operations might fail.
+    Breaking,
+    ///Existing operations keep working, but clients might not handle new values.
+    Dangerous,
+    ///Existing operations are not affected.
+    Safe,
+}
+
+impl Severity {
+    #[inline(always)]
+    ///Returns textual representation of severity.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Breaking => "breaking",
+            Severity::Dangerous => "dangerous",
+            Severity::Safe => "safe",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Single change between schemas
+pub struct SchemaChange {
+    ///Impact of change.
+    pub severity: Severity,
+    ///Coordinate of changed element, e.g. `User.name` or `Query.user(id:)`.
+    pub path: String,
+    ///Human readable description.
+    pub description: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+///Changes from current schema to candidate
+pub struct SchemaDiff {
+    ///Changes, ordered by path.
+    pub changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    #[inline(always)]
+    ///Returns whether schemas expose the same types.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    #[inline]
+    ///Returns whether any change can break existing operations.
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(|change| change.severity == Severity::Breaking)
+    }
+
+    ///Returns JSON representation of diff.
+    pub fn to_json(&self) -> serde_json::Value {
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| {
+                serde_json::json!({
+                    "severity": change.severity.as_str(),
+                    "path": change.path,
+                    "description": change.description,
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "breaking": self.is_breaking(),
+            "changes": changes,
+        })
+    }
+
+    #[inline]
+    fn push(&mut self, severity: Severity, path: String, description: String) {
+        self.changes.push(SchemaChange {
+            severity,
+            path,
+            description,
+        });
+    }
+}
+
+struct InputValue {
+    ty: Type,
+    has_default: bool,
+}
+
+impl InputValue {
+    #[inline(always)]
+    fn new(value: &InputValueDefinition) -> Self {
+        Self {
+            ty: value.ty.node.clone(),
+            has_default: value.default_value.is_some(),
+        }
+    }
+
+    #[inline(always)]
+    fn is_required(&self) -> bool {
+        !self.ty.nullable && !self.has_default
+    }
+}
+
+struct Field {
+    ty: Type,
+    arguments: BTreeMap<String, InputValue>,
+}
+
+enum Definition {
+    Scalar,
+    Object(BTreeSet<String>, BTreeMap<String, Field>),
+    Interface(BTreeSet<String>, BTreeMap<String, Field>),
+    Union(BTreeSet<String>),
+    Enum(BTreeSet<String>),
+    Input(BTreeMap<String, InputValue>),
+}
+
+impl Definition {
+    #[inline(always)]
+    fn kind(&self) -> &'static str {
+        match self {
+            Definition::Scalar => "scalar",
+            Definition::Object(..) => "object",
+            Definition::Interface(..) => "interface",
+            Definition::Union(..) => "union",
+            Definition::Enum(..) => "enum",
+            Definition::Input(..) => "input object",
+        }
+    }
+}
+
+#[inline(always)]
+fn is_internal(name: &str) -> bool {
+    //Federation specs prefix their names with spec name, e.g. `join__Graph`, while introspection uses `__Type`.
+    name.contains("__")
+}
+
+fn parse(sdl: &str) -> Result<BTreeMap<String, Definition>, String> {
+    let document = async_graphql::parser::parse_schema(sdl).map_err(|error| error.to_string())?;
+    let mut types = BTreeMap::new();
+    for definition in document.definitions {
+        let definition = match definition {
+            TypeSystemDefinition::Type(definition) => definition.node,
+            _ => continue,
+        };
+        let name = definition.name.node.to_string();
+        if is_internal(&name) {
+            continue;
+        }
+
+        //Extensions are merged into their type, as clients see them the same way.
+        let entry = types.entry(name).or_insert_with(|| match &definition.kind {
+            TypeKind::Scalar => Definition::Scalar,
+            TypeKind::Object(_) => Definition::Object(BTreeSet::new(), BTreeMap::new()),
+            TypeKind::Interface(_) => Definition::Interface(BTreeSet::new(), BTreeMap::new()),
+            TypeKind::Union(_) => Definition::Union(BTreeSet::new()),
+            TypeKind::Enum(_) => Definition::Enum(BTreeSet::new()),
+            TypeKind::InputObject(_) => Definition::Input(BTreeMap::new()),
+        });
+        match (entry, definition.kind) {
+            (Definition::Object(implements, fields), TypeKind::Object(object)) => {
+                implements.extend(object.implements.iter().map(|name| name.node.to_string()));
+                fields.extend(
+                    object.fields.iter().map(|field| (field.node.name.node.to_string(), field_of(&field.node))),
+                );
+            }
+            (Definition::Interface(implements, fields), TypeKind::Interface(interface)) => {
+                implements.extend(interface.implements.iter().map(|name| name.node.to_string()));
+                fields.extend(
+                    interface.fields.iter().map(|field| (field.node.name.node.to_string(), field_of(&field.node))),
+                );
+            }
+            (Definition::Union(members), TypeKind::Union(union)) => {
+                members.extend(union.members.iter().map(|member| member.node.to_string()));
+            }
+            (Definition::Enum(values), TypeKind::Enum(enumeration)) => {
+                values.extend(enumeration.values.iter().map(|value| value.node.value.node.to_string()));
+            }
+            (Definition::Input(fields), TypeKind::InputObject(input)) => {
+                fields.extend(
+                    input.fields.iter().map(|field| (field.node.name.node.to_string(), InputValue::new(&field.node))),
+                );
+            }
+            (Definition::Scalar, TypeKind::Scalar) => (),
+            (_, _) => return Err(format!("Type '{}' is extended with different kind", definition.name.node)),
+        }
+    }
+    Ok(types)
+}
+
+fn field_of(field: &FieldDefinition) -> Field {
+    Field {
+        ty: field.ty.node.clone(),
+        arguments: field
+            .arguments
+            .iter()
+            .map(|argument| (argument.node.name.node.to_string(), InputValue::new(&argument.node)))
+            .collect(),
+    }
+}
+
+///Returns whether values of `new` type can be returned to clients expecting `old` type.
+fn is_output_compatible(old: &Type, new: &Type) -> bool {
+    if !old.nullable && new.nullable {
+        return false;
+    }
+    match (&old.base, &new.base) {
+        (BaseType::Named(old), BaseType::Named(new)) => old == new,
+        (BaseType::List(old), BaseType::List(new)) => is_output_compatible(old, new),
+        _ => false,
+    }
+}
+
+///Returns whether values, that clients send as `old` type, are accepted as `new` type.
+fn is_input_compatible(old: &Type, new: &Type) -> bool {
+    if old.nullable && !new.nullable {
+        return false;
+    }
+    match (&old.base, &new.base) {
+        (BaseType::Named(old), BaseType::Named(new)) => old == new,
+        (BaseType::List(old), BaseType::List(new)) => is_input_compatible(old, new),
+        _ => false,
+    }
+}
+
+fn diff_set(diff: &mut SchemaDiff, path: &str, what: &str, old: &BTreeSet<String>, new: &BTreeSet<String>) {
+    for removed in old.difference(new) {
+        let description = format!("{} '{}' was removed from '{}'", what, removed, path);
+        diff.push(Severity::Breaking, format!("{}.{}", path, removed), description);
+    }
+    for added in new.difference(old) {
+        //New enum values and union members might be returned to clients, which do not expect them.
+        let description = format!("{} '{}' was added to '{}'", what, added, path);
+        diff.push(Severity::Dangerous, format!("{}.{}", path, added), description);
+    }
+}
+
+fn diff_inputs<P: Fn(&str) -> String>(
+    diff: &mut SchemaDiff,
+    path: P,
+    what: &str,
+    old: &BTreeMap<String, InputValue>,
+    new: &BTreeMap<String, InputValue>,
+) {
+    for (name, old_value) in old.iter() {
+        let value_path = path(name);
+        match new.get(name) {
+            None => {
+                let description = format!("{} '{}' was removed", what, value_path);
+                diff.push(Severity::Breaking, value_path, description);
+            }
+            Some(new_value) if old_value.ty != new_value.ty => {
+                let severity = match is_input_compatible(&old_value.ty, &new_value.ty) {
+                    true => Severity::Safe,
+                    false => Severity::Breaking,
+                };
+                let description = format!(
+                    "{} '{}' changed type from '{}' to '{}'",
+                    what, value_path, old_value.ty, new_value.ty
+                );
+                diff.push(severity, value_path, description);
+            }
+            Some(_) => (),
+        }
+    }
+    for (name, new_value) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        let value_path = path(name);
+        let severity = match new_value.is_required() {
+            true => Severity::Breaking,
+            false => Severity::Safe,
+        };
+        let description = format!("{} '{}' of type '{}' was added", what, value_path, new_value.ty);
+        diff.push(severity, value_path, description);
+    }
+}
+
+fn diff_fields(diff: &mut SchemaDiff, type_name: &str, old: &BTreeMap<String, Field>, new: &BTreeMap<String, Field>) {
+    for (name, old_field) in old.iter() {
+        let path = format!("{}.{}", type_name, name);
+        let new_field = match new.get(name) {
+            Some(new_field) => new_field,
+            None => {
+                let description = format!("Field '{}' was removed", path);
+                diff.push(Severity::Breaking, path, description);
+                continue;
+            }
+        };
+        if old_field.ty != new_field.ty {
+            let severity = match is_output_compatible(&old_field.ty, &new_field.ty) {
+                true => Severity::Safe,
+                false => Severity::Breaking,
+            };
+            let description = format!("Field '{}' changed type from '{}' to '{}'", path, old_field.ty, new_field.ty);
+            diff.push(severity, path.clone(), description);
+        }
+        let argument_path = |argument: &str| format!("{}({}:)", path, argument);
+        diff_inputs(diff, argument_path, "Argument", &old_field.arguments, &new_field.arguments);
+    }
+    for (name, new_field) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        let path = format!("{}.{}", type_name, name);
+        let description = format!("Field '{}' of type '{}' was added", path, new_field.ty);
+        diff.push(Severity::Safe, path, description);
+    }
+}
+
+///Compares `current` SDL with `candidate` one.
+pub fn diff(current: &str, candidate: &str) -> Result<SchemaDiff, SchemaDiffError> {
+    let current = parse(current).map_err(SchemaDiffError::Current)?;
+    let candidate = parse(candidate).map_err(SchemaDiffError::Candidate)?;
+
+    let mut diff = SchemaDiff::default();
+    for (name, old) in current.iter() {
+        let new = match candidate.get(name) {
+            Some(new) => new,
+            None => {
+                let description = format!("Type '{}' was removed", name);
+                diff.push(Severity::Breaking, name.clone(), description);
+                continue;
+            }
+        };
+        match (old, new) {
+            (Definition::Scalar, Definition::Scalar) => (),
+            (Definition::Object(old_implements, old), Definition::Object(new_implements, new))
+            | (Definition::Interface(old_implements, old), Definition::Interface(new_implements, new)) => {
+                for removed in old_implements.difference(new_implements) {
+                    let description = format!("Type '{}' no longer implements '{}'", name, removed);
+                    diff.push(Severity::Breaking, name.clone(), description);
+                }
+                for added in new_implements.difference(old_implements) {
+                    let description = format!("Type '{}' now implements '{}'", name, added);
+                    diff.push(Severity::Safe, name.clone(), description);
+                }
+                diff_fields(&mut diff, name, old, new);
+            }
+            (Definition::Union(old), Definition::Union(new)) => diff_set(&mut diff, name, "Member", old, new),
+            (Definition::Enum(old), Definition::Enum(new)) => diff_set(&mut diff, name, "Value", old, new),
+            (Definition::Input(old), Definition::Input(new)) => {
+                diff_inputs(&mut diff, |field| format!("{}.{}", name, field), "Input field", old, new)
+            }
+            (old, new) => {
+                let description = format!("Type '{}' changed kind from {} to {}", name, old.kind(), new.kind());
+                diff.push(Severity::Breaking, name.clone(), description);
+            }
+        }
+    }
+    for (name, new) in candidate.iter().filter(|(name, _)| !current.contains_key(*name)) {
+        let description = format!("Type '{}' of kind {} was added", name, new.kind());
+        diff.push(Severity::Safe, name.clone(), description);
+    }
+
+    diff.changes.sort_by(|left, right| left.path.cmp(&right.path));
+    Ok(diff)
+}
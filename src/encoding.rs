@@ -0,0 +1,177 @@
+//! Wire encodings of subgraph traffic
+//!
+//! JSON is always supported, while binary encodings are enabled by features:
+//! - `msgpack` - MessagePack;
+//! - `cbor` - CBOR.
+//!
+//! Router sends subgraph request in configured encoding, listing it together with JSON in `Accept`, and decodes
+//! response according to its `Content-Type`, so subgraphs are free to answer with JSON.
+//! Subgraphs built with `async-graphql` can use [decode_request] and [negotiate] to support the same negotiation.
+
+use bytes::Bytes;
+use hyper::http::HeaderValue;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use core::fmt;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+///Encoding of request and response bodies
+pub enum Encoding {
+    ///`application/json`
+    Json,
+    #[cfg(feature = "msgpack")]
+    #[serde(rename = "msgpack")]
+    ///`application/msgpack`
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    ///`application/cbor`
+    Cbor,
+}
+
+impl Default for Encoding {
+    #[inline(always)]
+    fn default() -> Self {
+        Encoding::Json
+    }
+}
+
+impl Encoding {
+    #[inline]
+    ///Returns MIME type of encoding.
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => "application/msgpack",
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => "application/cbor",
+        }
+    }
+
+    #[inline]
+    ///Returns value of `Accept` header, preferring this encoding over JSON.
+    pub const fn accept(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => "application/msgpack, application/json;q=0.9",
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => "application/cbor, application/json;q=0.9",
+        }
+    }
+
+    ///Returns encoding of MIME type, ignoring its parameters.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        let json = ["application/json", "application/graphql+json", "application/graphql-response+json"];
+        if json.iter().any(|json| mime.eq_ignore_ascii_case(json)) {
+            return Some(Encoding::Json);
+        }
+        #[cfg(feature = "msgpack")]
+        {
+            if mime.eq_ignore_ascii_case("application/msgpack") || mime.eq_ignore_ascii_case("application/x-msgpack") {
+                return Some(Encoding::MessagePack);
+            }
+        }
+        #[cfg(feature = "cbor")]
+        {
+            if mime.eq_ignore_ascii_case("application/cbor") {
+                return Some(Encoding::Cbor);
+            }
+        }
+        None
+    }
+
+    ///Encodes `value`.
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Bytes, EncodingError> {
+        match self {
+            Encoding::Json => crate::pool::serialize(value).map_err(EncodingError::Json),
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => match rmp_serde::to_vec_named(value) {
+                Ok(bytes) => Ok(bytes.into()),
+                Err(error) => Err(EncodingError::Other(error.to_string())),
+            },
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                match ciborium::ser::into_writer(value, &mut bytes) {
+                    Ok(()) => Ok(bytes.into()),
+                    Err(error) => Err(EncodingError::Other(error.to_string())),
+                }
+            }
+        }
+    }
+
+    ///Decodes value from `bytes`.
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, EncodingError> {
+        match self {
+            Encoding::Json => serde_json::from_slice(bytes).map_err(EncodingError::Json),
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|error| EncodingError::Other(error.to_string()))
+            }
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => ciborium::de::from_reader(bytes).map_err(|error| EncodingError::Other(error.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+///Failure to encode or decode body
+pub enum EncodingError {
+    ///Invalid JSON.
+    Json(serde_json::Error),
+    ///Invalid body in binary encoding.
+    Other(String),
+    ///Content type is not supported.
+    Unsupported(String),
+}
+
+impl fmt::Display for EncodingError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodingError::Json(error) => fmt.write_fmt(format_args!("Invalid JSON: {}", error)),
+            EncodingError::Other(error) => fmt.write_fmt(format_args!("Invalid body: {}", error)),
+            EncodingError::Unsupported(content_type) => {
+                fmt.write_fmt(format_args!("Unsupported content type '{}'", content_type))
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+///Returns encoding of body with `content_type`, which is JSON if not specified.
+pub fn body_encoding(content_type: Option<&HeaderValue>) -> Result<Encoding, EncodingError> {
+    match content_type {
+        None => Ok(Encoding::Json),
+        Some(content_type) => {
+            let content_type = String::from_utf8_lossy(content_type.as_bytes());
+            Encoding::from_content_type(&content_type).ok_or_else(|| EncodingError::Unsupported(content_type.into()))
+        }
+    }
+}
+
+///Decodes `async-graphql` request with `content_type`.
+pub fn decode_request(
+    content_type: Option<&HeaderValue>,
+    body: &[u8],
+) -> Result<async_graphql::Request, EncodingError> {
+    body_encoding(content_type)?.decode(body)
+}
+
+///Selects encoding of response from `Accept` header, which is first supported type or JSON.
+pub fn negotiate(accept: Option<&HeaderValue>) -> Encoding {
+    let accept = match accept.and_then(|accept| accept.to_str().ok()) {
+        Some(accept) => accept,
+        None => return Encoding::Json,
+    };
+    accept
+        .split(',')
+        .filter_map(Encoding::from_content_type)
+        .next()
+        .unwrap_or(Encoding::Json)
+}
@@ -0,0 +1,382 @@
+//! Router level errors
+
+use apollo_router_core::{Context, Plugin, ResponseBody, RouterRequest};
+use hyper::http::header::RETRY_AFTER;
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::{GraphqlError, GraphqlResponse, RouterResponse};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::time::Duration;
+
+///Creates JSON representation of GraphQL error with `code` extension.
+pub fn graphql_error(message: &str, code: &str) -> serde_json::Value {
+    serde_json::json!({
+        "message": message,
+        "extensions": {
+            "code": code,
+        }
+    })
+}
+
+///Creates router response with status `status`, consisting only of `errors`.
+pub fn errors_response(status: StatusCode, errors: Vec<serde_json::Value>, context: Context) -> RouterResponse {
+    let body = serde_json::json!({ "errors": errors });
+    let body = serde_json::to_vec(&body).expect("JSON serialization should not fail");
+    let body = GraphqlResponse::from_bytes("router", body.into()).expect("Valid GraphQL response");
+    let response = http::Response::builder()
+        .status(status)
+        .body(ResponseBody::GraphQL(body))
+        .expect("no argument can fail to parse or converted to the internal representation here");
+    RouterResponse {
+        response: response.into(),
+        context,
+    }
+}
+
+//Context key, which holds [RouterError] of response created by [router_error].
+const ROUTER_ERROR: &str = "graphql_router::router_error";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+///Error produced by router itself, rather than by subgraph.
+pub struct RouterError {
+    ///HTTP status of response.
+    pub status: u16,
+    ///Error message.
+    pub message: String,
+    ///Error code, set as `code` extension by default.
+    pub code: String,
+    #[serde(default)]
+    ///Extensions in addition to `code`.
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+///Formatter of [RouterError], set via [error_formatter](crate::GraphqlRouterBuilder::error_formatter).
+pub type ErrorFormatter = fn(&RouterError, &Context) -> GraphqlError;
+
+#[inline]
+///Creates router response with status `status`, consisting of single error.
+pub fn router_error(status: StatusCode, message: &str, code: &str, context: Context) -> RouterResponse {
+    router_error_with_extensions(status, message, code, serde_json::Map::new(), context)
+}
+
+///Creates router response with status `status`, consisting of single error with additional `extensions`.
+pub fn router_error_with_extensions(
+    status: StatusCode,
+    message: &str,
+    code: &str,
+    extensions: serde_json::Map<String, serde_json::Value>,
+    context: Context,
+) -> RouterResponse {
+    let mut graphql = graphql_error(message, code);
+    if let Some(serde_json::Value::Object(graphql)) = graphql.get_mut("extensions") {
+        graphql.extend(extensions.clone());
+    }
+    let error = RouterError {
+        status: status.as_u16(),
+        message: message.to_owned(),
+        code: code.to_owned(),
+        extensions,
+    };
+    if let Err(error) = context.insert(ROUTER_ERROR, error) {
+        tracing::warn!("Unable to store router error: {}", error);
+    }
+    errors_response(status, vec![graphql], context)
+}
+
+///Creates `TIMEOUT` error response of request, which exceeded `budget`.
+pub fn timeout_error(budget: Duration, context: Context) -> RouterResponse {
+    let message = format!("Request timed out after {}ms", budget.as_millis());
+    let mut extensions = serde_json::Map::new();
+    extensions.insert("budgetMs".to_owned(), (budget.as_millis() as u64).into());
+    router_error_with_extensions(StatusCode::GATEWAY_TIMEOUT, &message, "TIMEOUT", extensions, context)
+}
+
+///Adds retry hint to `response` of transient failure: `Retry-After` header and `retryAfterMs` extension of
+///its errors.
+pub fn set_retry_hint(response: &mut RouterResponse, retry_after: Duration) {
+    let retry_after_ms = retry_after.as_millis() as u64;
+    //Header is in whole seconds, so it is rounded up to not make clients retry too early.
+    let retry_after_secs = (retry_after_ms + 999) / 1000;
+    response.response.headers_mut().insert(RETRY_AFTER, retry_after_secs.into());
+
+    if let ResponseBody::GraphQL(body) = response.response.body_mut() {
+        if let Ok(value) = serde_json::from_value::<serde_json_bytes::Value>(retry_after_ms.into()) {
+            for error in body.errors.iter_mut() {
+                error.extensions.insert("retryAfterMs".into(), value.clone());
+            }
+        }
+    }
+    if let Ok(Some(mut error)) = response.context.get::<_, RouterError>(ROUTER_ERROR) {
+        error.extensions.insert("retryAfterMs".to_owned(), retry_after_ms.into());
+        if let Err(error) = response.context.insert(ROUTER_ERROR, error) {
+            tracing::warn!("Unable to store router error: {}", error);
+        }
+    }
+}
+
+///Replaces error of response created by [router_error] with error made by `formatter`.
+pub(crate) fn format_router_error(response: &mut RouterResponse, formatter: ErrorFormatter) {
+    let error = match response.context.get::<_, RouterError>(ROUTER_ERROR) {
+        Ok(Some(error)) => error,
+        _ => return,
+    };
+    let error = formatter(&error, &response.context);
+    if let ResponseBody::GraphQL(body) = response.response.body_mut() {
+        body.errors = vec![error];
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+///Details of remote subgraph fetch, which router gave up on.
+///
+///They are added to extensions of corresponding `SubrequestHttpError` error.
+pub struct FetchFailure {
+    ///HTTP status of last attempt, if subgraph responded.
+    pub status: Option<u16>,
+    ///Number of attempts made.
+    pub attempts: usize,
+    ///Host of subgraph.
+    pub host: String,
+    #[serde(default)]
+    ///Delay in milliseconds requested by subgraph via `Retry-After`, when it is temporarily unavailable.
+    pub retry_after_ms: Option<u64>,
+}
+
+#[inline(always)]
+fn fetch_failure_key(service: &str) -> String {
+    format!("graphql_router::fetch_failure::{}", service)
+}
+
+///Stores `failure` of `service` fetch in `context`, to be added to its error.
+pub(crate) fn record_fetch_failure(context: &Context, service: &str, failure: FetchFailure) {
+    if let Err(error) = context.insert(fetch_failure_key(service), failure) {
+        tracing::warn!("{}: Unable to record fetch failure: {}", service, error);
+    }
+}
+
+#[inline(always)]
+fn fetch_timeout_key(service: &str) -> String {
+    format!("graphql_router::fetch_timeout::{}", service)
+}
+
+///Stores that `service` fetch exceeded `budget` in `context`, to be added to its error.
+pub(crate) fn record_fetch_timeout(context: &Context, service: &str, budget: Duration) {
+    if let Err(error) = context.insert(fetch_timeout_key(service), budget.as_millis() as u64) {
+        tracing::warn!("{}: Unable to record fetch timeout: {}", service, error);
+    }
+}
+
+fn add_fetch_failures(response: &mut RouterResponse) {
+    let context = &response.context;
+    let body = match response.response.body_mut() {
+        ResponseBody::GraphQL(body) => body,
+        _ => return,
+    };
+    let mut retry_after = None;
+
+    for error in body.errors.iter_mut() {
+        let extensions = &mut error.extensions;
+        if extensions.get("type").and_then(|value| value.as_str()) != Some("SubrequestHttpError") {
+            continue;
+        }
+        let service = match extensions.get("service").and_then(|value| value.as_str()) {
+            Some(service) => service.to_owned(),
+            None => continue,
+        };
+        let mut details = serde_json::Map::new();
+        if let Ok(Some(failure)) = context.get::<_, FetchFailure>(fetch_failure_key(&service)) {
+            details.insert("status".to_owned(), failure.status.into());
+            details.insert("attempts".to_owned(), failure.attempts.into());
+            details.insert("host".to_owned(), failure.host.into());
+            if let Some(retry_after_ms) = failure.retry_after_ms {
+                details.insert("retryAfterMs".to_owned(), retry_after_ms.into());
+                retry_after = retry_after.max(Some(retry_after_ms));
+            }
+        }
+        if let Ok(Some(budget)) = context.get::<_, u64>(fetch_timeout_key(&service)) {
+            details.insert("code".to_owned(), "TIMEOUT".into());
+            details.insert("subgraph".to_owned(), service.into());
+            details.insert("budgetMs".to_owned(), budget.into());
+        }
+        if details.is_empty() {
+            continue;
+        }
+        let details = serde_json::from_value(serde_json::Value::Object(details));
+        if let Ok(serde_json_bytes::Value::Object(details)) = details {
+            for (key, value) in details {
+                extensions.insert(key, value);
+            }
+        }
+    }
+
+    if let Some(retry_after_ms) = retry_after {
+        let retry_after_secs = (retry_after_ms + 999) / 1000;
+        response.response.headers_mut().insert(RETRY_AFTER, retry_after_secs.into());
+    }
+}
+
+//Collects paths of objects in `value`, selected by `path`, where `@` selects every element of array.
+//
+//This mirrors how entities are selected for representations, so N-th path is path of N-th entity.
+fn select_paths(
+    value: &serde_json::Value,
+    path: &[serde_json::Value],
+    current: &mut Vec<serde_json::Value>,
+    result: &mut Vec<Vec<serde_json::Value>>,
+) {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            match value {
+                serde_json::Value::Object(_) => result.push(current.clone()),
+                //Entities in list field are selected without trailing `@`
+                serde_json::Value::Array(values) => {
+                    for (idx, value) in values.iter().enumerate() {
+                        if value.is_object() {
+                            current.push(idx.into());
+                            result.push(current.clone());
+                            current.pop();
+                        }
+                    }
+                }
+                _ => (),
+            }
+            return;
+        }
+    };
+
+    match (segment, value) {
+        (serde_json::Value::String(flatten), serde_json::Value::Array(values)) if flatten == "@" => {
+            for (idx, value) in values.iter().enumerate() {
+                current.push(idx.into());
+                select_paths(value, rest, current, result);
+                current.pop();
+            }
+        }
+        (serde_json::Value::String(key), serde_json::Value::Object(object)) => {
+            if let Some(value) = object.get(key) {
+                current.push(segment.clone());
+                select_paths(value, rest, current, result);
+                current.pop();
+            }
+        }
+        (serde_json::Value::Number(idx), serde_json::Value::Array(values)) => {
+            if let Some(value) = idx.as_u64().and_then(|idx| values.get(idx as usize)) {
+                current.push(segment.clone());
+                select_paths(value, rest, current, result);
+                current.pop();
+            }
+        }
+        _ => (),
+    }
+}
+
+//Rewrites `<fetch path>/_entities/<idx>/<rest>` to path of entity in response `data`.
+fn rewrite_entity_path(data: &serde_json::Value, path: &[serde_json::Value]) -> Option<Vec<serde_json::Value>> {
+    let entities = path.iter().position(|segment| segment.as_str() == Some("_entities"))?;
+    //Without fetch path there is no way to tell, where entity is located.
+    if entities == 0 {
+        return None;
+    }
+    let idx = path.get(entities + 1)?.as_u64()? as usize;
+
+    let mut selected = Vec::new();
+    select_paths(data, &path[..entities], &mut Vec::new(), &mut selected);
+    let mut result = selected.into_iter().nth(idx)?;
+    result.extend_from_slice(&path[entities + 2..]);
+    Some(result)
+}
+
+fn rewrite_entity_paths(response: &mut RouterResponse) {
+    let body = match response.response.body_mut() {
+        ResponseBody::GraphQL(body) => body,
+        _ => return,
+    };
+    //Most responses have no errors within `_entities` fetches, so data is converted only when needed.
+    let paths = body
+        .errors
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, error)| match serde_json::to_value(&error.path) {
+            Ok(serde_json::Value::Array(path)) if path.iter().any(|segment| segment.as_str() == Some("_entities")) => {
+                Some((idx, path))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return;
+    }
+    let data = match serde_json::to_value(&body.data) {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+
+    for (idx, path) in paths {
+        if let Some(path) = rewrite_entity_path(&data, &path) {
+            match serde_json::from_value(serde_json::Value::Array(path)) {
+                Ok(path) => body.errors[idx].path = path,
+                Err(error) => tracing::warn!("Unable to rewrite entity error path: {}", error),
+            }
+        }
+    }
+}
+
+///Rewrites paths of errors within `_entities` fetches to paths of entities in response.
+pub(crate) struct EntityErrorPaths;
+
+impl Plugin for EntityErrorPaths {
+    type Config = ();
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self)))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        service
+            .map_response(|mut response: RouterResponse| {
+                rewrite_entity_paths(&mut response);
+                response
+            })
+            .boxed()
+    }
+}
+
+///Adds [FetchFailure] and timeout details to errors of failed subgraph fetches.
+pub(crate) struct FetchFailureDetails;
+
+impl Plugin for FetchFailureDetails {
+    type Config = ();
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self)))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        service
+            .map_response(|mut response: RouterResponse| {
+                add_fetch_failures(&mut response);
+                response
+            })
+            .boxed()
+    }
+}
@@ -0,0 +1,13 @@
+use sha2::{Digest, Sha256};
+
+use core::fmt::Write;
+
+#[inline]
+///Returns hex encoded SHA-256 of `bytes`.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hash = String::with_capacity(64);
+    for byte in Sha256::digest(bytes).iter() {
+        let _ = write!(hash, "{:02x}", byte);
+    }
+    hash
+}
@@ -0,0 +1,59 @@
+//! JSON parsing of inbound requests and subgraph responses
+//!
+//! With `simd-json` feature, bodies are parsed by simd-json instead of `serde_json`.
+
+use apollo_router_core::FetchError;
+use bytes::Bytes;
+
+use crate::{GraphqlRequest, GraphqlResponse};
+
+#[cfg(feature = "simd-json")]
+//Buffers larger than this are not kept after parsing, so that single huge body doesn't stay allocated.
+const MAX_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+#[cfg(feature = "simd-json")]
+thread_local! {
+    //simd-json parses in place, while body might be shared, so it is copied into buffer reused by thread.
+    static BUFFER: core::cell::RefCell<Vec<u8>> = core::cell::RefCell::new(Vec::new());
+}
+
+#[cfg(feature = "simd-json")]
+#[inline]
+fn simd_parse<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, simd_json::Error> {
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.clear();
+        buffer.extend_from_slice(bytes);
+        let result = simd_json::serde::from_slice(&mut buffer);
+        buffer.clear();
+        buffer.shrink_to(MAX_BUFFER_CAPACITY);
+        result
+    })
+}
+
+///Parses GraphQL request body.
+pub(crate) fn parse_request(bytes: Bytes) -> Result<GraphqlRequest, serde_json::Error> {
+    #[cfg(feature = "simd-json")]
+    {
+        simd_parse(&bytes).map_err(serde::de::Error::custom)
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        GraphqlRequest::from_bytes(bytes)
+    }
+}
+
+///Parses GraphQL response body of subgraph `service_name`.
+pub(crate) fn parse_response(service_name: &str, bytes: Bytes) -> Result<GraphqlResponse, FetchError> {
+    #[cfg(feature = "simd-json")]
+    {
+        simd_parse(&bytes).map_err(|error| FetchError::SubrequestMalformedResponse {
+            service: service_name.to_owned(),
+            reason: error.to_string(),
+        })
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        GraphqlResponse::from_bytes(service_name, bytes)
+    }
+}
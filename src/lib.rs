@@ -1,5 +1,6 @@
 //! Graphql Router
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 ///Error result of graphql router handler
@@ -8,20 +9,57 @@ pub type HandleError = Box<dyn std::error::Error + Send + Sync>;
 pub type HttpRequest = hyper::Request<hyper::Body>;
 pub use apollo_router_core::Request as GraphqlRequest;
 pub use apollo_router_core::Response as GraphqlResponse;
-use apollo_router_core::{PluggableRouterServiceBuilder, SubgraphRequest, SubgraphResponse};
-pub use apollo_router_core::{RouterRequest, RouterResponse, Schema};
+pub use apollo_router_core::Error as GraphqlError;
+use apollo_router_core::{SubgraphRequest, SubgraphResponse};
+pub use apollo_router_core::{QueryPlannerRequest, QueryPlannerResponse, RouterRequest, RouterResponse, Schema};
 
 use core::future::Future;
 use core::pin::Pin;
 use core::task;
+use core::time::Duration;
 
 mod parser;
-mod plugins;
-pub use parser::{from_request_parts, parse_http_request, ParseHttpError};
+mod json;
+mod sample;
+mod hash;
+pub mod error;
+pub mod context;
+pub mod plugins;
+pub mod encoding;
+pub mod time;
+pub mod tls;
+pub mod pool;
+pub mod secret;
+pub use parser::{from_request_parts, parse_http_request, parse_http_request_with, ParseHttpError, RequestHead};
 pub mod local;
 pub use local::LocalGraphBuilder;
 pub mod remote;
-pub use remote::RemoteGraphBuilder;
+pub mod rest;
+pub use remote::{RemoteGraphBuilder, RemoteHeaders, RemoteSettings, RemoteSettingsHandle, SubgraphHealth};
+pub mod server;
+pub mod proxy;
+pub mod admin;
+pub mod config;
+pub use config::RouterConfig;
+pub mod warmup;
+pub use warmup::WarmupOperation;
+pub mod manifest;
+pub use manifest::PlanManifest;
+pub mod plan;
+pub mod rebuild;
+pub mod diff;
+pub mod log;
+pub use rebuild::RebuildError;
+pub use plan::{PlanError, QueryPlan, QueryPlannerHook, QueryPlannerService};
+#[cfg(feature = "testing")]
+pub mod testing;
+
+///Subgraph timeout set by [GraphqlRouterBuilder::with_recommended_defaults].
+pub const RECOMMENDED_SUBGRAPH_TIMEOUT: Duration = Duration::from_secs(30);
+///Retry backoff set by [GraphqlRouterBuilder::with_recommended_defaults].
+pub const RECOMMENDED_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+///Query size limit set by [GraphqlRouterBuilder::with_recommended_defaults].
+pub const RECOMMENDED_MAX_QUERY_BYTES: usize = 64 * 1024;
 
 pub trait BuildGraph: Sized + Send {
     ///Service type
@@ -34,38 +72,115 @@ pub trait BuildGraph: Sized + Send {
 
     ///Returns service name.
     fn name(&self) -> &str;
+    ///Returns handle to settings that can be changed at runtime, if subgraph supports it.
+    fn settings(&self) -> Option<RemoteSettingsHandle> {
+        None
+    }
     ///Builds service
     fn build(self) -> Self::SubgraphSerivce;
 }
 
+///Hook, applied to final response before it is serialized.
+pub type ResponseHook = Arc<dyn Fn(&mut GraphqlResponse, &apollo_router_core::Context) + Send + Sync>;
+
+///Router service, built from plugins and subgraphs.
+pub type RouterService = tower::util::BoxCloneService<RouterRequest, RouterResponse, HandleError>;
+
 #[allow(clippy::large_enum_variant)]
-enum GraphqlRouterHandlerState {
+enum GraphqlRouterHandlerState<F> {
     //RouterRequest is relative big, but we don't move it all that much
     Pending(RouterRequest),
-    Ongoing(Pin<Box<dyn Future<Output = Result<RouterResponse, HandleError>> + Send + 'static>>),
+    Ongoing(F),
+    //Ongoing future is dropped on timeout, cancelling all outstanding subgraph requests
+    TimedOut,
 }
 
-pub struct GraphqlRouterHandler {
-    service: tower::util::BoxCloneService<RouterRequest, RouterResponse, HandleError>,
-    state: GraphqlRouterHandlerState,
+struct HandlerTimeout {
+    //Timer requires runtime, so it is created on first poll, while deadline counts from handler creation.
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+    deadline: std::time::Instant,
+    duration: Duration,
+    context: apollo_router_core::Context,
 }
 
-impl Future for GraphqlRouterHandler {
-    type Output = Result<RouterResponse, HandleError>;
+///Future, handling single request with router service.
+///
+///Service's future is polled in place, so only services with boxed futures, like [RouterService], incur allocation.
+pub struct GraphqlRouterHandler<S: tower_service::Service<RouterRequest> = RouterService> {
+    service: S,
+    state: GraphqlRouterHandlerState<S::Future>,
+    timeout: Option<HandlerTimeout>,
+    error_formatter: Option<error::ErrorFormatter>,
+    response_hook: Option<ResponseHook>,
+}
+
+impl<S: tower_service::Service<RouterRequest, Response = RouterResponse, Error = HandleError>> GraphqlRouterHandler<S> {
+    #[inline]
+    ///Creates handler, executing `req` with `service` once it is ready.
+    pub fn new(service: S, req: RouterRequest) -> Self {
+        Self {
+            service,
+            state: GraphqlRouterHandlerState::Pending(req),
+            timeout: None,
+            error_formatter: None,
+            response_hook: None,
+        }
+    }
+
+    #[inline(always)]
+    ///Sets formatter of errors, produced by router itself.
+    pub fn with_error_formatter(mut self, formatter: error::ErrorFormatter) -> Self {
+        self.error_formatter = Some(formatter);
+        self
+    }
 
-    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+    #[inline(always)]
+    ///Sets hook, applied to GraphQL response after all plugins.
+    pub fn with_response_hook(mut self, hook: ResponseHook) -> Self {
+        self.response_hook = Some(hook);
+        self
+    }
+
+    #[inline]
+    ///Sets time limit, after which `GATEWAY_TIMEOUT` response with `TIMEOUT` error is returned.
+    ///
+    ///Time limit counts from this call, but timer is started only once handler is polled, so handler can be created
+    ///outside of tokio runtime.
+    pub fn with_timeout(mut self, duration: Duration) -> Self {
+        let context = match &self.state {
+            GraphqlRouterHandlerState::Pending(req) => req.context.clone(),
+            _ => apollo_router_core::Context::default(),
+        };
+        self.timeout = Some(HandlerTimeout {
+            sleep: None,
+            deadline: std::time::Instant::now() + duration,
+            duration,
+            context,
+        });
+        self
+    }
+
+    fn poll_service(
+        self: Pin<&mut Self>,
+        ctx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<RouterResponse, HandleError>> {
         use tower_service::Service;
 
-        let this = Pin::into_inner(self);
+        //Only ongoing future is pinned: it is never moved out of `state` and is dropped in place once replaced.
+        let this = unsafe { self.get_unchecked_mut() };
         loop {
-            this.state = match &mut this.state {
-                GraphqlRouterHandlerState::Ongoing(ongoing) => return Future::poll(Pin::new(ongoing), ctx),
-                GraphqlRouterHandlerState::Pending(req) => match this.service.poll_ready(ctx) {
+            match &mut this.state {
+                GraphqlRouterHandlerState::Ongoing(ongoing) => {
+                    return Future::poll(unsafe { Pin::new_unchecked(ongoing) }, ctx)
+                }
+                GraphqlRouterHandlerState::TimedOut => return task::Poll::Pending,
+                GraphqlRouterHandlerState::Pending(_) => match this.service.poll_ready(ctx) {
                     task::Poll::Ready(Ok(())) => {
-                        let mut future_req = RouterRequest::fake_builder().build().unwrap();
-                        core::mem::swap(&mut future_req, req);
-                        //intentionally falling through to poll future
-                        GraphqlRouterHandlerState::Ongoing(this.service.call(future_req))
+                        let state = core::mem::replace(&mut this.state, GraphqlRouterHandlerState::TimedOut);
+                        if let GraphqlRouterHandlerState::Pending(req) = state {
+                            //intentionally falling through to poll future
+                            this.state = GraphqlRouterHandlerState::Ongoing(this.service.call(req));
+                        }
                     }
                     task::Poll::Pending => return task::Poll::Pending,
                     task::Poll::Ready(Err(error)) => return task::Poll::Ready(Err(error)),
@@ -73,43 +188,277 @@ impl Future for GraphqlRouterHandler {
             }
         }
     }
+
+    fn poll_timed(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<RouterResponse, HandleError>> {
+        if let task::Poll::Ready(result) = self.as_mut().poll_service(ctx) {
+            return task::Poll::Ready(result);
+        }
+
+        //Timeout is not pinned, while ongoing future is dropped in place by assignment of new state.
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Some(timeout) = this.timeout.as_mut() {
+            let deadline = timeout.deadline;
+            let sleep = timeout
+                .sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(tokio::time::Instant::from_std(deadline))));
+            if Future::poll(sleep.as_mut(), ctx).is_ready() {
+                tracing::info!("Request timed out after {}ms", timeout.duration.as_millis());
+                this.state = GraphqlRouterHandlerState::TimedOut;
+                return task::Poll::Ready(Ok(error::timeout_error(timeout.duration, timeout.context.clone())));
+            }
+        }
+
+        task::Poll::Pending
+    }
+}
+
+impl<S> Future for GraphqlRouterHandler<S>
+where
+    S: tower_service::Service<RouterRequest, Response = RouterResponse, Error = HandleError>,
+{
+    type Output = Result<RouterResponse, HandleError>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let result = match self.as_mut().poll_timed(ctx) {
+            task::Poll::Ready(result) => result,
+            task::Poll::Pending => return task::Poll::Pending,
+        };
+        task::Poll::Ready(result.map(|mut response| {
+            if let Some(formatter) = self.error_formatter {
+                error::format_router_error(&mut response, formatter);
+            }
+            if let Some(hook) = self.response_hook.as_ref() {
+                if let apollo_router_core::ResponseBody::GraphQL(body) = response.response.body_mut() {
+                    hook(body, &response.context);
+                }
+            }
+            response
+        }))
+    }
 }
 
 #[derive(Clone)]
 ///Router
 pub struct GraphqlRouter {
-    pub schema: Arc<Schema>,
-    service: tower::util::BoxCloneService<RouterRequest, RouterResponse, HandleError>,
+    //Shared by clones, so that all of them use service and schema of the latest build.
+    current: Arc<std::sync::RwLock<rebuild::Generation>>,
+    //Held while router is rebuilt, so that rebuilds never overlap.
+    recipe: Arc<tokio::sync::Mutex<rebuild::Recipe>>,
+    //Shared by clones, so that subgraphs added or removed at runtime are visible to all of them.
+    settings: Arc<std::sync::RwLock<BTreeMap<String, RemoteSettingsHandle>>>,
+    metrics: Option<plugins::Metrics>,
+    maintenance: Option<plugins::Maintenance>,
+    api_keys: Option<plugins::ApiKeys>,
+    pause: plugins::Maintenance,
+    persisted_queries: Option<plugins::PersistedQueries>,
+    //Shared by clones, so that server checks requests against limits of reconfigured plugin.
+    request_limits: Arc<std::sync::RwLock<Option<plugins::RequestLimitsConfig>>>,
+    plan_manifest: Option<PlanManifest>,
+    timeout: Option<Duration>,
+    error_formatter: Option<error::ErrorFormatter>,
+    response_hook: Option<ResponseHook>,
+    plans: plan::Plans,
 }
 
 impl GraphqlRouter {
     #[inline(always)]
     pub fn build(schema: Arc<Schema>) -> GraphqlRouterBuilder {
         GraphqlRouterBuilder {
-            builder: PluggableRouterServiceBuilder::new(schema.clone()),
             schema,
+            plugins: Vec::new(),
+            subgraphs: Vec::new(),
+            settings: BTreeMap::new(),
+            metrics: None,
+            maintenance: None,
+            api_keys: None,
+            persisted_queries: None,
+            request_limits: None,
+            plan_manifest: None,
+            subgraph_defaults: None,
+            timeout: None,
+            error_formatter: None,
+            response_hook: None,
+            warmup: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns metrics, if router was built with them.
+    pub fn metrics(&self) -> Option<&plugins::Metrics> {
+        self.metrics.as_ref()
+    }
+
+    #[inline(always)]
+    ///Returns maintenance switch, if router was built with it.
+    pub fn maintenance(&self) -> Option<&plugins::Maintenance> {
+        self.maintenance.as_ref()
+    }
+
+    #[inline(always)]
+    ///Returns API keys, if router was built with them.
+    pub fn api_keys(&self) -> Option<&plugins::ApiKeys> {
+        self.api_keys.as_ref()
+    }
+
+    #[inline(always)]
+    ///Returns switch to pause request processing of built-in [server].
+    ///
+    ///Unlike [maintenance](Self::maintenance), it is checked by server, so that requests affecting all operations
+    ///are rejected before being parsed, while established subscriptions continue.
+    pub fn pause_switch(&self) -> &plugins::Maintenance {
+        &self.pause
+    }
+
+    #[inline(always)]
+    ///Returns persisted queries store, if router was built with it.
+    pub fn persisted_queries(&self) -> Option<&plugins::PersistedQueries> {
+        self.persisted_queries.as_ref()
+    }
+
+    ///Rejects request, which exceeds request limits or is outside of persisted queries safelist, by its `head`.
+    ///
+    ///Built-in [server] uses it to reject request before its variables and extensions are parsed.
+    pub fn check_request_head(&self, head: &RequestHead<'_>) -> Result<(), RouterResponse> {
+        let query = match head.query.as_deref() {
+            Some(query) => query,
+            //Persisted operation is resolved and checked by router.
+            None => return Ok(()),
+        };
+        let context = apollo_router_core::Context::new();
+        if let Some(limits) = rebuild::request_limits(&self.request_limits).as_ref() {
+            plugins::check_query(limits, query, &context)?;
         }
+        match self.persisted_queries.as_ref() {
+            Some(persisted) => persisted.check_safelist(query, &context),
+            None => Ok(()),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns manifest of hot operations, if router was built with it.
+    pub fn plan_manifest(&self) -> Option<&PlanManifest> {
+        self.plan_manifest.as_ref()
+    }
+
+    #[inline]
+    ///Returns runtime settings of subgraph, if it supports them.
+    pub fn subgraph_settings(&self, name: &str) -> Option<RemoteSettingsHandle> {
+        rebuild::settings(&self.settings).get(name).cloned()
+    }
+
+    #[inline]
+    ///Returns runtime settings of all subgraphs, that support them.
+    pub fn subgraphs_settings(&self) -> Vec<(String, RemoteSettingsHandle)> {
+        rebuild::settings(&self.settings)
+            .iter()
+            .map(|(name, settings)| (name.clone(), settings.clone()))
+            .collect()
+    }
+
+    #[inline]
+    ///Compares current schema with `candidate` SDL, e.g. to check for breaking changes before deploying it.
+    pub fn diff_schema(&self, candidate: &str) -> Result<diff::SchemaDiff, diff::SchemaDiffError> {
+        diff::diff(self.schema().as_str(), candidate)
+    }
+
+    ///Returns health of all subgraphs, that track it.
+    pub fn subgraphs_health(&self) -> BTreeMap<String, SubgraphHealth> {
+        rebuild::settings(&self.settings)
+            .iter()
+            .map(|(name, settings)| (name.clone(), settings.health()))
+            .collect()
+    }
+
+    ///Returns whether router is ready to serve requests, i.e. none of its subgraphs is down.
+    pub fn is_ready(&self) -> bool {
+        rebuild::settings(&self.settings)
+            .values()
+            .all(|settings| settings.health().status != remote::HealthStatus::Down)
+    }
+
+    ///Modifies runtime settings of subgraph `name`, returning `false` if it does not support them.
+    ///
+    ///Timeouts and retries take effect for subsequent fetches right away, without rebuilding router service.
+    pub fn update_subgraph_settings<F: FnOnce(&mut RemoteSettings)>(&self, name: &str, cb: F) -> bool {
+        match rebuild::settings(&self.settings).get(name) {
+            Some(settings) => {
+                settings.update(cb);
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Modifies runtime settings of all subgraphs, that support them.
+    pub fn update_subgraphs_settings<F: FnMut(&str, &mut RemoteSettings)>(&self, mut cb: F) {
+        for (name, settings) in rebuild::settings(&self.settings).iter() {
+            settings.update(|settings| cb(name, settings));
+        }
+    }
+
+    ///Plans `req` without executing any of its subgraph fetches.
+    ///
+    ///Request still goes through router plugins, so it can be rejected before planning.
+    pub async fn plan(&mut self, req: RouterRequest) -> Result<QueryPlan, PlanError> {
+        let plans = self.plans.clone();
+        plans.plan(self, req).await
+    }
+
+    #[inline(always)]
+    ///Returns router service, to be used with [GraphqlRouterHandler::new] or as plain tower service.
+    pub fn service(&self) -> RouterService {
+        rebuild::current(&self.current).service.clone()
+    }
+
+    #[inline(always)]
+    ///Returns schema of current router service.
+    pub fn schema(&self) -> Arc<Schema> {
+        rebuild::current(&self.current).schema.clone()
     }
 
     #[inline(always)]
     pub fn handle(&mut self, req: RouterRequest) -> GraphqlRouterHandler {
-        GraphqlRouterHandler {
-            service: self.service.clone(),
-            state: GraphqlRouterHandlerState::Pending(req),
+        let mut handler = GraphqlRouterHandler::new(self.service(), req);
+        if let Some(duration) = self.timeout {
+            handler = handler.with_timeout(duration);
+        }
+        if let Some(formatter) = self.error_formatter {
+            handler = handler.with_error_formatter(formatter);
+        }
+        if let Some(hook) = self.response_hook.as_ref() {
+            handler = handler.with_response_hook(hook.clone());
         }
+        handler
     }
 }
 
 ///Router builder
 pub struct GraphqlRouterBuilder {
-    builder: PluggableRouterServiceBuilder,
     schema: Arc<Schema>,
+    plugins: Vec<(String, rebuild::PluginSource)>,
+    subgraphs: Vec<(String, rebuild::SubgraphService)>,
+    settings: BTreeMap<String, RemoteSettingsHandle>,
+    metrics: Option<plugins::Metrics>,
+    maintenance: Option<plugins::Maintenance>,
+    api_keys: Option<plugins::ApiKeys>,
+    persisted_queries: Option<plugins::PersistedQueries>,
+    request_limits: Option<plugins::RequestLimitsConfig>,
+    plan_manifest: Option<PlanManifest>,
+    //Applied to remote subgraphs at finish, unless they already have own settings.
+    subgraph_defaults: Option<RemoteSettings>,
+    timeout: Option<Duration>,
+    error_formatter: Option<error::ErrorFormatter>,
+    response_hook: Option<ResponseHook>,
+    warmup: Vec<WarmupOperation>,
 }
 
 impl GraphqlRouterBuilder {
     #[inline]
     ///Adds subgraph
-    pub fn add_subgraph<T: BuildGraph>(self, graph: T) -> Self
+    pub fn add_subgraph<T: BuildGraph>(mut self, graph: T) -> Self
     where
         <<T as BuildGraph>::SubgraphSerivce as tower_service::Service<SubgraphRequest>>::Future: Send,
     {
@@ -131,33 +480,354 @@ impl GraphqlRouterBuilder {
         }
 
         let name = graph.name().to_owned();
-        Self {
-            schema: self.schema,
-            builder: self.builder.with_subgraph_service(&name, graph.build()),
+        if let Some(settings) = graph.settings() {
+            self.settings.insert(name.clone(), settings);
         }
+        self.subgraphs.push((name, tower::util::BoxService::new(graph.build())));
+        self
     }
 
     #[inline]
     ///Enables header propagation.
     pub fn propagate_headers(self) -> Self {
+        self.propagate_headers_with(plugins::PropagateHeadersConfig::default())
+    }
+
+    #[inline]
+    ///Enables header propagation with `config`, e.g. stripping credentials for some subgraphs.
+    pub fn propagate_headers_with(self, config: plugins::PropagateHeadersConfig) -> Self {
+        self.plugin("propagate_headers", plugins::PropagateHeaders::with_config(config))
+    }
+
+    #[inline]
+    ///Rejects requests exceeding `limits`.
+    ///
+    ///Limits of query document are also checked by built-in [server], before variables are parsed.
+    pub fn request_limits(self, limits: plugins::RequestLimitsConfig) -> Self {
+        let plugin = plugins::RequestLimits::with_config(limits.clone());
         Self {
-            schema: self.schema,
-            builder: self
-                .builder
-                .with_plugin("propagate_headers".to_owned(), plugins::PropagateHeaders),
+            request_limits: Some(limits),
+            ..self.plugin("request_limits", plugin)
+        }
+    }
+
+    #[inline]
+    ///Rejects requests without enabled API key in `keys`.
+    ///
+    ///Keys can be managed at runtime via [GraphqlRouter::api_keys] or clone of `keys`.
+    pub fn with_api_keys(self, keys: plugins::ApiKeys) -> Self {
+        let plugin = keys.clone();
+        Self {
+            api_keys: Some(keys),
+            ..self.plugin("api_keys", plugin)
         }
     }
 
+    #[inline]
+    ///Removes field suggestions from error messages.
+    pub fn hide_suggestions(self) -> Self {
+        self.plugin("hide_suggestions", plugins::HideSuggestions)
+    }
+
+    #[inline]
+    ///Rejects operations exceeding `limits`.
+    pub fn operation_limits(self, limits: plugins::OperationLimitsConfig) -> Self {
+        self.plugin("operation_limits", plugins::OperationLimits::with_config(limits))
+    }
+
+    #[inline(always)]
+    ///Sets time limit for whole request handling, including planning and all subgraph fetches.
+    ///
+    ///When exceeded, request is cancelled and responded with `TIMEOUT` error, with `budgetMs` extension.
+    ///
+    ///Default is no limit.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     #[inline(always)]
+    ///Sets `formatter` of errors, produced by router itself, e.g. when request is rejected or timed out.
+    ///
+    ///Errors of subgraphs are not affected.
+    pub fn error_formatter(mut self, formatter: error::ErrorFormatter) -> Self {
+        self.error_formatter = Some(formatter);
+        self
+    }
+
+    #[inline]
+    ///Sets `hook`, which can modify final response before serialization, e.g. to remove fields or
+    ///extensions for compliance.
+    ///
+    ///It is applied after all plugins and error formatter, replacing previously set hook.
+    pub fn response_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut GraphqlResponse, &apollo_router_core::Context) + Send + Sync + 'static,
+    {
+        self.response_hook = Some(Arc::new(hook));
+        self
+    }
+
+    #[inline]
+    ///Adds operation, executed right after router is built to populate query plan cache and
+    ///establish subgraph connections.
+    ///
+    ///Operations are executed in order they are added, and their failures are only logged.
+    pub fn warmup(mut self, operation: WarmupOperation) -> Self {
+        self.warmup.push(operation);
+        self
+    }
+
+    #[inline]
+    ///Rejects requests with `SERVER_OVERLOADED` error, when limit of requests in flight is reached.
+    pub fn load_shed(self, config: plugins::LoadShedConfig) -> Self {
+        self.plugin("load_shed", plugins::LoadShed::with_config(config))
+    }
+
+    #[inline]
+    ///Limits number of subgraph fetches, which run at the same time, per request and in total.
+    pub fn fetch_parallelism(self, config: plugins::FetchParallelismConfig) -> Self {
+        self.plugin("fetch_parallelism", plugins::FetchParallelism::with_config(config))
+    }
+
+    #[inline]
+    ///Sets how subgraph fetch failures affect federated response.
+    pub fn partial_results(self, config: plugins::PartialResultsConfig) -> Self {
+        self.plugin("partial_results", plugins::PartialResults::with_config(config))
+    }
+
+    #[inline]
+    ///Injects subgraph failures according to `config`, for resilience testing.
+    pub fn fault_injection(self, config: plugins::FaultInjectionConfig) -> Self {
+        self.plugin("fault_injection", plugins::FaultInjection::with_config(config))
+    }
+
+    #[inline]
+    ///Delays subgraph requests according to `config`, for resilience testing.
+    pub fn latency_injection(self, config: plugins::LatencyInjectionConfig) -> Self {
+        self.plugin("latency_injection", plugins::LatencyInjection::with_config(config))
+    }
+
+    #[inline]
+    ///Records subgraph exchanges of requests for debugging, according to `config`.
+    pub fn debug_capture(self, config: plugins::DebugCaptureConfig) -> Self {
+        self.plugin("debug_capture", plugins::DebugCapture::with_config(config))
+    }
+
+    #[inline]
+    ///Includes query plan and fetch timings in response extensions, according to `config`.
+    pub fn expose_query_plan(self, config: plugins::ExposeQueryPlanConfig) -> Self {
+        self.plugin("expose_query_plan", plugins::ExposeQueryPlan::with_config(config))
+    }
+
+    #[inline]
+    ///Sets handling of non-null violations in subgraph data, according to `config`.
+    pub fn nullability(self, config: plugins::NullabilityConfig) -> Self {
+        self.schema_plugin("nullability", move |schema| plugins::Nullability::with_config(schema, config.clone()))
+    }
+
+    #[inline]
+    ///Reports parse, plan, fetch and total timings of requests, according to `config`.
+    pub fn server_timing(self, config: plugins::ServerTimingConfig) -> Self {
+        self.plugin("server_timing", plugins::ServerTiming::with_config(config))
+    }
+
+    #[inline]
+    ///Routes and orders subgraph fetches, overriding query plan, according to `config`.
+    pub fn plan_overrides(self, config: plugins::PlanOverridesConfig) -> Self {
+        self.plugin("plan_overrides", plugins::PlanOverrides::with_config(config))
+    }
+
+    #[inline]
+    ///Splits `_entities` requests with too many representations, according to `config`.
+    pub fn entity_batching(self, config: plugins::EntityBatchingConfig) -> Self {
+        self.plugin("entity_batching", plugins::EntityBatching::with_config(config))
+    }
+
+    #[inline]
+    ///Applies execution policies to operations, selected by name or document hash, according to `config`.
+    pub fn operation_policies(self, config: plugins::OperationPoliciesConfig) -> Self {
+        self.plugin("operation_policies", plugins::OperationPolicies::with_config(config))
+    }
+
+    #[inline]
+    ///Defers or skips low priority subgraph fetches, according to `config`.
+    pub fn fetch_priority(self, config: plugins::FetchPriorityConfig) -> Self {
+        self.plugin("fetch_priority", plugins::FetchPriority::with_config(config))
+    }
+
+    #[inline]
+    ///Controls execution order of root mutation fields, according to `config`.
+    pub fn mutation_ordering(self, config: plugins::MutationOrderingConfig) -> Self {
+        self.plugin("mutation_ordering", plugins::MutationOrdering::with_config(config))
+    }
+
+    #[inline]
+    ///Logs subgraph requests with redaction of sensitive values, according to `config`.
+    pub fn subgraph_logging(self, config: plugins::SubgraphLoggingConfig) -> Self {
+        self.plugin("subgraph_logging", plugins::SubgraphLogging::with_config(config))
+    }
+
+    #[inline]
+    ///Collects request metrics into `metrics`, which are available via [GraphqlRouter::metrics].
+    pub fn with_metrics(self, metrics: plugins::Metrics) -> Self {
+        let plugin = metrics.clone();
+        Self {
+            metrics: Some(metrics),
+            ..self.plugin("metrics", plugin)
+        }
+    }
+
+    #[inline]
+    ///Responds with maintenance error while `maintenance` is enabled.
+    ///
+    ///Switch is available via [GraphqlRouter::maintenance].
+    pub fn with_maintenance(self, maintenance: plugins::Maintenance) -> Self {
+        let plugin = maintenance.clone();
+        Self {
+            maintenance: Some(maintenance),
+            ..self.plugin("maintenance", plugin)
+        }
+    }
+
+    #[inline]
+    ///Resolves operations by id using `persisted`, rejecting other operations if it is safelist.
+    ///
+    ///Store is available via [GraphqlRouter::persisted_queries].
+    ///Operations in its shared store are namespaced by hash of the current schema.
+    pub fn with_persisted_queries(self, persisted: plugins::PersistedQueries) -> Self {
+        let plugin = persisted.clone();
+        Self {
+            persisted_queries: Some(persisted),
+            ..self.schema_plugin("persisted_queries", move |schema| plugin.for_schema(schema))
+        }
+    }
+
+    #[inline]
+    ///Records operations router handles into `manifest`, planning its operations before router is returned.
+    ///
+    ///Manifest is available via [GraphqlRouter::plan_manifest], to be saved on shutdown and loaded on next start.
+    pub fn with_plan_manifest(self, manifest: PlanManifest) -> Self {
+        let recorded = manifest.clone();
+        Self {
+            plan_manifest: Some(manifest),
+            ..self.plugin("plan_manifest", manifest::ManifestRecorder::new(recorded))
+        }
+    }
+
+    ///Applies recommended production defaults:
+    ///
+    ///- basic metrics, which also count requests rejected by other plugins;
+    ///- header propagation;
+    ///- request limits, with query size up to [RECOMMENDED_MAX_QUERY_BYTES];
+    ///- for remote subgraphs, timeout of [RECOMMENDED_SUBGRAPH_TIMEOUT] and retry with backoff of
+    ///[RECOMMENDED_RETRY_BACKOFF], unless subgraph has own timeout or backoff.
+    ///
+    ///Subgraph defaults apply to subgraphs added before and after this call.
+    pub fn with_recommended_defaults(self) -> Self {
+        let limits = plugins::RequestLimitsConfig {
+            max_query_bytes: Some(RECOMMENDED_MAX_QUERY_BYTES),
+            ..plugins::RequestLimitsConfig::default()
+        };
+        let subgraph_defaults = RemoteSettings {
+            timeout: Some(RECOMMENDED_SUBGRAPH_TIMEOUT),
+            retry_backoff: Some(RECOMMENDED_RETRY_BACKOFF),
+            ..RemoteSettings::default()
+        };
+        let this = self
+            .with_metrics(plugins::Metrics::new())
+            .propagate_headers()
+            .request_limits(limits);
+        Self {
+            subgraph_defaults: Some(subgraph_defaults),
+            ..this
+        }
+    }
+
+    #[inline]
+    ///Wraps query planner service with `hook`, e.g. to add caching layers, rewrite plans or experiment with planning.
+    ///
+    ///Hook is applied as `query_planning_service` of plugin, in order of plugins.
+    pub fn query_planner<F>(self, hook: F) -> Self
+    where
+        F: FnMut(QueryPlannerService) -> QueryPlannerService + Send + Sync + 'static,
+    {
+        self.plugin("query_planner", plan::PlannerHook::new(Box::new(hook)))
+    }
+
+    #[inline]
+    ///Adds plugin created dynamically, e.g. by [PluginRegistry](plugins::PluginRegistry).
+    ///
+    ///Plugin is shared by all builds of router, so it keeps its state, when router is rebuilt.
+    pub fn with_dyn_plugin(mut self, name: String, plugin: Box<dyn apollo_router_core::DynPlugin>) -> Self {
+        self.plugins.push((name, rebuild::PluginSource::Shared(rebuild::SharedPlugin::new(plugin))));
+        self
+    }
+
+    #[inline(always)]
+    fn plugin<P: apollo_router_core::DynPlugin>(self, name: &str, plugin: P) -> Self {
+        self.with_dyn_plugin(name.to_owned(), Box::new(plugin))
+    }
+
+    ///Adds plugin depending on schema, which is created by `factory` again with new schema when router is rebuilt.
+    fn schema_plugin<P, F>(mut self, name: &str, factory: F) -> Self
+    where
+        P: apollo_router_core::DynPlugin,
+        F: Fn(&Arc<Schema>) -> P + Send + Sync + 'static,
+    {
+        let factory: rebuild::PluginFactory = Arc::new(move |schema: &Arc<Schema>| {
+            Box::new(factory(schema)) as Box<dyn apollo_router_core::DynPlugin>
+        });
+        self.plugins.push((name.to_owned(), rebuild::PluginSource::Schema(factory)));
+        self
+    }
+
     ///Finalizes builder
     ///
     ///Not being able to build query likely means that query planner is unable to handle schema
     ///with subgraphs, which is probably means error in schema, so cannot be recovered so treat it
     ///as 500 error
     pub async fn finish(self) -> Result<GraphqlRouter, apollo_router_core::ServiceBuildError> {
-        Ok(GraphqlRouter {
+        if let Some(defaults) = self.subgraph_defaults.as_ref() {
+            for handle in self.settings.values() {
+                rebuild::apply_defaults(handle, defaults);
+            }
+        }
+
+        let plans = plan::Plans::default();
+        let recipe = rebuild::Recipe::new(
+            self.plugins,
+            self.subgraphs,
+            plans.clone(),
+            self.subgraph_defaults,
+            self.warmup.clone(),
+        );
+        let current = rebuild::Generation {
+            service: recipe.build(&self.schema).await?,
             schema: self.schema,
-            service: self.builder.with_naive_introspection().build().await?.0,
-        })
+        };
+        let mut router = GraphqlRouter {
+            current: Arc::new(std::sync::RwLock::new(current)),
+            recipe: Arc::new(tokio::sync::Mutex::new(recipe)),
+            settings: Arc::new(std::sync::RwLock::new(self.settings)),
+            metrics: self.metrics,
+            maintenance: self.maintenance,
+            api_keys: self.api_keys,
+            pause: plugins::Maintenance::new(),
+            persisted_queries: self.persisted_queries,
+            request_limits: Arc::new(std::sync::RwLock::new(self.request_limits)),
+            plan_manifest: self.plan_manifest,
+            timeout: self.timeout,
+            error_formatter: self.error_formatter,
+            response_hook: self.response_hook,
+            plans,
+        };
+        if let Some(manifest) = router.plan_manifest.clone() {
+            manifest::run(&mut router, &manifest).await;
+        }
+        if !self.warmup.is_empty() {
+            warmup::run(&mut router, &self.warmup).await;
+        }
+        Ok(router)
     }
 }
@@ -8,11 +8,18 @@ use core::future::Future;
 use core::pin::Pin;
 use core::{mem, task};
 
+mod documents;
+use documents::DocumentCache;
+
+///Number of parsed documents cached by local subgraph, unless set by [LocalGraphBuilder::document_cache].
+pub const DEFAULT_DOCUMENT_CACHE_CAPACITY: usize = 256;
+
 ///Builder to create local graphql service
 pub struct LocalGraphBuilder<Q, M, S> {
     schema: Schema<Q, M, S>,
     name: &'static str,
     data: async_graphql::context::Data,
+    document_cache: usize,
 }
 
 impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'static> LocalGraphBuilder<Q, M, S> {
@@ -23,6 +30,7 @@ impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'st
             schema,
             name,
             data: Default::default(),
+            document_cache: DEFAULT_DOCUMENT_CACHE_CAPACITY,
         }
     }
 
@@ -33,6 +41,15 @@ impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'st
         self
     }
 
+    #[inline(always)]
+    ///Sets number of parsed query documents to cache, so that repeated operations are not parsed again.
+    ///
+    ///Setting `0` disables cache.
+    pub fn document_cache(&mut self, capacity: usize) -> &mut Self {
+        self.document_cache = capacity;
+        self
+    }
+
     #[inline(always)]
     ///Builds service
     pub fn build(self) -> LocalGraphService<Q, M, S> {
@@ -40,6 +57,7 @@ impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'st
             name: self.name,
             inner: self.schema,
             data: self.data,
+            documents: DocumentCache::new(self.document_cache),
         }
     }
 }
@@ -65,6 +83,7 @@ pub struct LocalGraphService<Q, M, S> {
     name: &'static str,
     inner: Schema<Q, M, S>,
     data: async_graphql::context::Data,
+    documents: DocumentCache,
 }
 
 impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'static>
@@ -86,7 +105,6 @@ impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'st
 
         let (_http, graphql) = request.subgraph_request.into_parts();
         let context = request.context;
-        let service_name = self.name;
         let mut variables = async_graphql::Variables::default();
 
         for (key, val) in graphql.variables.iter() {
@@ -96,12 +114,17 @@ impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'st
             variables.insert(key, val);
         }
 
-        let mut transformed_req = async_graphql::Request::new(graphql.query.unwrap_or_default())
+        let query = graphql.query.unwrap_or_default();
+        let document = self.documents.get(&query);
+        let mut transformed_req = async_graphql::Request::new(query)
             .operation_name(graphql.operation_name.unwrap_or_default())
             .variables(variables);
+        if let Some(document) = document {
+            transformed_req.set_parsed_query(document);
+        }
         for (key, val) in graphql.extensions.into_iter() {
             let key = key.as_str().to_owned();
-            let val = serde_json_bytes::from_value(val.clone()).unwrap_or_default();
+            let val = serde_json_bytes::from_value(val).unwrap_or_default();
             transformed_req.extensions.insert(key, val);
         }
         //TODO: This subgraph is valid once if we insert data.
@@ -111,8 +134,9 @@ impl<Q: ObjectType + 'static, M: ObjectType + 'static, S: SubscriptionType + 'st
         let schema = self.inner.clone();
         let res = async move {
             let res = schema.execute(transformed_req).await;
-            let bytes = serde_json::to_vec(&res)?;
-            let res = apollo_router_core::Response::from_bytes(service_name, bytes.into())?;
+            //Converted in memory, without serializing response into bytes only to parse them back.
+            let res = serde_json_bytes::to_value(&res)?;
+            let res = serde_json_bytes::from_value::<apollo_router_core::Response>(res)?;
             let res = apollo_router_core::SubgraphResponse {
                 //It shouldn't fail here actually but just in case propagate error
                 response: http::Response::builder().body(res)?.into(),
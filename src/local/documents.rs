@@ -0,0 +1,88 @@
+use async_graphql::parser::types::ExecutableDocument;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Default)]
+struct Entries {
+    //Documents with tick of their last use.
+    documents: HashMap<Arc<str>, (ExecutableDocument, u64)>,
+    //Keys by tick of last use, least recently used documents are evicted first.
+    order: BTreeMap<u64, Arc<str>>,
+    tick: u64,
+}
+
+impl Entries {
+    #[inline]
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn touch(&mut self, key: &str) -> Option<ExecutableDocument> {
+        let tick = self.next_tick();
+        let (document, used) = self.documents.get_mut(key)?;
+        let used = core::mem::replace(used, tick);
+        let document = document.clone();
+        if let Some(key) = self.order.remove(&used) {
+            self.order.insert(tick, key);
+        }
+        Some(document)
+    }
+}
+
+///Cache of parsed documents, keyed by normalized query text, so that documents differing only in formatting are
+///parsed once.
+///
+///Positions in parsed document refer to the query, which was cached first.
+pub(crate) struct DocumentCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl DocumentCache {
+    #[inline]
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    #[inline(always)]
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    ///Returns parsed `query`, parsing and caching it if necessary.
+    ///
+    ///Invalid queries are not cached, leaving error reporting to schema.
+    pub(crate) fn get(&self, query: &str) -> Option<ExecutableDocument> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let key = crate::parser::normalize_document(query);
+        if let Some(document) = self.lock().touch(&key) {
+            return Some(document);
+        }
+
+        let document = async_graphql::parser::parse_query(query).ok()?;
+        let mut entries = self.lock();
+        if entries.touch(&key).is_none() {
+            if entries.documents.len() >= self.capacity {
+                let oldest = entries.order.keys().next().copied();
+                if let Some(oldest) = oldest.and_then(|oldest| entries.order.remove(&oldest)) {
+                    entries.documents.remove(&oldest);
+                }
+            }
+            let tick = entries.next_tick();
+            let key: Arc<str> = key.into();
+            entries.order.insert(tick, key.clone());
+            entries.documents.insert(key, (document.clone(), tick));
+        }
+        Some(document)
+    }
+}
@@ -0,0 +1,266 @@
+//! Runtime control of logging
+//!
+//! Router does not install tracing subscriber, so [LogFilter] is meant to be plugged into application's one,
+//! making verbosity adjustable at runtime, e.g. via [admin](crate::admin) endpoints:
+//!
+//! ```ignore
+//! use graphql_router::log::LogFilter;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let filter = tracing_subscriber::filter::filter_fn(|metadata| LogFilter::global().enabled(metadata));
+//! tracing_subscriber::registry().with(tracing_subscriber::fmt::layer().with_filter(filter)).init();
+//! ```
+
+use serde::{Deserialize, Deserializer};
+use tracing::level_filters::LevelFilter;
+
+use crate::sample::Sequence;
+
+use std::collections::BTreeMap;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+///Target of events, logging requests and responses of subgraphs with debug enabled.
+///
+///Events are emitted at `INFO` level only for such subgraphs, so [LogFilter] always enables them.
+pub const SUBGRAPH_DEBUG_TARGET: &str = "graphql_router::subgraph_debug";
+
+struct State {
+    default: LevelFilter,
+    //Target prefixes with their levels, the longest matching prefix applies.
+    modules: Vec<(String, LevelFilter)>,
+    debug_subgraphs: Vec<String>,
+    trace_sampling: Option<f64>,
+}
+
+#[inline]
+fn is_within(target: &str, module: &str) -> bool {
+    match target.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+//Distinguishes `null`, which is `Some(None)`, from missing field.
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Partial update of [LogFilter]
+///
+///Unspecified fields are left unchanged.
+pub struct LogPatch {
+    #[serde(default)]
+    ///Level of targets without own level, e.g. `info` or `off`.
+    pub default: Option<String>,
+    #[serde(default)]
+    ///Levels of target prefixes, with `null` removing prefix's own level.
+    pub modules: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    ///Subgraphs, whose requests and responses are logged, replacing current ones.
+    pub debug_subgraphs: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    ///Ratio of traces to sample, when client defers sampling decision, with `null` keeping it deferred.
+    pub trace_sampling: Option<Option<f64>>,
+}
+
+#[inline]
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| format!("Invalid log level '{}'", level))
+}
+
+impl LogPatch {
+    #[allow(clippy::type_complexity)]
+    fn parse(&self) -> Result<(Option<LevelFilter>, Vec<(&str, Option<LevelFilter>)>), String> {
+        let default = self.default.as_deref().map(parse_level).transpose()?;
+        let mut modules = Vec::with_capacity(self.modules.len());
+        for (module, level) in self.modules.iter() {
+            modules.push((module.as_str(), level.as_deref().map(parse_level).transpose()?));
+        }
+        if let Some(Some(ratio)) = self.trace_sampling {
+            if !(0.0..=1.0).contains(&ratio) {
+                return Err(format!("Trace sampling ratio {} is not within 0..1", ratio));
+            }
+        }
+        Ok((default, modules))
+    }
+
+    #[inline]
+    ///Checks that all levels and sampling ratio are valid.
+    pub fn validate(&self) -> Result<(), String> {
+        self.parse().map(|_| ())
+    }
+
+    ///Returns update, which turns filter configured by `self` into one configured by `new`.
+    ///
+    ///Levels of modules, missing in `new`, are removed, while missing default level is reset to `info`.
+    pub fn diff(&self, new: &LogPatch) -> LogPatch {
+        let mut patch = new.clone();
+        if patch.default.is_none() && self.default.is_some() {
+            patch.default = Some("info".to_owned());
+        }
+        for module in self.modules.keys() {
+            patch.modules.entry(module.clone()).or_insert(None);
+        }
+        if patch.debug_subgraphs.is_none() && self.debug_subgraphs.is_some() {
+            patch.debug_subgraphs = Some(Vec::new());
+        }
+        if patch.trace_sampling.is_none() && self.trace_sampling.is_some() {
+            patch.trace_sampling = Some(None);
+        }
+        patch
+    }
+
+    ///Applies update to `filter`, leaving it unchanged if any of levels is invalid.
+    pub fn apply(&self, filter: &LogFilter) -> Result<(), String> {
+        let (default, modules) = self.parse()?;
+
+        if let Some(default) = default {
+            filter.set_default(default);
+        }
+        for (module, level) in modules {
+            filter.set_level(module, level);
+        }
+        if let Some(debug_subgraphs) = self.debug_subgraphs.as_ref() {
+            filter.write().debug_subgraphs = debug_subgraphs.clone();
+        }
+        if let Some(trace_sampling) = self.trace_sampling {
+            filter.set_trace_sampling(trace_sampling);
+        }
+        Ok(())
+    }
+}
+
+///Log filter, which can be changed at runtime.
+pub struct LogFilter {
+    state: RwLock<State>,
+    sequence: Sequence,
+}
+
+impl LogFilter {
+    #[inline(always)]
+    ///Creates filter, enabling `INFO` and above.
+    pub const fn new() -> Self {
+        Self {
+            state: RwLock::new(State {
+                default: LevelFilter::INFO,
+                modules: Vec::new(),
+                debug_subgraphs: Vec::new(),
+                trace_sampling: None,
+            }),
+            sequence: Sequence::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns filter shared by router.
+    pub fn global() -> &'static Self {
+        static FILTER: LogFilter = LogFilter::new();
+        &FILTER
+    }
+
+    #[inline(always)]
+    fn read(&self) -> RwLockReadGuard<'_, State> {
+        match self.state.read() {
+            Ok(state) => state,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    #[inline(always)]
+    fn write(&self) -> RwLockWriteGuard<'_, State> {
+        match self.state.write() {
+            Ok(state) => state,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    ///Returns whether event or span with `metadata` is enabled.
+    pub fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        let target = metadata.target();
+        if target == SUBGRAPH_DEBUG_TARGET {
+            return true;
+        }
+        let state = self.read();
+        let level = state
+            .modules
+            .iter()
+            .filter(|(module, _)| is_within(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(state.default, |(_, level)| *level);
+        *metadata.level() <= level
+    }
+
+    #[inline]
+    ///Sets level of targets without own level.
+    pub fn set_default(&self, level: LevelFilter) {
+        self.write().default = level;
+    }
+
+    ///Sets level of targets starting with `module`, or removes it, if `level` is `None`.
+    pub fn set_level(&self, module: &str, level: Option<LevelFilter>) {
+        let mut state = self.write();
+        state.modules.retain(|(current, _)| current != module);
+        if let Some(level) = level {
+            state.modules.push((module.to_owned(), level));
+        }
+    }
+
+    ///Enables or disables logging of requests and responses of subgraph `name`.
+    pub fn debug_subgraph(&self, name: &str, is_enabled: bool) {
+        let mut state = self.write();
+        state.debug_subgraphs.retain(|current| current != name);
+        if is_enabled {
+            state.debug_subgraphs.push(name.to_owned());
+        }
+    }
+
+    #[inline]
+    ///Returns whether requests and responses of subgraph `name` are logged.
+    pub fn is_subgraph_debug(&self, name: &str) -> bool {
+        self.read().debug_subgraphs.iter().any(|current| current == name)
+    }
+
+    #[inline]
+    ///Sets ratio of traces to sample, when client defers sampling decision, clamped to `0.0..=1.0`.
+    ///
+    ///With `None`, decision is left to subgraphs.
+    pub fn set_trace_sampling(&self, ratio: Option<f64>) {
+        self.write().trace_sampling = ratio.map(|ratio| ratio.clamp(0.0, 1.0));
+    }
+
+    #[inline]
+    ///Returns sampling decision for trace, whose client deferred it, if ratio is set.
+    pub fn sample_trace(&self) -> Option<bool> {
+        let ratio = self.read().trace_sampling?;
+        Some(self.sequence.next_fraction() < ratio)
+    }
+
+    ///Returns JSON representation of filter.
+    pub fn to_json(&self) -> serde_json::Value {
+        let state = self.read();
+        let modules = state
+            .modules
+            .iter()
+            .map(|(module, level)| (module.clone(), serde_json::Value::String(level.to_string().to_ascii_lowercase())))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::json!({
+            "default": state.default.to_string().to_ascii_lowercase(),
+            "modules": modules,
+            "debug_subgraphs": state.debug_subgraphs,
+            "trace_sampling": state.trace_sampling,
+        })
+    }
+}
+
+impl Default for LogFilter {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,255 @@
+//! Manifest of hot operations, planned again on startup
+//!
+//! Query planner keeps plans in memory only, so restarted router pays planning latency for every operation
+//! it sees first. [PlanManifest] records operations router handles, can be saved to disk and loaded on next
+//! start, so that all of them are planned before router takes live traffic.
+
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse};
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::parser::from_graphql_request;
+use crate::plan::PLAN_ONLY;
+use crate::{GraphqlRouter, WarmupOperation};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+///Operation of manifest
+pub struct ManifestOperation {
+    ///GraphQL query.
+    pub query: String,
+    #[serde(default)]
+    ///Name of operation, if query has multiple.
+    pub operation_name: Option<String>,
+    #[serde(default)]
+    ///Number of times operation was handled.
+    pub hits: u64,
+}
+
+struct ManifestState {
+    capacity: usize,
+    operations: HashMap<(String, Option<String>), u64>,
+}
+
+#[derive(Clone)]
+///Manifest of hot operations, shared between router and its owner.
+///
+///Manifest keeps at most `capacity` operations, replacing least used one, when new operation is recorded.
+pub struct PlanManifest {
+    state: Arc<Mutex<ManifestState>>,
+}
+
+impl PlanManifest {
+    #[inline]
+    ///Creates empty manifest, keeping up to `capacity` operations.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ManifestState {
+                capacity,
+                operations: HashMap::new(),
+            })),
+        }
+    }
+
+    ///Loads manifest from JSON file at `path`, keeping up to `capacity` of most used operations.
+    ///
+    ///Missing file results in empty manifest, as it is expected on first start.
+    pub fn load<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<Self> {
+        let manifest = Self::new(capacity);
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(manifest),
+            Err(error) => return Err(error),
+        };
+        let mut operations: Vec<ManifestOperation> = serde_json::from_slice(&bytes)?;
+        operations.sort_by(|left, right| right.hits.cmp(&left.hits));
+        operations.truncate(capacity);
+        {
+            let mut state = manifest.lock();
+            for operation in operations {
+                state.operations.insert((operation.query, operation.operation_name), operation.hits);
+            }
+        }
+        Ok(manifest)
+    }
+
+    ///Saves manifest as JSON file at `path`, replacing it atomically.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let bytes = serde_json::to_vec_pretty(&self.operations())?;
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, bytes)?;
+        std::fs::rename(&temp, path)
+    }
+
+    ///Returns operations, from most used to least used.
+    pub fn operations(&self) -> Vec<ManifestOperation> {
+        let state = self.lock();
+        let mut operations = state
+            .operations
+            .iter()
+            .map(|((query, operation_name), hits)| ManifestOperation {
+                query: query.clone(),
+                operation_name: operation_name.clone(),
+                hits: *hits,
+            })
+            .collect::<Vec<_>>();
+        operations.sort_by(|left, right| right.hits.cmp(&left.hits).then_with(|| left.query.cmp(&right.query)));
+        operations
+    }
+
+    #[inline]
+    ///Returns number of operations.
+    pub fn len(&self) -> usize {
+        self.lock().operations.len()
+    }
+
+    #[inline]
+    ///Returns whether manifest has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    fn lock(&self) -> std::sync::MutexGuard<'_, ManifestState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    fn record(&self, query: &str, operation_name: Option<&str>) {
+        let mut state = self.lock();
+        let key = (query.to_owned(), operation_name.map(str::to_owned));
+        if let Some(hits) = state.operations.get_mut(&key) {
+            *hits += 1;
+            return;
+        } else if state.capacity == 0 {
+            return;
+        }
+
+        if state.operations.len() >= state.capacity {
+            let least_used = state
+                .operations
+                .iter()
+                .min_by_key(|(_, hits)| **hits)
+                .map(|(key, _)| key.clone());
+            if let Some(least_used) = least_used {
+                state.operations.remove(&least_used);
+            }
+        }
+        state.operations.insert(key, 1);
+    }
+}
+
+///Plans operations of `manifest` one by one, returning number of successfully planned ones.
+///
+///Failures are only logged, as operations may no longer be valid against current schema.
+pub(crate) async fn run(router: &mut GraphqlRouter, manifest: &PlanManifest) -> usize {
+    let operations = manifest.operations();
+    let mut planned = 0;
+
+    for operation in operations.iter() {
+        let name = operation.operation_name.as_deref().unwrap_or("<anonymous>");
+        let mut warmup = WarmupOperation::new(operation.query.as_str());
+        warmup.operation_name = operation.operation_name.clone();
+        let body = match warmup.request() {
+            Ok(body) => body,
+            Err(error) => {
+                tracing::warn!("Plan manifest '{}': Invalid operation: {}", name, error);
+                continue;
+            }
+        };
+        match router.plan(from_graphql_request(body)).await {
+            Ok(_) => planned += 1,
+            Err(error) => tracing::warn!("Plan manifest '{}': {}", name, error),
+        }
+    }
+
+    tracing::info!("Plan manifest: {}/{} operations planned", planned, operations.len());
+    planned
+}
+
+///Records operations of successful requests into [PlanManifest].
+pub(crate) struct ManifestRecorder {
+    manifest: PlanManifest,
+}
+
+impl ManifestRecorder {
+    #[inline(always)]
+    pub(crate) fn new(manifest: PlanManifest) -> Self {
+        Self { manifest }
+    }
+}
+
+impl Plugin for ManifestRecorder {
+    type Config = ();
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::new(PlanManifest::new(0)))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        RecorderService {
+            inner: service,
+            manifest: self.manifest.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct RecorderService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    manifest: PlanManifest,
+}
+
+impl tower::Service<RouterRequest> for RecorderService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        //Operations planned from manifest itself are not its hits.
+        if let Ok(Some(_)) = req.context.get::<_, u64>(PLAN_ONLY) {
+            return self.inner.call(req);
+        }
+        let body = req.originating_request.body();
+        let operation = match body.query.as_ref() {
+            Some(query) => (query.clone(), body.operation_name.clone()),
+            None => return self.inner.call(req),
+        };
+
+        let manifest = self.manifest.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            //Rejected requests are not planned, so they are not worth planning on startup.
+            if response.response.status().is_success() {
+                manifest.record(&operation.0, operation.1.as_deref());
+            }
+            Ok(response)
+        })
+    }
+}
@@ -1,3 +1,7 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
 use core::fmt;
 
 use crate::{HttpRequest, RouterRequest};
@@ -8,6 +12,15 @@ pub enum ParseHttpError {
     Http(hyper::Error),
     ///Body contains invalid Graphql Request.
     Invalid(serde_json::Error),
+    ///Request is rejected by check, with reason.
+    Rejected(String),
+    ///Batch contains more operations than allowed.
+    BatchSize {
+        ///Number of operations in batch.
+        size: usize,
+        ///Maximum number of operations.
+        limit: usize,
+    },
 }
 
 impl From<hyper::Error> for ParseHttpError {
@@ -30,6 +43,11 @@ impl fmt::Display for ParseHttpError {
         match self {
             ParseHttpError::Http(error) => fmt.write_fmt(format_args!("Failed to read Graphql request: {}", error)),
             ParseHttpError::Invalid(error) => fmt.write_fmt(format_args!("Invalid Graphql Request: {}", error)),
+            ParseHttpError::Rejected(reason) => fmt.write_str(reason),
+            ParseHttpError::BatchSize { size, limit } => fmt.write_fmt(format_args!(
+                "Batch of {} operations exceeds limit of {}",
+                size, limit
+            )),
         }
     }
 }
@@ -40,11 +58,275 @@ pub fn from_request_parts(parts: http::request::Parts, body: crate::GraphqlReque
     graphql.into()
 }
 
+///Creates router request from GraphQL request, as if it was sent via `POST /`.
+pub(crate) fn from_graphql_request(body: crate::GraphqlRequest) -> RouterRequest {
+    let (parts, _) = http::Request::post("/")
+        .body(())
+        .expect("no argument can fail to parse or converted to the internal representation here")
+        .into_parts();
+    from_request_parts(parts, body)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GetBody<'a> {
+    query: Option<&'a str>,
+    operation_name: Option<&'a str>,
+    variables: Option<&'a RawValue>,
+    extensions: Option<&'a RawValue>,
+}
+
+pub(crate) fn is_mutation(query: &str, operation_name: Option<&str>) -> bool {
+    use async_graphql::parser::types::{DocumentOperations, OperationType};
+
+    //Invalid documents are left to be reported by router.
+    let document = match async_graphql::parser::parse_query(query) {
+        Ok(document) => document,
+        Err(_) => return false,
+    };
+    let operation = match (&document.operations, operation_name) {
+        (DocumentOperations::Single(operation), _) => Some(operation),
+        (DocumentOperations::Multiple(operations), Some(name)) => operations.get(name),
+        (DocumentOperations::Multiple(_), None) => None,
+    };
+    operation.map_or(false, |operation| operation.node.ty == OperationType::Mutation)
+}
+
+#[inline(always)]
+fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '"'
+}
+
+///Returns `query` without comments and insignificant whitespace and commas, so that documents differing only in
+///formatting are the same.
+///
+///Strings are kept as they are, with tokens separated by single space only where it is required.
+pub(crate) fn normalize_document(query: &str) -> String {
+    let mut result = String::with_capacity(query.len());
+    let mut is_separated = false;
+    let mut chars = query.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '#' => {
+                while chars.next_if(|ch| *ch != '\n' && *ch != '\r').is_some() {}
+                is_separated = true;
+                continue;
+            }
+            ',' => {
+                is_separated = true;
+                continue;
+            }
+            ch if ch.is_whitespace() || ch == '\u{feff}' => {
+                is_separated = true;
+                continue;
+            }
+            _ => (),
+        }
+
+        if is_separated && is_name_char(ch) && result.ends_with(is_name_char) {
+            result.push(' ');
+        }
+        is_separated = false;
+        result.push(ch);
+
+        if ch == '"' {
+            let mut rest = chars.clone();
+            let is_block = rest.next() == Some('"') && rest.next() == Some('"');
+            if is_block {
+                result.push_str("\"\"");
+                chars.next();
+                chars.next();
+            }
+            //Copies string up to and including closing quote.
+            let mut quotes = 0;
+            while let Some(ch) = chars.next() {
+                result.push(ch);
+                match ch {
+                    //Block string has only one escape sequence: `\"""`.
+                    '\\' if is_block => {
+                        let mut rest = chars.clone();
+                        if rest.next() == Some('"') && rest.next() == Some('"') && rest.next() == Some('"') {
+                            result.push_str("\"\"\"");
+                            chars = rest;
+                        }
+                        quotes = 0;
+                    }
+                    '\\' => {
+                        if let Some(escaped) = chars.next() {
+                            result.push(escaped);
+                        }
+                    }
+                    '"' if !is_block => break,
+                    '"' => {
+                        quotes += 1;
+                        if quotes == 3 {
+                            break;
+                        }
+                    }
+                    _ => quotes = 0,
+                }
+            }
+        }
+    }
+    result
+}
+
+///Creates JSON body out of `GET` request's query string.
+fn get_request_body(http: &http::request::Parts) -> Result<Bytes, ParseHttpError> {
+    let mut query = None;
+    let mut operation_name = None;
+    let mut variables = None;
+    let mut extensions = None;
+    for (key, value) in form_urlencoded::parse(http.uri.query().unwrap_or_default().as_bytes()) {
+        match key.as_ref() {
+            "query" => query = Some(value),
+            "operationName" => operation_name = Some(value),
+            "variables" => variables = Some(value),
+            "extensions" => extensions = Some(value),
+            _ => (),
+        }
+    }
+
+    let query = query.as_deref();
+    let operation_name = operation_name.as_deref();
+    if let Some(query) = query {
+        if is_mutation(query, operation_name) {
+            return Err(ParseHttpError::Rejected("Mutations are not allowed over GET".to_owned()));
+        }
+    }
+    let body = GetBody {
+        query,
+        operation_name,
+        variables: variables.as_deref().map(serde_json::from_str).transpose()?,
+        extensions: extensions.as_deref().map(serde_json::from_str).transpose()?,
+    };
+    Ok(serde_json::to_vec(&body)?.into())
+}
+
+///Reads GraphQL request as JSON, either from body or, for `GET` requests, from query string.
+async fn read_body(http: &http::request::Parts, body: hyper::Body) -> Result<Bytes, ParseHttpError> {
+    match http.method {
+        http::Method::GET => get_request_body(http),
+        _ => Ok(hyper::body::to_bytes(body).await?),
+    }
+}
+
 ///Parses raw HTTP Request into GraphqlRouter's request.
+///
+///`GET` requests are read from query string, except for mutations which are rejected.
 pub async fn parse_http_request(req: HttpRequest) -> Result<RouterRequest, ParseHttpError> {
     let (http, body) = req.into_parts();
-    let bytes = hyper::body::to_bytes(body).await?;
-    let graphql = apollo_router_core::Request::from_bytes(bytes)?;
+    let bytes = read_body(&http, body).await?;
+    let graphql = crate::json::parse_request(bytes)?;
+    let graphql = apollo_router_core::http_compat::Request::from_parts(http, graphql);
+    Ok(graphql.into())
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+///Graphql Request with variables and extensions left unparsed.
+pub struct RequestHead<'a> {
+    #[serde(default)]
+    ///Query document.
+    pub query: Option<String>,
+    #[serde(default)]
+    ///Name of operation to execute.
+    pub operation_name: Option<String>,
+    #[serde(borrow, default)]
+    ///Raw JSON of variables.
+    pub variables: Option<&'a RawValue>,
+    #[serde(borrow, default)]
+    ///Raw JSON of extensions.
+    pub extensions: Option<&'a RawValue>,
+}
+
+type Object = serde_json_bytes::Map<serde_json_bytes::ByteString, serde_json_bytes::Value>;
+
+//Parses raw JSON object, treating missing value or `null` as empty object.
+fn parse_object(raw: Option<&RawValue>) -> Result<Object, serde_json::Error> {
+    match raw {
+        Some(raw) if raw.get() != "null" => serde_json::from_str(raw.get()),
+        _ => Ok(Default::default()),
+    }
+}
+
+impl RequestHead<'_> {
+    ///Creates GraphQL request, deserializing variables and extensions.
+    pub fn into_request(self) -> Result<crate::GraphqlRequest, serde_json::Error> {
+        let mut request = crate::GraphqlRequest::default();
+        request.variables = parse_object(self.variables)?.into();
+        request.extensions = parse_object(self.extensions)?;
+        request.query = self.query;
+        request.operation_name = self.operation_name;
+        Ok(request)
+    }
+}
+
+///Parses raw HTTP Request into GraphqlRouter's request, if it passes `check`.
+///
+///`check` sees only [RequestHead], so that rejected requests don't pay for deserialization of variables and
+///extensions, which can be arbitrarily large.
+///
+///Body is deserialized once, with variables and extensions parsed out of head only after `check`.
+pub async fn parse_http_request_with<F>(req: HttpRequest, check: F) -> Result<RouterRequest, ParseHttpError>
+where
+    F: FnOnce(&http::request::Parts, &RequestHead<'_>) -> Result<(), String>,
+{
+    let (http, body) = req.into_parts();
+    let bytes = read_body(&http, body).await?;
+    let head = serde_json::from_slice::<RequestHead>(&bytes)?;
+    check(&http, &head).map_err(ParseHttpError::Rejected)?;
+    let graphql = head.into_request()?;
     let graphql = apollo_router_core::http_compat::Request::from_parts(http, graphql);
     Ok(graphql.into())
 }
+
+#[inline]
+pub(crate) fn clone_parts(parts: &http::request::Parts) -> http::request::Parts {
+    let (mut result, _) = http::Request::builder()
+        .method(parts.method.clone())
+        .uri(parts.uri.clone())
+        .version(parts.version)
+        .body(())
+        .expect("no argument can fail to parse or converted to the internal representation here")
+        .into_parts();
+    result.headers = parts.headers.clone();
+    result
+}
+
+///Returns whether body is batch of operations, i.e. JSON array.
+#[inline]
+pub(crate) fn is_batch(bytes: &[u8]) -> bool {
+    bytes.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[')
+}
+
+///Parses batch of GraphQL requests into router requests sharing HTTP head.
+///
+///Operation that fails `check` is rejected individually, leaving its slot with error message.
+pub(crate) fn parse_batch<F>(
+    http: http::request::Parts,
+    bytes: &[u8],
+    limit: usize,
+    check: F,
+) -> Result<Vec<Result<RouterRequest, String>>, ParseHttpError>
+where
+    F: Fn(&http::request::Parts, &RequestHead<'_>) -> Result<(), String>,
+{
+    let heads = serde_json::from_slice::<Vec<RequestHead>>(bytes)?;
+    if heads.is_empty() {
+        return Err(ParseHttpError::Rejected("Batch must contain at least one operation".to_owned()));
+    } else if heads.len() > limit {
+        return Err(ParseHttpError::BatchSize {
+            size: heads.len(),
+            limit,
+        });
+    }
+    let checks = heads.iter().map(|head| check(&http, head)).collect::<Vec<_>>();
+
+    let graphql = serde_json::from_slice::<Vec<crate::GraphqlRequest>>(bytes)?;
+    let mut result = Vec::with_capacity(graphql.len());
+    for (body, check) in graphql.into_iter().zip(checks) {
+        result.push(check.map(|_| from_request_parts(clone_parts(&http), body)));
+    }
+    Ok(result)
+}
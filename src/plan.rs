@@ -0,0 +1,257 @@
+//! Query plan inspection
+
+use apollo_router_core::{
+    ExecutionRequest, ExecutionResponse, Plugin, QueryPlannerRequest, QueryPlannerResponse, ResponseBody,
+};
+use serde::Serialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use core::fmt;
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+
+use crate::plugins::Ongoing;
+use crate::{GraphqlRouter, HandleError, RouterRequest};
+
+///Query planner service, as seen by plugins.
+pub type QueryPlannerService = BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>;
+///Hook, wrapping query planner service.
+pub type QueryPlannerHook = Box<dyn FnMut(QueryPlannerService) -> QueryPlannerService + Send + Sync>;
+
+//Context key, which marks request as planning only, holding id of its plan.
+pub(crate) const PLAN_ONLY: &str = "graphql_router::plan_only";
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+///Query plan of operation, as produced by query planner.
+pub struct QueryPlan {
+    root: serde_json::Value,
+}
+
+impl QueryPlan {
+    pub(crate) fn from_planner(response: &QueryPlannerResponse) -> Option<Self> {
+        match serde_json::to_value(&*response.query_plan) {
+            Ok(root) => Some(Self { root }),
+            Err(error) => {
+                tracing::warn!("Unable to serialize query plan: {}", error);
+                None
+            }
+        }
+    }
+
+    #[inline(always)]
+    ///Returns JSON representation of plan.
+    pub fn as_json(&self) -> &serde_json::Value {
+        &self.root
+    }
+
+    #[inline(always)]
+    ///Returns JSON representation of plan.
+    pub fn into_json(self) -> serde_json::Value {
+        self.root
+    }
+
+    ///Returns names of subgraphs fetched by plan, in plan order, one per fetch.
+    pub fn fetches(&self) -> Vec<&str> {
+        fn collect<'a>(node: &'a serde_json::Value, fetches: &mut Vec<&'a str>) {
+            match node {
+                serde_json::Value::Object(node) => {
+                    if node.get("kind").and_then(serde_json::Value::as_str) == Some("Fetch") {
+                        if let Some(service) = node.get("serviceName").and_then(serde_json::Value::as_str) {
+                            fetches.push(service);
+                        }
+                    }
+                    for value in node.values() {
+                        collect(value, fetches);
+                    }
+                }
+                serde_json::Value::Array(nodes) => {
+                    for node in nodes {
+                        collect(node, fetches);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut fetches = Vec::new();
+        collect(&self.root, &mut fetches);
+        fetches
+    }
+}
+
+#[derive(Debug)]
+///Failure to plan operation
+pub enum PlanError {
+    ///Router failed to handle request.
+    Handle(HandleError),
+    ///Request was rejected before planning, with GraphQL errors of response.
+    Rejected(serde_json::Value),
+}
+
+impl fmt::Display for PlanError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::Handle(error) => fmt.write_fmt(format_args!("Failed to plan request: {}", error)),
+            PlanError::Rejected(errors) => fmt.write_fmt(format_args!("Request is rejected: {}", errors)),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+#[derive(Clone, Default)]
+///Query plans of ongoing requests, shared with [PlanInspector].
+pub(crate) struct Plans {
+    ongoing: Ongoing<Option<QueryPlan>>,
+}
+
+impl Plans {
+    ///Plans `req` using `router`, without executing any fetches.
+    pub(crate) async fn plan(&self, router: &mut GraphqlRouter, req: RouterRequest) -> Result<QueryPlan, PlanError> {
+        let id = self.ongoing.start(None);
+        if let Err(error) = req.context.insert(PLAN_ONLY, id) {
+            self.ongoing.finish(id);
+            return Err(PlanError::Handle(error));
+        }
+
+        let response = router.handle(req).await;
+        match (self.ongoing.finish(id).flatten(), response) {
+            (Some(plan), _) => Ok(plan),
+            (None, Err(error)) => Err(PlanError::Handle(error)),
+            (None, Ok(response)) => {
+                let errors = match response.response.body() {
+                    ResponseBody::GraphQL(body) => serde_json::to_value(&body.errors).unwrap_or_default(),
+                    _ => serde_json::Value::Null,
+                };
+                Err(PlanError::Rejected(errors))
+            }
+        }
+    }
+}
+
+///Captures query plans of planning only requests, skipping their execution.
+///
+///Always installed by [GraphqlRouterBuilder::finish](crate::GraphqlRouterBuilder::finish).
+pub(crate) struct PlanInspector {
+    plans: Plans,
+}
+
+impl PlanInspector {
+    #[inline(always)]
+    pub(crate) fn new(plans: Plans) -> Self {
+        Self { plans }
+    }
+}
+
+impl Plugin for PlanInspector {
+    type Config = ();
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::new(Plans::default()))))
+    }
+
+    fn query_planning_service(
+        &mut self,
+        service: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ) -> BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError> {
+        PlanCaptureService {
+            inner: service,
+            plans: self.plans.clone(),
+        }
+        .boxed()
+    }
+
+    fn execution_service(
+        &mut self,
+        service: BoxService<ExecutionRequest, ExecutionResponse, BoxError>,
+    ) -> BoxService<ExecutionRequest, ExecutionResponse, BoxError> {
+        crate::plugins::checkpoint(service, |req: ExecutionRequest| {
+            match req.context.get::<_, u64>(PLAN_ONLY) {
+                Ok(Some(_)) => (),
+                _ => return Ok(req),
+            }
+
+            let response = apollo_router_core::Response::from_bytes("router", "{\"data\":null}".into())
+                .expect("Valid GraphQL response");
+            let response = http::Response::builder()
+                .body(response)
+                .expect("no argument can fail to parse or converted to the internal representation here")
+                .into();
+            Err(ExecutionResponse {
+                response,
+                context: req.context,
+            })
+        })
+    }
+}
+
+///Wraps query planner service with hook, set by
+///[GraphqlRouterBuilder::query_planner](crate::GraphqlRouterBuilder::query_planner).
+pub(crate) struct PlannerHook {
+    hook: QueryPlannerHook,
+}
+
+impl PlannerHook {
+    #[inline(always)]
+    pub(crate) fn new(hook: QueryPlannerHook) -> Self {
+        Self { hook }
+    }
+}
+
+impl Plugin for PlannerHook {
+    type Config = ();
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::new(Box::new(|service| service)))))
+    }
+
+    #[inline]
+    fn query_planning_service(&mut self, service: QueryPlannerService) -> QueryPlannerService {
+        (self.hook)(service)
+    }
+}
+
+struct PlanCaptureService {
+    inner: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    plans: Plans,
+}
+
+impl tower::Service<QueryPlannerRequest> for PlanCaptureService {
+    type Response = QueryPlannerResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<QueryPlannerResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: QueryPlannerRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(PLAN_ONLY) {
+            Ok(Some(id)) => id,
+            _ => return self.inner.call(req),
+        };
+
+        let plans = self.plans.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            if let Some(plan) = QueryPlan::from_planner(&response) {
+                plans.ongoing.update(id, |slot| *slot = Some(plan));
+            }
+            Ok(response)
+        })
+    }
+}
@@ -1,9 +1,11 @@
 //! Plugin repository
 
-use apollo_router_core::{Plugin, SubgraphRequest, SubgraphResponse};
+use apollo_router_core::{Context, Plugin, ResponseBody, RouterResponse, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
 use hyper::http::header::{
-    HeaderName, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, TE, TRAILER,
-    TRANSFER_ENCODING, UPGRADE,
+    HeaderName, HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HOST, PROXY_AUTHENTICATE,
+    PROXY_AUTHORIZATION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE,
 };
 use tower::util::BoxService;
 use tower::{BoxError, ServiceExt};
@@ -11,6 +13,95 @@ use tower::{BoxError, ServiceExt};
 use core::future::{ready, Future};
 use core::pin::Pin;
 use core::task;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+mod template;
+use template::HeaderTemplate;
+mod registry;
+pub use registry::PluginRegistry;
+mod lexer;
+mod limits;
+pub use limits::{RequestLimits, RequestLimitsConfig};
+pub(crate) use limits::check_query;
+mod operation;
+pub use operation::{OperationLimits, OperationLimitsConfig};
+mod load_shed;
+pub use load_shed::{LoadShed, LoadShedConfig};
+mod partial;
+pub use partial::{PartialResults, PartialResultsConfig, SubgraphFailurePolicy};
+mod maintenance;
+pub use maintenance::{Maintenance, MaintenanceMode};
+mod fault;
+pub use fault::{Fault, FaultInjection, FaultInjectionConfig, FaultRule};
+mod latency;
+pub use latency::{Delay, LatencyInjection, LatencyInjectionConfig, LatencyRule};
+mod ongoing;
+pub(crate) use ongoing::Ongoing;
+mod capture;
+pub use capture::{CaptureFormat, CapturedExchange, DebugCapture, DebugCaptureConfig};
+mod expose_plan;
+pub use expose_plan::{ExposeQueryPlan, ExposeQueryPlanConfig};
+mod nullability;
+pub use nullability::{Nullability, NullabilityConfig, NullabilityPolicy};
+mod timing;
+pub use timing::{ServerTiming, ServerTimingConfig};
+mod cookies;
+pub use cookies::{CookiePolicy, CookiePolicyConfig, CookieRule};
+mod baggage;
+pub use baggage::{Baggage, BaggageConfig};
+mod trace;
+pub use trace::{TraceFormat, TracePropagation, TracePropagationConfig};
+mod response_headers;
+pub use response_headers::{HeaderMerge, ResponseHeaders, ResponseHeadersConfig};
+mod user_agent;
+pub use user_agent::{UserAgent, UserAgentConfig, UserAgentMode};
+mod overrides;
+pub use overrides::{FetchOrder, FetchRoute, PlanOverrides, PlanOverridesConfig};
+mod entity_batch;
+pub use entity_batch::{EntityBatching, EntityBatchingConfig};
+mod policies;
+pub use policies::{OperationPolicies, OperationPoliciesConfig, OperationPolicy};
+mod priority;
+pub use priority::{FetchPriority, FetchPriorityConfig};
+pub(crate) use priority::InFlight;
+mod mutations;
+pub use mutations::{MutationMode, MutationOrdering, MutationOrderingConfig};
+pub(crate) use timing::record_parse_duration;
+mod logging;
+pub use logging::{Redaction, SubgraphLogging, SubgraphLoggingConfig};
+mod parallelism;
+pub use parallelism::{FetchParallelism, FetchParallelismConfig, FETCH_PARALLELISM};
+mod exposure;
+pub use exposure::{ErrorExposure, SubgraphErrors, SubgraphErrorsConfig};
+mod queue;
+mod audit;
+pub use audit::{Audit, AuditConfig, AuditRecord, AuditSink, AuditSinkConfig, FileAuditSink, HttpAuditSink};
+#[cfg(feature = "kafka")]
+pub use audit::KafkaAuditSink;
+mod events;
+pub use events::{
+    HttpEventSink, OperationEvent, OperationEventSink, OperationEventSinkConfig, OperationEvents, OperationEventsConfig,
+};
+#[cfg(feature = "kafka")]
+pub use events::KafkaEventSink;
+#[cfg(feature = "nats")]
+pub use events::NatsEventSink;
+mod api_key;
+pub use api_key::{ApiKeyConfig, ApiKeyInfo, ApiKeyStore, ApiKeys, ApiKeysConfig, API_CLIENT};
+mod suggestions;
+pub use suggestions::{strip_suggestions, HideSuggestions, HideSuggestionsConfig};
+mod persisted;
+pub use persisted::{PersistedQueries, PersistedQueriesConfig, PersistedQueryStore, StoreFuture, StoredQuery};
+#[cfg(feature = "redis-store")]
+pub use persisted::{RedisStore, RedisStoreConfig};
+mod cache;
+pub use cache::{CacheStore, MemoryCacheStore};
+#[cfg(feature = "memcached-store")]
+pub use cache::{MemcachedStore, MemcachedStoreConfig};
+mod metrics;
+pub use metrics::{Metrics, MetricsConfig, MetricsSnapshot};
+pub(crate) use metrics::Counters;
 
 static RESERVED_HEADERS: [HeaderName; 10] = [
     CONNECTION,
@@ -25,26 +116,148 @@ static RESERVED_HEADERS: [HeaderName; 10] = [
     HOST,
 ];
 
-pub struct PropagateHeaders;
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Header propagation config
+pub struct PropagateHeadersConfig {
+    #[serde(default)]
+    ///Names of client headers, which are not propagated, per subgraph name.
+    ///
+    ///This allows to keep credentials from reaching subgraphs, like third-party hosted ones.
+    pub strip: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    ///Names of headers added to reserved ones, which are never propagated.
+    pub reserve: Vec<String>,
+    #[serde(default)]
+    ///Names of headers removed from reserved ones, so that they are propagated.
+    ///
+    ///Reserved by default are hop-by-hop headers, `Content-Length`, `Content-Type` and `Host`.
+    pub unreserve: Vec<String>,
+    #[serde(default)]
+    ///Headers set on every subgraph request, with values templated from request context, replacing client's ones.
+    ///
+    ///Placeholders are `{request_id}`, `{client}`, `{jwt.<claim>}` and `{context.<key>}`, e.g.
+    ///`x-user-id: "{jwt.sub}"`. Header is not sent, if any placeholder has no value.
+    pub templates: BTreeMap<String, String>,
+}
+
+impl PropagateHeadersConfig {
+    #[inline]
+    ///Stops propagation of header `header` to subgraph `name`.
+    pub fn strip(mut self, name: &str, header: &str) -> Self {
+        self.strip.entry(name.to_owned()).or_default().push(header.to_owned());
+        self
+    }
+
+    #[inline]
+    ///Stops propagation of client credentials, `Authorization` and `Cookie`, to subgraph `name`.
+    pub fn strip_credentials(self, name: &str) -> Self {
+        self.strip(name, AUTHORIZATION.as_str()).strip(name, COOKIE.as_str())
+    }
+
+    #[inline]
+    ///Adds `header` to reserved headers, which are never propagated.
+    pub fn reserve(mut self, header: &str) -> Self {
+        self.reserve.push(header.to_owned());
+        self
+    }
+
+    #[inline]
+    ///Sets `header` of every subgraph request from `template`.
+    pub fn template(mut self, header: &str, template: &str) -> Self {
+        self.templates.insert(header.to_owned(), template.to_owned());
+        self
+    }
+
+    #[inline]
+    ///Removes `header` from reserved headers, so that it is propagated.
+    pub fn unreserve(mut self, header: &str) -> Self {
+        self.unreserve.push(header.to_owned());
+        self
+    }
+}
+
+#[inline]
+fn lowercase(headers: &[String]) -> Arc<[String]> {
+    headers.iter().map(|header| header.to_ascii_lowercase()).collect()
+}
+
+///Propagates headers of original request to subgraphs, except reserved ones, which are hop-by-hop and content
+///headers by default.
+///
+///Headers listed in `Connection` header of original request are hop-by-hop as well, so they are not propagated.
+///Headers set by other plugins, like `Cookie` with [CookiePolicy], are left to them.
+pub struct PropagateHeaders {
+    reserved: Arc<[String]>,
+    strip: BTreeMap<String, Arc<[String]>>,
+    templates: Arc<[(HeaderName, HeaderTemplate)]>,
+}
+
+impl PropagateHeaders {
+    ///Creates plugin with specified config.
+    pub fn with_config(config: PropagateHeadersConfig) -> Self {
+        let unreserve = lowercase(&config.unreserve);
+        let reserved = RESERVED_HEADERS
+            .iter()
+            .map(|header| header.as_str().to_owned())
+            .filter(|header| !unreserve.contains(header))
+            .chain(config.reserve.iter().map(|header| header.to_ascii_lowercase()))
+            .collect();
+        let strip = config
+            .strip
+            .iter()
+            .map(|(name, headers)| (name.clone(), lowercase(headers)))
+            .collect();
+        let templates = config
+            .templates
+            .iter()
+            .filter_map(|(header, template)| match HeaderName::from_bytes(header.as_bytes()) {
+                Ok(header) => Some((header, HeaderTemplate::parse(template))),
+                Err(_) => {
+                    tracing::warn!("Invalid templated header name '{}'", header);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            reserved,
+            strip,
+            templates,
+        }
+    }
+}
+
+impl Default for PropagateHeaders {
+    #[inline]
+    fn default() -> Self {
+        Self::with_config(PropagateHeadersConfig::default())
+    }
+}
 
 impl Plugin for PropagateHeaders {
-    type Config = ();
+    type Config = PropagateHeadersConfig;
 
     #[inline(always)]
-    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
     where
         Self: 'a,
     {
-        Box::pin(ready(Ok(Self)))
+        Box::pin(ready(Ok(Self::with_config(config))))
     }
 
     #[inline(always)]
     fn subgraph_service(
         &mut self,
-        _subgraph_name: &str,
+        subgraph_name: &str,
         service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
     ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
-        tower::ServiceBuilder::new().layer(Self).service(service).boxed()
+        PropagateHeadersService {
+            inner: service,
+            reserved: self.reserved.clone(),
+            strip: self.strip.get(subgraph_name).cloned().unwrap_or_else(|| Arc::from(Vec::new())),
+            templates: self.templates.clone(),
+        }
+        .boxed()
     }
 }
 
@@ -53,12 +266,20 @@ impl<S> tower::Layer<S> for PropagateHeaders {
 
     #[inline(always)]
     fn layer(&self, inner: S) -> Self::Service {
-        PropagateHeadersService { inner }
+        PropagateHeadersService {
+            inner,
+            reserved: self.reserved.clone(),
+            strip: Arc::from(Vec::new()),
+            templates: self.templates.clone(),
+        }
     }
 }
 
 pub struct PropagateHeadersService<S> {
     inner: S,
+    reserved: Arc<[String]>,
+    strip: Arc<[String]>,
+    templates: Arc<[(HeaderName, HeaderTemplate)]>,
 }
 
 impl<S: tower::Service<SubgraphRequest>> tower::Service<SubgraphRequest> for PropagateHeadersService<S> {
@@ -73,12 +294,153 @@ impl<S: tower::Service<SubgraphRequest>> tower::Service<SubgraphRequest> for Pro
 
     #[inline(always)]
     fn call(&mut self, mut req: SubgraphRequest) -> Self::Future {
+        let managed = managed_headers(&req.context);
+        let client = req.originating_request.headers();
+        //RFC 7230: headers listed in `Connection` apply only to current connection.
+        let connection = client
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
         let headers = req.subgraph_request.headers_mut();
-        for (key, value) in req.originating_request.headers().iter() {
-            if !RESERVED_HEADERS.contains(key) {
+        for (key, value) in client.iter() {
+            let key_name = key.as_str();
+            let is_skipped = self.reserved.iter().any(|name| name == key_name)
+                || connection.iter().any(|name| name == key_name)
+                || managed.iter().any(|pattern| matches_pattern(pattern, key_name))
+                || self.strip.iter().any(|name| name == key_name)
+                || self.templates.iter().any(|(name, _)| name == key);
+            if !is_skipped {
                 headers.insert(key, value.clone());
             }
         }
+        for (name, template) in self.templates.iter() {
+            //Client's value is never trusted, even if template cannot be rendered.
+            headers.remove(name);
+            let value = match template.render(&req.context) {
+                Some(value) => value,
+                None => continue,
+            };
+            match HeaderValue::from_str(&value) {
+                Ok(value) => {
+                    headers.insert(name, value);
+                }
+                Err(error) => tracing::warn!("Invalid value of templated header '{}': {}", name, error),
+            }
+        }
         self.inner.call(req)
     }
 }
+
+//Context key, which holds patterns of header names set by plugins, rather than propagated.
+const MANAGED_HEADERS: &str = "graphql_router::managed_headers";
+
+#[inline]
+fn managed_headers(context: &Context) -> Vec<String> {
+    context.get::<_, Vec<String>>(MANAGED_HEADERS).ok().flatten().unwrap_or_default()
+}
+
+///Marks subgraph request headers matching `pattern` as set by plugin, so that [PropagateHeaders] does not
+///override them.
+///
+///Expected to be called by router service, before any subgraph request is made.
+pub(crate) fn manage_header(context: &Context, pattern: &str) -> Result<(), BoxError> {
+    let mut managed = managed_headers(context);
+    if !managed.iter().any(|managed| managed == pattern) {
+        managed.push(pattern.to_owned());
+        context.insert(MANAGED_HEADERS, managed)?;
+    }
+    Ok(())
+}
+
+///Returns whether `name` matches `pattern`, where `*` matches any sequence of characters.
+pub(crate) fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let mut rest = match name.strip_prefix(parts.next().unwrap_or_default()) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        //No wildcard, so whole name must match
+        None => rest.is_empty(),
+        Some((suffix, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(idx) => rest = &rest[idx + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.ends_with(suffix)
+        }
+    }
+}
+
+#[inline]
+///Returns whether operation is listed in `names`, with empty list matching any operation.
+pub(crate) fn is_operation_listed(names: &[String], operation_name: Option<&str>) -> bool {
+    match operation_name {
+        _ if names.is_empty() => true,
+        Some(operation_name) => names.iter().any(|name| name == operation_name),
+        None => false,
+    }
+}
+
+///Service, which either passes request to inner service or responds immediately.
+pub(crate) struct CheckpointService<S, F> {
+    inner: S,
+    check: F,
+}
+
+impl<Req, S, F> tower::Service<Req> for CheckpointService<S, F>
+where
+    S: tower::Service<Req, Error = BoxError>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    F: FnMut(Req) -> Result<Req, S::Response>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: Req) -> Self::Future {
+        match (self.check)(req) {
+            Ok(req) => Box::pin(self.inner.call(req)),
+            Err(response) => Box::pin(ready(Ok(response))),
+        }
+    }
+}
+
+#[inline]
+///Wraps `service`, so that `check` can respond instead of it, by returning `Err`.
+pub(crate) fn checkpoint<Req, Res, F>(
+    service: BoxService<Req, Res, BoxError>,
+    check: F,
+) -> BoxService<Req, Res, BoxError>
+where
+    Req: 'static,
+    Res: Send + 'static,
+    F: FnMut(Req) -> Result<Req, Res> + Send + 'static,
+{
+    CheckpointService { inner: service, check }.boxed()
+}
+
+///Inserts `value` into GraphQL response extensions under `key`.
+pub(crate) fn insert_extension(response: &mut RouterResponse, key: &str, value: serde_json::Value) {
+    if let ResponseBody::GraphQL(body) = response.response.body_mut() {
+        match serde_json::from_value::<serde_json_bytes::Value>(value) {
+            Ok(value) => {
+                body.extensions.insert(key.into(), value);
+            }
+            Err(error) => tracing::warn!("Unable to insert extension '{}': {}", key, error),
+        }
+    }
+}
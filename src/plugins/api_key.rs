@@ -0,0 +1,312 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::StoreFuture;
+use crate::context::{ClientIdentity, ContextEntry, TypedContext};
+use crate::error::router_error;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const BUFFER_SIZE: usize = 1024;
+
+///Context key, which holds client identity of authenticated request, as [ClientIdentity].
+pub const API_CLIENT: &str = ClientIdentity::KEY;
+
+fn default_header() -> String {
+    "x-api-key".to_owned()
+}
+
+#[inline(always)]
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///API key settings
+pub struct ApiKeyConfig {
+    ///Client identity, stored in context under [API_CLIENT].
+    pub client: String,
+    #[serde(default = "default_enabled")]
+    ///Specifies whether key is accepted.
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///API keys config
+pub struct ApiKeysConfig {
+    #[serde(default = "default_header")]
+    ///Header with API key.
+    pub header: String,
+    #[serde(default)]
+    ///Keys mapped to their settings.
+    pub keys: BTreeMap<String, ApiKeyConfig>,
+}
+
+impl Default for ApiKeysConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            header: default_header(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+///Store of API keys, e.g. database shared between router replicas.
+pub trait ApiKeyStore: Send + Sync {
+    ///Returns settings of `key`, if it exists.
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<ApiKeyConfig>>;
+}
+
+struct ApiKey {
+    client: String,
+    enabled: AtomicBool,
+    //Seconds since UNIX epoch, 0 if never used.
+    last_used: AtomicU64,
+    //Set for keys found in store, which are looked up again once expired.
+    expires_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///State of API key.
+pub struct ApiKeyInfo {
+    ///Client identity.
+    pub client: String,
+    ///Specifies whether key is accepted.
+    pub enabled: bool,
+    ///Last time key was used to authenticate request.
+    pub last_used: Option<SystemTime>,
+}
+
+#[derive(Clone)]
+///Authenticates requests by API key header, which can be managed at runtime.
+///
+///Can be used as plugin, with all clones sharing the same keys.
+///
+///With [store](ApiKeys::with_store), unknown keys are looked up in it and cached for configured time.
+pub struct ApiKeys {
+    header: Arc<str>,
+    keys: Arc<RwLock<HashMap<String, Arc<ApiKey>>>>,
+    store: Option<(Arc<dyn ApiKeyStore>, Duration)>,
+}
+
+impl ApiKeys {
+    #[inline]
+    ///Creates store without keys, reading key from `header`.
+    pub fn new(header: &str) -> Self {
+        Self {
+            header: header.to_ascii_lowercase().into(),
+            keys: Default::default(),
+            store: None,
+        }
+    }
+
+    #[inline]
+    ///Sets `store`, resolving unknown keys, which are cached for `cache_ttl`.
+    ///
+    ///Changes to cached keys, e.g. via [set_enabled](Self::set_enabled), last until they are looked up again.
+    pub fn with_store(mut self, store: Arc<dyn ApiKeyStore>, cache_ttl: Duration) -> Self {
+        self.store = Some((store, cache_ttl));
+        self
+    }
+
+    ///Creates store according to `config`.
+    pub fn with_config(config: ApiKeysConfig) -> Self {
+        let this = Self::new(&config.header);
+        for (key, settings) in config.keys {
+            this.insert_with(key, settings.client, settings.enabled);
+        }
+        this
+    }
+
+    #[inline(always)]
+    ///Adds enabled `key` of `client`, replacing existing one.
+    pub fn insert(&self, key: String, client: String) {
+        self.insert_with(key, client, true)
+    }
+
+    #[inline(always)]
+    fn insert_with(&self, key: String, client: String, enabled: bool) {
+        self.insert_entry(key, client, enabled, None)
+    }
+
+    fn insert_entry(&self, key: String, client: String, enabled: bool, expires_at: Option<Instant>) {
+        let entry = Arc::new(ApiKey {
+            client,
+            enabled: AtomicBool::new(enabled),
+            last_used: AtomicU64::new(0),
+            expires_at,
+        });
+        match self.keys.write() {
+            Ok(mut keys) => keys.insert(key, entry),
+            Err(error) => error.into_inner().insert(key, entry),
+        };
+    }
+
+    ///Removes `key`, returning whether it existed.
+    pub fn remove(&self, key: &str) -> bool {
+        match self.keys.write() {
+            Ok(mut keys) => keys.remove(key).is_some(),
+            Err(error) => error.into_inner().remove(key).is_some(),
+        }
+    }
+
+    #[inline]
+    fn get(&self, key: &str) -> Option<Arc<ApiKey>> {
+        let entry = match self.keys.read() {
+            Ok(keys) => keys.get(key).cloned(),
+            Err(error) => error.into_inner().get(key).cloned(),
+        };
+        entry.filter(|entry| entry.expires_at.map_or(true, |expires_at| expires_at > Instant::now()))
+    }
+
+    #[inline]
+    fn key<'a>(&self, req: &'a RouterRequest) -> Option<&'a str> {
+        req.originating_request
+            .headers()
+            .get(&*self.header)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    //Looks up `key` in `store`, caching found key for `cache_ttl`.
+    async fn resolve(&self, store: &dyn ApiKeyStore, cache_ttl: Duration, key: String) {
+        match store.get(&key).await {
+            Ok(Some(settings)) => {
+                self.insert_entry(key, settings.client, settings.enabled, Some(Instant::now() + cache_ttl))
+            }
+            Ok(None) => (),
+            Err(error) => tracing::warn!("Failed to look up API key in store: {}", error),
+        }
+    }
+
+    ///Enables or disables `key`, returning whether it exists.
+    pub fn set_enabled(&self, key: &str, enabled: bool) -> bool {
+        match self.get(key) {
+            Some(entry) => {
+                entry.enabled.store(enabled, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Returns state of `key`.
+    pub fn info(&self, key: &str) -> Option<ApiKeyInfo> {
+        self.get(key).map(|entry| {
+            let last_used = entry.last_used.load(Ordering::Relaxed);
+            ApiKeyInfo {
+                client: entry.client.clone(),
+                enabled: entry.enabled.load(Ordering::Relaxed),
+                last_used: match last_used {
+                    0 => None,
+                    secs => Some(UNIX_EPOCH + Duration::from_secs(secs)),
+                },
+            }
+        })
+    }
+
+    fn check(&self, req: RouterRequest) -> Result<RouterRequest, RouterResponse> {
+        let entry = match self.key(&req).and_then(|key| self.get(key)) {
+            Some(entry) if entry.enabled.load(Ordering::Relaxed) => entry,
+            _ => {
+                tracing::info!("Rejected request with missing or invalid API key");
+                return Err(router_error(
+                    StatusCode::UNAUTHORIZED,
+                    "Invalid API key",
+                    "UNAUTHENTICATED",
+                    req.context,
+                ));
+            }
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs());
+        entry.last_used.store(now, Ordering::Relaxed);
+        if let Err(error) = TypedContext::new(&req.context).insert(ClientIdentity(entry.client.clone())) {
+            tracing::warn!("Unable to store API client: {}", error);
+        }
+        Ok(req)
+    }
+}
+
+impl Plugin for ApiKeys {
+    type Config = ApiKeysConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let keys = self.clone();
+        match self.store.clone() {
+            Some((store, cache_ttl)) => StoreLookupService {
+                inner: Buffer::new(service, BUFFER_SIZE),
+                keys,
+                store,
+                cache_ttl,
+            }
+            .boxed(),
+            None => super::checkpoint(service, move |req| keys.check(req)),
+        }
+    }
+}
+
+struct StoreLookupService {
+    inner: Buffer<BoxService<RouterRequest, RouterResponse, BoxError>, RouterRequest>,
+    keys: ApiKeys,
+    store: Arc<dyn ApiKeyStore>,
+    cache_ttl: Duration,
+}
+
+impl tower::Service<RouterRequest> for StoreLookupService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Inner service is cloned on call, so readiness is awaited by it.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let keys = self.keys.clone();
+        let store = self.store.clone();
+        let cache_ttl = self.cache_ttl;
+
+        let key = match keys.key(&req) {
+            Some(key) if keys.get(key).is_none() => Some(key.to_owned()),
+            _ => None,
+        };
+
+        Box::pin(async move {
+            if let Some(key) = key {
+                keys.resolve(store.as_ref(), cache_ttl, key).await;
+            }
+            match keys.check(req) {
+                Ok(req) => inner.oneshot(req).await,
+                Err(response) => Ok(response),
+            }
+        })
+    }
+}
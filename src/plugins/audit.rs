@@ -0,0 +1,486 @@
+use apollo_router_core::{Plugin, ResponseBody, RouterRequest, RouterResponse};
+use hyper::http::header::CONTENT_TYPE;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::logging::ANY;
+use super::queue::PayloadQueue;
+use super::Redaction;
+use crate::context::ClientIdentity;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+///Audit record of executed mutation.
+///
+///Records are chained by `hash`, which covers record and hash of previous record, so that modification
+///or removal of any record can be detected.
+pub struct AuditRecord {
+    ///Sequence number of record, starting from `1`.
+    pub sequence: u64,
+    ///RFC 3339 timestamp.
+    pub timestamp: String,
+    ///Identity of client, if known.
+    pub client: Option<String>,
+    ///Name of operation.
+    pub operation_name: Option<String>,
+    ///Variables with redacted values.
+    pub variables: serde_json::Map<String, serde_json::Value>,
+    ///Outcome of operation: `success`, `error` when response has errors or `failed`.
+    pub outcome: &'static str,
+    ///HTTP status of response, if router responded.
+    pub status: Option<u16>,
+    ///Hex encoded hash of previous record, empty for first record.
+    pub previous_hash: String,
+    ///Hex encoded SHA-256 of JSON record, with this field being empty.
+    pub hash: String,
+}
+
+///Destination of audit records.
+///
+///Records are written in order of their sequence on request path, so sink must not block, e.g. by queueing
+///records for background task.
+pub trait AuditSink: Send + Sync {
+    ///Writes `record`.
+    fn write(&self, record: &AuditRecord);
+
+    #[inline(always)]
+    ///Returns sequence and hash of the last record written before sink was created, so that chain is continued.
+    fn last(&self) -> Option<(u64, String)> {
+        None
+    }
+}
+
+#[inline(always)]
+fn default_queue_capacity() -> usize {
+    10_000
+}
+
+#[inline]
+fn serialize(record: &AuditRecord) -> Vec<u8> {
+    serde_json::to_vec(record).expect("JSON serialization should not fail")
+}
+
+//Queues record, which is lost if queue is full, breaking chain at its sequence.
+fn push(queue: &PayloadQueue, record: &AuditRecord) {
+    if !queue.push(serialize(record)) {
+        tracing::error!("Audit queue is full, record {} is lost", record.sequence);
+    }
+}
+
+#[derive(Deserialize)]
+struct ChainLink {
+    sequence: u64,
+    hash: String,
+}
+
+//Reads the last line of `file`, without its new line.
+fn read_last_line(file: &mut std::fs::File) -> io::Result<Vec<u8>> {
+    const CHUNK_SIZE: u64 = 4096;
+
+    let mut line = Vec::new();
+    let mut end = file.seek(SeekFrom::End(0))?;
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_SIZE);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&line);
+        line = chunk;
+
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        if let Some(idx) = content.iter().rposition(|byte| *byte == b'\n') {
+            return Ok(content[idx + 1..].to_vec());
+        }
+        end = start;
+    }
+    if line.ends_with(b"\n") {
+        line.pop();
+    }
+    Ok(line)
+}
+
+///Sink appending records to file as JSON lines.
+///
+///Records are written by background thread, so that requests never wait for file system.
+pub struct FileAuditSink {
+    sender: std::sync::mpsc::SyncSender<Vec<u8>>,
+    last: Option<(u64, String)>,
+}
+
+impl FileAuditSink {
+    ///Opens `path` for appending, queueing up to `capacity` records.
+    ///
+    ///Chain is continued from the last record of existing file, failing if it is malformed.
+    pub fn open(path: &std::path::Path, capacity: usize) -> io::Result<Self> {
+        let mut file = std::fs::OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        let line = read_last_line(&mut file)?;
+        let last = match line.is_empty() {
+            true => None,
+            false => match serde_json::from_slice::<ChainLink>(&line) {
+                Ok(link) => Some((link.sequence, link.hash)),
+                Err(error) => {
+                    let message = format!("{}: Last audit record is malformed: {}", path.display(), error);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            },
+        };
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<Vec<u8>>(capacity.max(1));
+        let path = path.to_owned();
+        std::thread::Builder::new().name("audit-file".to_owned()).spawn(move || {
+            for line in receiver {
+                if let Err(error) = file.write_all(&line) {
+                    tracing::error!("{}: Failed to write audit record: {}", path.display(), error);
+                }
+            }
+        })?;
+        Ok(Self { sender, last })
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn write(&self, record: &AuditRecord) {
+        let mut line = serialize(record);
+        line.push(b'\n');
+        if self.sender.try_send(line).is_err() {
+            tracing::error!("Audit queue is full, record {} is lost", record.sequence);
+        }
+    }
+
+    #[inline(always)]
+    fn last(&self) -> Option<(u64, String)> {
+        self.last.clone()
+    }
+}
+
+///Sink sending each record as JSON via `POST` to URL, in background task.
+pub struct HttpAuditSink {
+    queue: PayloadQueue,
+}
+
+impl HttpAuditSink {
+    ///Creates sink sending records to `url`, queueing up to `capacity` records.
+    ///
+    ///Must be called within tokio runtime.
+    pub fn new(url: hyper::Uri, capacity: usize) -> Self {
+        let client = crate::remote::http_client();
+        let queue = PayloadQueue::spawn("audit record", capacity, move |body| {
+            let req = hyper::Request::post(url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.into())
+                .expect("Valid audit request");
+            let response = client.request(req);
+            async move {
+                match response.await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => Err(format!("Audit sink responded with {}", response.status())),
+                    Err(error) => Err(error.to_string()),
+                }
+            }
+        });
+        Self { queue }
+    }
+}
+
+impl AuditSink for HttpAuditSink {
+    #[inline(always)]
+    fn write(&self, record: &AuditRecord) {
+        push(&self.queue, record)
+    }
+}
+
+#[cfg(feature = "kafka")]
+///Sink producing each record as JSON message to Kafka topic, in background task.
+pub struct KafkaAuditSink {
+    queue: PayloadQueue,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaAuditSink {
+    ///Creates sink producing records to `topic` of `brokers`, queueing up to `capacity` records.
+    ///
+    ///Records are produced one by one without key, so topic must have single partition to keep their order.
+    ///
+    ///Must be called within tokio runtime.
+    pub fn new(brokers: &str, topic: String, capacity: usize) -> Result<Self, BoxError> {
+        use rdkafka::producer::{FutureProducer, FutureRecord};
+
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create::<FutureProducer>()?;
+        let queue = PayloadQueue::spawn("audit record", capacity, move |payload| {
+            let producer = producer.clone();
+            let topic = topic.clone();
+            async move {
+                let record = FutureRecord::<(), _>::to(&topic).payload(&payload);
+                match producer.send(record, core::time::Duration::ZERO).await {
+                    Ok(_) => Ok(()),
+                    Err((error, _)) => Err(error.to_string()),
+                }
+            }
+        });
+        Ok(Self { queue })
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl AuditSink for KafkaAuditSink {
+    #[inline(always)]
+    fn write(&self, record: &AuditRecord) {
+        push(&self.queue, record)
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+///Audit sink config
+///
+///Other sinks can be used via [Audit::with_sink].
+pub enum AuditSinkConfig {
+    ///Appends JSON lines to file.
+    File {
+        ///Path to file.
+        path: PathBuf,
+    },
+    ///Sends JSON via `POST`.
+    Http {
+        ///URL of endpoint.
+        url: String,
+    },
+    #[cfg(feature = "kafka")]
+    ///Produces JSON messages to Kafka.
+    Kafka {
+        ///Comma separated list of bootstrap brokers.
+        brokers: String,
+        ///Topic to produce messages to.
+        topic: String,
+    },
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Audit config
+pub struct AuditConfig {
+    ///Destination of records.
+    pub sink: AuditSinkConfig,
+    #[serde(default = "default_queue_capacity")]
+    ///Number of records waiting to be written, above which new records are lost.
+    pub queue_capacity: usize,
+    #[serde(default)]
+    ///Header identifying client, used when client is not authenticated by [ApiKeys](super::ApiKeys).
+    pub client_header: Option<String>,
+    #[serde(default)]
+    ///Names of variables, which values are redacted, or `*` to redact all.
+    pub redact_variables: Vec<String>,
+    #[serde(default)]
+    ///How redacted values are replaced.
+    pub redaction: Redaction,
+}
+
+#[derive(Default)]
+struct Chain {
+    sequence: u64,
+    last_hash: String,
+}
+
+#[derive(Clone)]
+struct Auditor {
+    sink: Arc<dyn AuditSink>,
+    chain: Arc<Mutex<Chain>>,
+    client_header: Option<Arc<str>>,
+    redact_variables: Arc<[String]>,
+    redaction: Redaction,
+}
+
+impl Auditor {
+    fn variables(&self, req: &RouterRequest) -> serde_json::Map<String, serde_json::Value> {
+        req.originating_request
+            .body()
+            .variables
+            .iter()
+            .map(|(name, value)| {
+                let name = name.as_str();
+                let value = serde_json_bytes::from_value::<serde_json::Value>(value.clone()).unwrap_or_default();
+                let is_redacted = self
+                    .redact_variables
+                    .iter()
+                    .any(|redacted| redacted == ANY || redacted == name);
+                let value = match is_redacted {
+                    true => serde_json::Value::String(self.redaction.apply(&value.to_string())),
+                    false => value,
+                };
+                (name.to_owned(), value)
+            })
+            .collect()
+    }
+
+    fn record(&self, mut record: AuditRecord) {
+        let mut chain = match self.chain.lock() {
+            Ok(chain) => chain,
+            Err(error) => error.into_inner(),
+        };
+        chain.sequence += 1;
+        record.sequence = chain.sequence;
+        record.previous_hash = chain.last_hash.clone();
+
+        //Hash covers record with empty `hash`, so verifier can reproduce it the same way.
+        let content = serde_json::to_vec(&record).expect("JSON serialization should not fail");
+        record.hash = crate::hash::sha256_hex(&content);
+        chain.last_hash = record.hash.clone();
+        self.sink.write(&record);
+    }
+}
+
+///Records mutations, who executed them and their outcome, into tamper-evident audit log.
+///
+///Operations sent by id are known to be mutations only once resolved, so plugin must be added after
+///[persisted queries](crate::GraphqlRouterBuilder::with_persisted_queries).
+pub struct Audit {
+    auditor: Auditor,
+}
+
+impl Audit {
+    #[inline]
+    ///Creates plugin writing records to `sink`, continuing chain from its [last](AuditSink::last) record.
+    pub fn with_sink(sink: Box<dyn AuditSink>) -> Self {
+        let chain = match sink.last() {
+            Some((sequence, last_hash)) => Chain { sequence, last_hash },
+            None => Chain::default(),
+        };
+        Self {
+            auditor: Auditor {
+                sink: sink.into(),
+                chain: Arc::new(Mutex::new(chain)),
+                client_header: None,
+                redact_variables: Arc::new([]),
+                redaction: Redaction::default(),
+            },
+        }
+    }
+
+    #[inline]
+    ///Sets `header` identifying client, used when client is not authenticated by [ApiKeys](super::ApiKeys).
+    pub fn client_header(mut self, header: &str) -> Self {
+        self.auditor.client_header = Some(header.into());
+        self
+    }
+
+    #[inline]
+    ///Redacts values of variables with `names`, or all variables if `*` is among them, using `redaction`.
+    pub fn redact_variables(mut self, names: Vec<String>, redaction: Redaction) -> Self {
+        self.auditor.redact_variables = names.into();
+        self.auditor.redaction = redaction;
+        self
+    }
+
+    ///Creates plugin according to `config`.
+    ///
+    ///HTTP and Kafka sinks must be created within tokio runtime.
+    pub fn with_config(config: AuditConfig) -> Result<Self, BoxError> {
+        let capacity = config.queue_capacity;
+        let sink: Box<dyn AuditSink> = match &config.sink {
+            AuditSinkConfig::File { path } => Box::new(FileAuditSink::open(path, capacity)?),
+            AuditSinkConfig::Http { url } => Box::new(HttpAuditSink::new(url.parse()?, capacity)),
+            #[cfg(feature = "kafka")]
+            AuditSinkConfig::Kafka { brokers, topic } => {
+                Box::new(KafkaAuditSink::new(brokers, topic.clone(), capacity)?)
+            }
+        };
+        let audit = Self::with_sink(sink).redact_variables(config.redact_variables, config.redaction);
+        Ok(match config.client_header {
+            Some(header) => audit.client_header(&header),
+            None => audit,
+        })
+    }
+}
+
+impl Plugin for Audit {
+    type Config = AuditConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Self::with_config(config)))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        AuditService {
+            inner: service,
+            auditor: Arc::new(self.auditor.clone()),
+        }
+        .boxed()
+    }
+}
+
+struct AuditService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    auditor: Arc<Auditor>,
+}
+
+impl tower::Service<RouterRequest> for AuditService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let body = req.originating_request.body();
+        let is_mutation = match body.query.as_deref() {
+            Some(query) => crate::parser::is_mutation(query, body.operation_name.as_deref()),
+            None => {
+                if body.extensions.contains_key("persistedQuery") {
+                    tracing::warn!("Operation sent by id is not audited, as it is not resolved by persisted queries");
+                }
+                false
+            }
+        };
+        if !is_mutation {
+            return self.inner.call(req);
+        }
+
+        let record = AuditRecord {
+            sequence: 0,
+            timestamp: super::capture::rfc3339(SystemTime::now()),
+            client: ClientIdentity::of(&req, self.auditor.client_header.as_deref()),
+            operation_name: body.operation_name.clone(),
+            variables: self.auditor.variables(&req),
+            outcome: "failed",
+            status: None,
+            previous_hash: String::new(),
+            hash: String::new(),
+        };
+        let auditor = self.auditor.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            let mut record = record;
+            if let Ok(response) = result.as_ref() {
+                record.status = Some(response.response.status().as_u16());
+                record.outcome = match response.response.body() {
+                    ResponseBody::GraphQL(body) if body.errors.is_empty() => "success",
+                    _ => "error",
+                };
+            }
+            auditor.record(record);
+            result
+        })
+    }
+}
@@ -0,0 +1,205 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderMap, HeaderName, HeaderValue};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::{manage_header, matches_pattern};
+
+use core::fmt::Write;
+use core::future::{ready, Future};
+use core::pin::Pin;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const BAGGAGE: HeaderName = HeaderName::from_static("baggage");
+
+#[inline(always)]
+fn default_max_members() -> usize {
+    64
+}
+
+#[inline(always)]
+fn default_max_bytes() -> usize {
+    8192
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Baggage propagation config
+pub struct BaggageConfig {
+    #[serde(default)]
+    ///Patterns of keys propagated from client's `baggage` header, with `*` matching any sequence of characters.
+    ///
+    ///Empty list propagates every key.
+    pub allow: Vec<String>,
+    #[serde(default)]
+    ///Entries appended to baggage, replacing client's entries with the same key.
+    pub append: BTreeMap<String, String>,
+    #[serde(default)]
+    ///Entries appended to baggage from client request headers, by header name, e.g. tenant id.
+    pub from_headers: BTreeMap<String, String>,
+    #[serde(default = "default_max_members")]
+    ///Maximum number of entries, with entries above limit dropped.
+    ///
+    ///Defaults to 64, as recommended by W3C.
+    pub max_members: usize,
+    #[serde(default = "default_max_bytes")]
+    ///Maximum size of `baggage` header in bytes, with entries above limit dropped.
+    ///
+    ///Defaults to 8192, as recommended by W3C.
+    pub max_bytes: usize,
+}
+
+impl Default for BaggageConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            append: BTreeMap::new(),
+            from_headers: BTreeMap::new(),
+            max_members: default_max_members(),
+            max_bytes: default_max_bytes(),
+        }
+    }
+}
+
+impl BaggageConfig {
+    #[inline]
+    ///Returns whether client's entry with `key` is propagated.
+    pub fn is_allowed(&self, key: &str) -> bool {
+        self.allow.is_empty() || self.allow.iter().any(|pattern| matches_pattern(pattern, key))
+    }
+
+    ///Returns `baggage` header for subgraph requests, made on behalf of client request with `headers`.
+    fn baggage(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        //Members are kept verbatim, including properties, and identified by key.
+        let mut members = Vec::<(String, String)>::new();
+        for header in headers.get_all(BAGGAGE) {
+            let header = match header.to_str() {
+                Ok(header) => header,
+                Err(_) => continue,
+            };
+            for member in header.split(',').map(str::trim) {
+                let key = member.split(|ch| ch == '=' || ch == ';').next().unwrap_or_default().trim();
+                if !key.is_empty() && member.contains('=') && self.is_allowed(key) {
+                    members.retain(|(existing, _)| existing != key);
+                    members.push((key.to_owned(), member.to_owned()));
+                }
+            }
+        }
+
+        let appended = self.append.iter().map(|(key, value)| (key.as_str(), value.as_str())).chain(
+            self.from_headers.iter().filter_map(|(header, key)| {
+                let value = headers.get(header.as_str())?.to_str().ok()?;
+                Some((key.as_str(), value))
+            }),
+        );
+        for (key, value) in appended {
+            members.retain(|(existing, _)| existing != key);
+            members.push((key.to_owned(), format!("{}={}", key, encode_value(value))));
+        }
+
+        let mut baggage = String::new();
+        for (key, member) in members.iter().take(self.max_members) {
+            let separator = if baggage.is_empty() { 0 } else { 1 };
+            if baggage.len() + separator + member.len() > self.max_bytes {
+                tracing::debug!("Baggage entry '{}' dropped due to size limit", key);
+                continue;
+            }
+            if separator > 0 {
+                baggage.push(',');
+            }
+            baggage.push_str(member);
+        }
+
+        if baggage.is_empty() {
+            return None;
+        }
+        match HeaderValue::from_str(&baggage) {
+            Ok(baggage) => Some(baggage),
+            Err(error) => {
+                tracing::warn!("Invalid baggage: {}", error);
+                None
+            }
+        }
+    }
+}
+
+//Percent-encodes everything outside of W3C `baggage-octet`.
+fn encode_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E if byte != b'%' => {
+                encoded.push(byte as char)
+            }
+            byte => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+///Propagates W3C `baggage` header of client request to subgraphs, so that context like tenant or experiment ids
+///flows across services.
+///
+///Propagated keys can be restricted, while extra entries can be appended, with size limits applied to result.
+pub struct Baggage {
+    config: Arc<BaggageConfig>,
+}
+
+impl Baggage {
+    #[inline(always)]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: BaggageConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for Baggage {
+    type Config = BaggageConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        super::checkpoint(service, |req: RouterRequest| {
+            if let Err(error) = manage_header(&req.context, BAGGAGE.as_str()) {
+                tracing::warn!("Unable to manage baggage: {}", error);
+            }
+            Ok(req)
+        })
+    }
+
+    fn subgraph_service(
+        &mut self,
+        _: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let config = self.config.clone();
+        service
+            .map_request(move |mut req: SubgraphRequest| {
+                let baggage = config.baggage(req.originating_request.headers());
+                let headers = req.subgraph_request.headers_mut();
+                headers.remove(BAGGAGE);
+                if let Some(baggage) = baggage {
+                    headers.insert(BAGGAGE, baggage);
+                }
+                req
+            })
+            .boxed()
+    }
+}
@@ -0,0 +1,89 @@
+use bytes::Bytes;
+
+use super::StoreFuture;
+
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Instant;
+
+#[cfg(feature = "memcached-store")]
+mod memcached;
+#[cfg(feature = "memcached-store")]
+pub use memcached::{MemcachedStore, MemcachedStoreConfig};
+
+///Store of cached responses, which can be shared between router replicas.
+///
+///Keys are arbitrary strings, so stores with restrictions on keys must encode them.
+pub trait CacheStore: Send + Sync {
+    ///Returns value cached under `key`, unless it expired.
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>>;
+    ///Caches `value` under `key` for `ttl`, or until it is evicted if there is no `ttl`.
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()>;
+    ///Removes value cached under `key`, if any.
+    fn remove<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()>;
+}
+
+#[derive(Default)]
+///In-memory cache store, local to router.
+///
+///Expired values are removed when they are accessed.
+pub struct MemoryCacheStore {
+    values: RwLock<HashMap<String, (Bytes, Option<Instant>)>>,
+}
+
+impl MemoryCacheStore {
+    #[inline(always)]
+    ///Creates empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Returns number of cached values, including expired ones, which were not accessed yet.
+    pub fn len(&self) -> usize {
+        match self.values.read() {
+            Ok(values) => values.len(),
+            Err(error) => error.into_inner().len(),
+        }
+    }
+
+    #[inline]
+    ///Returns whether store has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn values_mut(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, (Bytes, Option<Instant>)>> {
+        match self.values.write() {
+            Ok(values) => values,
+            Err(error) => error.into_inner(),
+        }
+    }
+}
+
+impl CacheStore for MemoryCacheStore {
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>> {
+        let now = Instant::now();
+        let mut values = self.values_mut();
+        let value = match values.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= now => {
+                values.remove(key);
+                None
+            }
+            Some((value, _)) => Some(value.clone()),
+            None => None,
+        };
+        Box::pin(core::future::ready(Ok(value)))
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()> {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.values_mut().insert(key.to_owned(), (value, expires_at));
+        Box::pin(core::future::ready(Ok(())))
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        self.values_mut().remove(key);
+        Box::pin(core::future::ready(Ok(())))
+    }
+}
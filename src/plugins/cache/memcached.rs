@@ -0,0 +1,212 @@
+use bytes::Bytes;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tower::BoxError;
+
+use super::CacheStore;
+use crate::plugins::StoreFuture;
+
+use core::time::Duration;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Connection = BufStream<TcpStream>;
+
+//Memcached treats expiration above 30 days as unix timestamp.
+const MAX_RELATIVE_EXPIRATION: u64 = 60 * 60 * 24 * 30;
+//Memcached keys are limited to 250 bytes, while hash takes 64 of them.
+const MAX_PREFIX_LEN: usize = 64;
+
+#[inline(always)]
+fn default_pool_size() -> usize {
+    4
+}
+
+#[inline(always)]
+fn default_prefix() -> String {
+    "graphql".to_owned()
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Memcached cache store config
+pub struct MemcachedStoreConfig {
+    ///Address of memcached server, e.g. `127.0.0.1:11211`.
+    pub address: String,
+    #[serde(default = "default_pool_size")]
+    ///Maximum number of idle connections kept open.
+    pub pool_size: usize,
+    #[serde(default = "default_prefix")]
+    ///Prefix of keys, followed by hash of cache key.
+    pub prefix: String,
+}
+
+///Cache store in memcached
+///
+///Values are stored under `{prefix}:{hex encoded SHA-256 of key}` keys, as memcached limits length and characters
+///of keys. Connections are established on demand and dropped on failure, so router can start while memcached is
+///down.
+pub struct MemcachedStore {
+    address: String,
+    idle: Mutex<Vec<Connection>>,
+    pool_size: usize,
+    prefix: String,
+}
+
+impl MemcachedStore {
+    ///Creates store according to `config`, failing if prefix cannot be used in memcached keys.
+    pub fn with_config(config: MemcachedStoreConfig) -> Result<Self, BoxError> {
+        let is_invalid = |byte: u8| byte.is_ascii_whitespace() || byte.is_ascii_control();
+        if config.prefix.len() > MAX_PREFIX_LEN || config.prefix.bytes().any(is_invalid) {
+            return Err("Memcached key prefix must be at most 64 bytes without whitespaces".into());
+        }
+        Ok(Self {
+            address: config.address,
+            idle: Mutex::new(Vec::new()),
+            pool_size: config.pool_size.max(1),
+            prefix: config.prefix,
+        })
+    }
+
+    #[inline]
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.prefix, crate::hash::sha256_hex(key.as_bytes()))
+    }
+
+    fn idle(&self) -> std::sync::MutexGuard<'_, Vec<Connection>> {
+        match self.idle.lock() {
+            Ok(idle) => idle,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    //Returns idle connection, connecting new one if there is none.
+    async fn connection(&self) -> Result<Connection, BoxError> {
+        let idle = self.idle().pop();
+        match idle {
+            Some(connection) => Ok(connection),
+            None => Ok(BufStream::new(TcpStream::connect(self.address.as_str()).await?)),
+        }
+    }
+
+    //Returns connection to pool, after command completed successfully.
+    fn release(&self, connection: Connection) {
+        let mut idle = self.idle();
+        if idle.len() < self.pool_size {
+            idle.push(connection);
+        }
+    }
+}
+
+async fn read_line(connection: &mut Connection) -> Result<String, BoxError> {
+    let mut line = String::new();
+    if connection.read_line(&mut line).await? == 0 {
+        return Err("Memcached closed connection".into());
+    }
+    match line.strip_suffix("\r\n") {
+        Some(line) => Ok(line.to_owned()),
+        None => Err("Memcached sent incomplete line".into()),
+    }
+}
+
+//Returns error for unexpected `line`, which is either error reply or protocol violation.
+#[inline]
+fn unexpected(line: &str) -> BoxError {
+    format!("Unexpected memcached reply: {}", line).into()
+}
+
+async fn get(connection: &mut Connection, key: &str) -> Result<Option<Bytes>, BoxError> {
+    connection.write_all(format!("get {}\r\n", key).as_bytes()).await?;
+    connection.flush().await?;
+
+    let line = read_line(connection).await?;
+    if line == "END" {
+        return Ok(None);
+    }
+    //VALUE <key> <flags> <bytes>
+    let size = match line.strip_prefix("VALUE ").map(|value| value.split(' ').nth(2)) {
+        Some(Some(size)) => size.parse::<usize>().map_err(|_| unexpected(&line))?,
+        _ => return Err(unexpected(&line)),
+    };
+    let mut value = vec![0; size + 2];
+    connection.read_exact(&mut value).await?;
+    if !value.ends_with(b"\r\n") {
+        return Err("Memcached sent value of unexpected size".into());
+    }
+    value.truncate(size);
+
+    let line = read_line(connection).await?;
+    match line == "END" {
+        true => Ok(Some(value.into())),
+        false => Err(unexpected(&line)),
+    }
+}
+
+async fn set(connection: &mut Connection, key: &str, value: &[u8], expiration: u64) -> Result<(), BoxError> {
+    let command = format!("set {} 0 {} {}\r\n", key, expiration, value.len());
+    connection.write_all(command.as_bytes()).await?;
+    connection.write_all(value).await?;
+    connection.write_all(b"\r\n").await?;
+    connection.flush().await?;
+
+    let line = read_line(connection).await?;
+    match line.as_str() {
+        "STORED" => Ok(()),
+        _ => Err(unexpected(&line)),
+    }
+}
+
+async fn delete(connection: &mut Connection, key: &str) -> Result<(), BoxError> {
+    connection.write_all(format!("delete {}\r\n", key).as_bytes()).await?;
+    connection.flush().await?;
+
+    let line = read_line(connection).await?;
+    match line.as_str() {
+        "DELETED" | "NOT_FOUND" => Ok(()),
+        _ => Err(unexpected(&line)),
+    }
+}
+
+//Converts `ttl` into memcached expiration, where `0` means no expiration.
+fn expiration(ttl: Option<Duration>) -> u64 {
+    let ttl = match ttl {
+        //Sub-second TTL is rounded up, as zero would store value indefinitely.
+        Some(ttl) => ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0),
+        None => return 0,
+    };
+    match ttl > MAX_RELATIVE_EXPIRATION {
+        true => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs()) + ttl,
+        false => ttl.max(1),
+    }
+}
+
+impl CacheStore for MemcachedStore {
+    fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<Bytes>> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let value = get(&mut connection, &self.key(key)).await?;
+            self.release(connection);
+            Ok(value)
+        })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Option<Duration>) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            set(&mut connection, &self.key(key), &value, expiration(ttl)).await?;
+            self.release(connection);
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            delete(&mut connection, &self.key(key)).await?;
+            self.release(connection);
+            Ok(())
+        })
+    }
+}
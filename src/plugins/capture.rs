@@ -0,0 +1,443 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderMap, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+use crate::context::{RequestId, TypedContext};
+use crate::time::{sequential_ids, system_clock, SharedClock, SharedRequestIds};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//Context key, which holds capture id of request.
+const CAPTURE_ID: &str = "graphql_router::capture_id";
+const REDACTED: &str = "<redacted>";
+
+fn default_header() -> String {
+    "x-debug-capture".to_owned()
+}
+
+fn default_request_id_header() -> String {
+    "x-request-id".to_owned()
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///Format of exported capture
+pub enum CaptureFormat {
+    ///Plain JSON with list of exchanges.
+    Json,
+    ///HTTP Archive 1.2.
+    Har,
+}
+
+impl Default for CaptureFormat {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Json
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Debug capture config
+pub struct DebugCaptureConfig {
+    #[serde(default = "default_header")]
+    ///Request header, which enables capture for request.
+    pub header: String,
+    #[serde(default)]
+    ///Captures every request, regardless of header.
+    pub always: bool,
+    #[serde(default = "default_request_id_header")]
+    ///Request header, which value is used as request id.
+    pub request_id_header: String,
+    #[serde(default)]
+    ///Export format.
+    pub format: CaptureFormat,
+    #[serde(default)]
+    ///Attaches capture to response `extensions.debugCapture`.
+    pub extensions: bool,
+    #[serde(default)]
+    ///Directory to write capture to, as file named by request id.
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for DebugCaptureConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            header: default_header(),
+            always: false,
+            request_id_header: default_request_id_header(),
+            format: CaptureFormat::default(),
+            extensions: false,
+            directory: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+///Subgraph request with its outcome
+pub struct CapturedExchange {
+    ///Subgraph name.
+    pub subgraph: String,
+    ///Subgraph URL.
+    pub url: String,
+    ///Start time in RFC 3339 format.
+    pub started_at: String,
+    ///Duration in milliseconds.
+    pub duration_ms: f64,
+    ///Request headers, with credentials redacted.
+    pub request_headers: BTreeMap<String, String>,
+    ///GraphQL request.
+    pub request: serde_json::Value,
+    ///Response status, if subgraph responded.
+    pub status: Option<u16>,
+    ///Response headers, with credentials redacted.
+    pub response_headers: BTreeMap<String, String>,
+    ///GraphQL response, if subgraph responded.
+    pub response: Option<serde_json::Value>,
+    ///Error, if subgraph fetch failed.
+    pub error: Option<String>,
+}
+
+impl CapturedExchange {
+    fn har_headers(headers: &BTreeMap<String, String>) -> serde_json::Value {
+        headers
+            .iter()
+            .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+            .collect()
+    }
+
+    fn to_har(&self) -> serde_json::Value {
+        let request = self.request.to_string();
+        let (status, response) = match (self.status, self.response.as_ref()) {
+            (Some(status), Some(response)) => (status, response.to_string()),
+            _ => (0, self.error.clone().unwrap_or_default()),
+        };
+        serde_json::json!({
+            "startedDateTime": self.started_at,
+            "time": self.duration_ms,
+            "request": {
+                "method": "POST",
+                "url": self.url,
+                "httpVersion": "HTTP/1.1",
+                "headers": Self::har_headers(&self.request_headers),
+                "queryString": [],
+                "cookies": [],
+                "postData": {
+                    "mimeType": "application/json",
+                    "text": request,
+                },
+                "headersSize": -1,
+                "bodySize": request.len(),
+            },
+            "response": {
+                "status": status,
+                "statusText": "",
+                "httpVersion": "HTTP/1.1",
+                "headers": Self::har_headers(&self.response_headers),
+                "cookies": [],
+                "content": {
+                    "size": response.len(),
+                    "mimeType": "application/json",
+                    "text": response,
+                },
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+            },
+            "cache": {},
+            "timings": {
+                "send": 0,
+                "wait": self.duration_ms,
+                "receive": 0,
+            },
+            "comment": self.subgraph,
+        })
+    }
+}
+
+///Formats time as RFC 3339 timestamp in UTC.
+pub(super) fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    //Civil from days algorithm by Howard Hinnant
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn capture_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE].contains(name) {
+                true => REDACTED.to_owned(),
+                false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            };
+            (name.as_str().to_owned(), value)
+        })
+        .collect()
+}
+
+///Records all subgraph exchanges of request, for debugging.
+///
+///Capture is exported to response extensions and/or written to disk.
+pub struct DebugCapture {
+    config: Arc<DebugCaptureConfig>,
+    captures: Ongoing<Vec<CapturedExchange>>,
+    request_ids: SharedRequestIds,
+    clock: SharedClock,
+}
+
+impl DebugCapture {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: DebugCaptureConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            captures: Ongoing::default(),
+            request_ids: sequential_ids(),
+            clock: system_clock(),
+        }
+    }
+
+    #[inline(always)]
+    ///Sets generator of request ids, used when request has no id header.
+    pub fn with_request_ids(self, request_ids: SharedRequestIds) -> Self {
+        Self { request_ids, ..self }
+    }
+
+    #[inline(always)]
+    ///Sets time source of exchange timestamps and durations.
+    pub fn with_clock(self, clock: SharedClock) -> Self {
+        Self { clock, ..self }
+    }
+}
+
+///Exports exchanges of request `request_id` in configured `format`.
+fn export(request_id: &str, exchanges: &[CapturedExchange], format: CaptureFormat) -> serde_json::Value {
+    match format {
+        CaptureFormat::Json => serde_json::json!({
+            "request_id": request_id,
+            "exchanges": exchanges,
+        }),
+        CaptureFormat::Har => serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "comment": request_id,
+                "entries": exchanges.iter().map(CapturedExchange::to_har).collect::<Vec<_>>(),
+            }
+        }),
+    }
+}
+
+fn write_capture(config: &DebugCaptureConfig, request_id: &str, capture: &serde_json::Value) {
+    let directory = match config.directory.as_ref() {
+        Some(directory) => directory,
+        None => return,
+    };
+    //Request id may come from client, so it must not escape directory
+    let file_name = request_id
+        .chars()
+        .map(|ch| match ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+            true => ch,
+            false => '_',
+        })
+        .collect::<String>();
+    let extension = match config.format {
+        CaptureFormat::Json => "json",
+        CaptureFormat::Har => "har",
+    };
+    let path = directory.join(format!("{}.{}", file_name, extension));
+    let capture = serde_json::to_vec_pretty(capture).expect("JSON serialization should not fail");
+    if let Err(error) = std::fs::write(&path, capture) {
+        tracing::warn!("{}: Failed to write debug capture: {}", path.display(), error);
+    }
+}
+
+impl Plugin for DebugCapture {
+    type Config = DebugCaptureConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        CaptureRouterService {
+            inner: service,
+            config: self.config.clone(),
+            captures: self.captures.clone(),
+            request_ids: self.request_ids.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        CaptureSubgraphService {
+            inner: service,
+            name: name.into(),
+            captures: self.captures.clone(),
+            clock: self.clock.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct CaptureRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    config: Arc<DebugCaptureConfig>,
+    captures: Ongoing<Vec<CapturedExchange>>,
+    request_ids: SharedRequestIds,
+}
+
+impl tower::Service<RouterRequest> for CaptureRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let headers = req.originating_request.headers();
+        if !self.config.always && !headers.contains_key(self.config.header.as_str()) {
+            return self.inner.call(req);
+        }
+
+        let capture_id = self.captures.start(Vec::new());
+        let context = TypedContext::new(&req.context);
+        let request_id = match (context.get::<RequestId>(), headers.get(self.config.request_id_header.as_str())) {
+            (Ok(Some(RequestId(request_id))), _) => request_id,
+            (_, Some(request_id)) => String::from_utf8_lossy(request_id.as_bytes()).into_owned(),
+            _ => self.request_ids.generate(),
+        };
+        if let Err(error) = context.insert(RequestId(request_id.clone())) {
+            tracing::warn!("Unable to store request id: {}", error);
+        }
+        if let Err(error) = req.context.insert(CAPTURE_ID, capture_id) {
+            tracing::warn!("Unable to start debug capture: {}", error);
+            self.captures.finish(capture_id);
+            return self.inner.call(req);
+        }
+
+        let config = self.config.clone();
+        let captures = self.captures.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut result = response.await;
+            let exchanges = captures.finish(capture_id).unwrap_or_default();
+            let capture = export(&request_id, &exchanges, config.format);
+            write_capture(&config, &request_id, &capture);
+
+            if config.extensions {
+                if let Ok(response) = result.as_mut() {
+                    super::insert_extension(response, "debugCapture", capture);
+                }
+            }
+            result
+        })
+    }
+}
+
+struct CaptureSubgraphService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    name: Arc<str>,
+    captures: Ongoing<Vec<CapturedExchange>>,
+    clock: SharedClock,
+}
+
+impl tower::Service<SubgraphRequest> for CaptureSubgraphService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let capture_id = match req.context.get::<_, u64>(CAPTURE_ID) {
+            Ok(Some(capture_id)) => capture_id,
+            _ => return self.inner.call(req),
+        };
+
+        let started = self.clock.now();
+        let mut exchange = CapturedExchange {
+            subgraph: self.name.to_string(),
+            url: req.subgraph_request.uri().to_string(),
+            started_at: rfc3339(self.clock.system_time()),
+            duration_ms: 0.0,
+            request_headers: capture_headers(req.subgraph_request.headers()),
+            request: serde_json::to_value(req.subgraph_request.body()).unwrap_or_default(),
+            status: None,
+            response_headers: BTreeMap::new(),
+            response: None,
+            error: None,
+        };
+
+        let captures = self.captures.clone();
+        let clock = self.clock.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            exchange.duration_ms = clock.now().saturating_duration_since(started).as_secs_f64() * 1000.0;
+            match result.as_ref() {
+                Ok(response) => {
+                    exchange.status = Some(response.response.status().as_u16());
+                    exchange.response_headers = capture_headers(response.response.headers());
+                    exchange.response = serde_json::to_value(response.response.body()).ok();
+                }
+                Err(error) => exchange.error = Some(error.to_string()),
+            }
+            captures.update(capture_id, |exchanges| exchanges.push(exchange));
+            result
+        })
+    }
+}
@@ -0,0 +1,241 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderValue, COOKIE, SET_COOKIE};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::{manage_header, matches_pattern, Ongoing};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+//Context key, which holds id of request, whose cookies are controlled by policy.
+const COOKIE_POLICY_ID: &str = "graphql_router::cookie_policy_id";
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Cookie rule of subgraph
+pub struct CookieRule {
+    #[serde(default)]
+    ///Patterns of cookie names forwarded to subgraph, with `*` matching any sequence of characters.
+    ///
+    ///Cookies not matching any pattern are not forwarded.
+    pub forward: Vec<String>,
+    #[serde(default)]
+    ///Surfaces `Set-Cookie` headers of subgraph responses to client.
+    pub set_cookie: bool,
+}
+
+impl CookieRule {
+    #[inline]
+    ///Returns whether cookie `name` is forwarded.
+    pub fn is_forwarded(&self, name: &str) -> bool {
+        self.forward.iter().any(|pattern| matches_pattern(pattern, name))
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Cookie policy config
+pub struct CookiePolicyConfig {
+    #[serde(default)]
+    ///Rule of subgraphs, that are not specified in `subgraphs`.
+    pub default: CookieRule,
+    #[serde(default)]
+    ///Rule per subgraph name.
+    pub subgraphs: BTreeMap<String, CookieRule>,
+}
+
+impl CookiePolicyConfig {
+    #[inline]
+    ///Returns rule of subgraph.
+    pub fn rule(&self, name: &str) -> &CookieRule {
+        self.subgraphs.get(name).unwrap_or(&self.default)
+    }
+
+    #[inline]
+    ///Forwards cookies matching `pattern` to subgraph `name`.
+    pub fn forward(mut self, name: &str, pattern: &str) -> Self {
+        let rule = self.subgraphs.entry(name.to_owned()).or_insert_with(|| self.default.clone());
+        rule.forward.push(pattern.to_owned());
+        self
+    }
+
+    ///Surfaces `Set-Cookie` headers of subgraph `name` only, stripping them from all other subgraphs.
+    ///
+    ///This allows single subgraph, like authentication one, to issue sessions through router.
+    pub fn set_cookie_from(mut self, name: &str) -> Self {
+        self.default.set_cookie = false;
+        for rule in self.subgraphs.values_mut() {
+            rule.set_cookie = false;
+        }
+        let rule = self.subgraphs.entry(name.to_owned()).or_insert_with(|| self.default.clone());
+        rule.set_cookie = true;
+        self
+    }
+}
+
+///Controls which cookies of client request are forwarded to which subgraphs and whether `Set-Cookie` headers of
+///subgraph responses reach client.
+///
+///By default no cookies are forwarded and no `Set-Cookie` headers are surfaced.
+pub struct CookiePolicy {
+    config: Arc<CookiePolicyConfig>,
+    ongoing: Ongoing<Vec<HeaderValue>>,
+}
+
+impl CookiePolicy {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: CookiePolicyConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for CookiePolicy {
+    type Config = CookiePolicyConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        CookieRouterService {
+            inner: service,
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        CookieSubgraphService {
+            inner: service,
+            rule: self.config.rule(name).clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct CookieRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ongoing: Ongoing<Vec<HeaderValue>>,
+}
+
+impl tower::Service<RouterRequest> for CookieRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let id = self.ongoing.start(Vec::new());
+        let result = req
+            .context
+            .insert(COOKIE_POLICY_ID, id)
+            .map_err(BoxError::from)
+            .and_then(|_| manage_header(&req.context, COOKIE.as_str()));
+        if let Err(error) = result {
+            //Without id subgraphs cannot be controlled, so fail closed.
+            self.ongoing.finish(id);
+            return Box::pin(ready(Err(error)));
+        }
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut result = response.await;
+            let set_cookies = ongoing.finish(id).unwrap_or_default();
+            if let Ok(response) = result.as_mut() {
+                let headers = response.response.headers_mut();
+                for set_cookie in set_cookies {
+                    headers.append(SET_COOKIE, set_cookie);
+                }
+            }
+            result
+        })
+    }
+}
+
+struct CookieSubgraphService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    rule: CookieRule,
+    ongoing: Ongoing<Vec<HeaderValue>>,
+}
+
+impl tower::Service<SubgraphRequest> for CookieSubgraphService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: SubgraphRequest) -> Self::Future {
+        //Client may send several `Cookie` headers, which are merged into one.
+        let mut cookies = String::new();
+        for header in req.originating_request.headers().get_all(COOKIE) {
+            let header = match header.to_str() {
+                Ok(header) => header,
+                Err(_) => continue,
+            };
+            for cookie in header.split(';').map(str::trim) {
+                let name = cookie.split('=').next().unwrap_or_default();
+                if !cookie.is_empty() && self.rule.is_forwarded(name) {
+                    if !cookies.is_empty() {
+                        cookies.push_str("; ");
+                    }
+                    cookies.push_str(cookie);
+                }
+            }
+        }
+
+        let headers = req.subgraph_request.headers_mut();
+        headers.remove(COOKIE);
+        if !cookies.is_empty() {
+            match HeaderValue::from_str(&cookies) {
+                Ok(cookies) => {
+                    headers.insert(COOKIE, cookies);
+                }
+                Err(error) => tracing::warn!("Unable to forward cookies: {}", error),
+            }
+        }
+
+        let id = match (self.rule.set_cookie, req.context.get::<_, u64>(COOKIE_POLICY_ID)) {
+            (true, Ok(Some(id))) => id,
+            _ => return self.inner.call(req),
+        };
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            let set_cookies = response.response.headers().get_all(SET_COOKIE);
+            ongoing.update(id, |values| values.extend(set_cookies.iter().cloned()));
+            Ok(response)
+        })
+    }
+}
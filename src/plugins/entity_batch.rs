@@ -0,0 +1,226 @@
+use apollo_router_core::{Plugin, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::parser::clone_parts;
+use crate::{GraphqlRequest, GraphqlResponse};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const REPRESENTATIONS: &str = "representations";
+const ENTITIES: &str = "_entities";
+const BUFFER_SIZE: usize = 1024;
+
+type BatchService = Buffer<BoxService<SubgraphRequest, SubgraphResponse, BoxError>, SubgraphRequest>;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Entity batching config
+pub struct EntityBatchingConfig {
+    #[serde(default)]
+    ///Maximum number of representations per `_entities` request of subgraphs, that are not specified in
+    ///`subgraphs`.
+    ///
+    ///Default is no limit.
+    pub default: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of representations per `_entities` request per subgraph name.
+    pub subgraphs: BTreeMap<String, usize>,
+}
+
+impl EntityBatchingConfig {
+    #[inline]
+    ///Returns maximum number of representations of subgraph, if it is limited.
+    pub fn limit(&self, name: &str) -> Option<usize> {
+        self.subgraphs.get(name).copied().or(self.default)
+    }
+
+    #[inline]
+    ///Limits `_entities` requests of subgraph `name` to `max` representations.
+    pub fn max_representations(mut self, name: &str, max: usize) -> Self {
+        self.subgraphs.insert(name.to_owned(), max);
+        self
+    }
+}
+
+///Limits number of representations per `_entities` request, so that large sets of entities don't exceed body
+///limits of subgraphs.
+///
+///Larger sets are split into multiple requests, sent in parallel, and their results are merged in original order.
+pub struct EntityBatching {
+    config: Arc<EntityBatchingConfig>,
+}
+
+impl EntityBatching {
+    #[inline(always)]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: EntityBatchingConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for EntityBatching {
+    type Config = EntityBatchingConfig;
+
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let result = match config.subgraphs.iter().find(|(_, max)| **max == 0) {
+            Some((name, _)) => Err(format!("Limit of '{}' must be greater than 0", name).into()),
+            None if config.default == Some(0) => Err("default must be greater than 0".into()),
+            None => Ok(Self::with_config(config)),
+        };
+        Box::pin(ready(result))
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        match self.config.limit(name) {
+            Some(max) => EntityBatchingService {
+                name: name.into(),
+                inner: Buffer::new(service, BUFFER_SIZE),
+                max: max.max(1),
+            }
+            .boxed(),
+            None => service,
+        }
+    }
+}
+
+///Returns request bodies, each with at most `max` of `representations`.
+fn split(
+    graphql: &GraphqlRequest,
+    representations: &[serde_json::Value],
+    max: usize,
+) -> Result<Vec<GraphqlRequest>, serde_json::Error> {
+    let mut body = serde_json::to_value(graphql)?;
+    let mut result = Vec::new();
+    for chunk in representations.chunks(max) {
+        body["variables"][REPRESENTATIONS] = serde_json::Value::Array(chunk.to_vec());
+        result.push(serde_json::from_value(body.clone())?);
+    }
+    Ok(result)
+}
+
+///Merges responses of split requests, shifting entity indexes in error paths to original positions.
+fn merge(responses: &[(usize, serde_json::Value)]) -> serde_json::Value {
+    let mut entities = Vec::new();
+    let mut errors = Vec::new();
+    let mut extensions = serde_json::Map::new();
+    for (len, response) in responses {
+        let offset = entities.len();
+        match response.pointer("/data/_entities") {
+            Some(serde_json::Value::Array(chunk)) if chunk.len() == *len => entities.extend(chunk.iter().cloned()),
+            //Missing entities are null, which router reports with errors of the chunk.
+            _ => entities.resize(offset + len, serde_json::Value::Null),
+        }
+        if let Some(serde_json::Value::Array(chunk)) = response.get("errors") {
+            for error in chunk {
+                let mut error = error.clone();
+                if let Some(serde_json::Value::Array(path)) = error.get_mut("path") {
+                    if let (Some(ENTITIES), Some(index)) = (path.first().and_then(|key| key.as_str()), path.get(1)) {
+                        if let Some(index) = index.as_u64() {
+                            path[1] = (index + offset as u64).into();
+                        }
+                    }
+                }
+                errors.push(error);
+            }
+        }
+        if let Some(serde_json::Value::Object(chunk)) = response.get("extensions") {
+            for (key, value) in chunk {
+                extensions.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+
+    let mut merged = serde_json::json!({
+        "data": { ENTITIES: entities },
+    });
+    if !errors.is_empty() {
+        merged["errors"] = serde_json::Value::Array(errors);
+    }
+    if !extensions.is_empty() {
+        merged["extensions"] = serde_json::Value::Object(extensions);
+    }
+    merged
+}
+
+struct EntityBatchingService {
+    name: Arc<str>,
+    inner: BatchService,
+    max: usize,
+}
+
+impl tower::Service<SubgraphRequest> for EntityBatchingService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let representations = match req.subgraph_request.body().variables.get(REPRESENTATIONS) {
+            Some(representations) => serde_json_bytes::from_value::<Vec<serde_json::Value>>(representations.clone()),
+            None => return Box::pin(self.inner.call(req)),
+        };
+        let representations = match representations {
+            Ok(representations) if representations.len() > self.max => representations,
+            _ => return Box::pin(self.inner.call(req)),
+        };
+        let bodies = match split(req.subgraph_request.body(), &representations, self.max) {
+            Ok(bodies) => bodies,
+            Err(error) => {
+                tracing::warn!("{}: Unable to split entities request: {}", self.name, error);
+                return Box::pin(self.inner.call(req));
+            }
+        };
+        tracing::debug!(
+            "{}: {} representations split into {} requests",
+            self.name,
+            representations.len(),
+            bodies.len()
+        );
+
+        let (parts, _) = req.subgraph_request.into_parts();
+        let requests = bodies.into_iter().map(|graphql| SubgraphRequest {
+            originating_request: req.originating_request.clone(),
+            subgraph_request: apollo_router_core::http_compat::Request::from_parts(clone_parts(&parts), graphql),
+            context: req.context.clone(),
+        });
+
+        let name = self.name.clone();
+        let lens = representations.chunks(self.max).map(<[_]>::len).collect::<Vec<_>>();
+        let responses = requests.into_iter().map(|req| self.inner.clone().oneshot(req));
+        let responses = futures_util::future::try_join_all(responses);
+        Box::pin(async move {
+            let mut responses = responses.await?;
+            let bodies = responses
+                .iter()
+                .map(|response| serde_json::to_value(response.response.body()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let merged = merge(&lens.into_iter().zip(bodies).collect::<Vec<_>>());
+            let merged = GraphqlResponse::from_bytes(&name, crate::pool::serialize(&merged)?)?;
+            //Headers of the first response are kept, as responses of the same subgraph share them.
+            let mut response = responses.swap_remove(0);
+            *response.response.body_mut() = merged;
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,342 @@
+use apollo_router_core::{Plugin, ResponseBody, RouterRequest, RouterResponse};
+use hyper::http::header::CONTENT_TYPE;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::queue::PayloadQueue;
+use crate::context::ClientIdentity;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[inline(always)]
+fn default_queue_capacity() -> usize {
+    10_000
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+///Compact event describing executed operation.
+pub struct OperationEvent {
+    ///Milliseconds since UNIX epoch, when operation was received.
+    pub timestamp_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ///Hex encoded SHA-256 of operation document without comments and insignificant whitespace, if request has
+    ///query.
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ///Name of operation.
+    pub operation_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ///Identity of client, if known.
+    pub client: Option<String>,
+    ///Time in milliseconds spent processing operation.
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ///HTTP status of response, if router responded.
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ///Code of the first error, `GRAPHQL_ERROR` if it has none, or `FAILED` if router failed to respond.
+    pub error_class: Option<String>,
+}
+
+///Destination of operation events.
+///
+///Called on request path, so sink must not block, e.g. by queueing events for background task.
+pub trait OperationEventSink: Send + Sync {
+    ///Publishes `event`.
+    fn publish(&self, event: &OperationEvent);
+}
+
+//Queues event, dropping it rather than delaying request, as events are best effort.
+fn push(queue: &PayloadQueue, event: &OperationEvent) {
+    let payload = serde_json::to_vec(event).expect("JSON serialization should not fail");
+    if !queue.push(payload) {
+        tracing::warn!("Operation events queue is full, event is dropped");
+    }
+}
+
+///Sink sending each event as JSON via `POST` to URL.
+pub struct HttpEventSink {
+    queue: PayloadQueue,
+}
+
+impl HttpEventSink {
+    ///Creates sink sending events to `url`, queueing up to `capacity` events.
+    ///
+    ///Must be called within tokio runtime.
+    pub fn new(url: hyper::Uri, capacity: usize) -> Self {
+        let client = crate::remote::http_client();
+        let queue = PayloadQueue::spawn("operation event", capacity, move |body| {
+            let req = hyper::Request::post(url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.into())
+                .expect("Valid event request");
+            let response = client.request(req);
+            async move {
+                match response.await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => Err(format!("Sink responded with {}", response.status())),
+                    Err(error) => Err(error.to_string()),
+                }
+            }
+        });
+        Self { queue }
+    }
+}
+
+impl OperationEventSink for HttpEventSink {
+    #[inline(always)]
+    fn publish(&self, event: &OperationEvent) {
+        push(&self.queue, event)
+    }
+}
+
+#[cfg(feature = "kafka")]
+///Sink producing each event as JSON message to Kafka topic.
+pub struct KafkaEventSink {
+    queue: PayloadQueue,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaEventSink {
+    ///Creates sink producing events to `topic` of `brokers`, queueing up to `capacity` events.
+    ///
+    ///Must be called within tokio runtime.
+    pub fn new(brokers: &str, topic: String, capacity: usize) -> Result<Self, BoxError> {
+        use rdkafka::producer::{FutureProducer, FutureRecord};
+
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create::<FutureProducer>()?;
+        let queue = PayloadQueue::spawn("operation event", capacity, move |payload| {
+            let producer = producer.clone();
+            let topic = topic.clone();
+            async move {
+                let record = FutureRecord::<(), _>::to(&topic).payload(&payload);
+                match producer.send(record, core::time::Duration::ZERO).await {
+                    Ok(_) => Ok(()),
+                    Err((error, _)) => Err(error.to_string()),
+                }
+            }
+        });
+        Ok(Self { queue })
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl OperationEventSink for KafkaEventSink {
+    #[inline(always)]
+    fn publish(&self, event: &OperationEvent) {
+        push(&self.queue, event)
+    }
+}
+
+#[cfg(feature = "nats")]
+///Sink publishing each event as JSON message to NATS subject.
+pub struct NatsEventSink {
+    queue: PayloadQueue,
+}
+
+#[cfg(feature = "nats")]
+impl NatsEventSink {
+    ///Connects to NATS server at `url`, publishing events to `subject` and queueing up to `capacity` events.
+    pub async fn connect(url: &str, subject: String, capacity: usize) -> Result<Self, BoxError> {
+        let client = async_nats::connect(url).await?;
+        let queue = PayloadQueue::spawn("operation event", capacity, move |payload| {
+            let client = client.clone();
+            let subject = subject.clone();
+            async move { client.publish(subject, payload.into()).await.map_err(|error| error.to_string()) }
+        });
+        Ok(Self { queue })
+    }
+}
+
+#[cfg(feature = "nats")]
+impl OperationEventSink for NatsEventSink {
+    #[inline(always)]
+    fn publish(&self, event: &OperationEvent) {
+        push(&self.queue, event)
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+///Operation events sink config
+///
+///Other sinks can be used via [OperationEvents::with_sink].
+pub enum OperationEventSinkConfig {
+    ///Sends JSON via `POST`.
+    Http {
+        ///URL of endpoint.
+        url: String,
+    },
+    #[cfg(feature = "kafka")]
+    ///Produces JSON messages to Kafka.
+    Kafka {
+        ///Comma separated list of bootstrap brokers.
+        brokers: String,
+        ///Topic to produce messages to.
+        topic: String,
+    },
+    #[cfg(feature = "nats")]
+    ///Publishes JSON messages to NATS.
+    Nats {
+        ///URL of NATS server.
+        url: String,
+        ///Subject to publish messages to.
+        subject: String,
+    },
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Operation events config
+pub struct OperationEventsConfig {
+    ///Destination of events.
+    pub sink: OperationEventSinkConfig,
+    #[serde(default = "default_queue_capacity")]
+    ///Number of events waiting to be published, above which new events are dropped.
+    pub queue_capacity: usize,
+    #[serde(default)]
+    ///Header identifying client, used when client is not authenticated by [ApiKeys](super::ApiKeys).
+    pub client_header: Option<String>,
+}
+
+#[inline]
+fn signature(query: &str) -> String {
+    crate::hash::sha256_hex(crate::parser::normalize_document(query).as_bytes())
+}
+
+#[inline]
+fn error_class(response: &RouterResponse) -> Option<String> {
+    let error = match response.response.body() {
+        ResponseBody::GraphQL(body) => body.errors.first()?,
+        _ => return None,
+    };
+    let code = error.extensions.get("code").and_then(|value| value.as_str());
+    Some(code.unwrap_or("GRAPHQL_ERROR").to_owned())
+}
+
+///Publishes compact event per executed operation, e.g. for analytics pipelines.
+pub struct OperationEvents {
+    sink: Arc<dyn OperationEventSink>,
+    client_header: Option<Arc<str>>,
+}
+
+impl OperationEvents {
+    #[inline]
+    ///Creates plugin publishing events to `sink`.
+    pub fn with_sink(sink: Box<dyn OperationEventSink>) -> Self {
+        Self {
+            sink: sink.into(),
+            client_header: None,
+        }
+    }
+
+    #[inline]
+    ///Sets `header` identifying client, used when client is not authenticated by [ApiKeys](super::ApiKeys).
+    pub fn client_header(mut self, header: &str) -> Self {
+        self.client_header = Some(header.into());
+        self
+    }
+
+    ///Creates plugin according to `config`, connecting to its sink.
+    ///
+    ///Must be called within tokio runtime.
+    pub async fn with_config(config: OperationEventsConfig) -> Result<Self, BoxError> {
+        let capacity = config.queue_capacity;
+        let sink: Box<dyn OperationEventSink> = match &config.sink {
+            OperationEventSinkConfig::Http { url } => Box::new(HttpEventSink::new(url.parse()?, capacity)),
+            #[cfg(feature = "kafka")]
+            OperationEventSinkConfig::Kafka { brokers, topic } => {
+                Box::new(KafkaEventSink::new(brokers, topic.clone(), capacity)?)
+            }
+            #[cfg(feature = "nats")]
+            OperationEventSinkConfig::Nats { url, subject } => {
+                Box::new(NatsEventSink::connect(url, subject.clone(), capacity).await?)
+            }
+        };
+        let events = Self::with_sink(sink);
+        Ok(match config.client_header {
+            Some(header) => events.client_header(&header),
+            None => events,
+        })
+    }
+}
+
+impl Plugin for OperationEvents {
+    type Config = OperationEventsConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(Self::with_config(config))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        OperationEventsService {
+            inner: service,
+            sink: self.sink.clone(),
+            client_header: self.client_header.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct OperationEventsService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    sink: Arc<dyn OperationEventSink>,
+    client_header: Option<Arc<str>>,
+}
+
+impl tower::Service<RouterRequest> for OperationEventsService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let body = req.originating_request.body();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let event = OperationEvent {
+            timestamp_ms: timestamp.as_millis() as u64,
+            signature: body.query.as_deref().map(signature),
+            operation_name: body.operation_name.clone(),
+            client: ClientIdentity::of(&req, self.client_header.as_deref()),
+            latency_ms: 0.0,
+            status: None,
+            error_class: None,
+        };
+        let sink = self.sink.clone();
+        let started = Instant::now();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            let mut event = event;
+            event.latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+            match result.as_ref() {
+                Ok(response) => {
+                    event.status = Some(response.response.status().as_u16());
+                    event.error_class = error_class(response);
+                }
+                Err(_) => event.error_class = Some("FAILED".to_owned()),
+            }
+            sink.publish(&event);
+            result
+        })
+    }
+}
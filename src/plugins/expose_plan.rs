@@ -0,0 +1,252 @@
+use apollo_router_core::{
+    Plugin, QueryPlannerRequest, QueryPlannerResponse, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+use crate::QueryPlan;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::sync::Arc;
+use std::time::Instant;
+
+//Context key, which holds id of request, which plan is exposed.
+const EXPOSE_ID: &str = "graphql_router::expose_plan_id";
+
+fn default_header() -> String {
+    "x-expose-query-plan".to_owned()
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Query plan exposure config
+pub struct ExposeQueryPlanConfig {
+    #[serde(default = "default_header")]
+    ///Request header, which enables exposure for request.
+    pub header: String,
+    #[serde(default)]
+    ///Exposes plan of every request, regardless of header.
+    pub always: bool,
+}
+
+impl Default for ExposeQueryPlanConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            header: default_header(),
+            always: false,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct FetchTiming {
+    subgraph: String,
+    start_ms: f64,
+    duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+struct Exposed {
+    started: Instant,
+    plan: Option<QueryPlan>,
+    fetches: Vec<FetchTiming>,
+}
+
+///Includes executed query plan and timings of its fetches in response `extensions.queryPlan`.
+///
+///Intended for debugging of slow federated queries, as it exposes internals of supergraph.
+pub struct ExposeQueryPlan {
+    config: Arc<ExposeQueryPlanConfig>,
+    ongoing: Ongoing<Exposed>,
+}
+
+impl ExposeQueryPlan {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: ExposeQueryPlanConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for ExposeQueryPlan {
+    type Config = ExposeQueryPlanConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        ExposeRouterService {
+            inner: service,
+            config: self.config.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn query_planning_service(
+        &mut self,
+        service: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ) -> BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError> {
+        ExposePlanService {
+            inner: service,
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        ExposeFetchService {
+            inner: service,
+            name: name.into(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct ExposeRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    config: Arc<ExposeQueryPlanConfig>,
+    ongoing: Ongoing<Exposed>,
+}
+
+impl tower::Service<RouterRequest> for ExposeRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        if !self.config.always && !req.originating_request.headers().contains_key(self.config.header.as_str()) {
+            return self.inner.call(req);
+        }
+
+        let id = self.ongoing.start(Exposed {
+            started: Instant::now(),
+            plan: None,
+            fetches: Vec::new(),
+        });
+        if let Err(error) = req.context.insert(EXPOSE_ID, id) {
+            tracing::warn!("Unable to expose query plan: {}", error);
+            self.ongoing.finish(id);
+            return self.inner.call(req);
+        }
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut result = response.await;
+            if let (Some(exposed), Ok(response)) = (ongoing.finish(id), result.as_mut()) {
+                let extension = serde_json::json!({
+                    "plan": exposed.plan,
+                    "durationMs": exposed.started.elapsed().as_secs_f64() * 1000.0,
+                    "fetches": exposed.fetches,
+                });
+                super::insert_extension(response, "queryPlan", extension);
+            }
+            result
+        })
+    }
+}
+
+struct ExposePlanService {
+    inner: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ongoing: Ongoing<Exposed>,
+}
+
+impl tower::Service<QueryPlannerRequest> for ExposePlanService {
+    type Response = QueryPlannerResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<QueryPlannerResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: QueryPlannerRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(EXPOSE_ID) {
+            Ok(Some(id)) => id,
+            _ => return self.inner.call(req),
+        };
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            if let Some(plan) = QueryPlan::from_planner(&response) {
+                ongoing.update(id, |exposed| exposed.plan = Some(plan));
+            }
+            Ok(response)
+        })
+    }
+}
+
+struct ExposeFetchService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    name: Arc<str>,
+    ongoing: Ongoing<Exposed>,
+}
+
+impl tower::Service<SubgraphRequest> for ExposeFetchService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(EXPOSE_ID) {
+            Ok(Some(id)) => id,
+            _ => return self.inner.call(req),
+        };
+
+        let name = self.name.clone();
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = response.await;
+            let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+            let error = result.as_ref().err().map(|error| error.to_string());
+            ongoing.update(id, |exposed| {
+                exposed.fetches.push(FetchTiming {
+                    subgraph: name.to_string(),
+                    start_ms: started.duration_since(exposed.started).as_secs_f64() * 1000.0,
+                    duration_ms,
+                    error,
+                })
+            });
+            result
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use apollo_router_core::{Plugin, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn default_debug_header() -> String {
+    "x-debug-errors".to_owned()
+}
+
+fn default_message() -> String {
+    "Subgraph error".to_owned()
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///How errors returned by subgraph are exposed to client
+pub enum ErrorExposure {
+    ///Errors are passed verbatim.
+    Passthrough,
+    ///Message is replaced with generic one and extensions are removed.
+    Redact,
+    ///Errors are passed verbatim only to requests with debug header, otherwise redacted.
+    Debug,
+}
+
+impl Default for ErrorExposure {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Passthrough
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Subgraph errors exposure config
+pub struct SubgraphErrorsConfig {
+    #[serde(default)]
+    ///Exposure of subgraphs, that are not specified in `subgraphs`.
+    pub default: ErrorExposure,
+    #[serde(default)]
+    ///Exposure per subgraph name.
+    pub subgraphs: BTreeMap<String, ErrorExposure>,
+    #[serde(default = "default_debug_header")]
+    ///Header enabling verbatim errors with `debug` exposure.
+    pub debug_header: String,
+    #[serde(default = "default_message")]
+    ///Message of redacted errors.
+    pub message: String,
+}
+
+impl Default for SubgraphErrorsConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            default: ErrorExposure::default(),
+            subgraphs: BTreeMap::new(),
+            debug_header: default_debug_header(),
+            message: default_message(),
+        }
+    }
+}
+
+impl SubgraphErrorsConfig {
+    #[inline]
+    ///Returns exposure of subgraph.
+    pub fn exposure(&self, name: &str) -> ErrorExposure {
+        self.subgraphs.get(name).copied().unwrap_or(self.default)
+    }
+}
+
+///Controls whether messages and extensions of subgraph errors reach clients.
+pub struct SubgraphErrors {
+    config: Arc<SubgraphErrorsConfig>,
+}
+
+impl SubgraphErrors {
+    #[inline(always)]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: SubgraphErrorsConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for SubgraphErrors {
+    type Config = SubgraphErrorsConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        match self.config.exposure(name) {
+            ErrorExposure::Passthrough => service,
+            exposure => SubgraphErrorsService {
+                inner: service,
+                config: self.config.clone(),
+                exposure,
+            }
+            .boxed(),
+        }
+    }
+}
+
+struct SubgraphErrorsService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    config: Arc<SubgraphErrorsConfig>,
+    exposure: ErrorExposure,
+}
+
+impl tower::Service<SubgraphRequest> for SubgraphErrorsService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let is_debug = self.exposure == ErrorExposure::Debug
+            && req
+                .originating_request
+                .headers()
+                .contains_key(self.config.debug_header.as_str());
+        if is_debug {
+            return self.inner.call(req);
+        }
+
+        let config = self.config.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            for error in response.response.body_mut().errors.iter_mut() {
+                error.message = config.message.clone();
+                error.extensions.clear();
+            }
+            Ok(response)
+        })
+    }
+}
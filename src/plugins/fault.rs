@@ -0,0 +1,186 @@
+use apollo_router_core::{FetchError, Plugin, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::error::{record_fetch_failure, FetchFailure};
+use crate::sample::Sampler;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::sync::Arc;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+///Kind of injected fault
+pub enum Fault {
+    ///Subgraph responds with `500 Internal Server Error`, which is reported as status of failed fetch.
+    Error,
+    ///Subgraph responds with invalid GraphQL response.
+    MalformedResponse,
+    ///Connection to subgraph is dropped before response, so failed fetch has no status.
+    DroppedConnection,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Fault injection rule
+pub struct FaultRule {
+    ///Injected fault.
+    pub fault: Fault,
+    ///Fraction of matching requests to fail, from `0.0` to `1.0`.
+    pub rate: f64,
+    #[serde(default)]
+    ///Names of affected subgraphs, all subgraphs are affected if empty.
+    pub subgraphs: Vec<String>,
+    #[serde(default)]
+    ///Names of affected operations, all operations are affected if empty.
+    pub operations: Vec<String>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Fault injection config
+pub struct FaultInjectionConfig {
+    #[serde(default)]
+    ///Rules, applied in order until first injected fault.
+    pub rules: Vec<FaultRule>,
+}
+
+struct Rule {
+    fault: Fault,
+    subgraphs: Vec<String>,
+    operations: Vec<String>,
+    sampler: Sampler,
+}
+
+///Injects subgraph failures, to test resilience of router and its clients.
+///
+///Intended for staging environments only.
+pub struct FaultInjection {
+    rules: Vec<Arc<Rule>>,
+}
+
+impl FaultInjection {
+    ///Creates plugin with specified rules.
+    pub fn with_config(config: FaultInjectionConfig) -> Self {
+        let rules = config
+            .rules
+            .into_iter()
+            .map(|rule| {
+                Arc::new(Rule {
+                    fault: rule.fault,
+                    subgraphs: rule.subgraphs,
+                    operations: rule.operations,
+                    sampler: Sampler::new(rule.rate),
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+}
+
+impl Plugin for FaultInjection {
+    type Config = FaultInjectionConfig;
+
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let result = match config.rules.iter().find(|rule| !(0.0..=1.0).contains(&rule.rate)) {
+            Some(rule) => Err(format!("Fault rate {} is not within 0.0..=1.0", rule.rate).into()),
+            None => Ok(Self::with_config(config)),
+        };
+        Box::pin(ready(result))
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.subgraphs.is_empty() || rule.subgraphs.iter().any(|subgraph| subgraph == name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if rules.is_empty() {
+            return service;
+        }
+
+        FaultInjectionService {
+            inner: service,
+            name: name.into(),
+            rules,
+        }
+        .boxed()
+    }
+}
+
+struct FaultInjectionService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    name: Arc<str>,
+    rules: Vec<Arc<Rule>>,
+}
+
+impl FaultInjectionService {
+    fn fault(&self, req: &SubgraphRequest) -> Option<Fault> {
+        let operation_name = req.originating_request.body().operation_name.as_deref();
+        self.rules
+            .iter()
+            .filter(|rule| super::is_operation_listed(&rule.operations, operation_name))
+            .find(|rule| rule.sampler.sample())
+            .map(|rule| rule.fault)
+    }
+}
+
+impl tower::Service<SubgraphRequest> for FaultInjectionService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let fault = match self.fault(&req) {
+            Some(fault) => fault,
+            None => return self.inner.call(req),
+        };
+
+        tracing::info!("{}: Injecting fault {:?}", self.name, fault);
+        let service = self.name.to_string();
+        let failure = |status: Option<u16>| FetchFailure {
+            status,
+            attempts: 1,
+            host: req.subgraph_request.uri().host().unwrap_or_default().to_owned(),
+            retry_after_ms: None,
+        };
+        let error = match fault {
+            Fault::Error => {
+                record_fetch_failure(&req.context, &service, failure(Some(500)));
+                FetchError::SubrequestHttpError {
+                    service,
+                    reason: "Injected fault: subgraph responded with status 500".to_owned(),
+                }
+            }
+            Fault::MalformedResponse => FetchError::SubrequestMalformedResponse {
+                service,
+                reason: "Injected fault: invalid GraphQL response".to_owned(),
+            },
+            Fault::DroppedConnection => {
+                record_fetch_failure(&req.context, &service, failure(None));
+                FetchError::SubrequestHttpError {
+                    service,
+                    reason: "Injected fault: connection closed before message completed".to_owned(),
+                }
+            }
+        };
+        Box::pin(ready(Err(error.into())))
+    }
+}
@@ -0,0 +1,207 @@
+use apollo_router_core::{Plugin, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::sample::{Sampler, Sequence};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use core::time::Duration;
+use std::sync::Arc;
+
+//Requests are queued only while subgraph service is not ready, so it doesn't need to be big.
+const BUFFER_SIZE: usize = 1024;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+///Distribution of injected delay
+pub enum Delay {
+    ///Always the same delay.
+    Fixed {
+        ///Delay in milliseconds.
+        ms: u64,
+    },
+    ///Delay evenly distributed within range.
+    Uniform {
+        ///Minimum delay in milliseconds.
+        min_ms: u64,
+        ///Maximum delay in milliseconds.
+        max_ms: u64,
+    },
+    ///Exponentially distributed delay, with mostly short delays and rare long ones.
+    Exponential {
+        ///Mean delay in milliseconds.
+        mean_ms: u64,
+    },
+}
+
+impl Delay {
+    ///Returns delay for `position` within `0..1`.
+    fn at(&self, position: f64) -> Duration {
+        let ms = match *self {
+            Self::Fixed { ms } => ms as f64,
+            Self::Uniform { min_ms, max_ms } => {
+                let (min_ms, max_ms) = (min_ms.min(max_ms) as f64, min_ms.max(max_ms) as f64);
+                min_ms + (max_ms - min_ms) * position
+            }
+            Self::Exponential { mean_ms } => -(mean_ms as f64) * (1.0 - position).ln(),
+        };
+        Duration::from_micros((ms * 1000.0) as u64)
+    }
+}
+
+fn default_rate() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Latency injection rule
+pub struct LatencyRule {
+    ///Injected delay.
+    pub delay: Delay,
+    #[serde(default = "default_rate")]
+    ///Fraction of matching requests to delay, from `0.0` to `1.0`.
+    ///
+    ///Default is `1.0`.
+    pub rate: f64,
+    #[serde(default)]
+    ///Names of affected subgraphs, all subgraphs are affected if empty.
+    pub subgraphs: Vec<String>,
+    #[serde(default)]
+    ///Names of affected operations, all operations are affected if empty.
+    pub operations: Vec<String>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Latency injection config
+pub struct LatencyInjectionConfig {
+    #[serde(default)]
+    ///Rules, applied in order until first injected delay.
+    pub rules: Vec<LatencyRule>,
+}
+
+struct Rule {
+    delay: Delay,
+    subgraphs: Vec<String>,
+    operations: Vec<String>,
+    sampler: Sampler,
+    sequence: Sequence,
+}
+
+///Delays subgraph requests, to test timeout budgets and client behavior with slow subgraphs.
+///
+///Request is passed to subgraph service only after delay, so it does not count towards subgraph's own timeout.
+///
+///Intended for staging environments only.
+pub struct LatencyInjection {
+    rules: Vec<Arc<Rule>>,
+}
+
+impl LatencyInjection {
+    ///Creates plugin with specified rules.
+    pub fn with_config(config: LatencyInjectionConfig) -> Self {
+        let rules = config
+            .rules
+            .into_iter()
+            .map(|rule| {
+                Arc::new(Rule {
+                    delay: rule.delay,
+                    subgraphs: rule.subgraphs,
+                    operations: rule.operations,
+                    sampler: Sampler::new(rule.rate),
+                    sequence: Sequence::default(),
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+}
+
+impl Plugin for LatencyInjection {
+    type Config = LatencyInjectionConfig;
+
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let result = match config.rules.iter().find(|rule| !(0.0..=1.0).contains(&rule.rate)) {
+            Some(rule) => Err(format!("Latency rate {} is not within 0.0..=1.0", rule.rate).into()),
+            None => Ok(Self::with_config(config)),
+        };
+        Box::pin(ready(result))
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.subgraphs.is_empty() || rule.subgraphs.iter().any(|subgraph| subgraph == name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if rules.is_empty() {
+            return service;
+        }
+
+        LatencyInjectionService {
+            inner: Buffer::new(service, BUFFER_SIZE),
+            name: name.into(),
+            rules,
+        }
+        .boxed()
+    }
+}
+
+struct LatencyInjectionService {
+    //Shared with delayed calls, which take it only after delay.
+    inner: Buffer<BoxService<SubgraphRequest, SubgraphResponse, BoxError>, SubgraphRequest>,
+    name: Arc<str>,
+    rules: Vec<Arc<Rule>>,
+}
+
+impl LatencyInjectionService {
+    fn delay(&self, req: &SubgraphRequest) -> Option<Duration> {
+        let operation_name = req.originating_request.body().operation_name.as_deref();
+        self.rules
+            .iter()
+            .filter(|rule| super::is_operation_listed(&rule.operations, operation_name))
+            .find(|rule| rule.sampler.sample())
+            .map(|rule| rule.delay.at(rule.sequence.next_fraction()))
+    }
+}
+
+impl tower::Service<SubgraphRequest> for LatencyInjectionService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Readiness of subgraph service is awaited by each call, after its delay.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let delay = match self.delay(&req) {
+            Some(delay) => delay,
+            None => return Box::pin(inner.oneshot(req)),
+        };
+
+        tracing::info!("{}: Injecting delay of {}ms", self.name, delay.as_millis());
+        //Remote subgraph service starts fetch and its mirror in `call`, so it is called only after delay.
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            inner.oneshot(req).await
+        })
+    }
+}
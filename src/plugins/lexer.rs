@@ -0,0 +1,98 @@
+//! Lightweight GraphQL lexer, counting tokens without building document.
+//!
+//! It is intended only to reject abusive documents cheaply, so it doesn't validate tokens beyond
+//! what is necessary to find their boundaries.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+///Lexical statistics of document.
+pub struct LexicalStats {
+    ///Number of tokens.
+    pub tokens: usize,
+    ///Number of top level definitions.
+    pub definitions: usize,
+}
+
+#[inline(always)]
+fn is_name_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+///Scans `document`, stopping once number of tokens exceeds `max_tokens`.
+pub fn scan(document: &str, max_tokens: usize) -> LexicalStats {
+    let bytes = document.as_bytes();
+    let mut stats = LexicalStats::default();
+    let mut braces = 0usize;
+    let mut parens = 0usize;
+    let mut idx = 0;
+
+    while idx < bytes.len() && stats.tokens <= max_tokens {
+        let byte = bytes[idx];
+        match byte {
+            //Ignored tokens
+            b' ' | b'\t' | b'\n' | b'\r' | b',' => {
+                idx += 1;
+                continue;
+            }
+            b'#' => {
+                while idx < bytes.len() && bytes[idx] != b'\n' && bytes[idx] != b'\r' {
+                    idx += 1;
+                }
+                continue;
+            }
+            b'"' if bytes[idx..].starts_with(b"\"\"\"") => {
+                idx += 3;
+                while idx < bytes.len() && !bytes[idx..].starts_with(b"\"\"\"") {
+                    idx += if bytes[idx..].starts_with(b"\\\"\"\"") { 4 } else { 1 };
+                }
+                idx += 3;
+            }
+            b'"' => {
+                idx += 1;
+                while idx < bytes.len() && bytes[idx] != b'"' && bytes[idx] != b'\n' {
+                    idx += match bytes[idx] {
+                        b'\\' => 2,
+                        _ => 1,
+                    };
+                }
+                idx += 1;
+            }
+            b'.' if bytes[idx..].starts_with(b"...") => idx += 3,
+            b'{' => {
+                if braces == 0 && parens == 0 {
+                    stats.definitions += 1;
+                }
+                braces += 1;
+                idx += 1;
+            }
+            b'}' => {
+                braces = braces.saturating_sub(1);
+                idx += 1;
+            }
+            b'(' => {
+                parens += 1;
+                idx += 1;
+            }
+            b')' => {
+                parens = parens.saturating_sub(1);
+                idx += 1;
+            }
+            //Number, with fraction and exponent.
+            b'-' | b'0'..=b'9' => {
+                idx += 1;
+                while idx < bytes.len() && (is_name_char(bytes[idx]) || matches!(bytes[idx], b'.' | b'-' | b'+')) {
+                    idx += 1;
+                }
+            }
+            byte if is_name_char(byte) => {
+                idx += 1;
+                while idx < bytes.len() && is_name_char(bytes[idx]) {
+                    idx += 1;
+                }
+            }
+            _ => idx += 1,
+        }
+        stats.tokens += 1;
+    }
+
+    stats
+}
@@ -0,0 +1,142 @@
+use apollo_router_core::{Context, Plugin, RouterRequest, RouterResponse};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::BoxError;
+
+use crate::error::router_error;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Request limits config
+pub struct RequestLimitsConfig {
+    #[serde(default)]
+    ///Maximum size of query document in bytes.
+    pub max_query_bytes: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of lexical tokens in query document.
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of operations and fragments in query document.
+    pub max_definitions: Option<usize>,
+    #[serde(default)]
+    ///Maximum size of variables in bytes, counting serialized names and values.
+    pub max_variables_bytes: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of variables.
+    pub max_variables: Option<usize>,
+}
+
+///Rejects requests exceeding limits, before query is planned.
+pub struct RequestLimits {
+    config: RequestLimitsConfig,
+}
+
+impl RequestLimits {
+    #[inline(always)]
+    ///Creates plugin with specified limits.
+    pub fn with_config(config: RequestLimitsConfig) -> Self {
+        Self { config }
+    }
+}
+
+///Checks `query` against limits of `config`, which do not require variables.
+///
+///Used by built-in [server](crate::server) to reject request, before its variables are parsed.
+pub(crate) fn check_query(config: &RequestLimitsConfig, query: &str, context: &Context) -> Result<(), RouterResponse> {
+    if let Some(max_query_bytes) = config.max_query_bytes {
+        if query.len() > max_query_bytes {
+            tracing::info!("Rejected query of {} bytes", query.len());
+            let message = format!("Query exceeds limit of {} bytes", max_query_bytes);
+            return Err(router_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &message,
+                "QUERY_TOO_LARGE",
+                context.clone(),
+            ));
+        }
+    }
+    if config.max_tokens.is_some() || config.max_definitions.is_some() {
+        let stats = super::lexer::scan(query, config.max_tokens.unwrap_or(usize::MAX));
+        if let Some(max_tokens) = config.max_tokens {
+            if stats.tokens > max_tokens {
+                tracing::info!("Rejected query exceeding {} tokens", max_tokens);
+                let message = format!("Query exceeds limit of {} tokens", max_tokens);
+                return Err(router_error(
+                    StatusCode::BAD_REQUEST,
+                    &message,
+                    "TOO_MANY_TOKENS",
+                    context.clone(),
+                ));
+            }
+        }
+        if let Some(max_definitions) = config.max_definitions {
+            if stats.definitions > max_definitions {
+                tracing::info!("Rejected query with {} definitions", stats.definitions);
+                let message = format!("Query exceeds limit of {} definitions", max_definitions);
+                return Err(router_error(
+                    StatusCode::BAD_REQUEST,
+                    &message,
+                    "TOO_MANY_DEFINITIONS",
+                    context.clone(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+///Returns size of variables, stopping once it exceeds `limit`.
+fn variables_size(req: &RouterRequest, limit: usize) -> usize {
+    let mut size = 0;
+    for (name, value) in req.originating_request.body().variables.iter() {
+        size += name.as_str().len() + serde_json::to_vec(value).map_or(0, |value| value.len());
+        if size > limit {
+            break;
+        }
+    }
+    size
+}
+
+impl Plugin for RequestLimits {
+    type Config = RequestLimitsConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let config = self.config.clone();
+        super::checkpoint(service, move |req: RouterRequest| {
+            let query = req.originating_request.body().query.as_deref().unwrap_or_default();
+            check_query(&config, query, &req.context)?;
+            if let Some(max_variables) = config.max_variables {
+                let variables_len = req.originating_request.body().variables.len();
+                if variables_len > max_variables {
+                    tracing::info!("Rejected request with {} variables", variables_len);
+                    let message = format!("Request exceeds limit of {} variables", max_variables);
+                    return Err(router_error(StatusCode::BAD_REQUEST, &message, "BAD_USER_INPUT", req.context));
+                }
+            }
+            if let Some(max_variables_bytes) = config.max_variables_bytes {
+                if variables_size(&req, max_variables_bytes) > max_variables_bytes {
+                    tracing::info!("Rejected request with variables exceeding {} bytes", max_variables_bytes);
+                    let message = format!("Variables exceed limit of {} bytes", max_variables_bytes);
+                    return Err(router_error(StatusCode::BAD_REQUEST, &message, "BAD_USER_INPUT", req.context));
+                }
+            }
+            Ok(req)
+        })
+    }
+}
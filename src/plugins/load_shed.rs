@@ -0,0 +1,139 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::error::{router_error, set_retry_hint};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use core::time::Duration;
+use std::sync::Arc;
+
+#[inline(always)]
+fn default_retry_after_ms() -> u64 {
+    1000
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Load shedding config
+pub struct LoadShedConfig {
+    ///Maximum number of requests processed at the same time.
+    pub max_in_flight: usize,
+    #[serde(default)]
+    ///Time in milliseconds request may wait for free slot, before being rejected.
+    ///
+    ///Default is `0`, rejecting requests immediately.
+    pub queue_timeout_ms: u64,
+    #[serde(default = "default_retry_after_ms")]
+    ///Delay in milliseconds, suggested to rejected clients via `Retry-After` and `retryAfterMs`.
+    pub retry_after_ms: u64,
+}
+
+///Rejects requests with `SERVER_OVERLOADED` error when too many are in flight.
+///
+///This keeps latency of accepted requests bounded, when planner or subgraphs cannot keep up.
+pub struct LoadShed {
+    config: LoadShedConfig,
+    //Shared by all builds of router, so that requests in flight are counted across rebuilds.
+    permits: Arc<Semaphore>,
+}
+
+impl LoadShed {
+    #[inline(always)]
+    ///Creates plugin with specified limits.
+    pub fn with_config(config: LoadShedConfig) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
+            config,
+        }
+    }
+}
+
+impl Plugin for LoadShed {
+    type Config = LoadShedConfig;
+
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let result = match config.max_in_flight {
+            0 => Err("max_in_flight must be greater than 0".into()),
+            _ => Ok(Self::with_config(config)),
+        };
+        Box::pin(ready(result))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let max_in_flight = self.config.max_in_flight.max(1);
+        LoadShedService {
+            //Requests are queued by semaphore, so buffer only needs to fit requests in flight.
+            inner: Buffer::new(service, max_in_flight),
+            permits: self.permits.clone(),
+            queue_timeout: Duration::from_millis(self.config.queue_timeout_ms),
+            retry_after: Duration::from_millis(self.config.retry_after_ms),
+        }
+        .boxed()
+    }
+}
+
+struct LoadShedService {
+    inner: Buffer<BoxService<RouterRequest, RouterResponse, BoxError>, RouterRequest>,
+    permits: Arc<Semaphore>,
+    queue_timeout: Duration,
+    retry_after: Duration,
+}
+
+impl tower::Service<RouterRequest> for LoadShedService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Capacity is checked on call, so that excess requests can be rejected instead of waiting.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let permits = self.permits.clone();
+        let queue_timeout = self.queue_timeout;
+        let retry_after = self.retry_after;
+
+        Box::pin(async move {
+            let permit = match permits.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) if queue_timeout.is_zero() => None,
+                Err(_) => match tokio::time::timeout(queue_timeout, permits.acquire_owned()).await {
+                    Ok(Ok(permit)) => Some(permit),
+                    _ => None,
+                },
+            };
+
+            match permit {
+                Some(_permit) => inner.oneshot(req).await,
+                None => {
+                    tracing::warn!("Request rejected due to overload");
+                    let mut response = router_error(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "Server is overloaded, try again later",
+                        "SERVER_OVERLOADED",
+                        req.context,
+                    );
+                    set_retry_hint(&mut response, retry_after);
+                    Ok(response)
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,219 @@
+use apollo_router_core::{Plugin, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderMap, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use core::future::{ready, Future};
+use core::hash::{Hash, Hasher};
+use core::pin::Pin;
+use core::task;
+use std::collections::hash_map::DefaultHasher;
+use std::sync::Arc;
+use std::time::Instant;
+
+const REDACTED: &str = "<redacted>";
+//Matches every variable or header.
+pub(super) const ANY: &str = "*";
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///Replacement of redacted values
+pub enum Redaction {
+    ///Value is replaced with `<redacted>`.
+    Mask,
+    ///Value is replaced with its hash, so equal values can still be correlated.
+    Hash,
+}
+
+impl Default for Redaction {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Mask
+    }
+}
+
+impl Redaction {
+    pub(super) fn apply(self, value: &str) -> String {
+        match self {
+            Self::Mask => REDACTED.to_owned(),
+            Self::Hash => {
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                format!("hash:{:016x}", hasher.finish())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Subgraph request logging config
+pub struct SubgraphLoggingConfig {
+    #[serde(default)]
+    ///Names of subgraphs to log, all subgraphs are logged if empty.
+    pub subgraphs: Vec<String>,
+    #[serde(default)]
+    ///Names of variables, which values are redacted, or `*` to redact all.
+    pub redact_variables: Vec<String>,
+    #[serde(default)]
+    ///Names of headers, which values are redacted, or `*` to redact all.
+    ///
+    ///Credential headers are always redacted.
+    pub redact_headers: Vec<String>,
+    #[serde(default)]
+    ///How redacted values are replaced.
+    pub redaction: Redaction,
+    #[serde(default)]
+    ///Logs request headers.
+    pub log_headers: bool,
+}
+
+impl SubgraphLoggingConfig {
+    fn is_redacted_variable(&self, name: &str) -> bool {
+        self.redact_variables.iter().any(|redacted| redacted == ANY || redacted == name)
+    }
+
+    fn is_redacted_header(&self, name: &str) -> bool {
+        [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE].iter().any(|header| header == name)
+            || self
+                .redact_headers
+                .iter()
+                .any(|redacted| redacted == ANY || redacted.eq_ignore_ascii_case(name))
+    }
+
+    fn variables(&self, req: &SubgraphRequest) -> serde_json::Map<String, serde_json::Value> {
+        req.subgraph_request
+            .body()
+            .variables
+            .iter()
+            .map(|(name, value)| {
+                let name = name.as_str();
+                let value = serde_json_bytes::from_value::<serde_json::Value>(value.clone()).unwrap_or_default();
+                let value = match self.is_redacted_variable(name) {
+                    true => serde_json::Value::String(self.redaction.apply(&value.to_string())),
+                    false => value,
+                };
+                (name.to_owned(), value)
+            })
+            .collect()
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> serde_json::Map<String, serde_json::Value> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes());
+                let value = match self.is_redacted_header(name.as_str()) {
+                    true => self.redaction.apply(&value),
+                    false => value.into_owned(),
+                };
+                (name.as_str().to_owned(), serde_json::Value::String(value))
+            })
+            .collect()
+    }
+}
+
+///Logs outgoing subgraph operations, redacting sensitive variables and headers.
+pub struct SubgraphLogging {
+    config: Arc<SubgraphLoggingConfig>,
+}
+
+impl SubgraphLogging {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: SubgraphLoggingConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for SubgraphLogging {
+    type Config = SubgraphLoggingConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        if !self.config.subgraphs.is_empty() && !self.config.subgraphs.iter().any(|subgraph| subgraph == name) {
+            return service;
+        }
+
+        SubgraphLoggingService {
+            inner: service,
+            name: name.into(),
+            config: self.config.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct SubgraphLoggingService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    name: Arc<str>,
+    config: Arc<SubgraphLoggingConfig>,
+}
+
+impl tower::Service<SubgraphRequest> for SubgraphLoggingService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let body = req.subgraph_request.body();
+        let operation_name = body.operation_name.as_deref().unwrap_or("<anonymous>");
+        let variables = serde_json::Value::Object(self.config.variables(&req));
+        match self.config.log_headers {
+            true => {
+                let headers = serde_json::Value::Object(self.config.headers(req.subgraph_request.headers()));
+                tracing::info!(
+                    "{}: Request '{}' query={:?} variables={} headers={}",
+                    self.name,
+                    operation_name,
+                    body.query.as_deref().unwrap_or_default(),
+                    variables,
+                    headers
+                );
+            }
+            false => tracing::info!(
+                "{}: Request '{}' query={:?} variables={}",
+                self.name,
+                operation_name,
+                body.query.as_deref().unwrap_or_default(),
+                variables
+            ),
+        }
+
+        let name = self.name.clone();
+        let started = Instant::now();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            let elapsed = started.elapsed().as_millis();
+            match result.as_ref() {
+                Ok(response) => {
+                    let errors = response.response.body().errors.len();
+                    tracing::info!("{}: Response in {}ms with {} errors", name, elapsed, errors);
+                }
+                Err(error) => tracing::info!("{}: Failed in {}ms: {}", name, elapsed, error),
+            }
+            result
+        })
+    }
+}
@@ -0,0 +1,164 @@
+use apollo_router_core::{Context, Plugin, RouterRequest, RouterResponse};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::BoxError;
+
+use crate::error::{router_error, set_retry_hint};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::time::Duration;
+use std::sync::{Arc, RwLock};
+
+fn default_message() -> String {
+    "Service is under maintenance".to_owned()
+}
+
+fn default_code() -> String {
+    "MAINTENANCE".to_owned()
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+///Maintenance response settings
+pub struct MaintenanceMode {
+    #[serde(default = "default_message")]
+    ///Error message.
+    pub message: String,
+    #[serde(default = "default_code")]
+    ///Error code, set in `extensions.code`.
+    pub code: String,
+    #[serde(default)]
+    ///Value of `Retry-After` header in seconds, also set as `retryAfterMs` extension.
+    pub retry_after_secs: Option<u64>,
+    #[serde(default)]
+    ///Names of affected operations, all operations are affected if empty.
+    pub operations: Vec<String>,
+}
+
+impl Default for MaintenanceMode {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            message: default_message(),
+            code: default_code(),
+            retry_after_secs: None,
+            operations: Vec::new(),
+        }
+    }
+}
+
+impl MaintenanceMode {
+    #[inline]
+    ///Returns whether operation is affected by maintenance.
+    pub fn is_affected(&self, operation_name: Option<&str>) -> bool {
+        super::is_operation_listed(&self.operations, operation_name)
+    }
+}
+
+#[derive(Clone, Default)]
+///Switch of maintenance mode, which can be toggled at runtime.
+///
+///Can be used as plugin, with all clones sharing the same state.
+pub struct Maintenance {
+    mode: Arc<RwLock<Option<MaintenanceMode>>>,
+}
+
+impl Maintenance {
+    #[inline(always)]
+    ///Creates switch with maintenance disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Enables maintenance, affecting subsequent requests.
+    pub fn enable(&self, mode: MaintenanceMode) {
+        match self.mode.write() {
+            Ok(mut current) => *current = Some(mode),
+            Err(error) => *error.into_inner() = Some(mode),
+        }
+    }
+
+    #[inline]
+    ///Disables maintenance.
+    pub fn disable(&self) {
+        match self.mode.write() {
+            Ok(mut current) => *current = None,
+            Err(error) => *error.into_inner() = None,
+        }
+    }
+
+    #[inline]
+    ///Returns maintenance settings, if it is enabled.
+    pub fn current(&self) -> Option<MaintenanceMode> {
+        match self.mode.read() {
+            Ok(current) => current.clone(),
+            Err(error) => error.into_inner().clone(),
+        }
+    }
+
+    #[inline]
+    ///Returns whether maintenance is enabled.
+    pub fn is_enabled(&self) -> bool {
+        match self.mode.read() {
+            Ok(current) => current.is_some(),
+            Err(error) => error.into_inner().is_some(),
+        }
+    }
+
+    fn reject(&self, operation_name: Option<&str>, context: Context) -> Option<RouterResponse> {
+        let current = match self.mode.read() {
+            Ok(current) => current,
+            Err(error) => error.into_inner(),
+        };
+        let mode = current.as_ref().filter(|mode| mode.is_affected(operation_name))?;
+
+        let mut response = router_error(StatusCode::SERVICE_UNAVAILABLE, &mode.message, &mode.code, context);
+        if let Some(retry_after_secs) = mode.retry_after_secs {
+            set_retry_hint(&mut response, Duration::from_secs(retry_after_secs));
+        }
+        Some(response)
+    }
+
+    ///Returns response to any request, if maintenance affects all operations.
+    ///
+    ///Used to reject requests before they are parsed.
+    pub(crate) fn check_all(&self) -> Option<RouterResponse> {
+        self.reject(None, Context::new())
+    }
+
+    pub(crate) fn check(&self, req: RouterRequest) -> Result<RouterRequest, RouterResponse> {
+        let operation_name = req.originating_request.body().operation_name.as_deref();
+        match self.reject(operation_name, req.context.clone()) {
+            Some(response) => Err(response),
+            None => Ok(req),
+        }
+    }
+}
+
+impl Plugin for Maintenance {
+    type Config = Option<MaintenanceMode>;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let maintenance = Self::new();
+        if let Some(mode) = config {
+            maintenance.enable(mode);
+        }
+        Box::pin(ready(Ok(maintenance)))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let maintenance = self.clone();
+        super::checkpoint(service, move |req| maintenance.check(req))
+    }
+}
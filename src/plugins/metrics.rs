@@ -0,0 +1,226 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Metrics config
+pub struct MetricsConfig {}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency_us: AtomicU64,
+}
+
+impl Counters {
+    #[inline]
+    pub(crate) fn record(&self, latency: Duration, is_ok: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if !is_ok {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_us.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            total_latency: Duration::from_micros(self.latency_us.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+///Counters at the moment of snapshot
+pub struct MetricsSnapshot {
+    ///Number of completed requests.
+    pub requests: u64,
+    ///Number of failed requests.
+    ///
+    ///For router it includes responses with non-success status.
+    pub errors: u64,
+    ///Sum of latencies of all completed requests.
+    pub total_latency: Duration,
+}
+
+impl MetricsSnapshot {
+    #[inline]
+    ///Returns average latency of completed requests.
+    pub fn average_latency(&self) -> Duration {
+        match self.requests {
+            0 => Duration::ZERO,
+            requests => Duration::from_nanos((self.total_latency.as_nanos() / requests as u128) as u64),
+        }
+    }
+
+    ///Returns JSON representation of metrics.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "requests": self.requests,
+            "errors": self.errors,
+            "average_latency_ms": self.average_latency().as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+///Basic request metrics of router and its subgraphs.
+///
+///Can be used as plugin, with all clones sharing the same counters.
+pub struct Metrics {
+    router: Arc<Counters>,
+    subgraphs: Arc<RwLock<BTreeMap<String, Arc<Counters>>>>,
+}
+
+impl Metrics {
+    #[inline(always)]
+    ///Creates new metrics with all counters set to zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    ///Creates new metrics according to `config`, with all counters set to zero.
+    pub fn with_config(_config: MetricsConfig) -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    ///Returns router metrics.
+    pub fn router(&self) -> MetricsSnapshot {
+        self.router.snapshot()
+    }
+
+    #[inline]
+    ///Returns metrics of subgraph, if it was built with these metrics.
+    pub fn subgraph(&self, name: &str) -> Option<MetricsSnapshot> {
+        let subgraphs = match self.subgraphs.read() {
+            Ok(subgraphs) => subgraphs,
+            Err(error) => error.into_inner(),
+        };
+        subgraphs.get(name).map(|counters| counters.snapshot())
+    }
+
+    ///Returns metrics of all subgraphs.
+    pub fn subgraphs(&self) -> BTreeMap<String, MetricsSnapshot> {
+        let subgraphs = match self.subgraphs.read() {
+            Ok(subgraphs) => subgraphs,
+            Err(error) => error.into_inner(),
+        };
+        subgraphs
+            .iter()
+            .map(|(name, counters)| (name.clone(), counters.snapshot()))
+            .collect()
+    }
+
+    fn subgraph_counters(&self, name: &str) -> Arc<Counters> {
+        let mut subgraphs = match self.subgraphs.write() {
+            Ok(subgraphs) => subgraphs,
+            Err(error) => error.into_inner(),
+        };
+        subgraphs.entry(name.to_owned()).or_default().clone()
+    }
+
+    ///Returns JSON representation of all metrics.
+    pub fn to_json(&self) -> serde_json::Value {
+        let subgraphs = self
+            .subgraphs()
+            .into_iter()
+            .map(|(name, snapshot)| (name, snapshot.to_json()))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::json!({
+            "router": self.router().to_json(),
+            "subgraphs": subgraphs,
+        })
+    }
+}
+
+impl Plugin for Metrics {
+    type Config = MetricsConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        MetricsService {
+            inner: service,
+            counters: self.router.clone(),
+            is_ok: |result: &Result<RouterResponse, BoxError>| match result {
+                Ok(response) => response.response.status().is_success(),
+                Err(_) => false,
+            },
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        MetricsService {
+            inner: service,
+            counters: self.subgraph_counters(name),
+            is_ok: |result: &Result<SubgraphResponse, BoxError>| result.is_ok(),
+        }
+        .boxed()
+    }
+}
+
+struct MetricsService<S, Res> {
+    inner: S,
+    counters: Arc<Counters>,
+    is_ok: fn(&Result<Res, BoxError>) -> bool,
+}
+
+impl<Req, Res, S> tower::Service<Req> for MetricsService<S, Res>
+where
+    S: tower::Service<Req, Response = Res, Error = BoxError>,
+    S::Future: Send + 'static,
+    Res: Send + 'static,
+{
+    type Response = Res;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Res, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: Req) -> Self::Future {
+        let started = Instant::now();
+        let counters = self.counters.clone();
+        let is_ok = self.is_ok;
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            counters.record(started.elapsed(), is_ok(&result));
+            result
+        })
+    }
+}
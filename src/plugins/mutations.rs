@@ -0,0 +1,383 @@
+use apollo_router_core::{
+    Plugin, QueryPlannerRequest, QueryPlannerResponse, RouterRequest, RouterResponse, SubgraphRequest,
+    SubgraphResponse,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+use crate::parser::is_mutation;
+use crate::plan::QueryPlan;
+use crate::GraphqlRequest;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+//Context key, which holds id of mutation request.
+const MUTATION_ORDERING_ID: &str = "graphql_router::mutation_ordering_id";
+const BUFFER_SIZE: usize = 1024;
+
+type FetchService = Buffer<BoxService<SubgraphRequest, SubgraphResponse, BoxError>, SubgraphRequest>;
+type Prefetch = JoinHandle<Result<SubgraphResponse, BoxError>>;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///Execution of root mutation fields, resolved by different fetches
+pub enum MutationMode {
+    ///Fetches run one after another, in order of fields, as required by GraphQL specification.
+    Serial,
+    ///All fetches start together with the first one, so later fields don't observe side effects of earlier ones
+    ///and keep running, even if earlier ones fail.
+    Parallel,
+}
+
+impl Default for MutationMode {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Serial
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Mutation ordering config
+pub struct MutationOrderingConfig {
+    #[serde(default)]
+    ///Execution of root mutation fields.
+    pub mode: MutationMode,
+}
+
+///Root fetch of mutation, as planned by query planner.
+#[derive(Debug, Clone)]
+struct RootFetch {
+    service: String,
+    operation: String,
+    variables: Vec<String>,
+}
+
+impl RootFetch {
+    ///Returns root fetches of `plan`, if it is sequence of them.
+    fn from_plan(plan: &QueryPlan) -> Vec<Self> {
+        let plan = plan.as_json();
+        let root = plan.get("root").unwrap_or(plan);
+        let nodes = match (root.get("kind").and_then(serde_json::Value::as_str), root.get("nodes")) {
+            (Some("Sequence"), Some(serde_json::Value::Array(nodes))) => nodes,
+            _ => return Vec::new(),
+        };
+
+        let mut fetches = Vec::new();
+        for node in nodes {
+            let is_root = node.get("kind").and_then(serde_json::Value::as_str) == Some("Fetch")
+                && node.get("requires").map_or(true, serde_json::Value::is_null);
+            let service = node.get("serviceName").and_then(serde_json::Value::as_str);
+            let operation = node.get("operation").and_then(serde_json::Value::as_str);
+            match (service, operation) {
+                (Some(service), Some(operation)) if is_root && is_mutation(operation, None) => {
+                    fetches.push(Self {
+                        service: service.to_owned(),
+                        operation: operation.to_owned(),
+                        variables: node
+                            .get("variableUsages")
+                            .and_then(serde_json::Value::as_array)
+                            .map(|usages| {
+                                usages
+                                    .iter()
+                                    .filter_map(serde_json::Value::as_str)
+                                    .map(str::to_owned)
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    });
+                }
+                //Entity fetches, that follow mutation, depend on its result.
+                _ => (),
+            }
+        }
+        fetches
+    }
+
+    ///Returns request of fetch, made on behalf of `req`, which is fetch of the same mutation.
+    fn request(&self, req: &SubgraphRequest) -> Result<SubgraphRequest, BoxError> {
+        let variables = &req.originating_request.body().variables;
+        let mut fetch_variables = serde_json::Map::new();
+        for name in self.variables.iter() {
+            if let Some(value) = variables.get(name.as_str()) {
+                fetch_variables.insert(name.clone(), serde_json_bytes::from_value(value.clone())?);
+            }
+        }
+        let body = serde_json::json!({
+            "query": self.operation,
+            "variables": fetch_variables,
+        });
+        let graphql = GraphqlRequest::from_bytes(crate::pool::serialize(&body)?)?;
+        let (mut parts, _) = http::Request::builder()
+            .method(req.subgraph_request.method().clone())
+            .uri(req.subgraph_request.uri().clone())
+            .version(req.subgraph_request.version())
+            .body(())?
+            .into_parts();
+        parts.headers = req.subgraph_request.headers().clone();
+        Ok(SubgraphRequest {
+            originating_request: req.originating_request.clone(),
+            subgraph_request: apollo_router_core::http_compat::Request::from_parts(parts, graphql),
+            context: req.context.clone(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct MutationState {
+    serial: Arc<Mutex<()>>,
+    fetches: Vec<RootFetch>,
+    is_dispatched: bool,
+    prefetched: HashMap<(String, String), Prefetch>,
+}
+
+///Guarantees execution order of root mutation fields, resolved by different fetches.
+///
+///In serial mode, which is default, root mutation fetches of request never overlap, even if other plugins split
+///or route them. Parallel mode is opt-in, for mutations whose fields are independent of each other.
+///
+///Fetches started ahead of router are made with headers of the first fetch, so plugins applied before this one
+///only see fetches made by router itself.
+pub struct MutationOrdering {
+    config: MutationOrderingConfig,
+    services: Arc<std::sync::RwLock<HashMap<String, FetchService>>>,
+    ongoing: Ongoing<MutationState>,
+}
+
+impl MutationOrdering {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: MutationOrderingConfig) -> Self {
+        Self {
+            config,
+            services: Arc::default(),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for MutationOrdering {
+    type Config = MutationOrderingConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        MutationRouterService {
+            inner: service,
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn query_planning_service(
+        &mut self,
+        service: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ) -> BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError> {
+        if self.config.mode == MutationMode::Serial {
+            return service;
+        }
+        let ongoing = self.ongoing.clone();
+        service
+            .map_response(move |response: QueryPlannerResponse| {
+                if let Ok(Some(id)) = response.context.get::<_, u64>(MUTATION_ORDERING_ID) {
+                    if let Some(plan) = QueryPlan::from_planner(&response) {
+                        let fetches = RootFetch::from_plan(&plan);
+                        ongoing.update(id, |state| state.fetches = fetches);
+                    }
+                }
+                response
+            })
+            .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let service = Buffer::new(service, BUFFER_SIZE);
+        let mut services = match self.services.write() {
+            Ok(services) => services,
+            Err(error) => error.into_inner(),
+        };
+        services.insert(name.to_owned(), service.clone());
+        MutationFetchService {
+            name: name.into(),
+            inner: service,
+            mode: self.config.mode,
+            services: self.services.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct MutationRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ongoing: Ongoing<MutationState>,
+}
+
+impl tower::Service<RouterRequest> for MutationRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let body = req.originating_request.body();
+        let is_mutation = match body.query.as_deref() {
+            Some(query) => is_mutation(query, body.operation_name.as_deref()),
+            None => false,
+        };
+        if !is_mutation {
+            return self.inner.call(req);
+        }
+
+        let id = self.ongoing.start(MutationState::default());
+        if let Err(error) = req.context.insert(MUTATION_ORDERING_ID, id) {
+            //Query planner still orders mutation fields on its own.
+            tracing::warn!("Unable to track mutation ordering: {}", error);
+            self.ongoing.finish(id);
+            return self.inner.call(req);
+        }
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            ongoing.finish(id);
+            result
+        })
+    }
+}
+
+struct MutationFetchService {
+    name: Arc<str>,
+    inner: FetchService,
+    mode: MutationMode,
+    services: Arc<std::sync::RwLock<HashMap<String, FetchService>>>,
+    ongoing: Ongoing<MutationState>,
+}
+
+impl MutationFetchService {
+    ///Starts fetches, that follow `req` in plan, if they are not started yet.
+    fn dispatch(&self, id: u64, req: &SubgraphRequest, operation: &str) {
+        let mut pending = Vec::new();
+        self.ongoing.update(id, |state| {
+            if state.is_dispatched {
+                return;
+            }
+            state.is_dispatched = true;
+            let position = state
+                .fetches
+                .iter()
+                .position(|fetch| *fetch.service == *self.name && fetch.operation == operation);
+            if let Some(position) = position {
+                pending.extend(state.fetches[position + 1..].iter().cloned());
+            }
+        });
+        if pending.is_empty() {
+            return;
+        }
+
+        let services = match self.services.read() {
+            Ok(services) => services,
+            Err(error) => error.into_inner(),
+        };
+        let mut prefetched = Vec::with_capacity(pending.len());
+        for fetch in pending {
+            let (service, request) = match (services.get(&fetch.service), fetch.request(req)) {
+                (Some(service), Ok(request)) => (service.clone(), request),
+                (None, _) => {
+                    tracing::warn!("{}: Unknown subgraph '{}' of mutation", self.name, fetch.service);
+                    continue;
+                }
+                (_, Err(error)) => {
+                    tracing::warn!("{}: Unable to start mutation fetch early: {}", fetch.service, error);
+                    continue;
+                }
+            };
+            let handle = tokio::spawn(service.oneshot(request));
+            prefetched.push(((fetch.service, fetch.operation), handle));
+        }
+        self.ongoing.update(id, |state| state.prefetched.extend(prefetched));
+    }
+}
+
+impl tower::Service<SubgraphRequest> for MutationFetchService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Readiness is awaited once fetch is allowed to run.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(MUTATION_ORDERING_ID) {
+            Ok(Some(id)) => id,
+            _ => return Box::pin(self.inner.clone().oneshot(req)),
+        };
+        let operation = match req.subgraph_request.body().query.as_deref() {
+            Some(query) if is_mutation(query, None) => query.to_owned(),
+            //Entity fetches are ordered by query planner, as they depend on results of mutation fields.
+            _ => return Box::pin(self.inner.clone().oneshot(req)),
+        };
+
+        match self.mode {
+            MutationMode::Serial => {
+                let mut serial = None;
+                self.ongoing.update(id, |state| serial = Some(state.serial.clone()));
+                let inner = self.inner.clone();
+                Box::pin(async move {
+                    let _guard = match serial {
+                        Some(serial) => Some(serial.lock_owned().await),
+                        None => None,
+                    };
+                    inner.oneshot(req).await
+                })
+            }
+            MutationMode::Parallel => {
+                let mut prefetch = None;
+                let key = (self.name.to_string(), operation);
+                self.ongoing.update(id, |state| prefetch = state.prefetched.remove(&key));
+                if let Some(prefetch) = prefetch {
+                    return Box::pin(async move {
+                        match prefetch.await {
+                            Ok(result) => result,
+                            Err(error) => Err(error.into()),
+                        }
+                    });
+                }
+
+                self.dispatch(id, &req, &key.1);
+                Box::pin(self.inner.clone().oneshot(req))
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use apollo_router_core::{Plugin, Schema, SubgraphRequest, SubgraphResponse};
+use async_graphql::parser::types::{
+    BaseType, DocumentOperations, ExecutableDocument, OperationType, Selection, SelectionSet, Type, TypeKind,
+    TypeSystemDefinition,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///Handling of `null` in non-null field of subgraph data
+pub enum NullabilityPolicy {
+    ///`null` bubbles to the nearest nullable ancestor, as specified by GraphQL.
+    Bubble,
+    ///`null` is replaced with placeholder of field type, if there is one, otherwise it bubbles.
+    Placeholder,
+}
+
+impl Default for NullabilityPolicy {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Bubble
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Nullability config
+pub struct NullabilityConfig {
+    #[serde(default)]
+    ///Handling of non-null violations.
+    pub policy: NullabilityPolicy,
+    #[serde(default)]
+    ///Placeholders per type name, overriding defaults.
+    ///
+    ///Defaults are `""` for `String` and `ID`, `0` for `Int` and `Float`, `false` for `Boolean` and `[]` for lists.
+    pub placeholders: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Default)]
+struct SchemaTypes {
+    fields: HashMap<String, HashMap<String, Type>>,
+    //Object and interface names mapped to names of interfaces and unions they belong to.
+    supertypes: HashMap<String, Vec<String>>,
+    query: String,
+    mutation: String,
+    subscription: String,
+}
+
+impl SchemaTypes {
+    fn parse(sdl: &str) -> Result<Self, async_graphql::parser::Error> {
+        let document = async_graphql::parser::parse_schema(sdl)?;
+        let mut types = Self {
+            query: "Query".to_owned(),
+            mutation: "Mutation".to_owned(),
+            subscription: "Subscription".to_owned(),
+            ..Self::default()
+        };
+
+        for definition in document.definitions {
+            match definition {
+                TypeSystemDefinition::Schema(schema) => {
+                    let schema = schema.node;
+                    if let Some(query) = schema.query {
+                        types.query = query.node.to_string();
+                    }
+                    if let Some(mutation) = schema.mutation {
+                        types.mutation = mutation.node.to_string();
+                    }
+                    if let Some(subscription) = schema.subscription {
+                        types.subscription = subscription.node.to_string();
+                    }
+                }
+                TypeSystemDefinition::Type(definition) => {
+                    let definition = definition.node;
+                    let name = definition.name.node.to_string();
+                    let (implements, fields) = match definition.kind {
+                        TypeKind::Object(object) => (object.implements, object.fields),
+                        TypeKind::Interface(interface) => (interface.implements, interface.fields),
+                        TypeKind::Union(union) => {
+                            for member in union.members {
+                                types.supertypes.entry(member.node.to_string()).or_default().push(name.clone());
+                            }
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    let supertypes = types.supertypes.entry(name.clone()).or_default();
+                    supertypes.extend(implements.into_iter().map(|interface| interface.node.to_string()));
+                    types.fields.entry(name).or_default().extend(
+                        fields
+                            .into_iter()
+                            .map(|field| (field.node.name.node.to_string(), field.node.ty.node)),
+                    );
+                }
+                TypeSystemDefinition::Directive(_) => (),
+            }
+        }
+
+        Ok(types)
+    }
+
+    #[inline]
+    fn field(&self, type_name: &str, field: &str) -> Option<&Type> {
+        self.fields.get(type_name).and_then(|fields| fields.get(field))
+    }
+
+    #[inline]
+    fn is_subtype(&self, type_name: &str, condition: &str) -> bool {
+        type_name == condition
+            || self
+                .supertypes
+                .get(type_name)
+                .map_or(false, |supertypes| supertypes.iter().any(|supertype| supertype == condition))
+    }
+}
+
+struct Placeholders<'a> {
+    types: &'a SchemaTypes,
+    config: &'a NullabilityConfig,
+    document: &'a ExecutableDocument,
+}
+
+impl Placeholders<'_> {
+    fn placeholder(&self, ty: &Type) -> Option<serde_json::Value> {
+        match &ty.base {
+            BaseType::List(_) => Some(serde_json::Value::Array(Vec::new())),
+            BaseType::Named(name) => match self.config.placeholders.get(name.as_str()) {
+                Some(placeholder) => Some(placeholder.clone()),
+                None => match name.as_str() {
+                    "String" | "ID" => Some("".into()),
+                    "Int" | "Float" => Some(0.into()),
+                    "Boolean" => Some(false.into()),
+                    _ => None,
+                },
+            },
+        }
+    }
+
+    fn fill(&self, value: &mut serde_json::Value, ty: &Type, selection_set: &SelectionSet) {
+        if value.is_null() {
+            if !ty.nullable {
+                if let Some(placeholder) = self.placeholder(ty) {
+                    *value = placeholder;
+                }
+            }
+            return;
+        }
+
+        match (&ty.base, value) {
+            (BaseType::List(item), serde_json::Value::Array(values)) => {
+                for value in values.iter_mut() {
+                    self.fill(value, item, selection_set);
+                }
+            }
+            (BaseType::Named(name), serde_json::Value::Object(object)) => {
+                self.fill_object(object, name.as_str(), selection_set);
+            }
+            _ => (),
+        }
+    }
+
+    fn fill_fragment(
+        &self,
+        object: &mut serde_json::Map<String, serde_json::Value>,
+        type_name: &str,
+        condition: Option<&str>,
+        selection_set: &SelectionSet,
+    ) {
+        match condition {
+            None => self.fill_object(object, type_name, selection_set),
+            //Type of entities is only known from fragment.
+            Some(condition) if type_name.is_empty() => self.fill_object(object, condition, selection_set),
+            Some(condition) if self.types.is_subtype(type_name, condition) => {
+                self.fill_object(object, type_name, selection_set)
+            }
+            Some(_) => (),
+        }
+    }
+
+    fn fill_object(
+        &self,
+        object: &mut serde_json::Map<String, serde_json::Value>,
+        type_name: &str,
+        selection_set: &SelectionSet,
+    ) {
+        //Field's type may be interface, while object knows its concrete type.
+        let typename = object.get("__typename").and_then(|value| value.as_str()).map(str::to_owned);
+        let type_name = typename.as_deref().unwrap_or(type_name);
+
+        for selection in selection_set.items.iter() {
+            match &selection.node {
+                Selection::Field(field) => {
+                    let field = &field.node;
+                    let name = field.name.node.as_str();
+                    let key = field.response_key().node.as_str();
+                    if name == "_entities" {
+                        if let Some(serde_json::Value::Array(entities)) = object.get_mut(key) {
+                            for entity in entities.iter_mut() {
+                                if let serde_json::Value::Object(entity) = entity {
+                                    self.fill_object(entity, "", &field.selection_set.node);
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    let ty = match self.types.field(type_name, name) {
+                        Some(ty) => ty,
+                        None => continue,
+                    };
+                    if let Some(value) = object.get_mut(key) {
+                        self.fill(value, ty, &field.selection_set.node);
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let fragment = &fragment.node;
+                    let condition = fragment.type_condition.as_ref().map(|condition| condition.node.on.node.as_str());
+                    self.fill_fragment(object, type_name, condition, &fragment.selection_set.node);
+                }
+                Selection::FragmentSpread(spread) => {
+                    if let Some(fragment) = self.document.fragments.get(&spread.node.fragment_name.node) {
+                        let fragment = &fragment.node;
+                        let condition = fragment.type_condition.node.on.node.as_str();
+                        self.fill_fragment(object, type_name, Some(condition), &fragment.selection_set.node);
+                    }
+                }
+            }
+        }
+    }
+
+    fn fill_data(&self, data: &mut serde_json::Value, operation_name: Option<&str>) {
+        let operation = match &self.document.operations {
+            DocumentOperations::Single(operation) => &operation.node,
+            DocumentOperations::Multiple(operations) => {
+                match operation_name.and_then(|name| operations.iter().find(|(key, _)| key.as_str() == name)) {
+                    Some((_, operation)) => &operation.node,
+                    None => return,
+                }
+            }
+        };
+        let root = match operation.ty {
+            OperationType::Query => self.types.query.as_str(),
+            OperationType::Mutation => self.types.mutation.as_str(),
+            OperationType::Subscription => self.types.subscription.as_str(),
+        };
+        if let serde_json::Value::Object(data) = data {
+            self.fill_object(data, root, &operation.selection_set.node);
+        }
+    }
+}
+
+///Controls whether non-null violations in subgraph data bubble to nullable ancestor or are replaced with
+///placeholders, for clients preferring maximal data.
+pub struct Nullability {
+    types: Option<Arc<SchemaTypes>>,
+    config: Arc<NullabilityConfig>,
+}
+
+impl Nullability {
+    ///Creates plugin with specified config, taking types of fields from `schema`.
+    ///
+    ///If `schema` cannot be parsed, non-null violations always bubble.
+    pub fn with_config(schema: &Schema, config: NullabilityConfig) -> Self {
+        let types = match config.policy {
+            NullabilityPolicy::Bubble => None,
+            NullabilityPolicy::Placeholder => match SchemaTypes::parse(schema.as_str()) {
+                Ok(types) => Some(Arc::new(types)),
+                Err(error) => {
+                    tracing::error!("Unable to parse schema, placeholders are disabled: {}", error);
+                    None
+                }
+            },
+        };
+        Self {
+            types,
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for Nullability {
+    type Config = NullabilityConfig;
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        //Schema is not available to plugins created from config.
+        let error = "Nullability requires schema, use GraphqlRouterBuilder::nullability";
+        Box::pin(ready(Err(error.into())))
+    }
+
+    fn subgraph_service(
+        &mut self,
+        _: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        match self.types.as_ref() {
+            Some(types) => NullabilityService {
+                inner: service,
+                types: types.clone(),
+                config: self.config.clone(),
+            }
+            .boxed(),
+            None => service,
+        }
+    }
+}
+
+struct NullabilityService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    types: Arc<SchemaTypes>,
+    config: Arc<NullabilityConfig>,
+}
+
+impl tower::Service<SubgraphRequest> for NullabilityService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let body = req.subgraph_request.body();
+        let document = match body.query.as_deref().map(async_graphql::parser::parse_query) {
+            Some(Ok(document)) => document,
+            _ => return self.inner.call(req),
+        };
+        let operation_name = body.operation_name.clone();
+
+        let types = self.types.clone();
+        let config = self.config.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            let body = response.response.body_mut();
+            let mut data = match serde_json::to_value(&body.data) {
+                Ok(data) => data,
+                Err(_) => return Ok(response),
+            };
+            let placeholders = Placeholders {
+                types: &types,
+                config: &config,
+                document: &document,
+            };
+            placeholders.fill_data(&mut data, operation_name.as_deref());
+            match serde_json::from_value(data) {
+                Ok(data) => body.data = data,
+                Err(error) => tracing::warn!("Unable to set placeholders: {}", error),
+            }
+            Ok(response)
+        })
+    }
+}
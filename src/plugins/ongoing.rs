@@ -0,0 +1,72 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+struct Inner<T> {
+    next_id: AtomicU64,
+    entries: Mutex<HashMap<u64, T>>,
+}
+
+///State of ongoing requests, keyed by id stored within request context.
+pub(crate) struct Ongoing<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Ongoing<T> {
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, T>> {
+        match self.inner.entries.lock() {
+            Ok(entries) => entries,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    ///Starts tracking request with initial `state`, returning its id.
+    pub(crate) fn start(&self, state: T) -> u64 {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, state);
+        id
+    }
+
+    ///Updates state of request `id`, if it is still tracked.
+    pub(crate) fn update<F: FnOnce(&mut T)>(&self, id: u64, update: F) {
+        if let Some(state) = self.lock().get_mut(&id) {
+            update(state);
+        }
+    }
+
+    #[inline(always)]
+    ///Returns copy of request `id` state, if it is still tracked.
+    pub(crate) fn get(&self, id: u64) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.lock().get(&id).cloned()
+    }
+
+    #[inline(always)]
+    ///Stops tracking request `id`, returning its state.
+    pub(crate) fn finish(&self, id: u64) -> Option<T> {
+        self.lock().remove(&id)
+    }
+}
+
+impl<T> Clone for Ongoing<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Default for Ongoing<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                next_id: AtomicU64::new(0),
+                entries: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
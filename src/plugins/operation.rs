@@ -0,0 +1,240 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse};
+use async_graphql::parser::types::{
+    Directive, DocumentOperations, ExecutableDocument, OperationDefinition, Selection, SelectionSet,
+};
+use async_graphql::parser::Positioned;
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::BoxError;
+
+use crate::error::router_error;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use std::collections::HashSet;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Operation limits config
+pub struct OperationLimitsConfig {
+    #[serde(default)]
+    ///Maximum number of aliased fields in document.
+    pub max_aliases: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of fields repeating already selected field within the same selection set.
+    pub max_duplicate_fields: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of directives in document.
+    pub max_directives: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of directives on single field, fragment or operation, e.g. chain of `@include`.
+    pub max_directives_per_location: Option<usize>,
+    #[serde(default)]
+    ///Maximum number of nested selections with directives.
+    pub max_directive_depth: Option<usize>,
+    #[serde(default)]
+    ///Specifies whether anonymous operations are rejected.
+    pub require_named_operations: bool,
+    #[serde(default)]
+    ///Specifies whether `operationName` is required for documents with multiple operations.
+    pub require_operation_name: bool,
+}
+
+impl OperationLimitsConfig {
+    #[inline(always)]
+    fn is_enabled(&self) -> bool {
+        self.max_aliases.is_some()
+            || self.max_duplicate_fields.is_some()
+            || self.max_directives.is_some()
+            || self.max_directives_per_location.is_some()
+            || self.max_directive_depth.is_some()
+            || self.require_named_operations
+            || self.require_operation_name
+    }
+}
+
+#[derive(Default)]
+struct OperationStats {
+    aliases: usize,
+    duplicate_fields: usize,
+    directives: usize,
+    directives_per_location: usize,
+    directive_depth: usize,
+}
+
+impl OperationStats {
+    ///Counts `directives` of single location, returning depth of directives including it.
+    fn directives(&mut self, directives: &[Positioned<Directive>], depth: usize) -> usize {
+        if directives.is_empty() {
+            return depth;
+        }
+        self.directives += directives.len();
+        self.directives_per_location = self.directives_per_location.max(directives.len());
+        self.directive_depth = self.directive_depth.max(depth + 1);
+        depth + 1
+    }
+
+    fn visit(&mut self, selection_set: &SelectionSet, depth: usize) {
+        let mut keys = HashSet::new();
+        for selection in selection_set.items.iter() {
+            match &selection.node {
+                Selection::Field(field) => {
+                    let field = &field.node;
+                    if field.alias.is_some() {
+                        self.aliases += 1;
+                    }
+                    if !keys.insert((field.response_key().node.as_str(), field.name.node.as_str())) {
+                        self.duplicate_fields += 1;
+                    }
+                    let depth = self.directives(&field.directives, depth);
+                    self.visit(&field.selection_set.node, depth);
+                }
+                Selection::InlineFragment(fragment) => {
+                    let depth = self.directives(&fragment.node.directives, depth);
+                    self.visit(&fragment.node.selection_set.node, depth);
+                }
+                Selection::FragmentSpread(spread) => {
+                    self.directives(&spread.node.directives, depth);
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn operation(&mut self, operation: &OperationDefinition) {
+        let depth = self.directives(&operation.directives, 0);
+        self.visit(&operation.selection_set.node, depth);
+    }
+
+    ///Collects stats of all definitions, with each fragment counted once, regardless of number of its spreads.
+    fn collect(document: &ExecutableDocument) -> Self {
+        let mut stats = Self::default();
+        match &document.operations {
+            DocumentOperations::Single(operation) => stats.operation(&operation.node),
+            DocumentOperations::Multiple(operations) => {
+                for operation in operations.values() {
+                    stats.operation(&operation.node);
+                }
+            }
+        }
+        for fragment in document.fragments.values() {
+            let depth = stats.directives(&fragment.node.directives, 0);
+            stats.visit(&fragment.node.selection_set.node, depth);
+        }
+        stats
+    }
+}
+
+///Rejects operations, which can be used to amplify load on subgraphs or are not named as required,
+///before query is planned.
+///
+///Invalid documents are passed through, to be reported by router.
+pub struct OperationLimits {
+    config: OperationLimitsConfig,
+}
+
+impl OperationLimits {
+    #[inline(always)]
+    ///Creates plugin with specified limits.
+    pub fn with_config(config: OperationLimitsConfig) -> Self {
+        Self { config }
+    }
+}
+
+fn check(config: &OperationLimitsConfig, req: RouterRequest) -> Result<RouterRequest, RouterResponse> {
+    if !config.is_enabled() {
+        return Ok(req);
+    }
+    let query = match req.originating_request.body().query.as_deref() {
+        Some(query) => query,
+        None => return Ok(req),
+    };
+    let document = match async_graphql::parser::parse_query(query) {
+        Ok(document) => document,
+        Err(_) => return Ok(req),
+    };
+
+    match &document.operations {
+        //Only anonymous operation can be single in document.
+        DocumentOperations::Single(_) if config.require_named_operations => {
+            tracing::info!("Rejected anonymous operation");
+            return Err(router_error(
+                StatusCode::BAD_REQUEST,
+                "Anonymous operations are not allowed",
+                "OPERATION_NAME_REQUIRED",
+                req.context,
+            ));
+        }
+        DocumentOperations::Multiple(operations)
+            if config.require_operation_name
+                && operations.len() > 1
+                && req.originating_request.body().operation_name.is_none() =>
+        {
+            tracing::info!("Rejected document with {} operations without name", operations.len());
+            return Err(router_error(
+                StatusCode::BAD_REQUEST,
+                "operationName is required for document with multiple operations",
+                "OPERATION_NAME_REQUIRED",
+                req.context,
+            ));
+        }
+        _ => (),
+    }
+
+    let stats = OperationStats::collect(&document);
+
+    let limits = [
+        (config.max_aliases, stats.aliases, "aliases", "TOO_MANY_ALIASES"),
+        (
+            config.max_duplicate_fields,
+            stats.duplicate_fields,
+            "duplicate fields",
+            "TOO_MANY_DUPLICATE_FIELDS",
+        ),
+        (config.max_directives, stats.directives, "directives", "TOO_MANY_DIRECTIVES"),
+        (
+            config.max_directives_per_location,
+            stats.directives_per_location,
+            "directives per location",
+            "TOO_MANY_DIRECTIVES",
+        ),
+        (
+            config.max_directive_depth,
+            stats.directive_depth,
+            "nested directives",
+            "DIRECTIVES_TOO_DEEP",
+        ),
+    ];
+    for (limit, value, name, code) in limits {
+        if let Some(limit) = limit {
+            if value > limit {
+                tracing::info!("Rejected operation with {} {}", value, name);
+                let message = format!("Operation exceeds limit of {} {}", limit, name);
+                return Err(router_error(StatusCode::BAD_REQUEST, &message, code, req.context));
+            }
+        }
+    }
+    Ok(req)
+}
+
+impl Plugin for OperationLimits {
+    type Config = OperationLimitsConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let config = self.config.clone();
+        super::checkpoint(service, move |req| check(&config, req))
+    }
+}
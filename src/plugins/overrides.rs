@@ -0,0 +1,414 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use async_graphql::parser::types::{DocumentOperations, OperationType, Selection};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+use crate::context::{ClientIdentity, RequestId, TypedContext};
+use crate::sample::Sequence;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+//Context key, which holds id of request's fetch ordering.
+const OVERRIDES_ID: &str = "graphql_router::plan_overrides_id";
+const BUFFER_SIZE: usize = 1024;
+
+type FetchService = Buffer<BoxService<SubgraphRequest, SubgraphResponse, BoxError>, SubgraphRequest>;
+
+#[inline(always)]
+fn default_percentage() -> f64 {
+    100.0
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Route of fetches from one subgraph to another
+pub struct FetchRoute {
+    ///Subgraph chosen by query planner.
+    pub from: String,
+    ///Subgraph resolving fetch instead, which must be able to resolve the same selection.
+    pub to: String,
+    ///Selections routed, as entity type names, like `Product`, or root fields, like `Query.topProducts`.
+    ///
+    ///Fetch is routed only if every entity type and root field it selects is listed.
+    pub matches: Vec<String>,
+    #[serde(default = "default_percentage")]
+    ///Percentage of requests, whose fetches are routed, defaulting to 100.
+    pub percentage: f64,
+    #[serde(default)]
+    ///Header of client request, whose value keeps routing sticky, so that the same value is always routed the same
+    ///way.
+    ///
+    ///Without header, client identity is used, falling back to request id.
+    pub sticky_header: Option<String>,
+}
+
+impl FetchRoute {
+    ///Returns whether fetch of request is routed, according to `percentage`.
+    fn is_selected(&self, req: &SubgraphRequest, sequence: &Sequence) -> bool {
+        if self.percentage >= 100.0 {
+            return true;
+        } else if self.percentage <= 0.0 {
+            return false;
+        }
+
+        let header = self.sticky_header.as_deref().and_then(|header| {
+            let value = req.originating_request.headers().get(header)?;
+            Some(value.as_bytes().to_vec())
+        });
+        let typed = TypedContext::new(&req.context);
+        let key = header
+            .or_else(|| typed.get::<ClientIdentity>().ok().flatten().map(|client| client.0.into_bytes()))
+            .or_else(|| typed.get::<RequestId>().ok().flatten().map(|id| id.0.into_bytes()));
+        let fraction = match key {
+            Some(key) => {
+                let digest = Sha256::digest(&key);
+                let mut bucket = [0u8; 8];
+                bucket.copy_from_slice(&digest[..8]);
+                u64::from_be_bytes(bucket) as f64 / u64::MAX as f64
+            }
+            None => sequence.next_fraction(),
+        };
+        fraction * 100.0 < self.percentage
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Order of fetches between two subgraphs
+pub struct FetchOrder {
+    ///Subgraph, whose fetches complete first.
+    pub before: String,
+    ///Subgraph, whose fetches wait for fetches to `before` of the same request, that are in flight or started
+    ///together with them.
+    pub after: String,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Plan overrides config
+pub struct PlanOverridesConfig {
+    #[serde(default)]
+    ///Routes of fetches to subgraphs other than planned ones.
+    pub routes: Vec<FetchRoute>,
+    #[serde(default)]
+    ///Orders of fetches, which query planner would run in parallel.
+    pub order: Vec<FetchOrder>,
+}
+
+impl PlanOverridesConfig {
+    #[inline]
+    ///Routes fetches to `from`, which select only `matches`, to `to`.
+    pub fn route(mut self, from: &str, to: &str, matches: &[&str]) -> Self {
+        self.routes.push(FetchRoute {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            matches: matches.iter().map(|selection| (*selection).to_owned()).collect(),
+            percentage: default_percentage(),
+            sticky_header: None,
+        });
+        self
+    }
+
+    ///Rolls out migration of `matches`, overridden by subgraph `to` via `@override(from: ...)`, to `percentage` of
+    ///requests, sticky by value of client request `header`.
+    ///
+    ///Fetches of remaining requests are routed back to subgraph `from`.
+    pub fn rollout(mut self, from: &str, to: &str, matches: &[&str], percentage: f64, header: Option<&str>) -> Self {
+        self.routes.push(FetchRoute {
+            from: to.to_owned(),
+            to: from.to_owned(),
+            matches: matches.iter().map(|selection| (*selection).to_owned()).collect(),
+            percentage: 100.0 - percentage.clamp(0.0, 100.0),
+            sticky_header: header.map(str::to_owned),
+        });
+        self
+    }
+
+    #[inline]
+    ///Makes fetches to `after` wait for fetches to `before`.
+    pub fn order(mut self, before: &str, after: &str) -> Self {
+        self.order.push(FetchOrder {
+            before: before.to_owned(),
+            after: after.to_owned(),
+        });
+        self
+    }
+}
+
+///Returns entity types and root fields selected by subgraph `query`.
+fn selections(query: &str, operation_name: Option<&str>) -> Option<BTreeSet<String>> {
+    let document = async_graphql::parser::parse_query(query).ok()?;
+    let operation = match &document.operations {
+        DocumentOperations::Single(operation) => &operation.node,
+        DocumentOperations::Multiple(operations) => &operations.get(operation_name?)?.node,
+    };
+    let root = match operation.ty {
+        OperationType::Query => "Query",
+        OperationType::Mutation => "Mutation",
+        OperationType::Subscription => "Subscription",
+    };
+
+    let mut selections = BTreeSet::new();
+    for selection in operation.selection_set.node.items.iter() {
+        let field = match &selection.node {
+            Selection::Field(field) => &field.node,
+            //Query planner only produces fragments within entities.
+            _ => return None,
+        };
+        match field.name.node.as_str() {
+            "__typename" => (),
+            "_entities" => {
+                for selection in field.selection_set.node.items.iter() {
+                    match &selection.node {
+                        Selection::InlineFragment(fragment) => {
+                            let condition = fragment.node.type_condition.as_ref()?;
+                            selections.insert(condition.node.on.node.to_string());
+                        }
+                        Selection::Field(field) if field.node.name.node == "__typename" => (),
+                        _ => return None,
+                    }
+                }
+            }
+            name => {
+                selections.insert(format!("{}.{}", root, name));
+            }
+        }
+    }
+    Some(selections)
+}
+
+///Overrides query plan for known pathological cases, by routing fetches to other subgraphs or ordering fetches,
+///which would run in parallel.
+///
+///Query planner is not affected, so routed fetches carry the same query and representations, and target
+///subgraph must be able to resolve them.
+pub struct PlanOverrides {
+    config: Arc<PlanOverridesConfig>,
+    services: Arc<std::sync::RwLock<HashMap<String, FetchService>>>,
+    ongoing: Ongoing<HashMap<String, Arc<RwLock<()>>>>,
+}
+
+impl PlanOverrides {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: PlanOverridesConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            services: Arc::default(),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for PlanOverrides {
+    type Config = PlanOverridesConfig;
+
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let result = match config.routes.iter().find(|route| route.from == route.to) {
+            Some(route) => Err(format!("Route of '{}' leads to itself", route.from).into()),
+            None => match config.routes.iter().find(|route| !(0.0..=100.0).contains(&route.percentage)) {
+                Some(route) => Err(format!("Route of '{}' has invalid percentage {}", route.from, route.percentage)
+                    .into()),
+                None => Ok(Self::with_config(config)),
+            },
+        };
+        Box::pin(ready(result))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        if self.config.order.is_empty() {
+            return service;
+        }
+        OverridesRouterService {
+            inner: service,
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let routes = self
+            .config
+            .routes
+            .iter()
+            .filter(|route| route.from == name)
+            .cloned()
+            .collect::<Vec<_>>();
+        let is_ordered = self
+            .config
+            .order
+            .iter()
+            .any(|order| order.before == name || order.after == name);
+        let is_target = self.config.routes.iter().any(|route| route.to == name);
+        if routes.is_empty() && !is_ordered && !is_target {
+            return service;
+        }
+
+        let service = Buffer::new(service, BUFFER_SIZE);
+        let mut services = match self.services.write() {
+            Ok(services) => services,
+            Err(error) => error.into_inner(),
+        };
+        services.insert(name.to_owned(), service.clone());
+        OverridesFetchService {
+            name: name.into(),
+            inner: service,
+            routes: routes.into(),
+            sequence: Arc::default(),
+            config: self.config.clone(),
+            services: self.services.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct OverridesRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ongoing: Ongoing<HashMap<String, Arc<RwLock<()>>>>,
+}
+
+impl tower::Service<RouterRequest> for OverridesRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let id = self.ongoing.start(HashMap::new());
+        if let Err(error) = req.context.insert(OVERRIDES_ID, id) {
+            tracing::warn!("Unable to order fetches: {}", error);
+            self.ongoing.finish(id);
+            return self.inner.call(req);
+        }
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            ongoing.finish(id);
+            result
+        })
+    }
+}
+
+struct OverridesFetchService {
+    name: Arc<str>,
+    inner: FetchService,
+    routes: Arc<[FetchRoute]>,
+    sequence: Arc<Sequence>,
+    config: Arc<PlanOverridesConfig>,
+    services: Arc<std::sync::RwLock<HashMap<String, FetchService>>>,
+    ongoing: Ongoing<HashMap<String, Arc<RwLock<()>>>>,
+}
+
+impl OverridesFetchService {
+    ///Returns name and service of subgraph, which resolves `req`.
+    fn target(&self, req: &SubgraphRequest) -> (Arc<str>, FetchService) {
+        let body = req.subgraph_request.body();
+        let selections = match (self.routes.is_empty(), body.query.as_deref()) {
+            (false, Some(query)) => selections(query, body.operation_name.as_deref()),
+            _ => None,
+        };
+        let route = selections.and_then(|selections| {
+            self.routes.iter().find(|route| {
+                !selections.is_empty()
+                    && selections.iter().all(|selection| route.matches.contains(selection))
+                    && route.is_selected(req, &self.sequence)
+            })
+        });
+        if let Some(route) = route {
+            let services = match self.services.read() {
+                Ok(services) => services,
+                Err(error) => error.into_inner(),
+            };
+            match services.get(route.to.as_str()) {
+                Some(service) => {
+                    tracing::debug!("{}: Fetch routed to '{}'", self.name, route.to);
+                    return (route.to.as_str().into(), service.clone());
+                }
+                None => tracing::warn!("{}: Unknown subgraph '{}' to route fetch", self.name, route.to),
+            }
+        }
+        (self.name.clone(), self.inner.clone())
+    }
+}
+
+impl tower::Service<SubgraphRequest> for OverridesFetchService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Readiness is awaited on target service, which is only known with request.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let (name, service) = self.target(&req);
+        let id = req.context.get::<_, u64>(OVERRIDES_ID).ok().flatten();
+        let lock = |subgraph: &str| {
+            let mut lock = None;
+            if let Some(id) = id {
+                self.ongoing.update(id, |locks| {
+                    lock = Some(locks.entry(subgraph.to_owned()).or_default().clone());
+                });
+            }
+            lock
+        };
+        let wait_for = self
+            .config
+            .order
+            .iter()
+            .filter(|order| *order.after == *name)
+            .filter_map(|order| lock(&order.before))
+            .collect::<Vec<_>>();
+        //Read access is taken right away, so that fetches started together are already visible.
+        let held = match self.config.order.iter().any(|order| *order.before == *name) {
+            true => lock(&name).map(|lock| lock.clone().try_read_owned().map_err(|_| lock)),
+            false => None,
+        };
+
+        Box::pin(async move {
+            if !wait_for.is_empty() {
+                //Fetches of the same plan node are started together, so they get chance to start first.
+                tokio::task::yield_now().await;
+            }
+            for lock in wait_for {
+                //Write access is granted only once all fetches holding read access complete.
+                drop(lock.write().await);
+            }
+            let _guard = match held {
+                Some(Ok(guard)) => Some(guard),
+                Some(Err(lock)) => Some(lock.read_owned().await),
+                None => None,
+            };
+            service.oneshot(req).await
+        })
+    }
+}
@@ -0,0 +1,200 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+use crate::context::{Priority, TypedContext};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::sync::Arc;
+
+//Context key, which holds id of request's fetch permits.
+const PARALLELISM_ID: &str = "graphql_router::fetch_parallelism_id";
+///Context key, which can be set to `usize` to lower limit of parallel fetches for single request.
+pub const FETCH_PARALLELISM: &str = "graphql_router::fetch_parallelism";
+//Buffer size, when total number of fetches is not limited.
+const UNLIMITED_BUFFER: usize = 1024;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Fetch parallelism config
+pub struct FetchParallelismConfig {
+    ///Maximum number of subgraph fetches, which single request runs at the same time.
+    pub max_per_request: usize,
+    #[serde(default)]
+    ///Maximum number of subgraph fetches, which all requests run at the same time.
+    ///
+    ///Fetches of requests with [Priority::High] are not limited by it.
+    ///
+    ///Default is no limit.
+    pub max_total: Option<usize>,
+    #[serde(default)]
+    ///Request header, which can lower per request limit.
+    ///
+    ///Values above `max_per_request` are ignored.
+    pub header: Option<String>,
+}
+
+///Limits number of subgraph fetches running at the same time, so that large fan-out queries don't starve others.
+///
+///Per request limit can be lowered by setting [FETCH_PARALLELISM] in request context or via configured header.
+///Fetches over limit wait for their turn.
+pub struct FetchParallelism {
+    config: Arc<FetchParallelismConfig>,
+    total: Option<Arc<Semaphore>>,
+    ongoing: Ongoing<Arc<Semaphore>>,
+}
+
+impl FetchParallelism {
+    #[inline]
+    ///Creates plugin with specified limits.
+    pub fn with_config(config: FetchParallelismConfig) -> Self {
+        Self {
+            total: config.max_total.map(|max_total| Arc::new(Semaphore::new(max_total.max(1)))),
+            config: Arc::new(config),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for FetchParallelism {
+    type Config = FetchParallelismConfig;
+
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        let result = match (config.max_per_request, config.max_total) {
+            (0, _) => Err("max_per_request must be greater than 0".into()),
+            (_, Some(0)) => Err("max_total must be greater than 0".into()),
+            _ => Ok(Self::with_config(config)),
+        };
+        Box::pin(ready(result))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        ParallelismRouterService {
+            inner: service,
+            config: self.config.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        _name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let bound = self.config.max_total.unwrap_or(UNLIMITED_BUFFER).max(1);
+        ParallelismFetchService {
+            //Fetches are queued by semaphores, so buffer only needs to fit fetches in flight.
+            inner: Buffer::new(service, bound),
+            total: self.total.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct ParallelismRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    config: Arc<FetchParallelismConfig>,
+    ongoing: Ongoing<Arc<Semaphore>>,
+}
+
+impl ParallelismRouterService {
+    fn limit(&self, req: &RouterRequest) -> usize {
+        let from_context = req.context.get::<_, usize>(FETCH_PARALLELISM).ok().flatten();
+        let from_header = self.config.header.as_ref().and_then(|header| {
+            let value = req.originating_request.headers().get(header.as_str())?;
+            value.to_str().ok()?.trim().parse::<usize>().ok()
+        });
+        let requested = match (from_context, from_header) {
+            (Some(left), Some(right)) => left.min(right),
+            (left, right) => left.or(right).unwrap_or(self.config.max_per_request),
+        };
+        requested.clamp(1, self.config.max_per_request.max(1))
+    }
+}
+
+impl tower::Service<RouterRequest> for ParallelismRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let limit = self.limit(&req);
+        let id = self.ongoing.start(Arc::new(Semaphore::new(limit)));
+        if let Err(error) = req.context.insert(PARALLELISM_ID, id) {
+            tracing::warn!("Unable to limit fetch parallelism: {}", error);
+            self.ongoing.finish(id);
+            return self.inner.call(req);
+        }
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let result = response.await;
+            ongoing.finish(id);
+            result
+        })
+    }
+}
+
+struct ParallelismFetchService {
+    inner: Buffer<BoxService<SubgraphRequest, SubgraphResponse, BoxError>, SubgraphRequest>,
+    total: Option<Arc<Semaphore>>,
+    ongoing: Ongoing<Arc<Semaphore>>,
+}
+
+impl tower::Service<SubgraphRequest> for ParallelismFetchService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Readiness is awaited after permits are acquired, so that waiting fetches don't hold buffer slots.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let per_request = match req.context.get::<_, u64>(PARALLELISM_ID) {
+            Ok(Some(id)) => self.ongoing.get(id),
+            _ => None,
+        };
+        let inner = self.inner.clone();
+        let total = match TypedContext::new(&req.context).get::<Priority>() {
+            Ok(Some(Priority::High)) => None,
+            _ => self.total.clone(),
+        };
+
+        Box::pin(async move {
+            //Semaphores are never closed, so acquiring never fails.
+            let _request_permit = match per_request {
+                Some(permits) => permits.acquire_owned().await.ok(),
+                None => None,
+            };
+            let _total_permit = match total {
+                Some(permits) => permits.acquire_owned().await.ok(),
+                None => None,
+            };
+            inner.oneshot(req).await
+        })
+    }
+}
@@ -0,0 +1,206 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::error::router_error;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+//Context key, which holds name of failed subgraph with `fail-request` policy.
+const FAILED_SUBGRAPH: &str = "graphql_router::failed_subgraph";
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+///Effect of subgraph fetch failure on federated response
+///
+///Fetch fails, when subgraph cannot be reached or when it responds with errors and without data, which is
+///how local subgraphs report failure.
+pub enum SubgraphFailurePolicy {
+    #[serde(alias = "critical")]
+    ///Whole request fails with `SUBGRAPH_FAILED` error, marking subgraph as critical.
+    FailRequest,
+    #[serde(alias = "optional")]
+    ///Fields of subgraph are `null`, with error in response, marking subgraph as optional.
+    NullWithError,
+    ///Fields of subgraph are `null`, without any error.
+    Omit,
+}
+
+impl Default for SubgraphFailurePolicy {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::NullWithError
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Partial results config
+pub struct PartialResultsConfig {
+    #[serde(default)]
+    ///Policy of subgraphs, that are not specified in `subgraphs`.
+    pub default: SubgraphFailurePolicy,
+    #[serde(default)]
+    ///Policy per subgraph name.
+    pub subgraphs: BTreeMap<String, SubgraphFailurePolicy>,
+}
+
+impl PartialResultsConfig {
+    #[inline]
+    ///Marks subgraph `name` as critical, failing whole request on its failure.
+    pub fn critical(mut self, name: &str) -> Self {
+        self.subgraphs.insert(name.to_owned(), SubgraphFailurePolicy::FailRequest);
+        self
+    }
+
+    #[inline]
+    ///Marks subgraph `name` as optional, nulling its fields with error on its failure.
+    pub fn optional(mut self, name: &str) -> Self {
+        self.subgraphs.insert(name.to_owned(), SubgraphFailurePolicy::NullWithError);
+        self
+    }
+
+    #[inline]
+    ///Returns policy of subgraph.
+    pub fn policy(&self, name: &str) -> SubgraphFailurePolicy {
+        self.subgraphs.get(name).copied().unwrap_or(self.default)
+    }
+}
+
+///Controls how subgraph fetch failure affects federated response.
+pub struct PartialResults {
+    config: PartialResultsConfig,
+}
+
+impl PartialResults {
+    #[inline(always)]
+    ///Creates plugin with specified policies.
+    pub fn with_config(config: PartialResultsConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for PartialResults {
+    type Config = PartialResultsConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let has_fail_request = self.config.default == SubgraphFailurePolicy::FailRequest
+            || self
+                .config
+                .subgraphs
+                .values()
+                .any(|policy| *policy == SubgraphFailurePolicy::FailRequest);
+        if !has_fail_request {
+            return service;
+        }
+
+        service
+            .map_response(|response: RouterResponse| {
+                match response.context.get::<_, String>(FAILED_SUBGRAPH) {
+                    Ok(Some(name)) => {
+                        let message = format!("Subgraph '{}' failed", name);
+                        router_error(StatusCode::BAD_GATEWAY, &message, "SUBGRAPH_FAILED", response.context)
+                    }
+                    _ => response,
+                }
+            })
+            .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        match self.config.policy(name) {
+            //This is how router handles fetch errors and responses without data on its own
+            SubgraphFailurePolicy::NullWithError => service,
+            policy => PartialResultsService {
+                inner: service,
+                name: name.into(),
+                policy,
+            }
+            .boxed(),
+        }
+    }
+}
+
+//Returns whether subgraph responded only with errors, as local subgraphs do on failure.
+fn is_failed(response: &SubgraphResponse) -> bool {
+    let body = response.response.body();
+    if body.errors.is_empty() {
+        return false;
+    }
+    matches!(body.data, None | Some(serde_json_bytes::Value::Null))
+}
+
+struct PartialResultsService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    name: Arc<str>,
+    policy: SubgraphFailurePolicy,
+}
+
+impl tower::Service<SubgraphRequest> for PartialResultsService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let context = req.context.clone();
+        let name = self.name.clone();
+        let policy = self.policy;
+        let response = self.inner.call(req);
+
+        Box::pin(async move {
+            let error: BoxError = match response.await {
+                Ok(response) => match is_failed(&response) {
+                    true => format!("Subgraph '{}' responded with errors and without data", name).into(),
+                    false => return Ok(response),
+                },
+                Err(error) => error,
+            };
+
+            match policy {
+                SubgraphFailurePolicy::FailRequest => {
+                    if let Err(error) = context.insert(FAILED_SUBGRAPH, name.to_string()) {
+                        tracing::warn!("{}: Unable to mark request as failed: {}", name, error);
+                    }
+                    Err(error)
+                }
+                SubgraphFailurePolicy::Omit => {
+                    tracing::info!("{}: Omitting failed subgraph: {}", name, error);
+                    let response = apollo_router_core::Response::from_bytes(&name, "{\"data\":null}".into())?;
+                    let response = http::Response::builder()
+                        .body(response)
+                        .expect("no argument can fail to parse or converted to the internal representation here")
+                        .into();
+                    Ok(SubgraphResponse { response, context })
+                }
+                SubgraphFailurePolicy::NullWithError => Err(error),
+            }
+        })
+    }
+}
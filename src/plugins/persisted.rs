@@ -0,0 +1,369 @@
+use apollo_router_core::{Context, Plugin, RouterRequest, RouterResponse, Schema};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::error::router_error;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use core::time::Duration;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+#[cfg(feature = "redis-store")]
+mod redis_store;
+#[cfg(feature = "redis-store")]
+pub use redis_store::{RedisStore, RedisStoreConfig};
+
+const BUFFER_SIZE: usize = 1024;
+
+///Future returned by [PersistedQueryStore].
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, BoxError>> + Send + 'a>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Operation found in [PersistedQueryStore].
+pub struct StoredQuery {
+    ///Query document.
+    pub document: String,
+    ///Time until operation expires in store, if it is not stored indefinitely.
+    pub expires_in: Option<Duration>,
+}
+
+///Store of persisted operations, shared between router replicas.
+///
+///Operations are namespaced by hex encoded SHA-256 of schema, so that they are resolved only against schema,
+///they were registered with.
+pub trait PersistedQueryStore: Send + Sync {
+    ///Returns operation persisted under `id` within `namespace`.
+    fn get<'a>(&'a self, namespace: &'a str, id: &'a str) -> StoreFuture<'a, Option<StoredQuery>>;
+    ///Persists `document` under `id` within `namespace`.
+    fn register<'a>(&'a self, namespace: &'a str, id: &'a str, document: &'a str) -> StoreFuture<'a, ()>;
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Persisted queries config
+pub struct PersistedQueriesConfig {
+    #[serde(default)]
+    ///Specifies whether only persisted operations are allowed to execute.
+    pub safelist: bool,
+    #[serde(default)]
+    ///Operations to persist at start, mapping id to query document.
+    pub operations: BTreeMap<String, String>,
+    #[cfg(feature = "redis-store")]
+    #[serde(default)]
+    ///Redis store, shared between router replicas.
+    pub redis: Option<RedisStoreConfig>,
+}
+
+#[derive(Default)]
+struct Store {
+    by_id: HashMap<String, String>,
+    documents: HashSet<String>,
+    //Operations found in shared store, which are resolved by id only and expire together with shared copy.
+    shared: HashMap<String, (String, Option<Instant>)>,
+}
+
+impl Store {
+    #[inline]
+    fn get_shared(&self, id: &str) -> Option<&String> {
+        match self.shared.get(id) {
+            Some((document, Some(expires_at))) if *expires_at > Instant::now() => Some(document),
+            Some((document, None)) => Some(document),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+///Store of persisted operations, which can be extended at runtime.
+///
+///Requests without query are resolved using `extensions.persistedQuery.sha256Hash` as id.
+///
+///Can be used as plugin, with all clones sharing the same store.
+///
+///With [shared store](PersistedQueries::with_store), unknown ids are looked up in it and cached locally until they
+///expire in shared store. Such operations are not added to safelist, as they can only be executed by id.
+pub struct PersistedQueries {
+    store: Arc<RwLock<Store>>,
+    shared: Option<Arc<dyn PersistedQueryStore>>,
+    //Namespace of operations in shared store.
+    schema_hash: Option<Arc<str>>,
+    safelist: bool,
+}
+
+#[inline]
+fn schema_hash(schema: &Schema) -> Arc<str> {
+    crate::hash::sha256_hex(schema.as_str().as_bytes()).into()
+}
+
+impl PersistedQueries {
+    #[inline(always)]
+    ///Creates empty store.
+    ///
+    ///When `safelist` is set, only operations present in store are allowed to execute.
+    pub fn new(safelist: bool) -> Self {
+        Self {
+            store: Default::default(),
+            shared: None,
+            schema_hash: None,
+            safelist,
+        }
+    }
+
+    ///Creates store according to `config`.
+    pub fn with_config(config: PersistedQueriesConfig) -> Result<Self, BoxError> {
+        let this = Self::new(config.safelist);
+        #[cfg(feature = "redis-store")]
+        let this = match config.redis {
+            Some(redis) => this.with_store(Arc::new(RedisStore::with_config(redis)?)),
+            None => this,
+        };
+        for (id, document) in config.operations {
+            this.register(id, document);
+        }
+        Ok(this)
+    }
+
+    #[inline]
+    ///Sets `shared` store, resolving operations registered by other replicas.
+    pub fn with_store(mut self, shared: Arc<dyn PersistedQueryStore>) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
+    #[inline]
+    ///Returns clone, using hash of `schema` as namespace in shared store.
+    pub(crate) fn for_schema(&self, schema: &Schema) -> Self {
+        let mut this = self.clone();
+        if this.shared.is_some() {
+            this.schema_hash = Some(schema_hash(schema));
+        }
+        this
+    }
+
+    ///Persists `document` under `id` in shared store, if any, within namespace of `schema`.
+    pub async fn share(&self, schema: &Schema, id: &str, document: &str) -> Result<(), BoxError> {
+        match self.shared.as_ref() {
+            Some(shared) => shared.register(&schema_hash(schema), id, document).await,
+            None => Ok(()),
+        }
+    }
+
+    ///Persists `document` under `id`.
+    ///
+    ///Returns `false` if `id` is already used by different document, leaving store unchanged.
+    pub fn register(&self, id: String, document: String) -> bool {
+        let mut store = match self.store.write() {
+            Ok(store) => store,
+            Err(error) => error.into_inner(),
+        };
+        match store.by_id.get(&id) {
+            Some(existing) => *existing == document,
+            None => {
+                store.documents.insert(document.clone());
+                store.by_id.insert(id, document);
+                true
+            }
+        }
+    }
+
+    #[inline]
+    ///Returns document persisted under `id`.
+    pub fn get(&self, id: &str) -> Option<String> {
+        match self.store.read() {
+            Ok(store) => store.by_id.get(id).cloned(),
+            Err(error) => error.into_inner().by_id.get(id).cloned(),
+        }
+    }
+
+    #[inline]
+    ///Returns number of persisted operations.
+    pub fn len(&self) -> usize {
+        match self.store.read() {
+            Ok(store) => store.by_id.len(),
+            Err(error) => error.into_inner().by_id.len(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether store has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    fn persisted_id(req: &RouterRequest) -> Option<&str> {
+        let body = req.originating_request.body();
+        match body.query {
+            Some(_) => None,
+            None => body
+                .extensions
+                .get("persistedQuery")
+                .and_then(|value| value.as_object())
+                .and_then(|value| value.get("sha256Hash"))
+                .and_then(|value| value.as_str()),
+        }
+    }
+
+    #[inline]
+    //Returns whether `id` is resolved without looking it up in shared store.
+    fn is_resolved(&self, id: &str) -> bool {
+        let store = match self.store.read() {
+            Ok(store) => store,
+            Err(error) => error.into_inner(),
+        };
+        store.by_id.contains_key(id) || store.get_shared(id).is_some()
+    }
+
+    //Looks up `id` in shared store, caching found document until it expires.
+    async fn resolve_shared(&self, shared: &dyn PersistedQueryStore, id: String) {
+        let namespace = self.schema_hash.as_deref().unwrap_or_default();
+        match shared.get(namespace, &id).await {
+            Ok(Some(query)) => {
+                let now = Instant::now();
+                let expires_at = query.expires_in.map(|expires_in| now + expires_in);
+                let mut store = match self.store.write() {
+                    Ok(store) => store,
+                    Err(error) => error.into_inner(),
+                };
+                store.shared.retain(|_, (_, cached)| cached.map_or(true, |cached| cached > now));
+                store.shared.insert(id, (query.document, expires_at));
+            }
+            Ok(None) => (),
+            Err(error) => tracing::warn!("Failed to look up persisted query in shared store: {}", error),
+        }
+    }
+
+    #[inline]
+    fn check_listed(&self, store: &Store, query: &str, context: &Context) -> Result<(), RouterResponse> {
+        if self.safelist && !store.documents.contains(query) {
+            tracing::info!("Rejected operation outside of safelist");
+            return Err(router_error(
+                StatusCode::FORBIDDEN,
+                "Operation is not in safelist",
+                "OPERATION_NOT_IN_SAFELIST",
+                context.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    ///Rejects `query` outside of safelist, if it is enabled.
+    ///
+    ///Used by built-in [server](crate::server) to reject request, before its variables are parsed.
+    pub(crate) fn check_safelist(&self, query: &str, context: &Context) -> Result<(), RouterResponse> {
+        if !self.safelist {
+            return Ok(());
+        }
+        let store = match self.store.read() {
+            Ok(store) => store,
+            Err(error) => error.into_inner(),
+        };
+        self.check_listed(&store, query, context)
+    }
+
+    fn check(&self, mut req: RouterRequest) -> Result<RouterRequest, RouterResponse> {
+        let store = match self.store.read() {
+            Ok(store) => store,
+            Err(error) => error.into_inner(),
+        };
+
+        let body = req.originating_request.body();
+        match body.query.as_ref() {
+            Some(query) => self.check_listed(&store, query, &req.context)?,
+            None => {
+                if let Some(id) = Self::persisted_id(&req) {
+                    match store.by_id.get(id).or_else(|| store.get_shared(id)) {
+                        Some(document) => {
+                            let document = document.clone();
+                            req.originating_request.body_mut().query = Some(document);
+                        }
+                        None => {
+                            return Err(router_error(
+                                StatusCode::OK,
+                                "PersistedQueryNotFound",
+                                "PERSISTED_QUERY_NOT_FOUND",
+                                req.context,
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(req)
+    }
+}
+
+impl Plugin for PersistedQueries {
+    type Config = PersistedQueriesConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Self::with_config(config)))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        let persisted = self.clone();
+        match self.shared.clone() {
+            Some(shared) => SharedLookupService {
+                inner: Buffer::new(service, BUFFER_SIZE),
+                persisted,
+                shared,
+            }
+            .boxed(),
+            None => super::checkpoint(service, move |req| persisted.check(req)),
+        }
+    }
+}
+
+struct SharedLookupService {
+    inner: Buffer<BoxService<RouterRequest, RouterResponse, BoxError>, RouterRequest>,
+    persisted: PersistedQueries,
+    shared: Arc<dyn PersistedQueryStore>,
+}
+
+impl tower::Service<RouterRequest> for SharedLookupService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Inner service is cloned on call, so readiness is awaited by it.
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let persisted = self.persisted.clone();
+        let shared = self.shared.clone();
+
+        let id = match PersistedQueries::persisted_id(&req) {
+            Some(id) if !persisted.is_resolved(id) => Some(id.to_owned()),
+            _ => None,
+        };
+
+        Box::pin(async move {
+            if let Some(id) = id {
+                persisted.resolve_shared(shared.as_ref(), id).await;
+            }
+            match persisted.check(req) {
+                Ok(req) => inner.oneshot(req).await,
+                Err(response) => Ok(response),
+            }
+        })
+    }
+}
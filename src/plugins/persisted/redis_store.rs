@@ -0,0 +1,116 @@
+use redis::aio::ConnectionManager;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+use tower::BoxError;
+
+use super::{PersistedQueryStore, StoreFuture, StoredQuery};
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+#[inline(always)]
+fn default_pool_size() -> usize {
+    4
+}
+
+#[inline(always)]
+fn default_prefix() -> String {
+    "apq".to_owned()
+}
+
+#[inline(always)]
+fn default_ttl_secs() -> u64 {
+    //One day
+    86_400
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Redis persisted queries store config
+pub struct RedisStoreConfig {
+    ///Redis URL, e.g. `redis://127.0.0.1:6379/0`.
+    pub url: String,
+    #[serde(default = "default_pool_size")]
+    ///Number of connections, used in turn.
+    pub pool_size: usize,
+    #[serde(default = "default_prefix")]
+    ///Prefix of keys, followed by schema hash and operation id.
+    pub prefix: String,
+    #[serde(default = "default_ttl_secs")]
+    ///Time in seconds operation is stored for, with `0` storing it indefinitely.
+    pub ttl_secs: u64,
+}
+
+///Persisted queries store in Redis
+///
+///Operations are stored under `{prefix}:{schema hash}:{id}` keys, expiring after configured TTL.
+///Connections are established on first use and re-established on failure, so router can start while Redis is down.
+pub struct RedisStore {
+    client: redis::Client,
+    pool: Box<[OnceCell<ConnectionManager>]>,
+    next: AtomicUsize,
+    prefix: String,
+    ttl_secs: u64,
+}
+
+impl RedisStore {
+    ///Creates store according to `config`, failing if URL is invalid.
+    pub fn with_config(config: RedisStoreConfig) -> Result<Self, BoxError> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let pool = (0..config.pool_size.max(1)).map(|_| OnceCell::new()).collect();
+        Ok(Self {
+            client,
+            pool,
+            next: AtomicUsize::new(0),
+            prefix: config.prefix,
+            ttl_secs: config.ttl_secs,
+        })
+    }
+
+    #[inline]
+    fn key(&self, namespace: &str, id: &str) -> String {
+        format!("{}:{}:{}", self.prefix, namespace, id)
+    }
+
+    //Returns next connection of pool, connecting it if necessary.
+    async fn connection(&self) -> Result<ConnectionManager, BoxError> {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.pool.len();
+        let connection = self.pool[idx]
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+impl PersistedQueryStore for RedisStore {
+    fn get<'a>(&'a self, namespace: &'a str, id: &'a str) -> StoreFuture<'a, Option<StoredQuery>> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let key = self.key(namespace, id);
+            let (document, ttl_ms) = redis::pipe()
+                .cmd("GET")
+                .arg(&key)
+                .cmd("PTTL")
+                .arg(&key)
+                .query_async::<_, (Option<String>, i64)>(&mut connection)
+                .await?;
+            //Negative TTL means that key has no expiration.
+            let expires_in = u64::try_from(ttl_ms).ok().map(Duration::from_millis);
+            Ok(document.map(|document| StoredQuery { document, expires_in }))
+        })
+    }
+
+    fn register<'a>(&'a self, namespace: &'a str, id: &'a str, document: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let mut cmd = redis::cmd("SET");
+            cmd.arg(self.key(namespace, id)).arg(document);
+            if self.ttl_secs > 0 {
+                cmd.arg("EX").arg(self.ttl_secs);
+            }
+            cmd.query_async::<_, ()>(&mut connection).await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,190 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, ResponseBody};
+use hyper::http::header::{HeaderValue, CACHE_CONTROL};
+use hyper::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use crate::context::{Priority, SubgraphVariant, TypedContext};
+use crate::error::timeout_error;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+///Execution policy of operation
+pub struct OperationPolicy {
+    #[serde(default)]
+    ///Time limit in milliseconds for whole request handling, responded with `TIMEOUT` error when exceeded.
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    ///Time in seconds, for which successful responses may be cached, set as `Cache-Control: max-age`.
+    pub cache_ttl_s: Option<u64>,
+    #[serde(default)]
+    ///Deployment variant of subgraphs, used by subgraphs that have it.
+    pub variant: Option<String>,
+    #[serde(default)]
+    ///Priority of request.
+    pub priority: Option<Priority>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Operation policies config
+pub struct OperationPoliciesConfig {
+    #[serde(default)]
+    ///Policy per operation name.
+    pub operations: BTreeMap<String, OperationPolicy>,
+    #[serde(default)]
+    ///Policy per hex encoded SHA-256 of operation document, which takes precedence over operation name.
+    pub documents: BTreeMap<String, OperationPolicy>,
+}
+
+impl OperationPoliciesConfig {
+    #[inline]
+    ///Sets `policy` of operation `name`.
+    pub fn operation(mut self, name: &str, policy: OperationPolicy) -> Self {
+        self.operations.insert(name.to_owned(), policy);
+        self
+    }
+
+    #[inline]
+    ///Sets `policy` of operation document with SHA-256 `hash`.
+    pub fn document(mut self, hash: &str, policy: OperationPolicy) -> Self {
+        self.documents.insert(hash.to_ascii_lowercase(), policy);
+        self
+    }
+
+    ///Returns policy of request, if any.
+    fn policy(&self, req: &RouterRequest) -> Option<&OperationPolicy> {
+        let body = req.originating_request.body();
+        let hash = match body.query.as_deref() {
+            _ if self.documents.is_empty() => None,
+            Some(query) => Some(crate::hash::sha256_hex(query.as_bytes())),
+            //Query may not be resolved yet from persisted query hash.
+            None => body
+                .extensions
+                .get("persistedQuery")
+                .and_then(|value| value.as_object())
+                .and_then(|value| value.get("sha256Hash"))
+                .and_then(|value| value.as_str())
+                .map(str::to_ascii_lowercase),
+        };
+        let by_document = hash.and_then(|hash| self.documents.get(&hash));
+        by_document.or_else(|| self.operations.get(body.operation_name.as_deref()?))
+    }
+}
+
+///Applies execution policies to critical operations, selected by operation name or document hash, without
+///affecting the rest of traffic.
+///
+///Variant and priority are stored in request context as [SubgraphVariant] and [Priority], for remote subgraphs and
+///[FetchParallelism](super::FetchParallelism) to follow.
+pub struct OperationPolicies {
+    config: Arc<OperationPoliciesConfig>,
+}
+
+impl OperationPolicies {
+    #[inline(always)]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: OperationPoliciesConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for OperationPolicies {
+    type Config = OperationPoliciesConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        PoliciesService {
+            inner: service,
+            config: self.config.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct PoliciesService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    config: Arc<OperationPoliciesConfig>,
+}
+
+impl tower::Service<RouterRequest> for PoliciesService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let policy = match self.config.policy(&req) {
+            Some(policy) => policy.clone(),
+            None => return self.inner.call(req),
+        };
+
+        let budget = policy.timeout_ms.map(Duration::from_millis);
+        let cache_ttl = policy.cache_ttl_s;
+        let typed = TypedContext::new(&req.context);
+        if let Some(variant) = policy.variant {
+            if let Err(error) = typed.insert(SubgraphVariant(variant)) {
+                tracing::warn!("Unable to set subgraph variant: {}", error);
+            }
+        }
+        if let Some(priority) = policy.priority {
+            if let Err(error) = typed.insert(priority) {
+                tracing::warn!("Unable to set priority: {}", error);
+            }
+        }
+
+        let context = req.context.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = match budget {
+                Some(budget) => match tokio::time::timeout(budget, response).await {
+                    Ok(response) => response?,
+                    Err(_) => {
+                        tracing::info!("Request timed out after {}ms by operation policy", budget.as_millis());
+                        return Ok(timeout_error(budget, context));
+                    }
+                },
+                None => response.await?,
+            };
+            if let Some(ttl) = cache_ttl {
+                let is_success = match response.response.body() {
+                    ResponseBody::GraphQL(body) => {
+                        response.response.status() == StatusCode::OK && body.errors.is_empty()
+                    }
+                    _ => false,
+                };
+                let headers = response.response.headers_mut();
+                if is_success && !headers.contains_key(CACHE_CONTROL) {
+                    let value = HeaderValue::from_str(&format!("max-age={}", ttl)).expect("Valid header value");
+                    headers.insert(CACHE_CONTROL, value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,271 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::RwLock;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+use crate::context::{Priority, TypedContext};
+use crate::GraphqlResponse;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task;
+use std::sync::Arc;
+
+//Context key, which holds id of request's primary fetches.
+const FETCH_PRIORITY_ID: &str = "graphql_router::fetch_priority_id";
+const BUFFER_SIZE: usize = 1024;
+
+#[inline(always)]
+fn default_defer() -> bool {
+    true
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Fetch priority config
+pub struct FetchPriorityConfig {
+    #[serde(default)]
+    ///Names of subgraphs, whose fetches are low priority, like expensive enrichment ones.
+    pub low: Vec<String>,
+    #[serde(default = "default_defer")]
+    ///Makes low priority fetches wait for primary fetches of the same request, that are in flight.
+    pub defer: bool,
+    #[serde(default)]
+    ///Number of requests in flight, above which low priority fetches are skipped.
+    ///
+    ///Fetches of requests with [Priority::High] are never skipped. Default is no limit.
+    pub shed_above: Option<usize>,
+}
+
+impl Default for FetchPriorityConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            low: Vec::new(),
+            defer: default_defer(),
+            shed_above: None,
+        }
+    }
+}
+
+impl FetchPriorityConfig {
+    #[inline]
+    ///Marks fetches of subgraph `name` as low priority.
+    pub fn low(mut self, name: &str) -> Self {
+        self.low.push(name.to_owned());
+        self
+    }
+
+    #[inline(always)]
+    ///Skips low priority fetches, when more than `max_in_flight` requests are in flight.
+    pub fn shed_above(mut self, max_in_flight: usize) -> Self {
+        self.shed_above = Some(max_in_flight);
+        self
+    }
+}
+
+///Counts request as in flight, until dropped.
+pub(crate) struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    #[inline]
+    pub(crate) fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlight {
+    #[inline]
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+///Returns response of skipped fetch, with `null` data and `FETCH_SKIPPED` error.
+fn skipped(name: &str, req: SubgraphRequest) -> Result<SubgraphResponse, BoxError> {
+    let body = serde_json::json!({
+        "data": null,
+        "errors": [crate::error::graphql_error(
+            &format!("Fetch of '{}' skipped under load", name),
+            "FETCH_SKIPPED",
+        )],
+    });
+    let body = GraphqlResponse::from_bytes(name, crate::pool::serialize(&body)?)?;
+    Ok(SubgraphResponse {
+        response: http::Response::builder().body(body)?.into(),
+        context: req.context,
+    })
+}
+
+///Lowers priority of fetches to some subgraphs, so that expensive enrichment degrades first during overload.
+///
+///Low priority fetches run after primary fetches of the same request, that are in flight, and are skipped with
+///`FETCH_SKIPPED` error, when too many requests are in flight.
+pub struct FetchPriority {
+    config: Arc<FetchPriorityConfig>,
+    in_flight: Arc<AtomicUsize>,
+    ongoing: Ongoing<Arc<RwLock<()>>>,
+}
+
+impl FetchPriority {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: FetchPriorityConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            in_flight: Arc::default(),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for FetchPriority {
+    type Config = FetchPriorityConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        if self.config.low.is_empty() {
+            return service;
+        }
+        PriorityRouterService {
+            inner: service,
+            in_flight: self.in_flight.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let is_low = self.config.low.iter().any(|low| low == name);
+        if self.config.low.is_empty() || (!is_low && !self.config.defer) {
+            return service;
+        }
+        PriorityFetchService {
+            name: name.into(),
+            inner: Buffer::new(service, BUFFER_SIZE),
+            is_low,
+            config: self.config.clone(),
+            in_flight: self.in_flight.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct PriorityRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    in_flight: Arc<AtomicUsize>,
+    ongoing: Ongoing<Arc<RwLock<()>>>,
+}
+
+impl tower::Service<RouterRequest> for PriorityRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let in_flight = InFlight::new(self.in_flight.clone());
+        let id = self.ongoing.start(Arc::default());
+        if let Err(error) = req.context.insert(FETCH_PRIORITY_ID, id) {
+            tracing::warn!("Unable to defer low priority fetches: {}", error);
+            self.ongoing.finish(id);
+            let response = self.inner.call(req);
+            return Box::pin(async move {
+                let _in_flight = in_flight;
+                response.await
+            });
+        }
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let _in_flight = in_flight;
+            let result = response.await;
+            ongoing.finish(id);
+            result
+        })
+    }
+}
+
+struct PriorityFetchService {
+    name: Arc<str>,
+    inner: Buffer<BoxService<SubgraphRequest, SubgraphResponse, BoxError>, SubgraphRequest>,
+    is_low: bool,
+    config: Arc<FetchPriorityConfig>,
+    in_flight: Arc<AtomicUsize>,
+    ongoing: Ongoing<Arc<RwLock<()>>>,
+}
+
+impl tower::Service<SubgraphRequest> for PriorityFetchService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let lock = match (self.config.defer, req.context.get::<_, u64>(FETCH_PRIORITY_ID)) {
+            (true, Ok(Some(id))) => self.ongoing.get(id),
+            _ => None,
+        };
+
+        if !self.is_low {
+            //Read access is taken right away, so that low priority fetches started together already see it.
+            let guard = lock.and_then(|lock| lock.try_read_owned().ok());
+            let response = self.inner.call(req);
+            return Box::pin(async move {
+                let _guard = guard;
+                response.await
+            });
+        }
+
+        let is_overloaded = match self.config.shed_above {
+            Some(shed_above) => self.in_flight.load(Ordering::Acquire) > shed_above,
+            None => false,
+        };
+        if is_overloaded && !matches!(TypedContext::new(&req.context).get::<Priority>(), Ok(Some(Priority::High))) {
+            tracing::info!("{}: Low priority fetch skipped under load", self.name);
+            return Box::pin(ready(skipped(&self.name, req)));
+        }
+        let lock = match lock {
+            Some(lock) => lock,
+            None => return Box::pin(self.inner.call(req)),
+        };
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            //Primary fetches of the same plan node are started together, so they get chance to start first.
+            tokio::task::yield_now().await;
+            //Write access is granted only once all primary fetches holding read access complete.
+            drop(lock.write().await);
+            inner.oneshot(req).await
+        })
+    }
+}
@@ -0,0 +1,37 @@
+use tokio::sync::mpsc;
+
+use core::future::Future;
+
+///Bounded queue of serialized payloads, sent in order by background task.
+pub(crate) struct PayloadQueue {
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl PayloadQueue {
+    ///Spawns task, passing each payload to `send` and logging failures as failures to publish `what`.
+    ///
+    ///Must be called within tokio runtime.
+    pub(crate) fn spawn<F, R>(what: &'static str, capacity: usize, mut send: F) -> Self
+    where
+        F: FnMut(Vec<u8>) -> R + Send + 'static,
+        R: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(capacity.max(1));
+        tokio::spawn(async move {
+            while let Some(payload) = receiver.recv().await {
+                if let Err(error) = send(payload).await {
+                    tracing::warn!("Failed to publish {}: {}", what, error);
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    #[inline]
+    ///Queues `payload`, returning `false` if it is dropped, because queue is full or its task is gone.
+    ///
+    ///Never waits, so that it can be called on request path.
+    pub(crate) fn push(&self, payload: Vec<u8>) -> bool {
+        self.sender.try_send(payload).is_ok()
+    }
+}
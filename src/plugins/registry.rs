@@ -0,0 +1,173 @@
+use apollo_router_core::{DynPlugin, Plugin};
+use tower::BoxError;
+
+use crate::config::from_value;
+use crate::GraphqlRouterBuilder;
+
+use core::future::Future;
+use core::pin::Pin;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+type PluginFuture = Pin<Box<dyn Future<Output = Result<Box<dyn DynPlugin>, BoxError>> + Send>>;
+type PluginFactory = Arc<dyn Fn(serde_json::Value) -> PluginFuture + Send + Sync>;
+type BuilderHook =
+    Arc<dyn Fn(serde_json::Value, GraphqlRouterBuilder) -> Result<GraphqlRouterBuilder, BoxError> + Send + Sync>;
+
+#[derive(Clone)]
+///Registry of plugins which can be created from config by name.
+pub struct PluginRegistry {
+    factories: BTreeMap<String, PluginFactory>,
+    builders: BTreeMap<String, BuilderHook>,
+}
+
+impl PluginRegistry {
+    #[inline]
+    ///Creates registry without any plugins.
+    pub fn empty() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+            builders: BTreeMap::new(),
+        }
+    }
+
+    ///Creates registry with all built-in plugins.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register::<super::PropagateHeaders>("propagate_headers");
+        registry.register::<super::RequestLimits>("request_limits");
+        registry.register::<super::Metrics>("metrics");
+        registry.register::<super::OperationLimits>("operation_limits");
+        registry.register::<super::LoadShed>("load_shed");
+        registry.register::<super::PartialResults>("partial_results");
+        registry.register::<super::FaultInjection>("fault_injection");
+        registry.register::<super::LatencyInjection>("latency_injection");
+        registry.register::<super::DebugCapture>("debug_capture");
+        registry.register::<super::ExposeQueryPlan>("expose_query_plan");
+        registry.register::<super::SubgraphLogging>("subgraph_logging");
+        registry.register::<super::FetchParallelism>("fetch_parallelism");
+        registry.register::<super::PersistedQueries>("persisted_queries");
+        registry.register::<super::HideSuggestions>("hide_suggestions");
+        registry.register::<super::ApiKeys>("api_keys");
+        registry.register::<super::Audit>("audit");
+        registry.register::<super::OperationEvents>("operation_events");
+        registry.register::<super::SubgraphErrors>("subgraph_errors");
+        registry.register::<super::ServerTiming>("server_timing");
+        registry.register::<super::Nullability>("nullability");
+        registry.register::<super::CookiePolicy>("cookie_policy");
+        registry.register::<super::Baggage>("baggage");
+        registry.register::<super::TracePropagation>("trace_propagation");
+        registry.register::<super::ResponseHeaders>("response_headers");
+        registry.register::<super::UserAgent>("user_agent");
+        registry.register::<super::PlanOverrides>("plan_overrides");
+        registry.register::<super::EntityBatching>("entity_batching");
+        registry.register::<super::OperationPolicies>("operation_policies");
+        registry.register::<super::FetchPriority>("fetch_priority");
+        registry.register::<super::MutationOrdering>("mutation_ordering");
+
+        //Plugins which router must be able to reach, or which require types of schema.
+        registry.register_builder("persisted_queries", |config, builder| {
+            let persisted = super::PersistedQueries::with_config(from_value::<_, serde_json::Error>(config)?)?;
+            Ok(builder.with_persisted_queries(persisted))
+        });
+        registry.register_builder("metrics", |config, builder| {
+            let metrics = super::Metrics::with_config(from_value::<_, serde_json::Error>(config)?);
+            Ok(builder.with_metrics(metrics))
+        });
+        registry.register_builder("api_keys", |config, builder| {
+            let keys = super::ApiKeys::with_config(from_value::<_, serde_json::Error>(config)?);
+            Ok(builder.with_api_keys(keys))
+        });
+        registry.register_builder("request_limits", |config, builder| {
+            Ok(builder.request_limits(from_value::<_, serde_json::Error>(config)?))
+        });
+        registry.register_builder("nullability", |config, builder| {
+            Ok(builder.nullability(from_value::<_, serde_json::Error>(config)?))
+        });
+        registry
+    }
+
+    ///Registers plugin under `name`, replacing existing one, if any.
+    ///
+    ///Plugin is created using its `Plugin::new` with `Config` deserialized from config value.
+    pub fn register<P: Plugin>(&mut self, name: &str) -> &mut Self {
+        let factory = |config: serde_json::Value| -> PluginFuture {
+            Box::pin(async move {
+                let config = crate::config::from_value::<P::Config, serde_json::Error>(config)?;
+                let plugin = P::new(config).await?;
+                Ok(Box::new(plugin) as Box<dyn DynPlugin>)
+            })
+        };
+        self.factories.insert(name.to_owned(), Arc::new(factory));
+        self
+    }
+
+    ///Registers `hook` under `name`, replacing existing one, if any.
+    ///
+    ///When router is built from config, hook is used instead of plugin factory to add plugin to router builder,
+    ///e.g. when router must be able to reach plugin or plugin requires types of schema.
+    pub fn register_builder<F>(&mut self, name: &str, hook: F) -> &mut Self
+    where
+        F: Fn(serde_json::Value, GraphqlRouterBuilder) -> Result<GraphqlRouterBuilder, BoxError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.builders.insert(name.to_owned(), Arc::new(hook));
+        self
+    }
+
+    #[inline(always)]
+    ///Returns whether plugin with `name` is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name) || self.builders.contains_key(name)
+    }
+
+    #[inline(always)]
+    ///Returns names of all registered plugins.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        let builders = self.builders.keys().filter(|name| !self.factories.contains_key(*name));
+        self.factories.keys().chain(builders).map(String::as_str)
+    }
+
+    ///Creates plugin with `name` using `config`.
+    ///
+    ///Returns `None` if plugin is not registered.
+    ///Missing config (i.e. `null`) is treated as empty object.
+    pub async fn create(&self, name: &str, config: serde_json::Value) -> Option<Result<Box<dyn DynPlugin>, BoxError>> {
+        let factory = self.factories.get(name)?;
+        let config = match config {
+            serde_json::Value::Null => serde_json::Value::Object(Default::default()),
+            config => config,
+        };
+        Some((factory)(config).await)
+    }
+
+    ///Adds plugin with `name` to `builder` using its builder hook with `config`.
+    ///
+    ///Returns `builder` back as error, if plugin has no builder hook.
+    ///Missing config (i.e. `null`) is treated as empty object.
+    pub(crate) fn build(
+        &self,
+        name: &str,
+        config: serde_json::Value,
+        builder: GraphqlRouterBuilder,
+    ) -> Result<Result<GraphqlRouterBuilder, BoxError>, GraphqlRouterBuilder> {
+        let hook = match self.builders.get(name) {
+            Some(hook) => hook,
+            None => return Err(builder),
+        };
+        let config = match config {
+            serde_json::Value::Null => serde_json::Value::Object(Default::default()),
+            config => config,
+        };
+        Ok((hook)(config, builder))
+    }
+}
+
+impl Default for PluginRegistry {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
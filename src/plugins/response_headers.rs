@@ -0,0 +1,217 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderName, HeaderValue, SET_COOKIE};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::{matches_pattern, Ongoing, RESERVED_HEADERS};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+//Context key, which holds id of request, whose subgraph response headers are collected.
+const RESPONSE_HEADERS_ID: &str = "graphql_router::response_headers_id";
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///Combination of header returned by multiple subgraphs
+pub enum HeaderMerge {
+    ///Value of subgraph, which responded first, is used.
+    FirstWins,
+    ///Value of subgraph, which responded last, is used.
+    LastWins,
+    ///Values of all subgraphs are used, in order of responses.
+    Append,
+}
+
+impl Default for HeaderMerge {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::FirstWins
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Response headers config
+pub struct ResponseHeadersConfig {
+    #[serde(default)]
+    ///Combination per pattern of header name, with `*` matching any sequence of characters.
+    ///
+    ///Headers not matching any pattern are not included in router response.
+    ///When several patterns match, the first one in alphabetical order applies.
+    pub headers: BTreeMap<String, HeaderMerge>,
+}
+
+impl ResponseHeadersConfig {
+    #[inline]
+    ///Returns combination of header `name`, if it is included in router response.
+    pub fn merge(&self, name: &str) -> Option<HeaderMerge> {
+        self.headers
+            .iter()
+            .find(|(pattern, _)| matches_pattern(&pattern.to_ascii_lowercase(), name))
+            .map(|(_, merge)| *merge)
+    }
+}
+
+///Includes headers of subgraph responses in router response, combining values returned by multiple subgraphs.
+///
+///Hop-by-hop and content headers are never included, while `Set-Cookie` is left to [CookiePolicy](super::CookiePolicy).
+///Headers already set on router response take precedence over subgraph ones.
+pub struct ResponseHeaders {
+    config: Arc<ResponseHeadersConfig>,
+    ongoing: Ongoing<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl ResponseHeaders {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: ResponseHeadersConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for ResponseHeaders {
+    type Config = ResponseHeadersConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        ResponseHeadersRouterService {
+            inner: service,
+            config: self.config.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        _: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        ResponseHeadersSubgraphService {
+            inner: service,
+            config: self.config.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct ResponseHeadersRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    config: Arc<ResponseHeadersConfig>,
+    ongoing: Ongoing<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl tower::Service<RouterRequest> for ResponseHeadersRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        let id = self.ongoing.start(Vec::new());
+        if let Err(error) = req.context.insert(RESPONSE_HEADERS_ID, id) {
+            tracing::warn!("Unable to collect subgraph response headers: {}", error);
+            self.ongoing.finish(id);
+            return self.inner.call(req);
+        }
+
+        let config = self.config.clone();
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut result = response.await;
+            let collected = ongoing.finish(id).unwrap_or_default();
+            if let Ok(response) = result.as_mut() {
+                let headers = response.response.headers_mut();
+                let own: HashSet<HeaderName> = headers.keys().cloned().collect();
+                for (name, value) in collected {
+                    if own.contains(&name) {
+                        continue;
+                    }
+                    match config.merge(name.as_str()) {
+                        Some(HeaderMerge::FirstWins) => {
+                            if !headers.contains_key(&name) {
+                                headers.insert(name, value);
+                            }
+                        }
+                        Some(HeaderMerge::LastWins) => {
+                            headers.insert(name, value);
+                        }
+                        Some(HeaderMerge::Append) => {
+                            headers.append(name, value);
+                        }
+                        None => (),
+                    }
+                }
+            }
+            result
+        })
+    }
+}
+
+struct ResponseHeadersSubgraphService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    config: Arc<ResponseHeadersConfig>,
+    ongoing: Ongoing<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl tower::Service<SubgraphRequest> for ResponseHeadersSubgraphService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(RESPONSE_HEADERS_ID) {
+            Ok(Some(id)) => id,
+            _ => return self.inner.call(req),
+        };
+
+        let config = self.config.clone();
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            let headers = response
+                .response
+                .headers()
+                .iter()
+                .filter(|(name, _)| !RESERVED_HEADERS.contains(*name) && **name != SET_COOKIE)
+                .filter(|(name, _)| config.merge(name.as_str()).is_some())
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            if !headers.is_empty() {
+                ongoing.update(id, |collected| collected.extend(headers));
+            }
+            Ok(response)
+        })
+    }
+}
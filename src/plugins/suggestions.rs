@@ -0,0 +1,71 @@
+use apollo_router_core::{Plugin, ResponseBody, RouterRequest, RouterResponse};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+
+const SUGGESTION: &str = "Did you mean ";
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Suggestions hiding config
+pub struct HideSuggestionsConfig {}
+
+///Removes `Did you mean ...?` suggestions from error messages, as they reveal schema even with introspection
+///disabled.
+pub struct HideSuggestions;
+
+///Returns `message` without suggestions, if it has any.
+pub fn strip_suggestions(message: &str) -> Option<String> {
+    let mut start = message.find(SUGGESTION)?;
+    let mut result = String::with_capacity(message.len());
+    let mut rest = message;
+    loop {
+        result.push_str(rest[..start].trim_end());
+        rest = &rest[start + SUGGESTION.len()..];
+        //Suggestion ends with question mark, but quoted names cannot contain it.
+        rest = match rest.find('?') {
+            Some(end) => &rest[end + 1..],
+            None => "",
+        };
+        start = match rest.find(SUGGESTION) {
+            Some(start) => start,
+            None => break,
+        };
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+impl Plugin for HideSuggestions {
+    type Config = HideSuggestionsConfig;
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self)))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        service
+            .map_response(|mut response: RouterResponse| {
+                if let ResponseBody::GraphQL(body) = response.response.body_mut() {
+                    for error in body.errors.iter_mut() {
+                        if let Some(message) = strip_suggestions(&error.message) {
+                            error.message = message;
+                        }
+                    }
+                }
+                response
+            })
+            .boxed()
+    }
+}
@@ -0,0 +1,93 @@
+use apollo_router_core::Context;
+
+use crate::context::{ClientIdentity, JwtClaims, RequestId, TypedContext};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    RequestId,
+    Client,
+    Jwt(String),
+    Context(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Header value, templated from request context
+///
+///Placeholders are enclosed in braces:
+///
+///- `{request_id}` - [RequestId];
+///- `{client}` - [ClientIdentity];
+///- `{jwt.<claim>}` - claim of [JwtClaims];
+///- `{context.<key>}` or `{<key>}` - context value by key.
+///
+///Unclosed brace is kept as it is.
+pub(crate) struct HeaderTemplate {
+    parts: Vec<Part>,
+}
+
+#[inline]
+fn to_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+impl HeaderTemplate {
+    pub(crate) fn parse(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+            let name = rest[start + 1..end].trim();
+            parts.push(match name {
+                "request_id" => Part::RequestId,
+                "client" => Part::Client,
+                name => match (name.strip_prefix("jwt."), name.strip_prefix("context.")) {
+                    (Some(claim), _) => Part::Jwt(claim.to_owned()),
+                    (None, Some(key)) => Part::Context(key.to_owned()),
+                    (None, None) => Part::Context(name.to_owned()),
+                },
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+        Self { parts }
+    }
+
+    ///Renders template, returning `None` if any placeholder has no value.
+    pub(crate) fn render(&self, context: &Context) -> Option<String> {
+        let typed = TypedContext::new(context);
+        let mut claims = None;
+        let mut value = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(literal) => value.push_str(literal),
+                Part::RequestId => value.push_str(&typed.get::<RequestId>().ok()??.0),
+                Part::Client => value.push_str(&typed.get::<ClientIdentity>().ok()??.0),
+                Part::Jwt(claim) => {
+                    if claims.is_none() {
+                        claims = typed.get::<JwtClaims>().ok()?;
+                    }
+                    let claims = claims.as_ref()?;
+                    value.push_str(&to_string(claims.0.get(claim)?)?);
+                }
+                Part::Context(key) => {
+                    let context_value = context.get::<_, serde_json::Value>(key.as_str()).ok()??;
+                    value.push_str(&to_string(&context_value)?);
+                }
+            }
+        }
+        Some(value)
+    }
+}
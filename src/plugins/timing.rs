@@ -0,0 +1,313 @@
+use apollo_router_core::{
+    Context, Plugin, QueryPlannerRequest, QueryPlannerResponse, RouterRequest, RouterResponse, SubgraphRequest,
+    SubgraphResponse,
+};
+use hyper::http::header::{HeaderName, HeaderValue};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::Ongoing;
+
+use core::fmt::Write;
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use core::time::Duration;
+use std::sync::Arc;
+use std::time::Instant;
+
+//Context key, which holds id of request, which timings are collected.
+const TIMING_ID: &str = "graphql_router::server_timing_id";
+//Context key, which holds duration of HTTP request parsing in milliseconds.
+const PARSE_DURATION: &str = "graphql_router::parse_duration";
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+#[inline]
+///Stores duration of HTTP request parsing in `context`, to be reported as `parse` phase.
+pub(crate) fn record_parse_duration(context: &Context, duration: Duration) {
+    if let Err(error) = context.insert(PARSE_DURATION, duration.as_secs_f64() * 1000.0) {
+        tracing::warn!("Unable to record parse duration: {}", error);
+    }
+}
+
+fn default_header() -> String {
+    "x-server-timing".to_owned()
+}
+
+#[inline(always)]
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///Server timing config
+pub struct ServerTimingConfig {
+    #[serde(default = "default_header")]
+    ///Request header, which enables timings for request.
+    pub header: String,
+    #[serde(default)]
+    ///Reports timings of every request, regardless of header.
+    pub always: bool,
+    #[serde(default = "default_enabled")]
+    ///Includes timings in response `extensions.serverTiming`.
+    pub extensions: bool,
+    #[serde(default = "default_enabled")]
+    ///Includes timings in `Server-Timing` response header.
+    pub server_timing_header: bool,
+}
+
+impl Default for ServerTimingConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            header: default_header(),
+            always: false,
+            extensions: default_enabled(),
+            server_timing_header: default_enabled(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FetchTiming {
+    subgraph: String,
+    duration_ms: f64,
+}
+
+struct Timings {
+    started: Instant,
+    plan_ms: Option<f64>,
+    fetches: Vec<FetchTiming>,
+}
+
+//Server-Timing metric names are tokens, so everything else is replaced.
+fn metric_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| match ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+            true => ch,
+            false => '_',
+        })
+        .collect()
+}
+
+///Reports per-phase timings of request: parse, plan, each subgraph fetch and total.
+///
+///Timings are included in response `extensions.serverTiming` and/or `Server-Timing` header.
+pub struct ServerTiming {
+    config: Arc<ServerTimingConfig>,
+    ongoing: Ongoing<Timings>,
+}
+
+impl ServerTiming {
+    #[inline]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: ServerTimingConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ongoing: Ongoing::default(),
+        }
+    }
+}
+
+impl Plugin for ServerTiming {
+    type Config = ServerTimingConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        TimingRouterService {
+            inner: service,
+            config: self.config.clone(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn query_planning_service(
+        &mut self,
+        service: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ) -> BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError> {
+        TimingPlanService {
+            inner: service,
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        TimingFetchService {
+            inner: service,
+            name: name.into(),
+            ongoing: self.ongoing.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct TimingRouterService {
+    inner: BoxService<RouterRequest, RouterResponse, BoxError>,
+    config: Arc<ServerTimingConfig>,
+    ongoing: Ongoing<Timings>,
+}
+
+impl tower::Service<RouterRequest> for TimingRouterService {
+    type Response = RouterResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<RouterResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RouterRequest) -> Self::Future {
+        if !self.config.always && !req.originating_request.headers().contains_key(self.config.header.as_str()) {
+            return self.inner.call(req);
+        }
+
+        let id = self.ongoing.start(Timings {
+            started: Instant::now(),
+            plan_ms: None,
+            fetches: Vec::new(),
+        });
+        if let Err(error) = req.context.insert(TIMING_ID, id) {
+            tracing::warn!("Unable to collect server timing: {}", error);
+            self.ongoing.finish(id);
+            return self.inner.call(req);
+        }
+
+        let config = self.config.clone();
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let mut result = response.await;
+            let (timings, response) = match (ongoing.finish(id), result.as_mut()) {
+                (Some(timings), Ok(response)) => (timings, response),
+                _ => return result,
+            };
+            let total_ms = timings.started.elapsed().as_secs_f64() * 1000.0;
+            let parse_ms = response.context.get::<_, f64>(PARSE_DURATION).ok().flatten();
+
+            if config.server_timing_header {
+                let mut header = String::new();
+                if let Some(parse_ms) = parse_ms {
+                    let _ = write!(header, "parse;dur={:.3}, ", parse_ms);
+                }
+                if let Some(plan_ms) = timings.plan_ms {
+                    let _ = write!(header, "plan;dur={:.3}, ", plan_ms);
+                }
+                for fetch in timings.fetches.iter() {
+                    let _ = write!(header, "fetch-{};dur={:.3}, ", metric_name(&fetch.subgraph), fetch.duration_ms);
+                }
+                let _ = write!(header, "total;dur={:.3}", total_ms);
+                match HeaderValue::from_str(&header) {
+                    Ok(header) => {
+                        response.response.headers_mut().insert(SERVER_TIMING, header);
+                    }
+                    Err(error) => tracing::warn!("Invalid Server-Timing header: {}", error),
+                }
+            }
+            if config.extensions {
+                let extension = serde_json::json!({
+                    "parseMs": parse_ms,
+                    "planMs": timings.plan_ms,
+                    "fetches": timings.fetches,
+                    "totalMs": total_ms,
+                });
+                super::insert_extension(response, "serverTiming", extension);
+            }
+            result
+        })
+    }
+}
+
+struct TimingPlanService {
+    inner: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ongoing: Ongoing<Timings>,
+}
+
+impl tower::Service<QueryPlannerRequest> for TimingPlanService {
+    type Response = QueryPlannerResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<QueryPlannerResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: QueryPlannerRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(TIMING_ID) {
+            Ok(Some(id)) => id,
+            _ => return self.inner.call(req),
+        };
+
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = response.await;
+            let plan_ms = started.elapsed().as_secs_f64() * 1000.0;
+            ongoing.update(id, |timings| timings.plan_ms = Some(plan_ms));
+            result
+        })
+    }
+}
+
+struct TimingFetchService {
+    inner: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    name: Arc<str>,
+    ongoing: Ongoing<Timings>,
+}
+
+impl tower::Service<SubgraphRequest> for TimingFetchService {
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<SubgraphResponse, BoxError>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SubgraphRequest) -> Self::Future {
+        let id = match req.context.get::<_, u64>(TIMING_ID) {
+            Ok(Some(id)) => id,
+            _ => return self.inner.call(req),
+        };
+
+        let name = self.name.clone();
+        let ongoing = self.ongoing.clone();
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = response.await;
+            let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+            ongoing.update(id, |timings| {
+                timings.fetches.push(FetchTiming {
+                    subgraph: name.to_string(),
+                    duration_ms,
+                })
+            });
+            result
+        })
+    }
+}
@@ -0,0 +1,279 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderMap, HeaderName, HeaderValue};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::manage_header;
+use crate::context::{TraceSampled, TypedContext};
+use crate::log::LogFilter;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+const B3: HeaderName = HeaderName::from_static("b3");
+const B3_TRACE_ID: HeaderName = HeaderName::from_static("x-b3-traceid");
+const B3_SPAN_ID: HeaderName = HeaderName::from_static("x-b3-spanid");
+const B3_PARENT_SPAN_ID: HeaderName = HeaderName::from_static("x-b3-parentspanid");
+const B3_SAMPLED: HeaderName = HeaderName::from_static("x-b3-sampled");
+const B3_FLAGS: HeaderName = HeaderName::from_static("x-b3-flags");
+
+static TRACE_HEADERS: [HeaderName; 8] = [
+    TRACEPARENT,
+    TRACESTATE,
+    B3,
+    B3_TRACE_ID,
+    B3_SPAN_ID,
+    B3_PARENT_SPAN_ID,
+    B3_SAMPLED,
+    B3_FLAGS,
+];
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///Format of trace context headers
+pub enum TraceFormat {
+    ///W3C Trace Context: `traceparent` and `tracestate`.
+    W3c,
+    ///Zipkin B3 single header: `b3`.
+    B3Single,
+    ///Zipkin B3 multiple headers: `X-B3-TraceId`, `X-B3-SpanId` and `X-B3-Sampled`.
+    B3Multi,
+}
+
+impl Default for TraceFormat {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::W3c
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+///Trace propagation config
+pub struct TracePropagationConfig {
+    #[serde(default)]
+    ///Format of trace context sent to subgraphs.
+    ///
+    ///Client's trace context is accepted in any format.
+    pub format: TraceFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+///Trace context of client request
+struct TraceContext {
+    //32 lowercase hex digits, with 64-bit B3 ids padded by zeros.
+    trace_id: String,
+    //16 lowercase hex digits.
+    span_id: String,
+    //`None` defers sampling decision to receiver.
+    sampled: Option<bool>,
+    tracestate: Option<HeaderValue>,
+}
+
+#[inline]
+fn header<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim)
+}
+
+#[inline]
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: String) {
+    match HeaderValue::from_str(&value) {
+        Ok(value) => {
+            headers.insert(name, value);
+        }
+        Err(error) => tracing::warn!("Invalid trace header '{}': {}", name, error),
+    }
+}
+
+#[inline]
+fn is_id(id: &str, len: usize) -> bool {
+    id.len() == len && id.bytes().all(|byte| byte.is_ascii_hexdigit()) && id.bytes().any(|byte| byte != b'0')
+}
+
+fn trace_id(id: &str) -> Option<String> {
+    match id.len() {
+        16 if is_id(id, 16) => Some(format!("{:0>32}", id.to_ascii_lowercase())),
+        32 if is_id(id, 32) => Some(id.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+fn span_id(id: &str) -> Option<String> {
+    match is_id(id, 16) {
+        true => Some(id.to_ascii_lowercase()),
+        false => None,
+    }
+}
+
+fn b3_sampled(sampled: &str) -> Option<bool> {
+    match sampled {
+        //Debug implies sampling
+        "1" | "d" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl TraceContext {
+    ///Extracts context from W3C headers, falling back to B3 single and multiple headers.
+    fn extract(headers: &HeaderMap) -> Option<Self> {
+        Self::extract_w3c(headers)
+            .or_else(|| Self::extract_b3_single(headers))
+            .or_else(|| Self::extract_b3_multi(headers))
+    }
+
+    fn extract_w3c(headers: &HeaderMap) -> Option<Self> {
+        let mut parts = header(headers, &TRACEPARENT)?.split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next().filter(|id| id.len() == 32).and_then(trace_id)?;
+        let span_id = parts.next().and_then(span_id)?;
+        let flags = parts.next().filter(|flags| flags.len() == 2)?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        if version.len() != 2 || version == "ff" {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: Some(flags & 1 == 1),
+            tracestate: headers.get(TRACESTATE).cloned(),
+        })
+    }
+
+    fn extract_b3_single(headers: &HeaderMap) -> Option<Self> {
+        let mut parts = header(headers, &B3)?.split('-');
+        let trace_id = parts.next().and_then(trace_id)?;
+        let span_id = parts.next().and_then(span_id)?;
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: parts.next().and_then(b3_sampled),
+            tracestate: None,
+        })
+    }
+
+    fn extract_b3_multi(headers: &HeaderMap) -> Option<Self> {
+        let trace_id = header(headers, &B3_TRACE_ID).and_then(trace_id)?;
+        let span_id = header(headers, &B3_SPAN_ID).and_then(span_id)?;
+        let sampled = match header(headers, &B3_FLAGS) {
+            Some("1") => Some(true),
+            _ => header(headers, &B3_SAMPLED).and_then(b3_sampled),
+        };
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled,
+            tracestate: None,
+        })
+    }
+
+    ///Writes context into `headers` in specified `format`.
+    fn inject(&self, format: TraceFormat, headers: &mut HeaderMap) {
+        match format {
+            TraceFormat::W3c => {
+                //W3C has no deferred sampling, so it is treated as not sampled.
+                let flags = if self.sampled == Some(true) { "01" } else { "00" };
+                insert(headers, TRACEPARENT, format!("00-{}-{}-{}", self.trace_id, self.span_id, flags));
+                if let Some(tracestate) = self.tracestate.as_ref() {
+                    headers.insert(TRACESTATE, tracestate.clone());
+                }
+            }
+            TraceFormat::B3Single => {
+                let value = match self.sampled {
+                    Some(sampled) => format!("{}-{}-{}", self.trace_id, self.span_id, sampled as u8),
+                    None => format!("{}-{}", self.trace_id, self.span_id),
+                };
+                insert(headers, B3, value);
+            }
+            TraceFormat::B3Multi => {
+                insert(headers, B3_TRACE_ID, self.trace_id.clone());
+                insert(headers, B3_SPAN_ID, self.span_id.clone());
+                if let Some(sampled) = self.sampled {
+                    insert(headers, B3_SAMPLED, (sampled as u8).to_string());
+                }
+            }
+        }
+    }
+}
+
+///Propagates trace context of client request to subgraphs in configured format.
+///
+///Client's trace context may be either W3C or B3, so that router can bridge Zipkin and W3C services.
+///As router does not record spans, subgraph spans are children of client's span.
+///When client defers sampling decision, router makes it according to [LogFilter::set_trace_sampling].
+pub struct TracePropagation {
+    format: TraceFormat,
+}
+
+impl TracePropagation {
+    #[inline(always)]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: TracePropagationConfig) -> Self {
+        Self { format: config.format }
+    }
+}
+
+impl Plugin for TracePropagation {
+    type Config = TracePropagationConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        super::checkpoint(service, |req: RouterRequest| {
+            for name in TRACE_HEADERS.iter() {
+                if let Err(error) = manage_header(&req.context, name.as_str()) {
+                    tracing::warn!("Unable to manage trace header '{}': {}", name, error);
+                }
+            }
+            //Decision is made once per request, so that all subgraphs agree on it.
+            let context = TraceContext::extract(req.originating_request.headers());
+            if let Some(None) = context.map(|context| context.sampled) {
+                if let Some(sampled) = LogFilter::global().sample_trace() {
+                    if let Err(error) = TypedContext::new(&req.context).insert(TraceSampled(sampled)) {
+                        tracing::warn!("Unable to store trace sampling decision: {}", error);
+                    }
+                }
+            }
+            Ok(req)
+        })
+    }
+
+    fn subgraph_service(
+        &mut self,
+        _: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let format = self.format;
+        service
+            .map_request(move |mut req: SubgraphRequest| {
+                let context = TraceContext::extract(req.originating_request.headers());
+                let headers = req.subgraph_request.headers_mut();
+                for name in TRACE_HEADERS.iter() {
+                    headers.remove(name);
+                }
+                if let Some(mut context) = context {
+                    if context.sampled.is_none() {
+                        if let Ok(Some(TraceSampled(sampled))) = TypedContext::new(&req.context).get() {
+                            context.sampled = Some(sampled);
+                        }
+                    }
+                    context.inject(format, req.subgraph_request.headers_mut());
+                }
+                req
+            })
+            .boxed()
+    }
+}
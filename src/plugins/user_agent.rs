@@ -0,0 +1,188 @@
+use apollo_router_core::{Plugin, RouterRequest, RouterResponse, SubgraphRequest, SubgraphResponse};
+use hyper::http::header::{HeaderMap, HeaderValue, USER_AGENT};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::util::BoxService;
+use tower::{BoxError, ServiceExt};
+
+use super::{manage_header, matches_pattern};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use std::sync::Arc;
+
+//Client hints, including legacy ones without `Sec-CH-` prefix.
+const CLIENT_HINTS: [&str; 8] = [
+    "sec-ch-*",
+    "dpr",
+    "viewport-width",
+    "width",
+    "device-memory",
+    "rtt",
+    "downlink",
+    "ect",
+];
+
+fn default_identity() -> String {
+    format!("graphql-router/{}", env!("CARGO_PKG_VERSION"))
+}
+
+#[inline(always)]
+fn default_forward_client_hints() -> bool {
+    true
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+///`User-Agent` sent to subgraphs
+pub enum UserAgentMode {
+    ///Client's `User-Agent` is forwarded as it is.
+    Forward,
+    ///Router identity is appended to client's `User-Agent`, or used alone if client has none.
+    Append,
+    ///Router identity replaces client's `User-Agent`.
+    Replace,
+}
+
+impl Default for UserAgentMode {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Forward
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+///User agent config
+pub struct UserAgentConfig {
+    #[serde(default)]
+    ///Handling of `User-Agent`.
+    pub mode: UserAgentMode,
+    #[serde(default = "default_identity")]
+    ///Router identity, defaulting to `graphql-router/<version>`.
+    pub identity: String,
+    #[serde(default = "default_forward_client_hints")]
+    ///Forwards client hints, like `Sec-CH-UA` and `Viewport-Width`, to subgraphs.
+    pub forward_client_hints: bool,
+}
+
+impl Default for UserAgentConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            mode: UserAgentMode::default(),
+            identity: default_identity(),
+            forward_client_hints: default_forward_client_hints(),
+        }
+    }
+}
+
+impl UserAgentConfig {
+    ///Returns `User-Agent` for subgraph requests, made on behalf of client request with `headers`.
+    fn user_agent(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let client = headers.get(USER_AGENT);
+        let user_agent = match (self.mode, client) {
+            (UserAgentMode::Forward, client) => return client.cloned(),
+            (UserAgentMode::Append, Some(client)) => {
+                let mut user_agent = client.as_bytes().to_vec();
+                user_agent.push(b' ');
+                user_agent.extend_from_slice(self.identity.as_bytes());
+                HeaderValue::from_bytes(&user_agent)
+            }
+            (UserAgentMode::Append, None) | (UserAgentMode::Replace, _) => HeaderValue::from_str(&self.identity),
+        };
+        match user_agent {
+            Ok(user_agent) => Some(user_agent),
+            Err(error) => {
+                tracing::warn!("Invalid User-Agent: {}", error);
+                client.cloned()
+            }
+        }
+    }
+}
+
+#[inline]
+fn is_client_hint(name: &str) -> bool {
+    CLIENT_HINTS.iter().any(|pattern| matches_pattern(pattern, name))
+}
+
+///Controls `User-Agent` and client hints sent to subgraphs, which some of them use for analytics or bot filtering.
+pub struct UserAgent {
+    config: Arc<UserAgentConfig>,
+}
+
+impl UserAgent {
+    #[inline(always)]
+    ///Creates plugin with specified config.
+    pub fn with_config(config: UserAgentConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl Plugin for UserAgent {
+    type Config = UserAgentConfig;
+
+    #[inline(always)]
+    fn new<'a>(config: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Ok(Self::with_config(config))))
+    }
+
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        super::checkpoint(service, |req: RouterRequest| {
+            for pattern in core::iter::once(USER_AGENT.as_str()).chain(CLIENT_HINTS.iter().copied()) {
+                if let Err(error) = manage_header(&req.context, pattern) {
+                    tracing::warn!("Unable to manage header '{}': {}", pattern, error);
+                }
+            }
+            Ok(req)
+        })
+    }
+
+    fn subgraph_service(
+        &mut self,
+        _: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let config = self.config.clone();
+        service
+            .map_request(move |mut req: SubgraphRequest| {
+                let client = req.originating_request.headers();
+                let user_agent = config.user_agent(client);
+                let client_hints = match config.forward_client_hints {
+                    true => client
+                        .iter()
+                        .filter(|(name, _)| is_client_hint(name.as_str()))
+                        .map(|(name, value)| (name.clone(), value.clone()))
+                        .collect(),
+                    false => Vec::new(),
+                };
+
+                let headers = req.subgraph_request.headers_mut();
+                let hints = headers
+                    .keys()
+                    .filter(|name| is_client_hint(name.as_str()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for name in hints {
+                    headers.remove(name);
+                }
+                headers.remove(USER_AGENT);
+                if let Some(user_agent) = user_agent {
+                    headers.insert(USER_AGENT, user_agent);
+                }
+                for (name, value) in client_hints {
+                    headers.append(name, value);
+                }
+                req
+            })
+            .boxed()
+    }
+}
@@ -0,0 +1,124 @@
+//! Reusable serialization buffers
+
+use bytes::Bytes;
+use serde::Serialize;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+const BUFFER_CAPACITY: usize = 4 * 1024;
+const MAX_POOLED_BUFFERS: usize = 64;
+//Buffers grown beyond are dropped, so that rare large payload does not pin memory.
+const MAX_POOLED_CAPACITY: usize = 1024 * 1024;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+///Buffer pool statistics
+pub struct PoolMetrics {
+    ///Number of serializations, which reused pooled buffer.
+    pub hits: u64,
+    ///Number of serializations, which allocated new buffer.
+    pub misses: u64,
+    ///Number of buffers dropped instead of returning to pool.
+    pub dropped: u64,
+    ///Number of buffers currently in pool.
+    pub pooled: usize,
+    ///Total capacity of buffers currently in pool.
+    pub pooled_capacity: usize,
+}
+
+///Pool of buffers, used to serialize request and response bodies.
+///
+///Serialized bytes are copied out of buffer, so buffer keeps its allocation for subsequent serializations.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl BufferPool {
+    #[inline(always)]
+    ///Creates empty pool.
+    pub const fn new() -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns pool shared by router.
+    pub fn global() -> &'static Self {
+        static POOL: BufferPool = BufferPool::new();
+        &POOL
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+        match self.buffers.lock() {
+            Ok(buffers) => buffers,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    fn take(&self) -> Vec<u8> {
+        let buffer = self.lock().pop();
+        match buffer {
+            Some(buffer) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(BUFFER_CAPACITY)
+            }
+        }
+    }
+
+    fn give(&self, mut buffer: Vec<u8>) {
+        buffer.clear();
+        if buffer.capacity() <= MAX_POOLED_CAPACITY {
+            let mut buffers = self.lock();
+            if buffers.len() < MAX_POOLED_BUFFERS {
+                buffers.push(buffer);
+                return;
+            }
+        }
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///Serializes `value` as JSON.
+    pub fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Bytes, serde_json::Error> {
+        let mut buffer = self.take();
+        let result = serde_json::to_writer(&mut buffer, value);
+        let result = result.map(|_| Bytes::copy_from_slice(&buffer));
+        self.give(buffer);
+        result
+    }
+
+    ///Returns current statistics.
+    pub fn metrics(&self) -> PoolMetrics {
+        let buffers = self.lock();
+        PoolMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            pooled: buffers.len(),
+            pooled_capacity: buffers.iter().map(|buffer| buffer.capacity()).sum(),
+        }
+    }
+}
+
+impl Default for BufferPool {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline(always)]
+///Serializes `value` as JSON, using [BufferPool::global].
+pub fn serialize<T: Serialize + ?Sized>(value: &T) -> Result<Bytes, serde_json::Error> {
+    BufferPool::global().serialize(value)
+}
@@ -0,0 +1,143 @@
+//! Fast path for supergraphs with single subgraph
+//!
+//! When router is merely a gateway in front of one subgraph, planning and re-serialization of every request are
+//! wasted work. [Proxy] forwards request body to subgraph as-is and streams its response back.
+//!
+//! As request never reaches router, plugins are not applied, so it should only be used when router does not
+//! transform requests or responses. Enable it using [ServerBuilder::proxy](crate::server::ServerBuilder::proxy).
+
+use hyper::http::header::{
+    HeaderMap, HeaderName, CONNECTION, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, TE, TRAILER, TRANSFER_ENCODING,
+    UPGRADE,
+};
+use hyper::StatusCode;
+
+use crate::remote::{http_client, HttpClient};
+use crate::server::error_response;
+use crate::{HttpRequest, Schema};
+
+use core::fmt;
+use core::time::Duration;
+use std::sync::Arc;
+
+static HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    CONNECTION,
+    PROXY_AUTHENTICATE,
+    PROXY_AUTHORIZATION,
+    TE,
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+    HOST,
+];
+
+#[derive(Debug)]
+///Failure to create proxy from schema
+pub enum ProxyError {
+    ///Schema has number of subgraphs other than one.
+    SubgraphCount(usize),
+}
+
+impl fmt::Display for ProxyError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProxyError::SubgraphCount(count) => {
+                fmt.write_fmt(format_args!("Proxy requires exactly one subgraph, but schema has {}", count))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {}
+
+///Forwards GraphQL requests to single subgraph without planning.
+pub struct Proxy {
+    name: String,
+    url: hyper::Uri,
+    client: Arc<HttpClient>,
+    timeout: Option<Duration>,
+}
+
+impl Proxy {
+    #[inline]
+    ///Creates proxy to subgraph `name` at `url`.
+    pub fn new(name: &str, url: hyper::Uri) -> Self {
+        Self {
+            name: name.to_owned(),
+            url,
+            client: Arc::new(http_client()),
+            timeout: None,
+        }
+    }
+
+    ///Creates proxy to the only subgraph of `schema`, using its URL.
+    pub fn from_schema(schema: &Schema) -> Result<Self, ProxyError> {
+        let mut subgraphs = schema.subgraphs();
+        match (subgraphs.next(), subgraphs.next()) {
+            (Some((name, url)), None) => Ok(Self::new(name, url.clone())),
+            (None, _) => Err(ProxyError::SubgraphCount(0)),
+            (Some(_), Some(_)) => Err(ProxyError::SubgraphCount(2 + subgraphs.count())),
+        }
+    }
+
+    #[inline(always)]
+    ///Uses `client` instead of creating own one.
+    pub fn client(self, client: Arc<HttpClient>) -> Self {
+        Self { client, ..self }
+    }
+
+    #[inline(always)]
+    ///Sets time limit to receive response headers from subgraph.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    #[inline(always)]
+    ///Returns name of subgraph.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    ///Forwards `req` to subgraph, returning its response with streamed body.
+    ///
+    ///Subgraph failures are responded with `BAD_GATEWAY` or `GATEWAY_TIMEOUT`.
+    pub async fn forward(&self, req: HttpRequest) -> hyper::Response<hyper::Body> {
+        let (mut parts, body) = req.into_parts();
+        remove_hop_by_hop(&mut parts.headers);
+        parts.uri = self.url.clone();
+        let request = hyper::Request::from_parts(parts, body);
+
+        let response = self.client.request(request);
+        let response = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, response).await {
+                Ok(response) => response,
+                Err(_) => {
+                    tracing::warn!("{}: Proxied request timed out", self.name);
+                    return error_response(StatusCode::GATEWAY_TIMEOUT, "Subgraph timed out");
+                }
+            },
+            None => response.await,
+        };
+
+        match response {
+            Ok(mut response) => {
+                remove_hop_by_hop(response.headers_mut());
+                response
+            }
+            Err(error) => {
+                tracing::warn!("{}: Proxied request failed: {}", self.name, error);
+                error_response(StatusCode::BAD_GATEWAY, "Subgraph is unavailable")
+            }
+        }
+    }
+}
+
+fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    for header in HOP_BY_HOP_HEADERS.iter() {
+        headers.remove(header);
+    }
+}
@@ -0,0 +1,669 @@
+//! Rebuilding of router at runtime
+//!
+//! Router service is built once from plugins and subgraphs, so bringing new subgraph online requires building it
+//! again. Router keeps its plugins and shared subgraph services, so that it can build new service and swap it with
+//! current one, without restarting process.
+//!
+//! Plugins are shared by all builds, so their state (e.g. counters, audit chain or connections) survives rebuild.
+//! Only plugins depending on schema are created again with new schema.
+//!
+//! New schema can also be staged in standby slot, smoke tested, promoted and rolled back, see
+//! [GraphqlRouter::stage_schema].
+
+use apollo_router_core::{
+    DynPlugin, ExecutionRequest, ExecutionResponse, PluggableRouterServiceBuilder, Plugin, QueryPlannerRequest,
+    QueryPlannerResponse, RouterRequest, RouterResponse, Schema, ServiceBuildError, SubgraphRequest,
+    SubgraphResponse,
+};
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::BoxError;
+
+use crate::plugins::{PluginRegistry, RequestLimitsConfig};
+use crate::{error, manifest, plan, warmup};
+use crate::{BuildGraph, GraphqlRouter, RemoteSettings, RemoteSettingsHandle, RouterService, WarmupOperation};
+
+use core::fmt;
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+const BUFFER_SIZE: usize = 1024;
+//Plugins guarding access or defining semantics of operations, which are not safe to disable at runtime.
+const PINNED_PLUGINS: [&str; 5] = [
+    "api_keys",
+    "request_limits",
+    "operation_limits",
+    "persisted_queries",
+    "mutation_ordering",
+];
+
+pub(crate) type PluginFactory = Arc<dyn Fn(&Arc<Schema>) -> Box<dyn DynPlugin> + Send + Sync>;
+pub(crate) type SubgraphService = BoxService<SubgraphRequest, SubgraphResponse, BoxError>;
+type SharedSubgraphService = Buffer<SubgraphService, SubgraphRequest>;
+pub(crate) type Settings = BTreeMap<String, RemoteSettingsHandle>;
+
+#[derive(Debug)]
+///Failure to rebuild router
+pub enum RebuildError {
+    ///Subgraph is not present in new schema.
+    NotInSchema(String),
+    ///Removed subgraph is still present in new schema.
+    InSchema(String),
+    ///Subgraph is already added.
+    SubgraphExists(String),
+    ///Subgraph is not added.
+    UnknownSubgraph(String),
+    ///Plugin is not added.
+    UnknownPlugin(String),
+    ///Plugin is not safe to disable at runtime.
+    PluginPinned(String),
+    ///Plugin failed to be created with new config.
+    Plugin {
+        ///Name of plugin.
+        name: String,
+        ///Error of plugin.
+        error: BoxError,
+    },
+    ///Router service failed to build, likely because query planner cannot handle new schema.
+    Build(ServiceBuildError),
+    ///No schema is staged.
+    NoStandby,
+    ///No schema was replaced by promotion.
+    NoPrevious,
+    ///Some of smoke operations failed against staged schema.
+    SmokeTest {
+        ///Number of failed operations.
+        failed: usize,
+        ///Number of executed operations.
+        total: usize,
+    },
+}
+
+impl fmt::Display for RebuildError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RebuildError::NotInSchema(name) => {
+                fmt.write_fmt(format_args!("Subgraph '{}' is not present in schema", name))
+            }
+            RebuildError::InSchema(name) => {
+                fmt.write_fmt(format_args!("Subgraph '{}' is still present in schema", name))
+            }
+            RebuildError::SubgraphExists(name) => fmt.write_fmt(format_args!("Subgraph '{}' is already added", name)),
+            RebuildError::UnknownSubgraph(name) => fmt.write_fmt(format_args!("Subgraph '{}' is not added", name)),
+            RebuildError::UnknownPlugin(name) => fmt.write_fmt(format_args!("Plugin '{}' is not added", name)),
+            RebuildError::PluginPinned(name) => {
+                fmt.write_fmt(format_args!("Plugin '{}' cannot be toggled at runtime", name))
+            }
+            RebuildError::Plugin { name, error } => {
+                fmt.write_fmt(format_args!("Plugin '{}' failed to be created: {}", name, error))
+            }
+            RebuildError::Build(error) => fmt.write_fmt(format_args!("Failed to build router: {}", error)),
+            RebuildError::NoStandby => fmt.write_str("No schema is staged"),
+            RebuildError::NoPrevious => fmt.write_str("No schema to roll back to"),
+            RebuildError::SmokeTest { failed, total } => {
+                fmt.write_fmt(format_args!("{}/{} smoke operations failed", failed, total))
+            }
+        }
+    }
+}
+
+impl std::error::Error for RebuildError {}
+
+///Plugin instance, shared by all builds of router.
+///
+///Each build wraps services of the same instance, so plugin keeps its state, when router is rebuilt.
+#[derive(Clone)]
+pub(crate) struct SharedPlugin {
+    plugin: Arc<Mutex<Box<dyn DynPlugin>>>,
+}
+
+impl SharedPlugin {
+    #[inline(always)]
+    pub(crate) fn new(plugin: Box<dyn DynPlugin>) -> Self {
+        Self {
+            plugin: Arc::new(Mutex::new(plugin)),
+        }
+    }
+
+    #[inline]
+    fn with<R, F: FnOnce(&mut Box<dyn DynPlugin>) -> R>(&self, cb: F) -> R {
+        //Lock is held only while router service is built.
+        match self.plugin.lock() {
+            Ok(mut plugin) => cb(&mut plugin),
+            Err(error) => cb(&mut error.into_inner()),
+        }
+    }
+}
+
+impl Plugin for SharedPlugin {
+    type Config = ();
+
+    #[inline(always)]
+    fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+    where
+        Self: 'a,
+    {
+        Box::pin(ready(Err("SharedPlugin can be only created by router builder".into())))
+    }
+
+    #[inline]
+    fn router_service(
+        &mut self,
+        service: BoxService<RouterRequest, RouterResponse, BoxError>,
+    ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+        self.with(|plugin| plugin.router_service(service))
+    }
+
+    #[inline]
+    fn query_planning_service(
+        &mut self,
+        service: BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError>,
+    ) -> BoxService<QueryPlannerRequest, QueryPlannerResponse, BoxError> {
+        self.with(|plugin| plugin.query_planning_service(service))
+    }
+
+    #[inline]
+    fn execution_service(
+        &mut self,
+        service: BoxService<ExecutionRequest, ExecutionResponse, BoxError>,
+    ) -> BoxService<ExecutionRequest, ExecutionResponse, BoxError> {
+        self.with(|plugin| plugin.execution_service(service))
+    }
+
+    #[inline]
+    fn subgraph_service(
+        &mut self,
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        self.with(|plugin| plugin.subgraph_service(name, service))
+    }
+}
+
+#[derive(Clone)]
+///Plugin of router, as it is used to build router service.
+pub(crate) enum PluginSource {
+    ///Instance shared by all builds.
+    Shared(SharedPlugin),
+    ///Factory of plugin depending on schema, called again with new schema on every build.
+    Schema(PluginFactory),
+}
+
+impl PluginSource {
+    #[inline]
+    fn create(&self, schema: &Arc<Schema>) -> Box<dyn DynPlugin> {
+        match self {
+            PluginSource::Shared(plugin) => Box::new(plugin.clone()),
+            PluginSource::Schema(factory) => factory(schema),
+        }
+    }
+}
+
+///Applies `defaults` to remote subgraph settings, unless subgraph has own ones.
+pub(crate) fn apply_defaults(handle: &RemoteSettingsHandle, defaults: &RemoteSettings) {
+    handle.update(|settings| {
+        settings.timeout = settings.timeout.or(defaults.timeout);
+        settings.retry_backoff = settings.retry_backoff.or(defaults.retry_backoff);
+    });
+}
+
+#[derive(Clone)]
+///Router service, which handles requests, with schema it is built from.
+pub(crate) struct Generation {
+    pub(crate) service: RouterService,
+    pub(crate) schema: Arc<Schema>,
+}
+
+#[inline]
+pub(crate) fn current(generation: &RwLock<Generation>) -> RwLockReadGuard<'_, Generation> {
+    match generation.read() {
+        Ok(generation) => generation,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[inline]
+fn current_mut(generation: &RwLock<Generation>) -> RwLockWriteGuard<'_, Generation> {
+    match generation.write() {
+        Ok(generation) => generation,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[inline]
+pub(crate) fn settings(settings: &RwLock<Settings>) -> RwLockReadGuard<'_, Settings> {
+    match settings.read() {
+        Ok(settings) => settings,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[inline]
+fn request_limits_mut(
+    limits: &RwLock<Option<RequestLimitsConfig>>,
+) -> RwLockWriteGuard<'_, Option<RequestLimitsConfig>> {
+    match limits.write() {
+        Ok(limits) => limits,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[inline]
+fn settings_mut(settings: &RwLock<Settings>) -> RwLockWriteGuard<'_, Settings> {
+    match settings.write() {
+        Ok(settings) => settings,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[inline]
+pub(crate) fn request_limits(
+    limits: &RwLock<Option<RequestLimitsConfig>>,
+) -> RwLockReadGuard<'_, Option<RequestLimitsConfig>> {
+    match limits.read() {
+        Ok(limits) => limits,
+        Err(error) => error.into_inner(),
+    }
+}
+
+///Everything router is built from, kept to build it again.
+pub(crate) struct Recipe {
+    plugins: Vec<(String, PluginSource)>,
+    subgraphs: BTreeMap<String, SharedSubgraphService>,
+    plans: plan::Plans,
+    subgraph_defaults: Option<RemoteSettings>,
+    warmup: Vec<WarmupOperation>,
+    //Plugins skipped by rebuild.
+    disabled: BTreeSet<String>,
+    //Blue/green slots: staged generation waiting for promotion and the one replaced by promotion.
+    standby: Option<Generation>,
+    previous: Option<Generation>,
+}
+
+impl Recipe {
+    ///Creates recipe.
+    pub(crate) fn new(
+        plugins: Vec<(String, PluginSource)>,
+        subgraphs: Vec<(String, SubgraphService)>,
+        plans: plan::Plans,
+        subgraph_defaults: Option<RemoteSettings>,
+        warmup: Vec<WarmupOperation>,
+    ) -> Self {
+        let subgraphs = subgraphs
+            .into_iter()
+            .map(|(name, service)| (name, Buffer::new(service, BUFFER_SIZE)))
+            .collect();
+        Self {
+            plugins,
+            subgraphs,
+            plans,
+            subgraph_defaults,
+            warmup,
+            disabled: BTreeSet::new(),
+            standby: None,
+            previous: None,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn warmup(&self) -> &[WarmupOperation] {
+        &self.warmup
+    }
+
+    ///Builds router service with `schema` and enabled plugins.
+    pub(crate) async fn build(&self, schema: &Arc<Schema>) -> Result<RouterService, ServiceBuildError> {
+        let mut builder = PluggableRouterServiceBuilder::new(schema.clone());
+        for (name, service) in self.subgraphs.iter() {
+            builder = builder.with_subgraph_service(name, service.clone());
+        }
+        for (name, plugin) in self.plugins.iter().filter(|(name, _)| !self.disabled.contains(name)) {
+            builder = builder.with_dyn_plugin(name.clone(), plugin.create(schema));
+        }
+        let builder = builder
+            .with_plugin("plan_inspector".to_owned(), plan::PlanInspector::new(self.plans.clone()))
+            .with_plugin("fetch_failure_details".to_owned(), error::FetchFailureDetails)
+            .with_plugin("entity_error_paths".to_owned(), error::EntityErrorPaths);
+        Ok(builder.with_naive_introspection().build().await?.0)
+    }
+}
+
+impl GraphqlRouter {
+    ///Adds subgraph `graph`, rebuilding router with new `schema`, which includes it.
+    ///
+    ///New router service replaces current one only once it is built and operations of plan manifest and warmup
+    ///are executed with it, while requests in flight complete with current one.
+    ///Clones of router, including ones made before, share new service, schema and subgraph settings.
+    pub async fn add_subgraph<T: BuildGraph>(&mut self, schema: Arc<Schema>, graph: T) -> Result<(), RebuildError>
+    where
+        <<T as BuildGraph>::SubgraphSerivce as tower_service::Service<SubgraphRequest>>::Future: Send,
+    {
+        let name = graph.name().to_owned();
+        if !schema.subgraphs().any(|(service_name, _)| graph.name() == service_name) {
+            return Err(RebuildError::NotInSchema(name));
+        }
+
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        if recipe.subgraphs.contains_key(&name) {
+            return Err(RebuildError::SubgraphExists(name));
+        }
+        let settings = graph.settings();
+        if let (Some(settings), Some(defaults)) = (settings.as_ref(), recipe.subgraph_defaults.as_ref()) {
+            apply_defaults(settings, defaults);
+        }
+        let service = Buffer::new(BoxService::new(graph.build()), BUFFER_SIZE);
+        recipe.subgraphs.insert(name.clone(), service);
+        if let Err(error) = self.swap(&recipe, schema).await {
+            recipe.subgraphs.remove(&name);
+            return Err(error);
+        }
+        //Staged and previous generations are built without new subgraph.
+        recipe.standby = None;
+        recipe.previous = None;
+
+        tracing::info!("Subgraph '{}' added", name);
+        if let Some(settings) = settings {
+            settings_mut(&self.settings).insert(name, settings);
+        }
+        Ok(())
+    }
+
+    ///Removes subgraph `name`, rebuilding router with new `schema`, which no longer includes it.
+    ///
+    ///Service is replaced the same way as with [GraphqlRouter::add_subgraph].
+    pub async fn remove_subgraph(&mut self, schema: Arc<Schema>, name: &str) -> Result<(), RebuildError> {
+        if schema.subgraphs().any(|(service_name, _)| name == service_name) {
+            return Err(RebuildError::InSchema(name.to_owned()));
+        }
+
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        let service = match recipe.subgraphs.remove(name) {
+            Some(service) => service,
+            None => return Err(RebuildError::UnknownSubgraph(name.to_owned())),
+        };
+        if let Err(error) = self.swap(&recipe, schema).await {
+            recipe.subgraphs.insert(name.to_owned(), service);
+            return Err(error);
+        }
+        recipe.standby = None;
+        recipe.previous = None;
+
+        tracing::info!("Subgraph '{}' removed", name);
+        settings_mut(&self.settings).remove(name);
+        Ok(())
+    }
+
+    ///Returns clone of router, which handles requests with `generation` instead of current one.
+    fn with_generation(&self, generation: Generation) -> GraphqlRouter {
+        let mut router = self.clone();
+        router.current = Arc::new(RwLock::new(generation));
+        router
+    }
+
+    ///Builds new generation according to `recipe`.
+    ///
+    ///Operations of plan manifest and warmup are planned by new service before it takes live traffic.
+    async fn prepare(&self, recipe: &Recipe, schema: Arc<Schema>) -> Result<Generation, RebuildError> {
+        let generation = Generation {
+            service: recipe.build(&schema).await.map_err(RebuildError::Build)?,
+            schema,
+        };
+        let mut next = self.with_generation(generation.clone());
+        if let Some(manifest) = next.plan_manifest.clone() {
+            manifest::run(&mut next, &manifest).await;
+        }
+        if !recipe.warmup().is_empty() {
+            warmup::run(&mut next, recipe.warmup()).await;
+        }
+        Ok(generation)
+    }
+
+    ///Replaces current generation, returning replaced one.
+    fn replace(&mut self, generation: Generation) -> Generation {
+        core::mem::replace(&mut *current_mut(&self.current), generation)
+    }
+
+    ///Builds new service according to `recipe` and replaces current one with it.
+    async fn swap(&mut self, recipe: &Recipe, schema: Arc<Schema>) -> Result<(), RebuildError> {
+        let generation = self.prepare(recipe, schema).await?;
+        self.replace(generation);
+        Ok(())
+    }
+
+    ///Loads `schema` into standby slot, replacing previously staged one.
+    ///
+    ///Standby service is built and plans operations of plan manifest and warmup, but takes no traffic until
+    ///[GraphqlRouter::promote_standby]. Schema must not reference subgraphs, that router does not have.
+    pub async fn stage_schema(&self, schema: Arc<Schema>) -> Result<(), RebuildError> {
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        if let Some((name, _)) = schema.subgraphs().find(|(name, _)| !recipe.subgraphs.contains_key(name.as_str())) {
+            return Err(RebuildError::UnknownSubgraph(name.clone()));
+        }
+        recipe.standby = Some(self.prepare(&recipe, schema).await?);
+        tracing::info!("Schema staged");
+        Ok(())
+    }
+
+    ///Returns schema in standby slot, if any.
+    pub async fn standby_schema(&self) -> Option<Arc<Schema>> {
+        let recipe = self.recipe.lock().await;
+        recipe.standby.as_ref().map(|standby| standby.schema.clone())
+    }
+
+    ///Executes `operations` against standby service, failing if any of them responds with errors.
+    pub async fn smoke_test_standby(&self, operations: &[WarmupOperation]) -> Result<(), RebuildError> {
+        let standby = match self.recipe.lock().await.standby.clone() {
+            Some(standby) => standby,
+            None => return Err(RebuildError::NoStandby),
+        };
+        let mut router = self.with_generation(standby);
+        let succeeded = warmup::run(&mut router, operations).await;
+        match operations.len() - succeeded {
+            0 => Ok(()),
+            failed => Err(RebuildError::SmokeTest {
+                failed,
+                total: operations.len(),
+            }),
+        }
+    }
+
+    ///Atomically replaces current service with standby one, keeping replaced one for [GraphqlRouter::rollback].
+    pub async fn promote_standby(&mut self) -> Result<(), RebuildError> {
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        let standby = match recipe.standby.take() {
+            Some(standby) => standby,
+            None => return Err(RebuildError::NoStandby),
+        };
+        recipe.previous = Some(self.replace(standby));
+        tracing::info!("Standby schema promoted");
+        Ok(())
+    }
+
+    ///Restores service replaced by the latest promotion, moving current one to standby slot.
+    pub async fn rollback(&mut self) -> Result<(), RebuildError> {
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        let previous = match recipe.previous.take() {
+            Some(previous) => previous,
+            None => return Err(RebuildError::NoPrevious),
+        };
+        recipe.standby = Some(self.replace(previous));
+        tracing::warn!("Schema rolled back");
+        Ok(())
+    }
+
+    ///Returns names of plugins, added by router builder, with whether they are enabled.
+    pub async fn plugins(&self) -> Vec<(String, bool)> {
+        let recipe = self.recipe.lock().await;
+        recipe
+            .plugins
+            .iter()
+            .map(|(name, _)| (name.clone(), !recipe.disabled.contains(name)))
+            .collect()
+    }
+
+    ///Enables or disables plugin `name`, rebuilding router with its current schema.
+    ///
+    ///New service replaces current one atomically, the same way as with [GraphqlRouter::add_subgraph], so requests
+    ///in flight complete with previous set of plugins. Plugins guarding access (e.g. `api_keys`, `request_limits`)
+    ///or defining semantics of operations (e.g. `persisted_queries`, `mutation_ordering`) cannot be toggled.
+    pub async fn set_plugin_enabled(&mut self, name: &str, is_enabled: bool) -> Result<(), RebuildError> {
+        let recipe = self.recipe.lock().await;
+        if !recipe.plugins.iter().any(|(plugin, _)| plugin == name) {
+            return Err(RebuildError::UnknownPlugin(name.to_owned()));
+        }
+        drop(recipe);
+
+        let mut flags = BTreeMap::new();
+        flags.insert(name.to_owned(), is_enabled);
+        self.update_plugins(&flags).await
+    }
+
+    ///Enables or disables plugins according to `flags`, e.g. pushed from feature flag service, rebuilding router
+    ///once with its current schema.
+    ///
+    ///Flags of plugins, which router does not have, are ignored, while flag disabling plugin, which cannot be
+    ///toggled, fails whole update. Service is replaced the same way as with [GraphqlRouter::set_plugin_enabled].
+    pub async fn update_plugins(&mut self, flags: &BTreeMap<String, bool>) -> Result<(), RebuildError> {
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        let mut disabled = recipe.disabled.clone();
+        for (name, is_enabled) in flags.iter() {
+            if !recipe.plugins.iter().any(|(plugin, _)| plugin == name) {
+                continue;
+            } else if PINNED_PLUGINS.contains(&name.as_str()) {
+                //Pinned plugins are never disabled, so enabling them is no-op.
+                match is_enabled {
+                    true => continue,
+                    false => return Err(RebuildError::PluginPinned(name.clone())),
+                }
+            }
+            match is_enabled {
+                true => disabled.remove(name),
+                false => disabled.insert(name.clone()),
+            };
+        }
+        if disabled == recipe.disabled {
+            return Ok(());
+        }
+
+        let previous = core::mem::replace(&mut recipe.disabled, disabled);
+        let schema = self.schema();
+        if let Err(error) = self.swap(&recipe, schema).await {
+            recipe.disabled = previous;
+            return Err(error);
+        }
+        for name in previous.symmetric_difference(&recipe.disabled) {
+            let state = if recipe.disabled.contains(name) { "disabled" } else { "enabled" };
+            tracing::info!("Plugin '{}' {}", name, state);
+        }
+        Ok(())
+    }
+
+    ///Polls `fetch` every `interval` until `shutdown` completes, applying fetched flags via
+    ///[GraphqlRouter::update_plugins].
+    pub async fn poll_plugin_flags<F, Fut, S>(&mut self, interval: Duration, mut fetch: F, shutdown: S)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<BTreeMap<String, bool>, BoxError>>,
+        S: Future<Output = ()>,
+    {
+        let mut interval = tokio::time::interval(interval);
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = interval.tick() => {
+                    let result = match fetch().await {
+                        Ok(flags) => self.update_plugins(&flags).await.map_err(Into::into),
+                        Err(error) => Err(error),
+                    };
+                    if let Err(error) = result {
+                        tracing::warn!("Failed to apply plugin flags: {}", error);
+                    }
+                }
+            }
+        }
+    }
+
+    ///Creates plugin `name` by `registry` with new `config`, rebuilding router with its current schema.
+    ///
+    ///Plugin must be already added, and new one takes its position, dropping state of replaced one. New service
+    ///replaces current one the same way as with [GraphqlRouter::add_subgraph], while current one is kept, if plugin
+    ///cannot be created.
+    pub async fn reconfigure_plugin(
+        &mut self,
+        name: &str,
+        registry: &PluginRegistry,
+        config: serde_json::Value,
+    ) -> Result<(), RebuildError> {
+        let mut recipe = self.recipe.clone().lock_owned().await;
+        let idx = match recipe.plugins.iter().position(|(plugin, _)| plugin == name) {
+            Some(idx) => idx,
+            None => return Err(RebuildError::UnknownPlugin(name.to_owned())),
+        };
+        if let PluginSource::Schema(_) = recipe.plugins[idx].1 {
+            return Err(RebuildError::Plugin {
+                name: name.to_owned(),
+                error: "Plugin depends on schema, so it is created by router only".into(),
+            });
+        }
+        //Server checks requests against the same limits as plugin.
+        let limits = match (name, &config) {
+            ("request_limits", serde_json::Value::Null) => Some(RequestLimitsConfig::default()),
+            ("request_limits", config) => {
+                match crate::config::from_value::<RequestLimitsConfig, serde_json::Error>(config.clone()) {
+                    Ok(limits) => Some(limits),
+                    Err(error) => {
+                        return Err(RebuildError::Plugin {
+                            name: name.to_owned(),
+                            error: error.into(),
+                        })
+                    }
+                }
+            }
+            _ => None,
+        };
+        let plugin = match registry.create(name, config).await {
+            Some(Ok(plugin)) => plugin,
+            Some(Err(error)) => {
+                return Err(RebuildError::Plugin {
+                    name: name.to_owned(),
+                    error,
+                })
+            }
+            None => {
+                return Err(RebuildError::Plugin {
+                    name: name.to_owned(),
+                    error: "Plugin is not registered".into(),
+                })
+            }
+        };
+        let plugin = PluginSource::Shared(SharedPlugin::new(plugin));
+        let previous = core::mem::replace(&mut recipe.plugins[idx].1, plugin);
+
+        let schema = self.schema();
+        if let Err(error) = self.swap(&recipe, schema).await {
+            recipe.plugins[idx].1 = previous;
+            return Err(error);
+        }
+        if let Some(limits) = limits {
+            *request_limits_mut(&self.request_limits) = Some(limits);
+        }
+        tracing::info!("Plugin '{}' reconfigured", name);
+        Ok(())
+    }
+
+    ///Drops cached query plans, rebuilding router with its current schema.
+    ///
+    ///Operations of plan manifest and warmup are planned again, before new service replaces current one.
+    pub async fn purge_plan_cache(&mut self) -> Result<(), RebuildError> {
+        let recipe = self.recipe.clone().lock_owned().await;
+        let schema = self.schema();
+        self.swap(&recipe, schema).await?;
+        tracing::info!("Query plan cache purged");
+        Ok(())
+    }
+}
@@ -1,42 +1,332 @@
 use apollo_router_core::{SubgraphRequest, SubgraphResponse};
 use hyper::client::HttpConnector;
-use hyper::header::HeaderValue;
-use hyper::http::header::{ACCEPT, CONTENT_TYPE};
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::http::header::AUTHORIZATION;
 use hyper_rustls::HttpsConnector;
+use tokio::sync::Semaphore;
 use tower_service::Service;
 
+use crate::encoding::Encoding;
+use crate::error::{record_fetch_failure, record_fetch_timeout, FetchFailure};
+use crate::log::{LogFilter, SUBGRAPH_DEBUG_TARGET};
+use crate::plugins::{Counters, InFlight, MetricsSnapshot};
+use crate::secret::Secret;
+use crate::time::{system_clock, SharedClock};
+use crate::tls::ClientTlsConfig;
 use crate::BuildGraph;
 
-use core::future::Future;
+use core::future::{ready, Future};
 use core::pin::Pin;
 use core::task;
+use core::time::Duration;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::sync::{Arc, RwLock};
+
+mod circuit;
+pub use circuit::{CircuitBreaker, CircuitBreakerSettings, CircuitState};
+mod health;
+pub use health::{HealthStatus, SubgraphHealth};
+use health::HealthTracker;
+mod mirror;
+use mirror::Mirror;
+mod variant;
+pub use variant::PRIMARY_VARIANT;
+use variant::Variants;
+mod transport;
+pub use transport::{HttpTransport, SubgraphTransport, TransportError, TransportFuture, TransportRequest};
 
 #[allow(clippy::declare_interior_mutable_const)]
 const APPLICATION_JSON: HeaderValue = HeaderValue::from_static("application/json");
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+///HTTP client of remote subgraphs
+pub type HttpClient = hyper::Client<HttpsConnector<HttpConnector>>;
+
+fn build_http_client(tls: Option<tokio_rustls::rustls::ClientConfig>) -> HttpClient {
+    let https = match tls {
+        Some(tls) => hyper_rustls::HttpsConnectorBuilder::new().with_tls_config(tls),
+        None => hyper_rustls::HttpsConnectorBuilder::new().with_native_roots(),
+    };
+    let https = https.https_or_http().enable_http1().build();
+    hyper::Client::builder().build(https)
+}
+
+#[inline]
+///Creates HTTP client with system's native roots, which can be shared via [RemoteGraphBuilder::client].
+pub fn http_client() -> HttpClient {
+    build_http_client(None)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Remote subgraph settings, which can be changed at runtime.
+pub struct RemoteSettings {
+    ///Number of attempts on network failure or temporary unavailability.
+    pub max_retry_num: usize,
+    ///Delay before first retry, doubled on each subsequent retry, up to 5 seconds.
+    pub retry_backoff: Option<Duration>,
+    ///Number of redirects to follow.
+    pub max_redirect_num: usize,
+    ///Time limit for whole subgraph fetch, including retries.
+    pub timeout: Option<Duration>,
+    ///Circuit breaker settings, disabled if not set.
+    pub circuit_breaker: Option<CircuitBreakerSettings>,
+}
+
+impl Default for RemoteSettings {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            max_retry_num: 2,
+            retry_backoff: None,
+            max_redirect_num: 10,
+            timeout: None,
+            circuit_breaker: None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+///Headers added to every subgraph request, which can be replaced at runtime.
+pub struct RemoteHeaders {
+    headers: HeaderMap,
+    secret_headers: Vec<(HeaderName, &'static str, Secret)>,
+}
+
+impl RemoteHeaders {
+    #[inline(always)]
+    ///Creates empty set of headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Adds header, overriding propagated value, if any.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    #[inline]
+    ///Adds header, with value resolved from `secret` on each request.
+    pub fn secret_header(mut self, name: HeaderName, secret: Secret) -> Self {
+        self.secret_headers.push((name, "", secret));
+        self
+    }
+
+    #[inline]
+    ///Adds `Authorization: Bearer <token>` header, with token resolved from `secret` on each request.
+    pub fn bearer_token(mut self, secret: Secret) -> Self {
+        self.secret_headers.push((AUTHORIZATION, "Bearer ", secret));
+        self
+    }
+
+    //Inserts headers into `headers`, failing if any of secrets cannot be resolved.
+    fn apply(&self, headers: &mut HeaderMap) -> Result<(), (HeaderName, String)> {
+        for (key, value) in self.headers.iter() {
+            headers.insert(key, value.clone());
+        }
+        for (key, prefix, secret) in self.secret_headers.iter() {
+            let value = match secret.get() {
+                Ok(value) => HeaderValue::from_str(&format!("{}{}", prefix, value))
+                    .map_err(|_| "Secret is not valid header value".to_owned()),
+                Err(error) => Err(error.to_string()),
+            };
+            match value {
+                Ok(value) => {
+                    headers.insert(key, value);
+                }
+                Err(reason) => return Err((key.clone(), reason)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+///Shared handle to remote subgraph settings.
+///
+///Changes are applied to subsequent requests.
+pub struct RemoteSettingsHandle {
+    inner: Arc<RwLock<RemoteSettings>>,
+    headers: Arc<RwLock<Arc<RemoteHeaders>>>,
+    circuit: CircuitBreaker,
+    variants: Arc<RwLock<BTreeMap<String, Arc<Counters>>>>,
+    draining: Arc<RwLock<BTreeSet<String>>>,
+    in_flight: Arc<AtomicUsize>,
+    health: HealthTracker,
+}
+
+impl RemoteSettingsHandle {
+    #[inline]
+    ///Returns current settings.
+    pub fn get(&self) -> RemoteSettings {
+        match self.inner.read() {
+            Ok(settings) => *settings,
+            Err(error) => *error.into_inner(),
+        }
+    }
+
+    #[inline]
+    ///Modifies settings.
+    pub fn update<F: FnOnce(&mut RemoteSettings)>(&self, cb: F) {
+        match self.inner.write() {
+            Ok(mut settings) => cb(&mut settings),
+            Err(error) => cb(&mut error.into_inner()),
+        }
+    }
+
+    #[inline]
+    ///Replaces settings.
+    pub fn set(&self, settings: RemoteSettings) {
+        self.update(|current| *current = settings)
+    }
+
+    #[inline]
+    ///Returns headers added to every request.
+    pub fn headers(&self) -> Arc<RemoteHeaders> {
+        match self.headers.read() {
+            Ok(headers) => headers.clone(),
+            Err(error) => error.into_inner().clone(),
+        }
+    }
+
+    ///Replaces headers added to every request.
+    pub fn set_headers(&self, headers: RemoteHeaders) {
+        let headers = Arc::new(headers);
+        match self.headers.write() {
+            Ok(mut current) => *current = headers,
+            Err(error) => *error.into_inner() = headers,
+        }
+    }
+
+    fn update_headers<F: FnOnce(&mut RemoteHeaders)>(&self, cb: F) {
+        let mut current = match self.headers.write() {
+            Ok(current) => current,
+            Err(error) => error.into_inner(),
+        };
+        cb(Arc::make_mut(&mut current));
+    }
+
+    #[inline(always)]
+    ///Returns subgraph's circuit breaker.
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.circuit
+    }
+
+    ///Returns metrics per deployment variant, empty if subgraph has no variants.
+    pub fn variant_metrics(&self) -> BTreeMap<String, MetricsSnapshot> {
+        let variants = match self.variants.read() {
+            Ok(variants) => variants,
+            Err(error) => error.into_inner(),
+        };
+        variants
+            .iter()
+            .map(|(name, counters)| (name.clone(), counters.snapshot()))
+            .collect()
+    }
+
+    ///Stops sending new requests to `endpoint`, which is [PRIMARY_VARIANT] or name of variant, while requests in
+    ///flight complete.
+    ///
+    ///Traffic of draining endpoint goes to other endpoints, and when all of them are draining, fetches fail right
+    ///away, leaving response partial. Returns `false`, if subgraph has no such endpoint.
+    pub fn drain(&self, endpoint: &str) -> bool {
+        let is_known = endpoint == PRIMARY_VARIANT
+            || match self.variants.read() {
+                Ok(variants) => variants.contains_key(endpoint),
+                Err(error) => error.into_inner().contains_key(endpoint),
+            };
+        if is_known {
+            match self.draining.write() {
+                Ok(mut draining) => draining.insert(endpoint.to_owned()),
+                Err(error) => error.into_inner().insert(endpoint.to_owned()),
+            };
+        }
+        is_known
+    }
+
+    ///Resumes sending requests to `endpoint`, returning whether it was draining.
+    pub fn resume(&self, endpoint: &str) -> bool {
+        match self.draining.write() {
+            Ok(mut draining) => draining.remove(endpoint),
+            Err(error) => error.into_inner().remove(endpoint),
+        }
+    }
+
+    #[inline]
+    ///Returns whether `endpoint` is draining.
+    pub fn is_draining(&self, endpoint: &str) -> bool {
+        match self.draining.read() {
+            Ok(draining) => draining.contains(endpoint),
+            Err(error) => error.into_inner().contains(endpoint),
+        }
+    }
+
+    ///Returns names of draining endpoints.
+    pub fn draining(&self) -> Vec<String> {
+        match self.draining.read() {
+            Ok(draining) => draining.iter().cloned().collect(),
+            Err(error) => error.into_inner().iter().cloned().collect(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns number of requests in flight to subgraph, e.g. to wait until draining endpoint is idle.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
 
-#[derive(Clone, Copy)]
-struct Config {
-    max_retry_num: usize,
-    max_redirect_num: usize,
+    ///Returns health of subgraph, computed from latest fetches and circuit state.
+    pub fn health(&self) -> SubgraphHealth {
+        let circuit = self
+            .get()
+            .circuit_breaker
+            .map(|circuit_breaker| self.circuit.state(&circuit_breaker));
+        self.health.health(circuit)
+    }
+
+    fn variant_counters(&self, name: &str) -> Arc<Counters> {
+        let mut variants = match self.variants.write() {
+            Ok(variants) => variants,
+            Err(error) => error.into_inner(),
+        };
+        variants.entry(name.to_owned()).or_default().clone()
+    }
 }
 
 ///Remote subgraph builder
 pub struct RemoteGraphBuilder {
     url: hyper::Uri,
-    name: &'static str,
-    config: Config,
+    name: Arc<str>,
+    settings: RemoteSettingsHandle,
+    tls: Option<tokio_rustls::rustls::ClientConfig>,
+    client: Option<Arc<HttpClient>>,
+    concurrency: Option<Arc<Semaphore>>,
+    mirror: Option<Arc<Mirror>>,
+    variants: Option<Variants>,
+    clock: SharedClock,
+    encoding: Encoding,
+    transport: Option<Arc<dyn SubgraphTransport>>,
 }
 
 impl RemoteGraphBuilder {
     #[inline(always)]
-    pub fn new(name: &'static str, url: hyper::Uri) -> Self {
+    pub fn new<N: Into<Arc<str>>>(name: N, url: hyper::Uri) -> Self {
         Self {
-            name,
+            name: name.into(),
             url,
-            config: Config {
-                max_redirect_num: 10,
-                max_retry_num: 2,
-            },
+            settings: RemoteSettingsHandle::default(),
+            tls: None,
+            client: None,
+            concurrency: None,
+            mirror: None,
+            variants: None,
+            clock: system_clock(),
+            encoding: Encoding::Json,
+            transport: None,
         }
     }
 
@@ -45,24 +335,194 @@ impl RemoteGraphBuilder {
     ///Retry happens only when there is network issue or service is temp unavailable.
     ///
     ///Default is 2.
-    pub fn max_retry_num(mut self, max_retry_num: usize) -> Self {
-        self.config.max_retry_num = max_retry_num;
+    pub fn max_retry_num(self, max_retry_num: usize) -> Self {
+        self.settings.update(|settings| settings.max_retry_num = max_retry_num);
+        self
+    }
+
+    ///Sets delay before first retry, which is doubled on each subsequent retry.
+    ///
+    ///Default is to retry immediately.
+    pub fn retry_backoff(self, retry_backoff: Duration) -> Self {
+        self.settings.update(|settings| settings.retry_backoff = Some(retry_backoff));
+        self
+    }
+
+    ///Sets time limit for subgraph fetch, including all retries.
+    ///
+    ///Default is no limit.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.settings.update(|settings| settings.timeout = Some(timeout));
+        self
+    }
+
+    ///Enables circuit breaker, which rejects requests after `failure_threshold` consecutive
+    ///failures for `reset_timeout`.
+    ///
+    ///Default is disabled.
+    pub fn circuit_breaker(self, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        self.settings.update(|settings| {
+            settings.circuit_breaker = Some(CircuitBreakerSettings {
+                failure_threshold,
+                reset_timeout,
+            })
+        });
+        self
+    }
+
+    #[inline]
+    ///Adds header to every subgraph request, overriding propagated value, if any.
+    pub fn header(self, name: HeaderName, value: HeaderValue) -> Self {
+        self.settings.update_headers(|headers| {
+            headers.headers.insert(name, value);
+        });
+        self
+    }
+
+    #[inline]
+    ///Adds header to every subgraph request, with value resolved from `secret` on each request.
+    ///
+    ///This allows to rotate credentials, like auth tokens, without restart.
+    pub fn secret_header(self, name: HeaderName, secret: Secret) -> Self {
+        self.settings
+            .update_headers(|headers| headers.secret_headers.push((name, "", secret)));
+        self
+    }
+
+    #[inline]
+    ///Adds `Authorization: Bearer <token>` header to every subgraph request, with token resolved
+    ///from `secret` on each request.
+    pub fn bearer_token(self, secret: Secret) -> Self {
+        self.settings
+            .update_headers(|headers| headers.secret_headers.push((AUTHORIZATION, "Bearer ", secret)));
+        self
+    }
+
+    #[inline]
+    ///Sets `headers` added to every subgraph request, replacing previously added ones.
+    ///
+    ///Headers can be replaced after service is built via [RemoteSettingsHandle::set_headers].
+    pub fn headers(self, headers: RemoteHeaders) -> Self {
+        self.settings.set_headers(headers);
+        self
+    }
+
+    ///Sets TLS config for connections towards subgraph.
+    ///
+    ///Returns error if certificates cannot be loaded.
+    pub fn tls(mut self, tls: ClientTlsConfig) -> io::Result<Self> {
+        self.tls = Some(tls.client_config()?);
+        Ok(self)
+    }
+
+    #[inline(always)]
+    ///Uses `client`, sharing its connection pool with other subgraphs.
+    ///
+    ///Ignored if subgraph has own TLS config, which requires dedicated connector.
+    pub fn client(mut self, client: Arc<HttpClient>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    #[inline]
+    ///Limits number of concurrent requests towards subgraph.
+    ///
+    ///Requests above limit wait for slot, which counts towards timeout.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.concurrency = Some(Arc::new(Semaphore::new(max_concurrency)));
+        self
+    }
+
+    #[inline]
+    ///Sends copy of `rate` fraction of requests to shadow endpoint `url`, discarding its responses.
+    ///
+    ///Shadow requests carry the same headers, including credentials, and are not retried.
+    ///When `log_diff` is set, responses that differ from primary response are logged.
+    pub fn mirror(mut self, url: hyper::Uri, rate: f64, log_diff: bool) -> Self {
+        self.mirror = Some(Arc::new(Mirror::new(url, rate, log_diff)));
+        self
+    }
+
+    ///Routes `weight` fraction of traffic to alternative deployment `name` at `url`.
+    ///
+    ///Remaining traffic goes to subgraph's own URL, which is variant [PRIMARY_VARIANT].
+    ///Metrics are collected per variant and available via [RemoteSettingsHandle::variant_metrics].
+    pub fn variant(mut self, name: &str, url: hyper::Uri, weight: f64) -> Self {
+        let settings = &self.settings;
+        let variants = self
+            .variants
+            .get_or_insert_with(|| Variants::new(settings.variant_counters(PRIMARY_VARIANT)));
+        variants.add(name.into(), url, weight, settings.variant_counters(name));
+        self
+    }
+
+    ///Allows to select variant by name in `header` of client request, overriding weighted selection.
+    pub fn variant_header(mut self, header: HeaderName) -> Self {
+        let settings = &self.settings;
+        self.variants
+            .get_or_insert_with(|| Variants::new(settings.variant_counters(PRIMARY_VARIANT)))
+            .set_header(header);
+        self
+    }
+
+    #[inline(always)]
+    ///Returns handle to modify settings after service is built.
+    pub fn settings(&self) -> RemoteSettingsHandle {
+        self.settings.clone()
+    }
+
+    #[inline]
+    ///Sets time source for retry backoff and circuit breaker.
+    ///
+    ///Resets circuit breaker, so it should be set before settings handle is obtained.
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.settings.circuit = CircuitBreaker::with_clock(clock.clone());
+        self.clock = clock;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets encoding of requests, with responses accepted in either this encoding or JSON.
+    ///
+    ///Default is JSON.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    #[inline(always)]
+    ///Sends requests using `transport` instead of HTTP.
+    ///
+    ///Retries, timeouts and circuit breaking still apply, while mirroring remains over HTTP.
+    pub fn transport(mut self, transport: Arc<dyn SubgraphTransport>) -> Self {
+        self.transport = Some(transport);
         self
     }
 
     #[inline(always)]
     ///Builds service
     pub fn build(self) -> RemoteGraphService {
-        let https = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .https_or_http()
-            .enable_http1()
-            .build();
+        let http = match (self.tls, self.client) {
+            (Some(tls), _) => build_http_client(Some(tls)),
+            //Client clones share connection pool
+            (None, Some(client)) => HttpClient::clone(&client),
+            (None, None) => http_client(),
+        };
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => Arc::new(HttpTransport::new(http.clone())),
+        };
         RemoteGraphService {
             url: self.url,
             name: self.name,
-            http: hyper::Client::builder().build(https),
-            config: self.config,
+            http,
+            settings: self.settings,
+            concurrency: self.concurrency,
+            mirror: self.mirror,
+            variants: self.variants.map(Arc::new),
+            clock: self.clock,
+            encoding: self.encoding,
+            transport,
         }
     }
 }
@@ -72,7 +532,12 @@ impl BuildGraph for RemoteGraphBuilder {
 
     #[inline(always)]
     fn name(&self) -> &str {
-        self.name
+        &self.name
+    }
+
+    #[inline(always)]
+    fn settings(&self) -> Option<RemoteSettingsHandle> {
+        Some(self.settings())
     }
 
     #[inline(always)]
@@ -84,9 +549,15 @@ impl BuildGraph for RemoteGraphBuilder {
 ///Remote subgraph service
 pub struct RemoteGraphService {
     url: hyper::Uri,
-    name: &'static str,
-    http: hyper::Client<HttpsConnector<HttpConnector>>,
-    config: Config,
+    name: Arc<str>,
+    http: HttpClient,
+    settings: RemoteSettingsHandle,
+    concurrency: Option<Arc<Semaphore>>,
+    mirror: Option<Arc<Mirror>>,
+    variants: Option<Arc<Variants>>,
+    clock: SharedClock,
+    encoding: Encoding,
+    transport: Arc<dyn SubgraphTransport>,
 }
 
 impl Service<SubgraphRequest> for RemoteGraphService {
@@ -102,168 +573,244 @@ impl Service<SubgraphRequest> for RemoteGraphService {
     }
 
     #[inline]
-    fn call(&mut self, request: SubgraphRequest) -> Self::Future {
-        Box::pin(remote_subgraph(
-            self.http.clone(),
-            request,
-            self.config,
-            self.name,
-            self.url.clone(),
-        ))
-    }
-}
+    fn call(&mut self, mut request: SubgraphRequest) -> Self::Future {
+        let headers = self.settings.headers();
+        if let Err((key, reason)) = headers.apply(request.subgraph_request.headers_mut()) {
+            tracing::warn!("{}: Unable to resolve header '{}': {}", self.name, key, reason);
+            let error = apollo_router_core::FetchError::SubrequestHttpError {
+                service: self.name.to_string(),
+                reason,
+            };
+            return Box::pin(ready(Err(error.into())));
+        }
+
+        let selected = match self.variants.as_ref() {
+            Some(variants) => variants
+                .select(&self.url, &request, |name| self.settings.is_draining(name))
+                .map(|(name, url, counters)| {
+                    tracing::debug!("{}: Selected variant '{}'", self.name, name);
+                    (url.clone(), Some(counters.clone()))
+                }),
+            None if self.settings.is_draining(PRIMARY_VARIANT) => None,
+            None => Some((self.url.clone(), None)),
+        };
+        let (url, variant) = match selected {
+            Some(selected) => selected,
+            None => {
+                tracing::info!("{}: All endpoints are draining", self.name);
+                let error = apollo_router_core::FetchError::SubrequestHttpError {
+                    service: self.name.to_string(),
+                    reason: "Subgraph is draining".to_owned(),
+                };
+                return Box::pin(ready(Err(error.into())));
+            }
+        };
+
+        let is_debug = LogFilter::global().is_subgraph_debug(&self.name);
+        if is_debug {
+            let body = serde_json::to_string(request.subgraph_request.body()).unwrap_or_default();
+            tracing::info!(target: SUBGRAPH_DEBUG_TARGET, "{}: Request to {}: {}", self.name, url, body);
+        }
 
-fn redirect_url(location: Option<hyper::Uri>, original: &hyper::Uri) -> Result<hyper::Uri, &'static str> {
-    match location {
-        Some(loc) => match loc.scheme().is_some() {
-            //We assume that if scheme is present then it is absolute redirect
-            //should clear some sensitive headers, but in our case it is unlikely graphql server
-            //would redirect to different host, so consider it an error.
-            true => {
-                if let Some(prev_host) = original.authority().map(|part| part.host()) {
-                    match loc.authority().map(|part| part.host() == prev_host).unwrap_or(false) {
-                        true => Ok(loc),
-                        false => Err("Redirect points to different host"),
+        let settings = self.settings.get();
+        if let Some(circuit_breaker) = settings.circuit_breaker.as_ref() {
+            if !self.settings.circuit.try_acquire(circuit_breaker) {
+                tracing::info!("{}: Circuit breaker is open", self.name);
+                let error = apollo_router_core::FetchError::SubrequestHttpError {
+                    service: self.name.to_string(),
+                    reason: "Circuit breaker is open".to_owned(),
+                };
+                return Box::pin(ready(Err(error.into())));
+            }
+        }
+
+        //Serialized once, so that retries and mirror share the same buffer
+        let encoding = self.encoding;
+        let body = match encoding.encode(request.subgraph_request.body()) {
+            Ok(body) => body,
+            Err(error) => {
+                let error = apollo_router_core::FetchError::SubrequestHttpError {
+                    service: self.name.to_string(),
+                    reason: error.to_string(),
+                };
+                return Box::pin(ready(Err(error.into())));
+            }
+        };
+        let shadow = self
+            .mirror
+            .as_ref()
+            .and_then(|mirror| mirror.send(&self.http, &self.name, &request, &body, encoding, settings.timeout));
+        let log_diff = self.mirror.as_ref().map_or(false, |mirror| mirror.log_diff());
+
+        let service_name = self.name.clone();
+        let circuit = self.settings.circuit.clone();
+        let health = self.settings.health.clone();
+        let concurrency = self.concurrency.clone();
+        let clock = self.clock.clone();
+        let started = clock.now();
+        let name = self.name.clone();
+        let transport = self.transport.clone();
+        let context = request.context.clone();
+        let in_flight = InFlight::new(self.settings.in_flight.clone());
+        let fetch = remote_subgraph(transport, request, body, encoding, settings, name, url, clock.clone());
+        let fetch = async move {
+            let _permit = match concurrency {
+                Some(concurrency) => Some(concurrency.acquire_owned().await?),
+                None => None,
+            };
+            fetch.await
+        };
+
+        Box::pin(async move {
+            let _in_flight = in_flight;
+            let result = match settings.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, fetch).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        tracing::info!("{}: Timed out after {}ms", service_name, timeout.as_millis());
+                        record_fetch_timeout(&context, &service_name, timeout);
+                        Err(apollo_router_core::FetchError::SubrequestHttpError {
+                            service: service_name.to_string(),
+                            reason: format!("Timed out after {}ms", timeout.as_millis()),
+                        }
+                        .into())
+                    }
+                },
+                None => fetch.await,
+            };
+
+            if let Some(circuit_breaker) = settings.circuit_breaker.as_ref() {
+                circuit.record(circuit_breaker, result.is_ok());
+            }
+            let elapsed = clock.now().saturating_duration_since(started);
+            health.record(elapsed, result.as_ref().err().map(|error| error.to_string()));
+            if is_debug {
+                let elapsed_ms = elapsed.as_millis();
+                match result.as_ref() {
+                    Ok(response) => {
+                        let body = serde_json::to_string(response.response.body()).unwrap_or_default();
+                        tracing::info!(
+                            target: SUBGRAPH_DEBUG_TARGET,
+                            "{}: Response in {}ms: {}",
+                            service_name,
+                            elapsed_ms,
+                            body
+                        );
+                    }
+                    Err(error) => {
+                        tracing::info!(
+                            target: SUBGRAPH_DEBUG_TARGET,
+                            "{}: Failed in {}ms: {}",
+                            service_name,
+                            elapsed_ms,
+                            error
+                        );
                     }
-                } else {
-                    Ok(loc)
                 }
             }
-            //relative to current location
-            false => {
-                use std::path::Path;
-
-                let current = Path::new(original.path());
-                let loc = Path::new(loc.path());
-                let loc = current.join(loc);
-                let loc = loc
-                    .to_str()
-                    .expect("Valid UTF-8 path")
-                    .parse::<hyper::Uri>()
-                    .expect("Valid URI");
-                let mut loc_parts = loc.into_parts();
-
-                loc_parts.scheme = original.scheme().cloned();
-                loc_parts.authority = original.authority().cloned();
-
-                hyper::Uri::from_parts(loc_parts).map_err(|_| "Relative redirect cannot be constructed")
+            if let Some(variant) = variant {
+                variant.record(elapsed, result.is_ok());
+            }
+            if let (Some(shadow), Ok(response), true) = (shadow, result.as_ref(), log_diff) {
+                match serde_json::to_value(response.response.body()) {
+                    Ok(primary) => mirror::compare(service_name, primary, shadow),
+                    Err(error) => tracing::info!("{}: Unable to compare with mirror: {}", service_name, error),
+                }
             }
-        },
-        None => Err("Redirect requested without Location header"),
+            result
+        })
+    }
+}
+
+#[inline]
+fn retry_delay(backoff: Duration, attempt: usize) -> Duration {
+    let factor = 1u32 << attempt.saturating_sub(1).min(31);
+    backoff.saturating_mul(factor).min(MAX_RETRY_BACKOFF)
+}
+
+async fn retry_wait(clock: &SharedClock, config: &RemoteSettings, retry_remain: usize) {
+    if let Some(backoff) = config.retry_backoff {
+        if retry_remain > 0 {
+            clock.sleep(retry_delay(backoff, config.max_retry_num - retry_remain)).await;
+        }
     }
 }
 
-#[tracing::instrument(skip(http, req, config))]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(transport, req, body, encoding, config, clock))]
 async fn remote_subgraph(
-    mut http: hyper::Client<HttpsConnector<HttpConnector>>,
+    transport: Arc<dyn SubgraphTransport>,
     req: SubgraphRequest,
-    config: Config,
-    service_name: &'static str,
-    mut url: hyper::Uri,
+    body: Bytes,
+    encoding: Encoding,
+    config: RemoteSettings,
+    service_name: Arc<str>,
+    url: hyper::Uri,
+    clock: SharedClock,
 ) -> Result<SubgraphResponse, Box<dyn std::error::Error + Send + Sync + 'static>> {
     tracing::info!("{}: Remote subgraph request towards {}", service_name, url);
 
-    let mut http_request = req.subgraph_request;
     let context = req.context;
-
-    http_request.headers_mut().insert(CONTENT_TYPE, APPLICATION_JSON);
-    http_request.headers_mut().insert(ACCEPT, APPLICATION_JSON);
-    let (parts, body) = http_request.into_parts();
-    let body = serde_json::to_string(&body).expect("JSON serialization should not fail");
-    let headers = parts.headers.clone();
-    let method = parts.method.clone();
+    let (parts, graphql) = req.subgraph_request.into_parts();
+    let request = TransportRequest {
+        service_name: service_name.clone(),
+        url,
+        headers: parts.headers,
+        graphql: Arc::new(graphql),
+        body,
+        encoding,
+        max_redirect_num: config.max_redirect_num,
+    };
 
     let mut fetch_error_reason = String::new();
+    let mut failure = FetchFailure {
+        status: None,
+        attempts: 0,
+        host: request.url.host().unwrap_or_default().to_owned(),
+        retry_after_ms: None,
+    };
     let mut retry_remain = config.max_retry_num;
-    let mut redirect_remain = config.max_redirect_num;
     while retry_remain > 0 {
-        let (mut parts, _) = hyper::Request::<()>::new(()).into_parts();
-        parts.headers = headers.clone();
-        parts.method = method.clone();
-        parts.uri = url.clone();
-
-        let request = hyper::Request::from_parts(parts, body.clone().into());
-        match http.call(request).await {
+        failure.attempts += 1;
+        match transport.send(request.clone()).await {
             Ok(response) => {
-                let status = response.status().as_u16();
-                tracing::debug!("Response status={}", status);
-
-                //Since we act as proxy here, we only propagate response back
-                //Unless we can retry.
-                match status {
-                    //We're redirected, let's follow it up, if we allow.
-                    301 | 302 | 303 | 307 | 308 if redirect_remain > 0 => {
-                        redirect_remain -= 1;
-                        let location = response
-                            .headers()
-                            .get(hyper::header::LOCATION)
-                            .and_then(|loc| loc.to_str().ok())
-                            .and_then(|loc| loc.parse::<hyper::Uri>().ok());
-                        match redirect_url(location, &url) {
-                            Ok(new_url) => {
-                                //Successful redirection, try again
-                                url = new_url;
-                                continue;
-                            }
-                            Err(error) => {
-                                //Error here means we do not have valid location for redirect so
-                                //give up
-                                fetch_error_reason = error.to_string();
-                                break;
-                            }
-                        }
-                    }
-                    //Temp unavailable, retry later
-                    503 => {
-                        tracing::info!("Server temp unavail. Retry");
-                        retry_remain -= 1;
-                        continue;
-                    }
-                    //We're good to return response
-                    _ => {
-                        let body = match hyper::body::to_bytes(response.into_body()).await {
-                            Ok(body) => body,
-                            //This case might be due to sudden loss of connection,
-                            //but it is a bit unlikely to happen during reading body so
-                            //let's assume error.
-                            Err(error) => {
-                                tracing::info!("Failed to read body: {}", error);
-                                fetch_error_reason = error.to_string();
-                                break;
-                            }
-                        };
-
-                        let response = match apollo_router_core::Response::from_bytes(service_name, body) {
-                            Ok(response) => response,
-                            //This should not happen
-                            Err(error) => {
-                                return Err(apollo_router_core::FetchError::SubrequestMalformedResponse {
-                                    service: service_name.to_owned(),
-                                    reason: error.to_string(),
-                                }
-                                .into());
-                            }
-                        };
-
-                        let response = hyper::Response::builder()
-                            .body(response)
-                            .expect("no argument can fail to parse or converted to the internal representation here")
-                            .into();
-                        return Ok(SubgraphResponse { response, context });
-                    }
+                return Ok(SubgraphResponse {
+                    response: response.into(),
+                    context,
+                });
+            }
+            Err(TransportError::Retry(reason)) => {
+                fetch_error_reason = reason;
+                retry_remain -= 1;
+                retry_wait(&clock, &config, retry_remain).await;
+            }
+            Err(TransportError::Failed(reason)) => {
+                fetch_error_reason = reason;
+                break;
+            }
+            Err(TransportError::Malformed(reason)) => {
+                return Err(apollo_router_core::FetchError::SubrequestMalformedResponse {
+                    service: service_name.to_string(),
+                    reason,
                 }
+                .into());
             }
-            Err(error) => {
-                tracing::info!("failed: {}", error);
-
+            Err(error @ TransportError::Status { status, retry, retry_after }) => {
                 fetch_error_reason = error.to_string();
+                failure.status = Some(status);
+                failure.retry_after_ms = retry_after.map(|retry_after| retry_after.as_millis() as u64);
+                if !retry {
+                    break;
+                }
                 retry_remain -= 1;
+                retry_wait(&clock, &config, retry_remain).await;
             }
-        };
+        }
     }
 
+    record_fetch_failure(&context, &service_name, failure);
     let fetch_error = apollo_router_core::FetchError::SubrequestHttpError {
-        service: service_name.to_owned(),
+        service: service_name.to_string(),
         reason: fetch_error_reason,
     };
     Err(fetch_error.into())
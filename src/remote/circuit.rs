@@ -0,0 +1,114 @@
+use core::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::time::{system_clock, SharedClock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Circuit breaker settings
+pub struct CircuitBreakerSettings {
+    ///Number of consecutive failures to open circuit.
+    pub failure_threshold: u32,
+    ///Time during which requests are rejected, before probe request is allowed.
+    pub reset_timeout: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Circuit breaker state
+pub enum CircuitState {
+    ///Requests are allowed.
+    Closed,
+    ///Requests are rejected without reaching subgraph.
+    Open,
+    ///Single probe request is allowed to check whether subgraph recovered.
+    HalfOpen,
+}
+
+#[derive(Default)]
+struct State {
+    failures: u32,
+    opened_at: Option<Instant>,
+    //Probe request might be cancelled without recording outcome, so it expires after reset timeout.
+    probe_at: Option<Instant>,
+}
+
+#[derive(Clone)]
+///Circuit breaker shared by all requests towards subgraph.
+pub struct CircuitBreaker {
+    state: Arc<Mutex<State>>,
+    clock: SharedClock,
+}
+
+impl CircuitBreaker {
+    #[inline(always)]
+    pub(crate) fn with_clock(clock: SharedClock) -> Self {
+        Self {
+            state: Arc::default(),
+            clock,
+        }
+    }
+
+    #[inline(always)]
+    fn lock(&self) -> MutexGuard<'_, State> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    ///Returns whether request is allowed to proceed.
+    pub(crate) fn try_acquire(&self, settings: &CircuitBreakerSettings) -> bool {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        match state.opened_at {
+            None => true,
+            Some(opened_at) if now.saturating_duration_since(opened_at) < settings.reset_timeout => false,
+            Some(_) => match state.probe_at {
+                Some(probe_at) if now.saturating_duration_since(probe_at) < settings.reset_timeout => false,
+                _ => {
+                    state.probe_at = Some(now);
+                    true
+                }
+            },
+        }
+    }
+
+    ///Records outcome of request.
+    pub(crate) fn record(&self, settings: &CircuitBreakerSettings, is_success: bool) {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        if is_success {
+            *state = State::default();
+        } else {
+            state.failures = state.failures.saturating_add(1);
+            if state.probe_at.is_some() || state.failures >= settings.failure_threshold {
+                state.opened_at = Some(now);
+                state.probe_at = None;
+            }
+        }
+    }
+
+    ///Returns current state.
+    pub fn state(&self, settings: &CircuitBreakerSettings) -> CircuitState {
+        let now = self.clock.now();
+        let state = self.lock();
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if now.saturating_duration_since(opened_at) < settings.reset_timeout => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    #[inline]
+    ///Closes circuit, resetting failure count.
+    pub fn reset(&self) {
+        *self.lock() = State::default();
+    }
+}
+
+impl Default for CircuitBreaker {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::with_clock(system_clock())
+    }
+}
@@ -0,0 +1,135 @@
+use core::time::Duration;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::CircuitState;
+
+///Number of latest fetches, health is computed from.
+const HEALTH_WINDOW: usize = 100;
+///Success rate below which subgraph is degraded.
+const DEGRADED_SUCCESS_RATE: f64 = 0.95;
+///Success rate below which subgraph is down.
+const DOWN_SUCCESS_RATE: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Health status of subgraph
+pub enum HealthStatus {
+    ///Subgraph serves requests normally.
+    Healthy,
+    ///Some of requests fail, or circuit is probing whether subgraph recovered.
+    Degraded,
+    ///Most of requests fail, or circuit is open.
+    Down,
+}
+
+impl HealthStatus {
+    #[inline(always)]
+    ///Returns textual representation of status.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Down => "down",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+///Health of subgraph over latest fetches
+pub struct SubgraphHealth {
+    ///Overall status.
+    pub status: HealthStatus,
+    ///Number of fetches health is computed from.
+    pub samples: usize,
+    ///Share of successful fetches, `1.0` if there were none.
+    pub success_rate: f64,
+    ///Average duration of fetches.
+    pub avg_latency: Duration,
+    ///Error of the latest failed fetch.
+    pub last_error: Option<String>,
+    ///Circuit state, if circuit breaker is enabled.
+    pub circuit: Option<CircuitState>,
+}
+
+impl SubgraphHealth {
+    ///Returns JSON representation of health.
+    pub fn to_json(&self) -> serde_json::Value {
+        let circuit = self.circuit.map(|circuit| match circuit {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        });
+        serde_json::json!({
+            "status": self.status.as_str(),
+            "samples": self.samples,
+            "success_rate": self.success_rate,
+            "avg_latency_ms": self.avg_latency.as_secs_f64() * 1000.0,
+            "last_error": self.last_error,
+            "circuit": circuit,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Window {
+    //Latency and success of latest fetches, oldest first.
+    outcomes: VecDeque<(Duration, bool)>,
+    last_error: Option<String>,
+}
+
+#[derive(Clone, Default)]
+///Rolling window of fetch outcomes.
+pub(crate) struct HealthTracker {
+    window: Arc<Mutex<Window>>,
+}
+
+impl HealthTracker {
+    #[inline(always)]
+    fn lock(&self) -> MutexGuard<'_, Window> {
+        match self.window.lock() {
+            Ok(window) => window,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    ///Records outcome of fetch, with `error` set on failure.
+    pub(crate) fn record(&self, latency: Duration, error: Option<String>) {
+        let mut window = self.lock();
+        if window.outcomes.len() == HEALTH_WINDOW {
+            window.outcomes.pop_front();
+        }
+        window.outcomes.push_back((latency, error.is_none()));
+        if error.is_some() {
+            window.last_error = error;
+        }
+    }
+
+    ///Computes health, taking into account `circuit` state.
+    pub(crate) fn health(&self, circuit: Option<CircuitState>) -> SubgraphHealth {
+        let window = self.lock();
+        let samples = window.outcomes.len();
+        let (success_rate, avg_latency) = match samples {
+            0 => (1.0, Duration::ZERO),
+            samples => {
+                let successes = window.outcomes.iter().filter(|(_, is_ok)| *is_ok).count();
+                let total = window.outcomes.iter().map(|(latency, _)| *latency).sum::<Duration>();
+                (successes as f64 / samples as f64, total / samples as u32)
+            }
+        };
+        let status = match circuit {
+            Some(CircuitState::Open) => HealthStatus::Down,
+            _ if success_rate < DOWN_SUCCESS_RATE => HealthStatus::Down,
+            Some(CircuitState::HalfOpen) => HealthStatus::Degraded,
+            _ if success_rate < DEGRADED_SUCCESS_RATE => HealthStatus::Degraded,
+            _ => HealthStatus::Healthy,
+        };
+        SubgraphHealth {
+            status,
+            samples,
+            success_rate,
+            avg_latency,
+            last_error: window.last_error.clone(),
+            circuit,
+        }
+    }
+}
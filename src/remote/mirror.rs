@@ -0,0 +1,116 @@
+use apollo_router_core::SubgraphRequest;
+use hyper::body::Bytes;
+use hyper::http::header::{ACCEPT, CONTENT_TYPE};
+use hyper::http::HeaderValue;
+use tokio::task::JoinHandle;
+
+use super::{HttpClient, APPLICATION_JSON};
+use crate::encoding::Encoding;
+use crate::sample::Sampler;
+
+use core::time::Duration;
+use std::sync::Arc;
+
+///Shadow endpoint, receiving copy of subgraph requests.
+pub(crate) struct Mirror {
+    url: hyper::Uri,
+    sampler: Sampler,
+    log_diff: bool,
+}
+
+impl Mirror {
+    #[inline]
+    pub(crate) fn new(url: hyper::Uri, rate: f64, log_diff: bool) -> Self {
+        Self {
+            url,
+            sampler: Sampler::new(rate),
+            log_diff,
+        }
+    }
+
+    ///Sends copy of `request` with serialized `body` in background, if request is sampled.
+    ///
+    ///Returned task resolves to shadow response's JSON, if it is needed to compare responses.
+    pub(crate) fn send(
+        &self,
+        http: &HttpClient,
+        service_name: &Arc<str>,
+        request: &SubgraphRequest,
+        body: &Bytes,
+        encoding: Encoding,
+        timeout: Option<Duration>,
+    ) -> Option<JoinHandle<Option<serde_json::Value>>> {
+        if !self.sampler.sample() {
+            return None;
+        }
+
+        let mut shadow = hyper::Request::post(self.url.clone())
+            .body(hyper::Body::from(body.clone()))
+            .expect("no argument can fail to parse or converted to the internal representation here");
+        *shadow.headers_mut() = request.subgraph_request.headers().clone();
+        shadow
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(encoding.content_type()));
+        //Responses are compared as JSON
+        shadow.headers_mut().insert(ACCEPT, APPLICATION_JSON);
+
+        let http = http.clone();
+        let service_name = service_name.clone();
+        let log_diff = self.log_diff;
+        let fetch = async move {
+            let response = http.request(shadow).await?;
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, hyper::Error>(body)
+        };
+
+        Some(tokio::spawn(async move {
+            let result = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, fetch).await {
+                    Ok(result) => result.map_err(|error| error.to_string()),
+                    Err(_) => Err(format!("Timed out after {}ms", timeout.as_millis())),
+                },
+                None => fetch.await.map_err(|error| error.to_string()),
+            };
+
+            match result {
+                Ok(body) if log_diff => match serde_json::from_slice(&body) {
+                    Ok(body) => Some(body),
+                    Err(error) => {
+                        tracing::info!("{}: Mirror response is not valid JSON: {}", service_name, error);
+                        None
+                    }
+                },
+                Ok(_) => None,
+                Err(error) => {
+                    tracing::debug!("{}: Mirror request failed: {}", service_name, error);
+                    None
+                }
+            }
+        }))
+    }
+
+    #[inline(always)]
+    pub(crate) fn log_diff(&self) -> bool {
+        self.log_diff
+    }
+}
+
+///Logs difference between primary and shadow responses, once shadow completes.
+pub(crate) fn compare(
+    service_name: Arc<str>,
+    primary: serde_json::Value,
+    shadow: JoinHandle<Option<serde_json::Value>>,
+) {
+    tokio::spawn(async move {
+        if let Ok(Some(shadow)) = shadow.await {
+            if shadow != primary {
+                tracing::info!(
+                    "{}: Mirror response differs. Primary: {} Mirror: {}",
+                    service_name,
+                    primary,
+                    shadow
+                );
+            }
+        }
+    });
+}
@@ -0,0 +1,244 @@
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::http::header::{ACCEPT, CONTENT_TYPE, LOCATION, RETRY_AFTER};
+use tower_service::Service;
+
+use super::HttpClient;
+use crate::encoding::{body_encoding, Encoding};
+use crate::{GraphqlRequest, GraphqlResponse};
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::time::Duration;
+use std::sync::Arc;
+
+///Future returned by [SubgraphTransport::send].
+///
+///Response headers are available to subgraph plugins, e.g. for `Set-Cookie` handling.
+pub type TransportFuture =
+    Pin<Box<dyn Future<Output = Result<hyper::Response<GraphqlResponse>, TransportError>> + Send>>;
+
+#[derive(Clone, Debug)]
+///Single attempt of subgraph request
+pub struct TransportRequest {
+    ///Name of subgraph.
+    pub service_name: Arc<str>,
+    ///Subgraph endpoint.
+    pub url: hyper::Uri,
+    ///Request headers, including propagated and configured ones.
+    pub headers: HeaderMap,
+    ///GraphQL request.
+    pub graphql: Arc<GraphqlRequest>,
+    ///GraphQL request, serialized with `encoding`.
+    pub body: Bytes,
+    ///Encoding of `body`.
+    pub encoding: Encoding,
+    ///Number of redirects to follow, if transport has redirects.
+    pub max_redirect_num: usize,
+}
+
+#[derive(Debug)]
+///Failure of single attempt
+pub enum TransportError {
+    ///Temporary failure, such as network error, which is retried.
+    Retry(String),
+    ///Permanent failure, which is not retried.
+    Failed(String),
+    ///Subgraph responded with invalid GraphQL response.
+    Malformed(String),
+    ///Subgraph responded with unexpected HTTP status, without valid GraphQL response.
+    Status {
+        ///HTTP status code.
+        status: u16,
+        ///Specifies whether status is temporary, so that request is retried.
+        retry: bool,
+        ///Delay requested by subgraph via `Retry-After` header in seconds.
+        retry_after: Option<Duration>,
+    },
+}
+
+impl fmt::Display for TransportError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransportError::Retry(reason) => fmt.write_str(reason),
+            TransportError::Failed(reason) => fmt.write_str(reason),
+            TransportError::Malformed(reason) => fmt.write_fmt(format_args!("Malformed response: {}", reason)),
+            TransportError::Status { status, .. } => {
+                fmt.write_fmt(format_args!("Subgraph responded with status {}", status))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+///Transport, delivering GraphQL requests to subgraph.
+///
+///[RemoteGraphService](super::RemoteGraphService) handles retries, timeouts, circuit breaking and errors,
+///so transport only needs to perform single attempt.
+pub trait SubgraphTransport: Send + Sync {
+    ///Sends `request`, returning subgraph's response.
+    fn send(&self, request: TransportRequest) -> TransportFuture;
+}
+
+///Transport over HTTP, following redirects towards the same host.
+pub struct HttpTransport {
+    http: HttpClient,
+}
+
+impl HttpTransport {
+    #[inline(always)]
+    ///Creates transport using `http` client.
+    pub fn new(http: HttpClient) -> Self {
+        Self { http }
+    }
+}
+
+impl SubgraphTransport for HttpTransport {
+    fn send(&self, request: TransportRequest) -> TransportFuture {
+        Box::pin(http_send(self.http.clone(), request))
+    }
+}
+
+async fn http_send(
+    mut http: HttpClient,
+    request: TransportRequest,
+) -> Result<hyper::Response<GraphqlResponse>, TransportError> {
+    let TransportRequest {
+        service_name,
+        mut url,
+        mut headers,
+        body,
+        encoding,
+        max_redirect_num,
+        ..
+    } = request;
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(encoding.content_type()));
+    headers.insert(ACCEPT, HeaderValue::from_static(encoding.accept()));
+
+    let mut redirect_remain = max_redirect_num;
+    loop {
+        let mut request = hyper::Request::post(url.clone())
+            .body(hyper::Body::from(body.clone()))
+            .expect("no argument can fail to parse or converted to the internal representation here");
+        *request.headers_mut() = headers.clone();
+
+        let response = match http.call(request).await {
+            Ok(response) => response,
+            Err(error) => {
+                tracing::info!("failed: {}", error);
+                return Err(TransportError::Retry(error.to_string()));
+            }
+        };
+
+        let status = response.status().as_u16();
+        tracing::debug!("Response status={}", status);
+
+        //Since we act as proxy here, we only propagate response back
+        //Unless we can retry.
+        match status {
+            //We're redirected, let's follow it up, if we allow.
+            301 | 302 | 303 | 307 | 308 if redirect_remain > 0 => {
+                redirect_remain -= 1;
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|loc| loc.to_str().ok())
+                    .and_then(|loc| loc.parse::<hyper::Uri>().ok());
+                //Error here means we do not have valid location for redirect so give up
+                url = redirect_url(location, &url).map_err(|error| TransportError::Failed(error.to_owned()))?;
+            }
+            //Temp unavailable, retry later
+            503 => {
+                tracing::info!("Server temp unavail. Retry");
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                return Err(TransportError::Status {
+                    status,
+                    retry: true,
+                    retry_after,
+                });
+            }
+            //We're good to return response
+            _ => {
+                //Unknown content type is parsed as JSON, as it is the only type expected from plain subgraphs.
+                let response_encoding = body_encoding(response.headers().get(CONTENT_TYPE)).unwrap_or_default();
+                let (parts, body) = response.into_parts();
+                let body = match hyper::body::to_bytes(body).await {
+                    Ok(body) => body,
+                    //This case might be due to sudden loss of connection,
+                    //but it is a bit unlikely to happen during reading body so
+                    //let's assume error.
+                    Err(error) => {
+                        tracing::info!("Failed to read body: {}", error);
+                        return Err(TransportError::Failed(error.to_string()));
+                    }
+                };
+
+                let response = match response_encoding {
+                    Encoding::Json => {
+                        crate::json::parse_response(&service_name, body).map_err(|error| error.to_string())
+                    }
+                    #[allow(unreachable_patterns)]
+                    encoding => encoding.decode(&body).map_err(|error| error.to_string()),
+                };
+                return match response {
+                    Ok(response) => Ok(hyper::Response::from_parts(parts, response)),
+                    //Error pages of proxies and auth layers are not GraphQL, so their status is more informative.
+                    Err(_) if !(200..300).contains(&status) => Err(TransportError::Status {
+                        status,
+                        retry: false,
+                        retry_after: None,
+                    }),
+                    Err(reason) => Err(TransportError::Malformed(reason)),
+                };
+            }
+        }
+    }
+}
+
+fn redirect_url(location: Option<hyper::Uri>, original: &hyper::Uri) -> Result<hyper::Uri, &'static str> {
+    match location {
+        Some(loc) => match loc.scheme().is_some() {
+            //We assume that if scheme is present then it is absolute redirect
+            //should clear some sensitive headers, but in our case it is unlikely graphql server
+            //would redirect to different host, so consider it an error.
+            true => {
+                if let Some(prev_host) = original.authority().map(|part| part.host()) {
+                    match loc.authority().map(|part| part.host() == prev_host).unwrap_or(false) {
+                        true => Ok(loc),
+                        false => Err("Redirect points to different host"),
+                    }
+                } else {
+                    Ok(loc)
+                }
+            }
+            //relative to current location
+            false => {
+                use std::path::Path;
+
+                let current = Path::new(original.path());
+                let loc = Path::new(loc.path());
+                let loc = current.join(loc);
+                let loc = loc
+                    .to_str()
+                    .expect("Valid UTF-8 path")
+                    .parse::<hyper::Uri>()
+                    .expect("Valid URI");
+                let mut loc_parts = loc.into_parts();
+
+                loc_parts.scheme = original.scheme().cloned();
+                loc_parts.authority = original.authority().cloned();
+
+                hyper::Uri::from_parts(loc_parts).map_err(|_| "Relative redirect cannot be constructed")
+            }
+        },
+        None => Err("Redirect requested without Location header"),
+    }
+}
@@ -0,0 +1,125 @@
+use apollo_router_core::SubgraphRequest;
+use hyper::header::HeaderName;
+
+use crate::context::{SubgraphVariant, TypedContext};
+use crate::plugins::Counters;
+use crate::sample::Sequence;
+
+use std::sync::Arc;
+
+///Name of variant, which uses subgraph's own URL.
+pub const PRIMARY_VARIANT: &str = "primary";
+
+struct Variant {
+    name: Arc<str>,
+    url: hyper::Uri,
+    weight: f64,
+    counters: Arc<Counters>,
+}
+
+///Alternative deployments of subgraph, receiving part of its traffic.
+pub(crate) struct Variants {
+    header: Option<HeaderName>,
+    primary: Arc<Counters>,
+    variants: Vec<Variant>,
+    sequence: Sequence,
+}
+
+impl Variants {
+    #[inline]
+    pub(crate) fn new(primary: Arc<Counters>) -> Self {
+        Self {
+            header: None,
+            primary,
+            variants: Vec::new(),
+            sequence: Sequence::default(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_header(&mut self, header: HeaderName) {
+        self.header = Some(header);
+    }
+
+    #[inline]
+    pub(crate) fn add(&mut self, name: Arc<str>, url: hyper::Uri, weight: f64, counters: Arc<Counters>) {
+        let weight = match weight.is_nan() {
+            true => 0.0,
+            false => weight.clamp(0.0, 1.0),
+        };
+        self.variants.retain(|variant| variant.name != name);
+        self.variants.push(Variant {
+            name,
+            url,
+            weight,
+            counters,
+        });
+    }
+
+    ///Selects variant for request, returning its name, URL and counters, or `None` if all variants are draining.
+    ///
+    ///Variant requested via header takes priority over variant requested via context, which takes priority over
+    ///weighted selection. Draining variants are never selected.
+    pub(crate) fn select<'a, F: Fn(&str) -> bool>(
+        &'a self,
+        primary: &'a hyper::Uri,
+        request: &SubgraphRequest,
+        is_draining: F,
+    ) -> Option<(&'a str, &'a hyper::Uri, &'a Arc<Counters>)> {
+        if let Some(header) = self.header.as_ref() {
+            if let Some(value) = request.originating_request.headers().get(header) {
+                let value = value.to_str().unwrap_or_default();
+                if let Some(selected) = self.find(primary, value).filter(|_| !is_draining(value)) {
+                    return Some(selected);
+                }
+            }
+        }
+
+        if let Ok(Some(SubgraphVariant(name))) = TypedContext::new(&request.context).get::<SubgraphVariant>() {
+            if let Some(selected) = self.find(primary, &name).filter(|_| !is_draining(&name)) {
+                return Some(selected);
+            }
+        }
+
+        let position = self.sequence.next_fraction();
+        let mut threshold = 0.0;
+        for variant in self.variants.iter().filter(|variant| !is_draining(&variant.name)) {
+            threshold += variant.weight;
+            if position < threshold {
+                return Some((&*variant.name, &variant.url, &variant.counters));
+            }
+        }
+        if !is_draining(PRIMARY_VARIANT) {
+            return Some((PRIMARY_VARIANT, primary, &self.primary));
+        }
+
+        //Traffic of draining primary is spread over remaining variants, according to their weights.
+        let alternates = self
+            .variants
+            .iter()
+            .filter(|variant| !is_draining(&variant.name))
+            .collect::<Vec<_>>();
+        let total = alternates.iter().map(|variant| variant.weight).sum::<f64>();
+        let mut threshold = 0.0;
+        for variant in alternates.iter().copied() {
+            threshold += variant.weight;
+            if position * total < threshold {
+                return Some((&*variant.name, &variant.url, &variant.counters));
+            }
+        }
+        alternates
+            .first()
+            .copied()
+            .map(|variant| (&*variant.name, &variant.url, &variant.counters))
+    }
+
+    fn find<'a>(&'a self, primary: &'a hyper::Uri, name: &str) -> Option<(&'a str, &'a hyper::Uri, &'a Arc<Counters>)> {
+        if name == PRIMARY_VARIANT {
+            return Some((PRIMARY_VARIANT, primary, &self.primary));
+        }
+        self.variants
+            .iter()
+            .find(|variant| *variant.name == *name)
+            .map(|variant| (&*variant.name, &variant.url, &variant.counters))
+    }
+}
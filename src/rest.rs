@@ -0,0 +1,396 @@
+//! REST datasource subgraph
+//!
+//! Allows simple REST services to participate in federation without GraphQL wrapper.
+//! Subgraph resolves entities only: each representation is fetched from URL, built from template with its key
+//! fields, and requested fields are extracted from JSON response using JSON pointers.
+//!
+//! ```no_run
+//! use graphql_router::rest::{RestEntity, RestGraphBuilder};
+//!
+//! let users = RestGraphBuilder::new("users").entity(
+//!     "User",
+//!     RestEntity::new("https://users.internal/v1/users/{id}").field("username", "/profile/login"),
+//! );
+//! ```
+
+use apollo_router_core::{SubgraphRequest, SubgraphResponse};
+use async_graphql::parser::types::{DocumentOperations, Selection, SelectionSet};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::http::header::ACCEPT;
+use hyper::StatusCode;
+
+use crate::error::graphql_error;
+use crate::remote::{http_client, HttpClient};
+use crate::BuildGraph;
+
+use core::fmt::Write;
+use core::future::Future;
+use core::pin::Pin;
+use core::task;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const APPLICATION_JSON: HeaderValue = HeaderValue::from_static("application/json");
+
+#[derive(Clone, Debug)]
+///Mapping of entity type to REST resource
+pub struct RestEntity {
+    url: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl RestEntity {
+    #[inline]
+    ///Creates mapping to resource at `url`, with `{field}` placeholders replaced by entity key fields.
+    ///
+    ///Nested key fields are referred with dot, like `{organization.id}`.
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    ///Maps field `name` to JSON `pointer` within resource, such as `/profile/name`.
+    ///
+    ///Unmapped fields are taken from top level property of the same name.
+    pub fn field(mut self, name: &str, pointer: &str) -> Self {
+        self.fields.insert(name.to_owned(), pointer.to_owned());
+        self
+    }
+
+    fn url(&self, representation: &serde_json::Value) -> Result<String, String> {
+        let mut url = String::with_capacity(self.url.len());
+        let mut rest = self.url.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            url.push_str(&rest[..start]);
+            let key = &rest[start + 1..end];
+            let value = key
+                .split('.')
+                .try_fold(representation, |value, key| value.get(key))
+                .ok_or_else(|| format!("Representation is missing key field '{}'", key))?;
+            match value {
+                serde_json::Value::String(value) => encode_component(&mut url, value),
+                serde_json::Value::Number(value) => encode_component(&mut url, &value.to_string()),
+                serde_json::Value::Bool(value) => encode_component(&mut url, &value.to_string()),
+                _ => return Err(format!("Key field '{}' is not scalar", key)),
+            }
+            rest = &rest[end + 1..];
+        }
+        url.push_str(rest);
+        Ok(url)
+    }
+
+    fn extract(&self, resource: &serde_json::Value, fields: &[(String, String)]) -> serde_json::Value {
+        let mut entity = serde_json::Map::new();
+        for (key, name) in fields {
+            let value = match self.fields.get(name) {
+                Some(pointer) => resource.pointer(pointer),
+                None => resource.get(name),
+            };
+            entity.insert(key.clone(), value.cloned().unwrap_or_default());
+        }
+        entity.into()
+    }
+}
+
+fn encode_component(out: &mut String, value: &str) {
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            byte => {
+                let _ = write!(out, "%{:02X}", byte);
+            }
+        }
+    }
+}
+
+///REST subgraph builder
+pub struct RestGraphBuilder {
+    name: Arc<str>,
+    entities: BTreeMap<String, RestEntity>,
+    headers: HeaderMap,
+    client: Option<Arc<HttpClient>>,
+    timeout: Option<Duration>,
+}
+
+impl RestGraphBuilder {
+    #[inline]
+    ///Starts building subgraph `name`.
+    pub fn new<N: Into<Arc<str>>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            entities: BTreeMap::new(),
+            headers: HeaderMap::new(),
+            client: None,
+            timeout: None,
+        }
+    }
+
+    #[inline]
+    ///Resolves entities of type `typename` using `entity` mapping.
+    pub fn entity(mut self, typename: &str, entity: RestEntity) -> Self {
+        self.entities.insert(typename.to_owned(), entity);
+        self
+    }
+
+    #[inline]
+    ///Adds header to every REST request.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    #[inline(always)]
+    ///Uses `client`, sharing its connection pool with other subgraphs.
+    pub fn client(mut self, client: Arc<HttpClient>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets time limit for each REST request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    ///Builds service
+    pub fn build(self) -> RestGraphService {
+        let http = match self.client {
+            Some(client) => HttpClient::clone(&client),
+            None => http_client(),
+        };
+        let mut headers = self.headers;
+        headers.insert(ACCEPT, APPLICATION_JSON);
+        RestGraphService {
+            name: self.name,
+            inner: Arc::new(Inner {
+                entities: self.entities,
+                headers,
+                http,
+                timeout: self.timeout,
+            }),
+        }
+    }
+}
+
+impl BuildGraph for RestGraphBuilder {
+    type SubgraphSerivce = RestGraphService;
+
+    #[inline(always)]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline(always)]
+    fn build(self) -> Self::SubgraphSerivce {
+        self.build()
+    }
+}
+
+struct Inner {
+    entities: BTreeMap<String, RestEntity>,
+    headers: HeaderMap,
+    http: HttpClient,
+    timeout: Option<Duration>,
+}
+
+impl Inner {
+    async fn fetch(&self, url: String) -> Result<Option<serde_json::Value>, String> {
+        let mut request = hyper::Request::get(url.as_str())
+            .body(hyper::Body::empty())
+            .map_err(|error| format!("Invalid URL '{}': {}", url, error))?;
+        *request.headers_mut() = self.headers.clone();
+
+        let fetch = async {
+            let response = self.http.request(request).await.map_err(|error| error.to_string())?;
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .map_err(|error| error.to_string())?;
+            match status {
+                StatusCode::NOT_FOUND => Ok(None),
+                status if status.is_success() => serde_json::from_slice(&body)
+                    .map(Some)
+                    .map_err(|error| format!("Invalid JSON: {}", error)),
+                status => Err(format!("Responded with status {}", status)),
+            }
+        };
+
+        match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, fetch).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("Timed out after {}ms", timeout.as_millis())),
+            },
+            None => fetch.await,
+        }
+    }
+
+    async fn resolve(
+        self: Arc<Self>,
+        selections: Selections,
+        representations: Vec<serde_json::Value>,
+    ) -> serde_json::Value {
+        let selections = Arc::new(selections);
+        let mut fetches = Vec::with_capacity(representations.len());
+        for representation in representations {
+            let inner = self.clone();
+            let selections = selections.clone();
+            fetches.push(tokio::spawn(async move {
+                let typename = representation
+                    .get("__typename")
+                    .and_then(|typename| typename.as_str())
+                    .ok_or_else(|| "Representation is missing __typename".to_owned())?;
+                let entity = inner
+                    .entities
+                    .get(typename)
+                    .ok_or_else(|| format!("Entity '{}' is not mapped", typename))?;
+                let fields = selections.get(typename).map(Vec::as_slice).unwrap_or_default();
+
+                let url = entity.url(&representation)?;
+                match inner.fetch(url).await? {
+                    Some(resource) => Ok::<_, String>(entity.extract(&resource, fields)),
+                    None => Ok(serde_json::Value::Null),
+                }
+            }));
+        }
+
+        let mut entities = Vec::with_capacity(fetches.len());
+        let mut errors = Vec::new();
+        for (idx, fetch) in fetches.into_iter().enumerate() {
+            let result = match fetch.await {
+                Ok(result) => result,
+                Err(error) => Err(error.to_string()),
+            };
+            match result {
+                Ok(entity) => entities.push(entity),
+                Err(message) => {
+                    entities.push(serde_json::Value::Null);
+                    errors.push(serde_json::json!({
+                        "message": message,
+                        "path": ["_entities", idx],
+                    }));
+                }
+            }
+        }
+
+        let mut response = serde_json::json!({
+            "data": {
+                "_entities": entities,
+            },
+        });
+        if !errors.is_empty() {
+            response["errors"] = errors.into();
+        }
+        response
+    }
+}
+
+//Response key and name of fields, selected per entity type.
+type Selections = BTreeMap<String, Vec<(String, String)>>;
+
+//Returns fields selected from `_entities`.
+fn entity_selections(query: &str) -> Result<Selections, String> {
+    fn collect(selection_set: &SelectionSet, typename: Option<&str>, out: &mut Selections) {
+        for selection in selection_set.items.iter() {
+            match &selection.node {
+                Selection::Field(field) => {
+                    if let Some(typename) = typename {
+                        let name = field.node.name.node.to_string();
+                        let alias = field.node.alias.as_ref();
+                        let key = alias.map_or_else(|| name.clone(), |alias| alias.node.to_string());
+                        out.entry(typename.to_owned()).or_default().push((key, name));
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let condition = fragment.node.type_condition.as_ref();
+                    let typename = condition.map(|condition| condition.node.on.node.as_str()).or(typename);
+                    collect(&fragment.node.selection_set.node, typename, out);
+                }
+                //Query planner inlines fragments
+                Selection::FragmentSpread(_) => (),
+            }
+        }
+    }
+
+    let document = async_graphql::parser::parse_query(query).map_err(|error| error.to_string())?;
+    let operation = match &document.operations {
+        DocumentOperations::Single(operation) => operation,
+        DocumentOperations::Multiple(_) => return Err("Multiple operations are not supported".to_owned()),
+    };
+
+    let mut selections = BTreeMap::new();
+    for selection in operation.node.selection_set.node.items.iter() {
+        match &selection.node {
+            Selection::Field(field) if field.node.name.node.as_str() == "_entities" => {
+                collect(&field.node.selection_set.node, None, &mut selections);
+            }
+            _ => return Err("REST subgraph can only resolve entities".to_owned()),
+        }
+    }
+    Ok(selections)
+}
+
+#[inline]
+fn invalid_request(message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "data": null,
+        "errors": [graphql_error(message, "INVALID_REST_REQUEST")],
+    })
+}
+
+///REST subgraph service
+pub struct RestGraphService {
+    name: Arc<str>,
+    inner: Arc<Inner>,
+}
+
+impl tower_service::Service<SubgraphRequest> for RestGraphService {
+    type Response = SubgraphResponse;
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        //Requests are sent by shared client, which is always ready
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: SubgraphRequest) -> Self::Future {
+        tracing::info!("{}: REST subgraph request", self.name);
+
+        let graphql = request.subgraph_request.body();
+        let selections = entity_selections(graphql.query.as_deref().unwrap_or_default());
+        let representations = match graphql.variables.get("representations") {
+            Some(representations) => serde_json::to_value(representations).map_err(|error| error.to_string()),
+            None => Ok(serde_json::Value::Array(Vec::new())),
+        };
+        let context = request.context;
+        let service_name = self.name.clone();
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            let response = match (selections, representations) {
+                (Ok(selections), Ok(serde_json::Value::Array(representations))) => {
+                    inner.resolve(selections, representations).await
+                }
+                (Ok(_), Ok(_)) => invalid_request("Representations must be a list"),
+                (Err(message), _) | (_, Err(message)) => invalid_request(&message),
+            };
+            let bytes = crate::pool::serialize(&response)?;
+            let response = apollo_router_core::Response::from_bytes(&service_name, bytes)?;
+            Ok(SubgraphResponse {
+                response: http::Response::builder().body(response)?.into(),
+                context,
+            })
+        })
+    }
+}
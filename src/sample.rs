@@ -0,0 +1,59 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+///Deterministic sampler, selecting exactly `rate` fraction of calls, spread evenly.
+pub(crate) struct Sampler {
+    rate: f64,
+    counter: AtomicU64,
+}
+
+impl Sampler {
+    #[inline]
+    ///Creates sampler, with `rate` clamped to `0.0..=1.0`.
+    pub(crate) fn new(rate: f64) -> Self {
+        Self {
+            rate: match rate.is_nan() {
+                true => 0.0,
+                false => rate.clamp(0.0, 1.0),
+            },
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    ///Returns whether current call is selected.
+    pub(crate) fn sample(&self) -> bool {
+        if self.rate <= 0.0 {
+            return false;
+        } else if self.rate >= 1.0 {
+            return true;
+        }
+
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        ((count + 1) as f64 * self.rate).floor() > (count as f64 * self.rate).floor()
+    }
+}
+
+//Fractional part of golden ratio, which spreads sequence evenly over `0..1`
+const SEQUENCE_STEP: f64 = 0.618_033_988_749_895;
+
+#[derive(Default)]
+///Deterministic low-discrepancy sequence over `0..1`, used instead of random numbers.
+pub(crate) struct Sequence {
+    counter: AtomicU64,
+}
+
+impl Sequence {
+    #[inline(always)]
+    pub(crate) const fn new() -> Self {
+        Self {
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    ///Returns next number of sequence.
+    pub(crate) fn next_fraction(&self) -> f64 {
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        (count as f64 * SEQUENCE_STEP).fract()
+    }
+}
@@ -0,0 +1,191 @@
+//! Secrets which can be rotated at runtime
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Source of secret value.
+pub enum SecretSource {
+    ///Value is specified as it is.
+    Inline(String),
+    ///Value is read from environment variable on every access.
+    Env(String),
+    ///Value is read from file, and re-read when file is modified.
+    ///
+    ///Trailing new line is not part of value.
+    File(PathBuf),
+}
+
+///Background refresh of shared state, started on first use.
+///
+///Refresh runs on blocking pool of tokio runtime every interval, until state is dropped, so that readers of state
+///never touch file system themselves.
+#[derive(Default)]
+pub(crate) struct Refresher {
+    is_started: AtomicBool,
+}
+
+impl Refresher {
+    #[inline(always)]
+    pub(crate) fn is_started(&self) -> bool {
+        self.is_started.load(Ordering::Acquire)
+    }
+
+    ///Starts refreshing `state` with `refresh`, unless already started or called outside of tokio runtime.
+    pub(crate) fn start<T, F>(&self, state: &Arc<T>, interval: Duration, refresh: F)
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) + Clone + Send + 'static,
+    {
+        if self.is_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => runtime,
+            Err(_) => {
+                self.is_started.store(false, Ordering::Release);
+                return;
+            }
+        };
+
+        let state = Arc::downgrade(state);
+        runtime.spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            //First tick completes right away, while state is already fresh.
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let state = match state.upgrade() {
+                    Some(state) => state,
+                    None => break,
+                };
+                let refresh = refresh.clone();
+                let _ = tokio::task::spawn_blocking(move || refresh(&state)).await;
+            }
+        });
+    }
+}
+
+#[derive(Default)]
+struct Cache {
+    value: Option<Arc<str>>,
+    modified: Option<SystemTime>,
+    error: Option<(io::ErrorKind, String)>,
+}
+
+impl Cache {
+    //Re-reads file at `path`, if it is modified, keeping previous value on failure.
+    fn refresh(cache: &RwLock<Cache>, path: &Path) {
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified());
+        {
+            let cache = match cache.read() {
+                Ok(cache) => cache,
+                Err(error) => error.into_inner(),
+            };
+            if let (Some(_), Ok(modified)) = (cache.value.as_ref(), modified.as_ref()) {
+                if cache.modified == Some(*modified) {
+                    return;
+                }
+            }
+        }
+
+        let value = std::fs::read_to_string(path);
+        let mut cache = match cache.write() {
+            Ok(cache) => cache,
+            Err(error) => error.into_inner(),
+        };
+        match value {
+            Ok(value) => {
+                cache.value = Some(value.trim_end_matches(&['\r', '\n'][..]).into());
+                cache.modified = modified.ok();
+                cache.error = None;
+            }
+            Err(error) => {
+                if cache.value.is_some() {
+                    tracing::warn!("{}: Failed to re-read secret: {}", path.display(), error);
+                }
+                cache.error = Some((error.kind(), error.to_string()));
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+///Secret value, which is resolved on access.
+///
+///File is read when secret is created, and then checked for modifications in background, once secret is accessed
+///within tokio runtime, so that access never blocks on file system.
+pub struct Secret {
+    source: Arc<SecretSource>,
+    cache: Arc<RwLock<Cache>>,
+    refresher: Arc<Refresher>,
+    refresh_interval: Duration,
+}
+
+impl Secret {
+    ///Creates secret, which checks for file modifications once per second.
+    pub fn new(source: SecretSource) -> Self {
+        let cache = Arc::new(RwLock::new(Cache::default()));
+        if let SecretSource::File(path) = &source {
+            Cache::refresh(&cache, path);
+        }
+        Self {
+            source: Arc::new(source),
+            cache,
+            refresher: Arc::new(Refresher::default()),
+            refresh_interval: Duration::from_secs(1),
+        }
+    }
+
+    #[inline(always)]
+    ///Sets how often file modification time is checked.
+    ///
+    ///Takes effect only before secret is accessed for the first time.
+    pub fn refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    #[inline(always)]
+    ///Returns secret's source.
+    pub fn source(&self) -> &SecretSource {
+        &self.source
+    }
+
+    ///Returns current value of secret.
+    ///
+    ///If file cannot be re-read, but it was read before, then previous value is returned.
+    pub fn get(&self) -> io::Result<Arc<str>> {
+        match &*self.source {
+            SecretSource::Inline(value) => Ok(value.as_str().into()),
+            SecretSource::Env(name) => match std::env::var(name) {
+                Ok(value) => Ok(value.into()),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Environment variable '{}' is not set", name),
+                )),
+            },
+            SecretSource::File(path) => {
+                if !self.refresher.is_started() {
+                    let path = path.clone();
+                    let refresh = move |cache: &RwLock<Cache>| Cache::refresh(cache, &path);
+                    self.refresher.start(&self.cache, self.refresh_interval, refresh);
+                }
+
+                let cache = match self.cache.read() {
+                    Ok(cache) => cache,
+                    Err(error) => error.into_inner(),
+                };
+                match (cache.value.as_ref(), cache.error.as_ref()) {
+                    (Some(value), _) => Ok(value.clone()),
+                    (None, Some((kind, error))) => Err(io::Error::new(*kind, error.clone())),
+                    (None, None) => Err(io::Error::new(io::ErrorKind::NotFound, "Secret is not loaded")),
+                }
+            }
+        }
+    }
+}
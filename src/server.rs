@@ -0,0 +1,622 @@
+//! Built-in HTTP server
+
+use apollo_router_core::ResponseBody;
+
+use crate::{parse_http_request_with, GraphqlRouter, HttpRequest, RequestHead, RouterResponse};
+
+mod compression;
+mod ip_filter;
+pub use ip_filter::IpFilter;
+mod security;
+pub use security::SecurityHeaders;
+mod tls;
+mod ws;
+pub use crate::tls::PemSource;
+pub use tls::TlsConfig;
+
+use core::convert::Infallible;
+use core::fmt;
+use core::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::http::header::{AUTHORIZATION, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use hyper::http::HeaderValue;
+use hyper::StatusCode;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const APPLICATION_JSON: HeaderValue = HeaderValue::from_static("application/json");
+
+#[derive(Debug)]
+pub enum ServerError {
+    ///Unable to bind listening socket.
+    Bind(io::Error),
+    ///Unable to load TLS configuration.
+    Tls(io::Error),
+}
+
+impl fmt::Display for ServerError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ServerError::Bind(error) => fmt.write_fmt(format_args!("Failed to bind server: {}", error)),
+            ServerError::Tls(error) => fmt.write_fmt(format_args!("Invalid TLS configuration: {}", error)),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+struct AdminConfig {
+    prefix: String,
+    token: String,
+}
+
+struct RegistrationConfig {
+    path: String,
+    token: String,
+}
+
+type RequestCheck = Box<dyn Fn(&http::request::Parts, &RequestHead<'_>) -> Result<(), String> + Send + Sync>;
+
+#[derive(Default)]
+struct Shared {
+    admin: Option<AdminConfig>,
+    request_check: Option<RequestCheck>,
+    proxy: Option<crate::proxy::Proxy>,
+    websocket: Option<String>,
+    readiness: Option<String>,
+    batch_limit: Option<usize>,
+    registration: Option<RegistrationConfig>,
+    etag: bool,
+    compression: Option<usize>,
+    ip_filter: Option<IpFilter>,
+    security_headers: Option<SecurityHeaders>,
+}
+
+///Server builder
+pub struct ServerBuilder {
+    addr: SocketAddr,
+    tls: Option<TlsConfig>,
+    shared: Shared,
+}
+
+impl ServerBuilder {
+    #[inline(always)]
+    ///Starts building server listening on `addr`
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            tls: None,
+            shared: Shared::default(),
+        }
+    }
+
+    #[inline]
+    ///Enables [admin](crate::admin) endpoints under `prefix` path.
+    ///
+    ///Admin endpoints share listener with GraphQL endpoint, so requests must have `Authorization: Bearer <token>`.
+    ///Use [AdminServer::localhost](crate::admin::AdminServer::localhost) to serve them without token.
+    pub fn admin(mut self, prefix: &str, token: String) -> Self {
+        self.shared.admin = Some(AdminConfig {
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            token,
+        });
+        self
+    }
+
+    #[inline]
+    ///Rejects requests failing `check` with `BAD_REQUEST`, before their variables and extensions are parsed.
+    ///
+    ///Within [batch](Self::batching) only failing operation is rejected, with error response in its place.
+    pub fn request_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&http::request::Parts, &RequestHead<'_>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.shared.request_check = Some(Box::new(check));
+        self
+    }
+
+    #[inline(always)]
+    ///Forwards GraphQL requests using `proxy`, bypassing router.
+    ///
+    ///Admin endpoints are still served, if enabled.
+    pub fn proxy(mut self, proxy: crate::proxy::Proxy) -> Self {
+        self.shared.proxy = Some(proxy);
+        self
+    }
+
+    #[inline]
+    ///Accepts `graphql-transport-ws` connections at `path`, executing queries and mutations sent over them.
+    ///
+    ///Each operation is executed with headers of WebSocket handshake request.
+    pub fn websocket(mut self, path: &str) -> Self {
+        self.shared.websocket = Some(path.to_owned());
+        self
+    }
+
+    #[inline]
+    ///Serves readiness check at `path`, responding with [health_json](crate::admin::health_json) of subgraphs.
+    ///
+    ///Status is `SERVICE_UNAVAILABLE` while any of subgraphs is down or processing is paused.
+    pub fn readiness(mut self, path: &str) -> Self {
+        self.shared.readiness = Some(path.to_owned());
+        self
+    }
+
+    #[inline(always)]
+    ///Accepts batched requests, i.e. JSON array of operations, with at most `limit` operations.
+    ///
+    ///Operations are executed concurrently and their responses are returned as array in the same order.
+    ///Failure of one operation results in error response in its place, without affecting the others.
+    pub fn batching(mut self, limit: usize) -> Self {
+        self.shared.batch_limit = Some(limit);
+        self
+    }
+
+    #[inline]
+    ///Enables `POST` endpoint at `path` to register operations in router's
+    ///[persisted queries](crate::GraphqlRouterBuilder::with_persisted_queries).
+    ///
+    ///Body is JSON object, mapping operation id to its document. Operations are registered unless
+    ///any of ids is already used by different document, in which case `CONFLICT` is returned.
+    ///They are also persisted in shared store, if any, so that other replicas resolve them.
+    ///
+    ///Requests must have `Authorization: Bearer <token>`, as registered operations bypass safelist.
+    pub fn persisted_query_registration(mut self, path: &str, token: String) -> Self {
+        self.shared.registration = Some(RegistrationConfig {
+            path: path.to_owned(),
+            token,
+        });
+        self
+    }
+
+    #[inline(always)]
+    ///Sets `ETag` on successful responses to `GET` requests, responding with `NOT_MODIFIED` when it matches
+    ///`If-None-Match`.
+    ///
+    ///Operation is still executed, so it only saves bandwidth of polling clients.
+    pub fn etag(mut self) -> Self {
+        self.shared.etag = true;
+        self
+    }
+
+    #[inline(always)]
+    ///Compresses JSON responses of at least `min_size` bytes with brotli or gzip, as negotiated via
+    ///`Accept-Encoding`.
+    ///
+    ///With [etag](Self::etag), compressed responses have `ETag` suffixed with coding, so that it is distinct
+    ///from `ETag` of uncompressed response.
+    pub fn compression(mut self, min_size: usize) -> Self {
+        self.shared.compression = Some(min_size);
+        self
+    }
+
+    #[inline(always)]
+    ///Rejects clients not allowed by `filter` with `FORBIDDEN`.
+    pub fn ip_filter(mut self, filter: IpFilter) -> Self {
+        self.shared.ip_filter = Some(filter);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets `headers` on all responses.
+    pub fn security_headers(mut self, headers: SecurityHeaders) -> Self {
+        self.shared.security_headers = Some(headers);
+        self
+    }
+
+    #[inline(always)]
+    ///Enables TLS termination.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    ///Serves `router` until `shutdown` completes.
+    ///
+    ///Each connection is handled in its own task, so it must be called within tokio runtime.
+    pub async fn serve<F: Future<Output = ()>>(self, router: GraphqlRouter, shutdown: F) -> Result<(), ServerError> {
+        let acceptor = match self.tls {
+            Some(tls) => Some(tls.acceptor().map_err(ServerError::Tls)?),
+            None => None,
+        };
+        let listener = tokio::net::TcpListener::bind(self.addr)
+            .await
+            .map_err(ServerError::Bind)?;
+        tracing::info!("Listening on {}", self.addr);
+        let shared = Arc::new(self.shared);
+
+        tokio::pin!(shutdown);
+        loop {
+            let (stream, remote) = tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(error) => {
+                        tracing::warn!("Failed to accept connection: {}", error);
+                        continue;
+                    }
+                },
+            };
+
+            let router = router.clone();
+            let shared = shared.clone();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req| respond(router.clone(), shared.clone(), remote, req));
+                let http = hyper::server::conn::Http::new();
+                let result = match acceptor {
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => http.serve_connection(stream, service).with_upgrades().await,
+                        Err(error) => {
+                            tracing::info!("{}: TLS handshake failed: {}", remote, error);
+                            return;
+                        }
+                    },
+                    None => http.serve_connection(stream, service).with_upgrades().await,
+                };
+
+                if let Err(error) = result {
+                    tracing::info!("{}: Connection error: {}", remote, error);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+///Creates JSON response with single GraphQL error.
+pub fn error_response(status: StatusCode, message: &str) -> hyper::Response<hyper::Body> {
+    let body = serde_json::json!({
+        "errors": [{
+            "message": message,
+        }]
+    });
+    let body = serde_json::to_vec(&body).expect("JSON serialization should not fail");
+    let mut response = hyper::Response::new(body.into());
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, APPLICATION_JSON);
+    response
+}
+
+///Converts router's response into plain HTTP response.
+pub fn to_http_response(response: RouterResponse) -> hyper::Response<hyper::Body> {
+    let (mut parts, body) = response.response.into_parts();
+    let body = crate::pool::serialize(&body).expect("JSON serialization should not fail");
+    parts.headers.insert(CONTENT_TYPE, APPLICATION_JSON);
+    hyper::Response::from_parts(parts, body.into())
+}
+
+///Computes strong `ETag` of `body`, using FNV-1a, so that it is the same across router instances.
+fn etag(body: &[u8]) -> HeaderValue {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in body {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    HeaderValue::from_str(&format!("\"{:016x}-{:x}\"", hash, body.len())).expect("Valid ETag")
+}
+
+//Returns `If-None-Match` candidate matching `etag` of either identity or compressed representation.
+fn matching_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> Option<HeaderValue> {
+    let candidate = if_none_match.to_str().ok()?.split(',').map(str::trim).find(|candidate| {
+        let candidate = candidate.trim_start_matches("W/");
+        candidate == "*"
+            || candidate.as_bytes() == etag.as_bytes()
+            || compression::ContentCoding::is_etag_of(candidate, etag)
+    })?;
+    match candidate {
+        "*" => Some(etag.clone()),
+        candidate => HeaderValue::from_str(candidate).ok(),
+    }
+}
+
+///Converts router's response into HTTP response with `ETag`, or into `NOT_MODIFIED` if `if_none_match` matches.
+///
+///Only successful responses without errors are tagged.
+fn to_conditional_response(
+    response: RouterResponse,
+    if_none_match: Option<HeaderValue>,
+) -> hyper::Response<hyper::Body> {
+    let is_cacheable = match response.response.body() {
+        ResponseBody::GraphQL(body) => response.response.status() == StatusCode::OK && body.errors.is_empty(),
+        _ => false,
+    };
+    if !is_cacheable {
+        return to_http_response(response);
+    }
+
+    let (mut parts, body) = response.response.into_parts();
+    let body = crate::pool::serialize(&body).expect("JSON serialization should not fail");
+    let etag = etag(&body);
+    if let Some(if_none_match) = if_none_match {
+        if let Some(etag) = matching_etag(&if_none_match, &etag) {
+            let mut response = hyper::Response::new(hyper::Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            response.headers_mut().insert(ETAG, etag);
+            return response;
+        }
+    }
+    parts.headers.insert(CONTENT_TYPE, APPLICATION_JSON);
+    parts.headers.insert(ETAG, etag);
+    hyper::Response::from_parts(parts, body.into())
+}
+
+//Compares secrets in time, which does not depend on position of the first mismatch.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    //Length of token is not secret, while comparison of its content must not short circuit.
+    left.len() == right.len() && left.iter().zip(right).fold(0u8, |diff, (left, right)| diff | (left ^ right)) == 0
+}
+
+pub(crate) fn is_authorized(req: &HttpRequest, token: &str) -> bool {
+    match req.headers().get(AUTHORIZATION).and_then(|value| value.as_bytes().strip_prefix(b"Bearer ")) {
+        Some(value) => constant_time_eq(value, token.as_bytes()),
+        None => false,
+    }
+}
+
+async fn respond(
+    router: GraphqlRouter,
+    shared: Arc<Shared>,
+    remote: SocketAddr,
+    req: HttpRequest,
+) -> Result<hyper::Response<hyper::Body>, Infallible> {
+    if let Some(filter) = shared.ip_filter.as_ref() {
+        let client = filter.client_addr(remote, req.headers());
+        if !filter.is_allowed(&client) {
+            tracing::info!("{}: Rejected client {}", remote, client);
+            return Ok(error_response(StatusCode::FORBIDDEN, "Forbidden"));
+        }
+    }
+
+    let coding = match shared.compression {
+        Some(min_size) => compression::negotiate(req.headers()).map(|coding| (coding, min_size)),
+        None => None,
+    };
+    let mut response = handle(router, shared.clone(), req).await?;
+    if let Some(security_headers) = shared.security_headers.as_ref() {
+        security_headers.apply(response.headers_mut(), false);
+    }
+    match coding {
+        Some((coding, min_size)) => Ok(compression::compress(response, coding, min_size).await),
+        None => Ok(response),
+    }
+}
+
+async fn handle(
+    mut router: GraphqlRouter,
+    shared: Arc<Shared>,
+    req: HttpRequest,
+) -> Result<hyper::Response<hyper::Body>, Infallible> {
+    if let Some(admin) = shared.admin.as_ref() {
+        if let Some(path) = req.uri().path().strip_prefix(admin.prefix.as_str()) {
+            if path.is_empty() || path.starts_with('/') {
+                if !is_authorized(&req, &admin.token) {
+                    return Ok(error_response(StatusCode::UNAUTHORIZED, "Unauthorized"));
+                }
+                let path = path.to_owned();
+                return Ok(crate::admin::handle(&router, &path, req).await);
+            }
+        }
+    }
+
+    if shared.readiness.as_deref() == Some(req.uri().path()) {
+        let health = crate::admin::health_json(&router);
+        let mut response = crate::admin::json_response(&health);
+        if health["ready"] != true {
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        }
+        return Ok(response);
+    }
+
+    //Pause of all operations is checked before parsing, so that paused server spends as little as possible on requests.
+    if let Some(response) = router.pause_switch().check_all() {
+        return Ok(to_http_response(response));
+    }
+
+    if let Some(registration) = shared.registration.as_ref() {
+        if req.uri().path() == registration.path {
+            if !is_authorized(&req, &registration.token) {
+                return Ok(error_response(StatusCode::UNAUTHORIZED, "Unauthorized"));
+            }
+            return Ok(register_persisted_queries(&router, req).await);
+        }
+    }
+
+    let req = match shared.websocket.as_deref() {
+        Some(path) if req.uri().path() == path => match ws::upgrade(router.clone(), req) {
+            Ok(response) => return Ok(response),
+            Err(req) => req,
+        },
+        _ => req,
+    };
+
+    if let Some(proxy) = shared.proxy.as_ref() {
+        return Ok(proxy.forward(req).await);
+    }
+
+    let req = match shared.batch_limit {
+        Some(limit) => {
+            let (parts, body) = req.into_parts();
+            let body = match hyper::body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(error) => return Ok(error_response(StatusCode::BAD_REQUEST, &error.to_string())),
+            };
+            if crate::parser::is_batch(&body) {
+                return Ok(handle_batch(router, &shared, parts, &body, limit).await);
+            }
+            HttpRequest::from_parts(parts, body.into())
+        }
+        None => req,
+    };
+
+    //Only `GET` requests are expected to be repeated by clients as is.
+    let if_none_match = if shared.etag && req.method() == http::Method::GET {
+        Some(req.headers().get(IF_NONE_MATCH).cloned())
+    } else {
+        None
+    };
+
+    let parse_started = std::time::Instant::now();
+    //Router's own checks reject request with the same response as its plugins would.
+    let mut rejected = None;
+    let req = parse_http_request_with(req, |parts, head| {
+        if let Err(response) = router.check_request_head(head) {
+            rejected = Some(response);
+            return Err("Rejected by router".to_owned());
+        }
+        match shared.request_check.as_ref() {
+            Some(check) => check(parts, head),
+            None => Ok(()),
+        }
+    })
+    .await;
+    let req = match (req, rejected) {
+        (Ok(req), _) => req,
+        (Err(_), Some(response)) => return Ok(to_http_response(response)),
+        (Err(error), None) => return Ok(error_response(StatusCode::BAD_REQUEST, &error.to_string())),
+    };
+    crate::plugins::record_parse_duration(&req.context, parse_started.elapsed());
+    let req = match router.pause_switch().check(req) {
+        Ok(req) => req,
+        Err(response) => return Ok(to_http_response(response)),
+    };
+    let mutation_headers = shared.security_headers.as_ref().filter(|_| {
+        let body = req.originating_request.body();
+        body.query
+            .as_deref()
+            .map_or(false, |query| crate::parser::is_mutation(query, body.operation_name.as_deref()))
+    });
+
+    match router.handle(req).await {
+        Ok(response) => {
+            let mut response = match if_none_match {
+                Some(if_none_match) => to_conditional_response(response, if_none_match),
+                None => to_http_response(response),
+            };
+            if let Some(security_headers) = mutation_headers {
+                security_headers.apply(response.headers_mut(), true);
+            }
+            Ok(response)
+        }
+        Err(error) => {
+            tracing::warn!("Router failed to handle request: {}", error);
+            Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"))
+        }
+    }
+}
+
+async fn register_persisted_queries(router: &GraphqlRouter, req: HttpRequest) -> hyper::Response<hyper::Body> {
+    if req.method() != http::Method::POST {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+    }
+    let persisted = match router.persisted_queries() {
+        Some(persisted) => persisted,
+        None => return error_response(StatusCode::NOT_FOUND, "Persisted queries are not enabled"),
+    };
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+    };
+    let operations = match serde_json::from_slice::<std::collections::BTreeMap<String, String>>(&body) {
+        Ok(operations) => operations,
+        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+    };
+
+    let conflicts = operations
+        .iter()
+        .filter(|(id, document)| matches!(persisted.get(id), Some(existing) if existing != **document))
+        .map(|(id, _)| id.as_str())
+        .collect::<Vec<_>>();
+    if !conflicts.is_empty() {
+        let message = format!("Operation ids are already registered: {}", conflicts.join(", "));
+        return error_response(StatusCode::CONFLICT, &message);
+    }
+
+    let registered = operations.len();
+    let schema = router.schema();
+    for (id, document) in operations {
+        //Operations are still resolved by this replica, so failure of shared store is not fatal.
+        if let Err(error) = persisted.share(&schema, &id, &document).await {
+            tracing::warn!("Failed to share persisted query '{}': {}", id, error);
+        }
+        persisted.register(id, document);
+    }
+    tracing::info!("Registered {} persisted queries", registered);
+
+    let body = serde_json::json!({
+        "registered": registered,
+        "total": persisted.len(),
+    });
+    let body = serde_json::to_vec(&body).expect("JSON serialization should not fail");
+    let mut response = hyper::Response::new(body.into());
+    response.headers_mut().insert(CONTENT_TYPE, APPLICATION_JSON);
+    response
+}
+
+async fn handle_batch(
+    router: GraphqlRouter,
+    shared: &Shared,
+    parts: http::request::Parts,
+    body: &[u8],
+    limit: usize,
+) -> hyper::Response<hyper::Body> {
+    let requests = crate::parser::parse_batch(parts, body, limit, |parts, head| match shared.request_check.as_ref() {
+        Some(check) => check(parts, head),
+        None => Ok(()),
+    });
+    let requests = match requests {
+        Ok(requests) => requests,
+        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+    };
+
+    let responses = futures_util::future::join_all(requests.into_iter().map(|req| {
+        let mut router = router.clone();
+        async move {
+            match req.map(|req| router.pause_switch().check(req)) {
+                Ok(Ok(req)) => Ok(router.handle(req).await),
+                Ok(Err(paused)) => Ok(Ok(paused)),
+                Err(rejected) => Err(rejected),
+            }
+        }
+    }))
+    .await;
+
+    let mut body = Vec::new();
+    body.push(b'[');
+    for (idx, response) in responses.into_iter().enumerate() {
+        if idx > 0 {
+            body.push(b',');
+        }
+        let result = match response {
+            Ok(Ok(response)) => serde_json::to_writer(&mut body, response.response.body()),
+            Err(rejected) => {
+                let error = serde_json::json!({
+                    "errors": [{
+                        "message": rejected,
+                    }]
+                });
+                serde_json::to_writer(&mut body, &error)
+            }
+            Ok(Err(error)) => {
+                tracing::warn!("Router failed to handle batched request: {}", error);
+                let error = serde_json::json!({
+                    "errors": [{
+                        "message": "Internal server error",
+                    }]
+                });
+                serde_json::to_writer(&mut body, &error)
+            }
+        };
+        result.expect("JSON serialization should not fail");
+    }
+    body.push(b']');
+
+    let mut response = hyper::Response::new(body.into());
+    response.headers_mut().insert(CONTENT_TYPE, APPLICATION_JSON);
+    response
+}
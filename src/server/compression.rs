@@ -0,0 +1,144 @@
+//! Compression of responses, negotiated via `Accept-Encoding`.
+
+use hyper::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, VARY};
+use hyper::http::{HeaderMap, HeaderValue};
+
+use std::io::Write;
+
+//Brotli quality, trading ratio for speed, as responses are compressed on every request.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Content coding of response.
+pub enum ContentCoding {
+    ///`br`
+    Brotli,
+    ///`gzip`
+    Gzip,
+}
+
+impl ContentCoding {
+    #[inline(always)]
+    ///Returns name of coding, as used in `Content-Encoding`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            ContentCoding::Brotli => "br",
+            ContentCoding::Gzip => "gzip",
+        }
+    }
+
+    ///Compresses `body`.
+    pub fn compress(self, body: &[u8]) -> Vec<u8> {
+        match self {
+            ContentCoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+                encoder.write_all(body).expect("Write to Vec should not fail");
+                encoder.into_inner()
+            }
+            ContentCoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body).expect("Write to Vec should not fail");
+                encoder.finish().expect("Write to Vec should not fail")
+            }
+        }
+    }
+
+    ///Returns `etag` of representation compressed with this coding.
+    ///
+    ///Strong `ETag` identifies exact bytes, so each coding gets its own by suffixing coding's name.
+    pub fn etag(self, etag: &HeaderValue) -> Option<HeaderValue> {
+        let tag = etag.to_str().ok()?.strip_suffix('"')?;
+        HeaderValue::from_str(&format!("{}-{}\"", tag, self.name())).ok()
+    }
+
+    ///Returns whether `candidate` is `etag` of representation compressed with any coding.
+    pub fn is_etag_of(candidate: &str, etag: &HeaderValue) -> bool {
+        let tag = match etag.to_str().ok().and_then(|etag| etag.strip_suffix('"')) {
+            Some(tag) => tag,
+            None => return false,
+        };
+        let coding = candidate
+            .strip_prefix(tag)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix('"'));
+        matches!(coding, Some(coding) if coding == ContentCoding::Brotli.name() || coding == ContentCoding::Gzip.name())
+    }
+}
+
+///Selects coding acceptable by client according to `Accept-Encoding`, preferring brotli on equal weight.
+pub fn negotiate(headers: &HeaderMap) -> Option<ContentCoding> {
+    let mut result: Option<(ContentCoding, f32)> = None;
+    for value in headers.get_all(ACCEPT_ENCODING).iter() {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        for item in value.split(',') {
+            let mut params = item.split(';');
+            let coding = match params.next().map(str::trim) {
+                Some(name) if name.eq_ignore_ascii_case("br") => ContentCoding::Brotli,
+                Some(name) if name.eq_ignore_ascii_case("gzip") => ContentCoding::Gzip,
+                _ => continue,
+            };
+            let weight = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|weight| weight.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if weight <= 0.0 {
+                continue;
+            }
+            result = match result {
+                Some((current, current_weight))
+                    if current_weight > weight || (current_weight == weight && current == ContentCoding::Brotli) =>
+                {
+                    Some((current, current_weight))
+                }
+                _ => Some((coding, weight)),
+            };
+        }
+    }
+    result.map(|(coding, _)| coding)
+}
+
+///Compresses JSON `response` with `coding`, if its body is at least `min_size` bytes.
+///
+///`ETag` of compressed response is [suffixed](ContentCoding::etag) with coding.
+pub async fn compress(
+    response: hyper::Response<hyper::Body>,
+    coding: ContentCoding,
+    min_size: usize,
+) -> hyper::Response<hyper::Body> {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("application/json"));
+    if !is_json || response.headers().contains_key(CONTENT_ENCODING) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    //Bodies are always fully in memory at this point.
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            tracing::warn!("Failed to read response for compression: {}", error);
+            return hyper::Response::from_parts(parts, hyper::Body::empty());
+        }
+    };
+    if body.len() < min_size {
+        return hyper::Response::from_parts(parts, body.into());
+    }
+
+    let body = coding.compress(&body);
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static(coding.name()));
+    if let Some(etag) = parts.headers.get(ETAG).and_then(|etag| coding.etag(etag)) {
+        parts.headers.insert(ETAG, etag);
+    }
+    hyper::Response::from_parts(parts, body.into())
+}
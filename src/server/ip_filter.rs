@@ -0,0 +1,130 @@
+use hyper::http::header::{HeaderMap, FORWARDED};
+use ipnet::IpNet;
+
+use std::net::{IpAddr, SocketAddr};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+#[derive(Clone, Debug, Default)]
+///Filter of clients by IP address.
+///
+///Client address is peer address, unless peer is trusted proxy, in which case client is the
+///closest untrusted address in `Forwarded` or, if absent, `X-Forwarded-For` header.
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    trusted_proxies: Vec<IpNet>,
+}
+
+impl IpFilter {
+    #[inline(always)]
+    ///Creates filter allowing all clients.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    ///Allows clients within `net`.
+    ///
+    ///Once any network is allowed, clients outside of allowed networks are rejected.
+    pub fn allow(mut self, net: IpNet) -> Self {
+        self.allow.push(net);
+        self
+    }
+
+    #[inline(always)]
+    ///Rejects clients within `net`, even if they are allowed.
+    pub fn deny(mut self, net: IpNet) -> Self {
+        self.deny.push(net);
+        self
+    }
+
+    #[inline(always)]
+    ///Trusts forwarding headers set by proxies within `net`.
+    pub fn trusted_proxy(mut self, net: IpNet) -> Self {
+        self.trusted_proxies.push(net);
+        self
+    }
+
+    #[inline(always)]
+    fn is_trusted(&self, addr: &IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(addr))
+    }
+
+    ///Determines address of client, connected from `peer`.
+    pub fn client_addr(&self, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = peer.ip();
+        if !self.is_trusted(&peer) {
+            return peer;
+        }
+
+        let mut forwarded = forwarded_for(headers);
+        if forwarded.is_empty() {
+            forwarded = x_forwarded_for(headers);
+        }
+        //Proxies append addresses, so the closest untrusted one is the last set by trusted proxy.
+        forwarded
+            .into_iter()
+            .rev()
+            .find(|addr| !self.is_trusted(addr))
+            .unwrap_or(peer)
+    }
+
+    ///Returns whether client at `addr` is allowed.
+    pub fn is_allowed(&self, addr: &IpAddr) -> bool {
+        if self.deny.iter().any(|net| net.contains(addr)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(addr))
+    }
+}
+
+fn parse_addr(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(addr) = value.parse::<IpAddr>() {
+        return Some(addr);
+    }
+    //Address with port, IPv6 being in brackets.
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .and_then(|value| value.parse().ok())
+}
+
+fn forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    let mut result = Vec::new();
+    for value in headers.get_all(FORWARDED).iter() {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        for element in value.split(',') {
+            for pair in element.split(';') {
+                let (name, value) = match pair.split_once('=') {
+                    Some(pair) => pair,
+                    None => continue,
+                };
+                if name.trim().eq_ignore_ascii_case("for") {
+                    //Obfuscated and unknown identifiers are skipped.
+                    if let Some(addr) = parse_addr(value) {
+                        result.push(addr);
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_addr)
+        .collect()
+}
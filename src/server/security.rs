@@ -0,0 +1,81 @@
+use hyper::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+
+#[derive(Clone, Debug)]
+///Security headers, set on every response, unless response already has them.
+///
+///Defaults are:
+///
+///- `X-Content-Type-Options: nosniff`;
+///- `X-Frame-Options: DENY`;
+///- `Referrer-Policy: no-referrer`;
+///- `Cache-Control: no-store` for mutations only.
+pub struct SecurityHeaders {
+    headers: HeaderMap,
+    mutation_cache_control: Option<HeaderValue>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        headers.insert(REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+        Self {
+            headers,
+            mutation_cache_control: Some(HeaderValue::from_static("no-store")),
+        }
+    }
+}
+
+impl SecurityHeaders {
+    #[inline(always)]
+    ///Creates default headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    ///Sets `name` header to `value`, overriding default.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    #[inline(always)]
+    ///Removes `name` header from defaults.
+    pub fn without(mut self, name: &HeaderName) -> Self {
+        self.headers.remove(name);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets `Strict-Transport-Security` with `max_age` seconds, for servers behind TLS.
+    pub fn hsts(self, max_age: u64) -> Self {
+        let value = HeaderValue::from_str(&format!("max-age={}", max_age)).expect("Valid header value");
+        self.header(STRICT_TRANSPORT_SECURITY, value)
+    }
+
+    #[inline(always)]
+    ///Sets `Cache-Control` of mutation responses, or disables it with `None`.
+    pub fn mutation_cache_control(mut self, value: Option<HeaderValue>) -> Self {
+        self.mutation_cache_control = value;
+        self
+    }
+
+    ///Sets headers on response `headers`, which are not yet set.
+    pub(crate) fn apply(&self, headers: &mut HeaderMap, is_mutation: bool) {
+        for (name, value) in self.headers.iter() {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+        if is_mutation {
+            if let Some(value) = self.mutation_cache_control.as_ref() {
+                headers.insert(CACHE_CONTROL, value.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+
+use crate::tls::{PemSource, ReloadingCertifiedKey};
+
+use std::io;
+use std::sync::Arc;
+
+struct ClientAuth {
+    roots: PemSource,
+    required: bool,
+}
+
+///TLS termination config
+pub struct TlsConfig {
+    cert: PemSource,
+    key: PemSource,
+    client_auth: Option<ClientAuth>,
+}
+
+impl TlsConfig {
+    #[inline(always)]
+    ///Creates config with certificate chain and its private key.
+    pub fn new(cert: PemSource, key: PemSource) -> Self {
+        Self {
+            cert,
+            key,
+            client_auth: None,
+        }
+    }
+
+    #[inline(always)]
+    ///Enables verification of client certificates against `roots`.
+    ///
+    ///When `required` is `false`, anonymous clients are still accepted, but presented certificates
+    ///must be valid.
+    pub fn client_auth(mut self, roots: PemSource, required: bool) -> Self {
+        self.client_auth = Some(ClientAuth { roots, required });
+        self
+    }
+
+    ///Creates acceptor.
+    ///
+    ///Certificate and key files are re-loaded when modified, allowing rotation without restart.
+    pub(crate) fn acceptor(&self) -> io::Result<TlsAcceptor> {
+        let resolver = ReloadingCertifiedKey::new(self.cert.clone(), self.key.clone())?;
+
+        let builder = rustls::ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_auth {
+            Some(auth) => {
+                let roots = auth.roots.root_store()?;
+                match auth.required {
+                    true => builder.with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots)),
+                    false => builder
+                        .with_client_cert_verifier(rustls::server::AllowAnyAnonymousOrAuthenticatedClient::new(roots)),
+                }
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder.with_cert_resolver(Arc::new(resolver));
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
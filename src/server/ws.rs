@@ -0,0 +1,269 @@
+//! `graphql-transport-ws` protocol, executing queries and mutations over single socket.
+
+use futures_util::{SinkExt, StreamExt};
+use hyper::http::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, UPGRADE};
+use hyper::http::HeaderValue;
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::parser::from_request_parts;
+use crate::{GraphqlRequest, GraphqlRouter, HttpRequest};
+
+use core::time;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+///Sub-protocol name, as negotiated via `Sec-WebSocket-Protocol`.
+pub const PROTOCOL: &str = "graphql-transport-ws";
+
+const CONNECTION_INIT_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    ConnectionInit,
+    Ping,
+    Pong,
+    Subscribe { id: String, payload: GraphqlRequest },
+    Complete { id: String },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    ConnectionAck,
+    Pong,
+    Next {
+        id: &'a str,
+        payload: &'a crate::GraphqlResponse,
+    },
+    Error {
+        id: &'a str,
+        payload: [serde_json::Value; 1],
+    },
+    Complete {
+        id: &'a str,
+    },
+}
+
+impl ServerMessage<'_> {
+    #[inline(always)]
+    fn to_message(&self) -> Message {
+        Message::Text(serde_json::to_string(self).expect("JSON serialization should not fail"))
+    }
+}
+
+//URI and headers of handshake request.
+type Head = (hyper::Uri, hyper::HeaderMap);
+
+enum Outgoing {
+    Message(Message),
+    //Last message of operation, sent after operation is forgotten, so that its id can be re-used.
+    Finished(String, Message),
+}
+
+#[inline(always)]
+fn close(code: u16, reason: &'static str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::from(code),
+        reason: Cow::Borrowed(reason),
+    }))
+}
+
+fn is_upgrade(req: &HttpRequest) -> bool {
+    let has_token = |name, token: &str| {
+        req.headers().get_all(name).iter().any(|value| match value.to_str() {
+            Ok(value) => value.split(',').any(|part| part.trim().eq_ignore_ascii_case(token)),
+            Err(_) => false,
+        })
+    };
+    has_token(UPGRADE, "websocket") && has_token(CONNECTION, "upgrade")
+}
+
+///Upgrades `req` to `graphql-transport-ws` connection, if it is WebSocket handshake.
+///
+///Returns `Err(req)` when request is not WebSocket handshake.
+pub(crate) fn upgrade(
+    router: GraphqlRouter,
+    mut req: HttpRequest,
+) -> Result<hyper::Response<hyper::Body>, HttpRequest> {
+    if !is_upgrade(&req) {
+        return Err(req);
+    }
+
+    let accept = match req.headers().get(SEC_WEBSOCKET_KEY) {
+        Some(key) => tokio_tungstenite::tungstenite::handshake::derive_accept_key(key.as_bytes()),
+        None => return Ok(super::error_response(StatusCode::BAD_REQUEST, "Missing Sec-WebSocket-Key")),
+    };
+    let protocol = req.headers().get_all(SEC_WEBSOCKET_PROTOCOL).iter().any(|value| match value.to_str() {
+        Ok(value) => value.split(',').any(|part| part.trim() == PROTOCOL),
+        Err(_) => false,
+    });
+    if !protocol {
+        return Ok(super::error_response(
+            StatusCode::BAD_REQUEST,
+            "Unsupported WebSocket sub-protocol, expected graphql-transport-ws",
+        ));
+    }
+
+    //Headers of handshake are used for every operation, so that authorization and propagation work as with HTTP.
+    let head = (req.uri().clone(), req.headers().clone());
+    let on_upgrade = hyper::upgrade::on(&mut req);
+
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                serve(router, head, socket).await;
+            }
+            Err(error) => tracing::info!("WebSocket upgrade failed: {}", error),
+        }
+    });
+
+    let mut response = hyper::Response::new(hyper::Body::empty());
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    let headers = response.headers_mut();
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(PROTOCOL));
+    headers.insert(
+        SEC_WEBSOCKET_ACCEPT,
+        HeaderValue::from_str(&accept).expect("Valid Sec-WebSocket-Accept"),
+    );
+    Ok(response)
+}
+
+fn execute(
+    mut router: GraphqlRouter,
+    head: &Head,
+    id: String,
+    request: GraphqlRequest,
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+) -> JoinHandle<()> {
+    let (mut parts, _) = hyper::Request::post(head.0.clone()).body(()).expect("Valid request").into_parts();
+    parts.headers = head.1.clone();
+    let request = from_request_parts(parts, request);
+
+    tokio::spawn(async move {
+        let message = match router.handle(request).await {
+            Ok(response) => {
+                let payload = response.response.body();
+                let next = ServerMessage::Next { id: &id, payload }.to_message();
+                let _ = outgoing.send(Outgoing::Message(next));
+                ServerMessage::Complete { id: &id }.to_message()
+            }
+            Err(error) => {
+                tracing::warn!("Router failed to handle WebSocket operation: {}", error);
+                let payload = [serde_json::json!({ "message": "Internal server error" })];
+                ServerMessage::Error { id: &id, payload }.to_message()
+            }
+        };
+        let _ = outgoing.send(Outgoing::Finished(id, message));
+    })
+}
+
+async fn serve<S>(router: GraphqlRouter, head: Head, socket: WebSocketStream<S>)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut sink, mut stream) = socket.split();
+    let (outgoing, mut incoming) = mpsc::unbounded_channel();
+    let mut operations = HashMap::<String, JoinHandle<()>>::new();
+    let mut acknowledged = false;
+    let init_timeout = tokio::time::sleep(CONNECTION_INIT_TIMEOUT);
+    tokio::pin!(init_timeout);
+
+    let close_message = loop {
+        let message = tokio::select! {
+            _ = &mut init_timeout, if !acknowledged => break Some(close(4408, "Connection initialisation timeout")),
+            outgoing = incoming.recv() => match outgoing {
+                Some(Outgoing::Message(message)) => {
+                    if sink.send(message).await.is_err() {
+                        break None;
+                    }
+                    continue;
+                }
+                Some(Outgoing::Finished(id, message)) => {
+                    operations.remove(&id);
+                    if sink.send(message).await.is_err() {
+                        break None;
+                    }
+                    continue;
+                }
+                //Sender is kept by this loop, so channel cannot be closed.
+                None => break None,
+            },
+            message = stream.next() => match message {
+                Some(Ok(message)) => message,
+                Some(Err(error)) => {
+                    tracing::info!("WebSocket error: {}", error);
+                    break None;
+                }
+                None => break None,
+            },
+        };
+
+        let message = match message {
+            Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(message) => message,
+                Err(_) => break Some(close(4400, "Invalid message")),
+            },
+            Message::Ping(data) => {
+                if sink.send(Message::Pong(data)).await.is_err() {
+                    break None;
+                }
+                continue;
+            }
+            Message::Close(_) => break None,
+            Message::Binary(_) => break Some(close(4400, "Binary messages are not supported")),
+            Message::Pong(_) | Message::Frame(_) => continue,
+        };
+
+        match message {
+            ClientMessage::ConnectionInit => {
+                if acknowledged {
+                    break Some(close(4429, "Too many initialisation requests"));
+                }
+                acknowledged = true;
+                if sink.send(ServerMessage::ConnectionAck.to_message()).await.is_err() {
+                    break None;
+                }
+            }
+            ClientMessage::Ping => {
+                if sink.send(ServerMessage::Pong.to_message()).await.is_err() {
+                    break None;
+                }
+            }
+            ClientMessage::Pong => (),
+            ClientMessage::Subscribe { id, payload } => {
+                if !acknowledged {
+                    break Some(close(4401, "Unauthorized"));
+                }
+                if operations.contains_key(&id) {
+                    break Some(close(4409, "Subscriber for id already exists"));
+                }
+                let operation = execute(router.clone(), &head, id.clone(), payload, outgoing.clone());
+                operations.insert(id, operation);
+            }
+            ClientMessage::Complete { id } => {
+                if let Some(operation) = operations.remove(&id) {
+                    operation.abort();
+                }
+            }
+        }
+    };
+
+    for (_, operation) in operations.drain() {
+        operation.abort();
+    }
+    if let Some(message) = close_message {
+        let _ = sink.send(message).await;
+    }
+    let _ = sink.close().await;
+}
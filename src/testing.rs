@@ -0,0 +1,18 @@
+//! Utilities to test router with mocked or locally served subgraphs
+//!
+//! Available with `testing` feature.
+
+mod mock;
+pub use mock::{MockGraphBuilder, MockGraphService};
+mod record;
+pub use record::{read_recording, Exchange, RecordingGraphBuilder, RecordingGraphService};
+mod harness;
+pub use harness::{CallLog, LoggedGraph, LoggedGraphService, RouterTestHarness, RouterTestHarnessBuilder, SubgraphCall};
+mod clock;
+pub use clock::TestClock;
+mod federation;
+pub use federation::{
+    spawn_federation, spawn_federation_with, FederatedSubgraph, Federation, FederationError, FederationShutdown,
+};
+mod golden;
+pub use golden::{stable_json, GoldenError, GoldenMismatch, GoldenSuite, MASKED, UPDATE_GOLDEN_ENV};
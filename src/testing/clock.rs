@@ -0,0 +1,88 @@
+use tokio::sync::watch;
+
+use crate::time::{Clock, Sleep};
+
+use core::time::Duration;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+struct Inner {
+    instant: Instant,
+    system_time: SystemTime,
+    elapsed_tx: watch::Sender<Duration>,
+    elapsed_rx: watch::Receiver<Duration>,
+}
+
+#[derive(Clone)]
+///Clock, which time moves only when advanced by test.
+///
+///Sleeps complete once clock is advanced past their deadline.
+pub struct TestClock {
+    inner: Arc<Inner>,
+}
+
+impl TestClock {
+    #[inline(always)]
+    ///Creates clock with wall time at UNIX epoch.
+    pub fn new() -> Self {
+        Self::at(SystemTime::UNIX_EPOCH)
+    }
+
+    ///Creates clock with wall time at `system_time`.
+    pub fn at(system_time: SystemTime) -> Self {
+        let (elapsed_tx, elapsed_rx) = watch::channel(Duration::ZERO);
+        Self {
+            inner: Arc::new(Inner {
+                instant: Instant::now(),
+                system_time,
+                elapsed_tx,
+                elapsed_rx,
+            }),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns time elapsed since clock creation.
+    pub fn elapsed(&self) -> Duration {
+        *self.inner.elapsed_rx.borrow()
+    }
+
+    ///Moves clock forward by `duration`, completing due sleeps.
+    pub fn advance(&self, duration: Duration) {
+        let elapsed = self.elapsed().saturating_add(duration);
+        //Clock holds receiver, so there is always someone to send to.
+        let _ = self.inner.elapsed_tx.send(elapsed);
+    }
+}
+
+impl Default for TestClock {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for TestClock {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        self.inner.instant + self.elapsed()
+    }
+
+    #[inline(always)]
+    fn system_time(&self) -> SystemTime {
+        self.inner.system_time + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let deadline = self.elapsed().saturating_add(duration);
+        let mut elapsed = self.inner.elapsed_rx.clone();
+        Box::pin(async move {
+            loop {
+                let current = *elapsed.borrow();
+                if current >= deadline || elapsed.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,223 @@
+use async_graphql::{ObjectType, SubscriptionType};
+use hyper::http::header::{ACCEPT, CONTENT_TYPE};
+use hyper::http::HeaderValue;
+use hyper::StatusCode;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::encoding::{decode_request, negotiate};
+use crate::server::error_response;
+use crate::{GraphqlRouter, GraphqlRouterBuilder, HttpRequest, RemoteGraphBuilder, Schema};
+
+use core::convert::Infallible;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+type Execute =
+    Arc<dyn Fn(async_graphql::Request) -> Pin<Box<dyn Future<Output = async_graphql::Response> + Send>> + Send + Sync>;
+
+///`async-graphql` schema, served as subgraph by [spawn_federation].
+pub struct FederatedSubgraph {
+    name: String,
+    execute: Execute,
+}
+
+impl FederatedSubgraph {
+    ///Creates subgraph `name`, executing requests with `schema`.
+    pub fn new<Q, M, S>(name: &str, schema: async_graphql::Schema<Q, M, S>) -> Self
+    where
+        Q: ObjectType + 'static,
+        M: ObjectType + 'static,
+        S: SubscriptionType + 'static,
+    {
+        let execute: Execute = Arc::new(move |request| {
+            let schema = schema.clone();
+            Box::pin(async move { schema.execute(request).await })
+        });
+        Self {
+            name: name.to_owned(),
+            execute,
+        }
+    }
+}
+
+#[derive(Debug)]
+///Failure to spawn federation
+pub enum FederationError {
+    ///Unable to bind subgraph server.
+    Bind(io::Error),
+    ///Unable to build router.
+    Router(apollo_router_core::ServiceBuildError),
+}
+
+impl fmt::Display for FederationError {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FederationError::Bind(error) => fmt.write_fmt(format_args!("Failed to bind subgraph: {}", error)),
+            FederationError::Router(error) => fmt.write_fmt(format_args!("Failed to build router: {}", error)),
+        }
+    }
+}
+
+impl std::error::Error for FederationError {}
+
+///Handle to stop subgraph servers of [Federation].
+pub struct FederationShutdown {
+    servers: Vec<(oneshot::Sender<()>, JoinHandle<()>)>,
+}
+
+impl FederationShutdown {
+    ///Stops all subgraph servers, waiting for them to finish.
+    pub async fn shutdown(self) {
+        for (stop, server) in self.servers {
+            let _ = stop.send(());
+            if let Err(error) = server.await {
+                tracing::warn!("Subgraph server failed: {}", error);
+            }
+        }
+    }
+}
+
+///Router over locally served subgraphs.
+pub struct Federation {
+    router: GraphqlRouter,
+    addresses: BTreeMap<String, SocketAddr>,
+    shutdown: FederationShutdown,
+}
+
+impl Federation {
+    #[inline(always)]
+    ///Returns router.
+    pub fn router(&mut self) -> &mut GraphqlRouter {
+        &mut self.router
+    }
+
+    #[inline(always)]
+    ///Returns address of subgraph server.
+    pub fn address(&self, subgraph: &str) -> Option<SocketAddr> {
+        self.addresses.get(subgraph).copied()
+    }
+
+    #[inline(always)]
+    ///Splits into router and shutdown handle.
+    pub fn into_parts(self) -> (GraphqlRouter, FederationShutdown) {
+        (self.router, self.shutdown)
+    }
+
+    #[inline(always)]
+    ///Stops all subgraph servers.
+    pub async fn shutdown(self) {
+        self.shutdown.shutdown().await
+    }
+}
+
+async fn handle(execute: Execute, req: HttpRequest) -> Result<hyper::Response<hyper::Body>, Infallible> {
+    let (parts, body) = req.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => return Ok(error_response(StatusCode::BAD_REQUEST, &error.to_string())),
+    };
+    let request = match decode_request(parts.headers.get(CONTENT_TYPE), &body) {
+        Ok(request) => request,
+        Err(error) => return Ok(error_response(StatusCode::BAD_REQUEST, &error.to_string())),
+    };
+
+    let response = (execute)(request).await;
+    let encoding = negotiate(parts.headers.get(ACCEPT));
+    let body = encoding.encode(&response).expect("Serialization should not fail");
+    let mut response = hyper::Response::new(body.into());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(encoding.content_type()));
+    Ok(response)
+}
+
+async fn serve(subgraph: FederatedSubgraph) -> io::Result<(SocketAddr, oneshot::Sender<()>, JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let addr = listener.local_addr()?;
+    let (stop, mut stopped) = oneshot::channel::<()>();
+    let name = subgraph.name;
+    let execute = subgraph.execute;
+
+    let server = tokio::spawn(async move {
+        loop {
+            let (stream, _) = tokio::select! {
+                _ = &mut stopped => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(error) => {
+                        tracing::warn!("{}: Failed to accept connection: {}", name, error);
+                        continue;
+                    }
+                },
+            };
+
+            let execute = execute.clone();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req| handle(execute.clone(), req));
+                //Errors are only possible when router drops connection, which is not relevant to tests.
+                let _ = hyper::server::conn::Http::new().serve_connection(stream, service).await;
+            });
+        }
+    });
+    Ok((addr, stop, server))
+}
+
+#[inline(always)]
+///Serves `subgraphs` on ephemeral local ports, returning router over them.
+pub async fn spawn_federation(
+    supergraph: Arc<Schema>,
+    subgraphs: Vec<FederatedSubgraph>,
+) -> Result<Federation, FederationError> {
+    spawn_federation_with(supergraph, subgraphs, |builder| builder).await
+}
+
+///Serves `subgraphs` on ephemeral local ports, returning router over them, configured by `configure`.
+///
+///Subgraph names must match names of subgraphs in `supergraph`.
+pub async fn spawn_federation_with<F: FnOnce(GraphqlRouterBuilder) -> GraphqlRouterBuilder>(
+    supergraph: Arc<Schema>,
+    subgraphs: Vec<FederatedSubgraph>,
+    configure: F,
+) -> Result<Federation, FederationError> {
+    let mut builder = GraphqlRouter::build(supergraph);
+    let client = Arc::new(crate::remote::http_client());
+    let mut addresses = BTreeMap::new();
+    let mut shutdown = FederationShutdown {
+        servers: Vec::with_capacity(subgraphs.len()),
+    };
+
+    for subgraph in subgraphs {
+        let name = subgraph.name.clone();
+        let (addr, stop, server) = match serve(subgraph).await {
+            Ok(server) => server,
+            Err(error) => {
+                shutdown.shutdown().await;
+                return Err(FederationError::Bind(error));
+            }
+        };
+        shutdown.servers.push((stop, server));
+
+        let url = format!("http://{}/", addr).parse().expect("Valid subgraph URL");
+        builder = builder.add_subgraph(RemoteGraphBuilder::new(name.as_str(), url).client(client.clone()));
+        addresses.insert(name, addr);
+    }
+
+    match configure(builder).finish().await {
+        Ok(router) => Ok(Federation {
+            router,
+            addresses,
+            shutdown,
+        }),
+        Err(error) => {
+            shutdown.shutdown().await;
+            Err(FederationError::Router(error))
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use crate::parser::from_graphql_request;
+use crate::{GraphqlResponse, GraphqlRouter, HandleError, WarmupOperation};
+
+use core::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+///Environment variable, which makes [GoldenSuite] overwrite golden files instead of comparing.
+pub const UPDATE_GOLDEN_ENV: &str = "UPDATE_GOLDEN";
+///Replacement of masked values.
+pub const MASKED: &str = "<masked>";
+//Matches any key or array index within mask path.
+const ANY: &str = "*";
+
+enum Mask {
+    //Path segments, starting from root.
+    Path(Vec<String>),
+    //Key anywhere within response.
+    Key(String),
+}
+
+impl Mask {
+    fn apply(&self, value: &mut serde_json::Value) {
+        fn by_path(value: &mut serde_json::Value, path: &[String]) {
+            let (segment, rest) = match path.split_first() {
+                Some(split) => split,
+                None => {
+                    *value = serde_json::Value::String(MASKED.to_owned());
+                    return;
+                }
+            };
+            match value {
+                serde_json::Value::Object(object) => {
+                    for (key, value) in object.iter_mut() {
+                        if segment == ANY || segment == key {
+                            by_path(value, rest);
+                        }
+                    }
+                }
+                serde_json::Value::Array(array) => {
+                    for (idx, value) in array.iter_mut().enumerate() {
+                        if segment == ANY || segment.parse::<usize>().ok() == Some(idx) {
+                            by_path(value, rest);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        fn by_key(value: &mut serde_json::Value, name: &str) {
+            match value {
+                serde_json::Value::Object(object) => {
+                    for (key, value) in object.iter_mut() {
+                        match key == name {
+                            true => *value = serde_json::Value::String(MASKED.to_owned()),
+                            false => by_key(value, name),
+                        }
+                    }
+                }
+                serde_json::Value::Array(array) => {
+                    for value in array.iter_mut() {
+                        by_key(value, name);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        match self {
+            Self::Path(path) => by_path(value, path),
+            Self::Key(name) => by_key(value, name),
+        }
+    }
+}
+
+///Returns `value` with object keys sorted recursively, so that its serialization is stable.
+pub fn stable_json(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries = object.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, stable_json(value)))
+                .collect::<serde_json::Map<_, _>>()
+                .into()
+        }
+        serde_json::Value::Array(array) => array.into_iter().map(stable_json).collect(),
+        value => value,
+    }
+}
+
+#[derive(Debug)]
+///Response, which differs from its golden file
+pub struct GoldenMismatch {
+    ///Name of operation.
+    pub name: String,
+    ///Golden response.
+    pub expected: serde_json::Value,
+    ///Actual response, after masking.
+    pub actual: serde_json::Value,
+}
+
+#[derive(Debug)]
+///Failure of golden suite
+pub enum GoldenError {
+    ///Unable to read or write file.
+    Io(PathBuf, io::Error),
+    ///Golden file or operation variables are not valid JSON.
+    Json(PathBuf, serde_json::Error),
+    ///Router failed to execute operation.
+    Execute(String, HandleError),
+    ///Responses differ from golden files.
+    Mismatch(Vec<GoldenMismatch>),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Io(path, error) => fmt.write_fmt(format_args!("{}: {}", path.display(), error)),
+            GoldenError::Json(path, error) => {
+                fmt.write_fmt(format_args!("{}: Invalid JSON: {}", path.display(), error))
+            }
+            GoldenError::Execute(name, error) => fmt.write_fmt(format_args!("{}: Failed to execute: {}", name, error)),
+            GoldenError::Mismatch(mismatches) => {
+                fmt.write_fmt(format_args!("{} responses differ from golden files", mismatches.len()))?;
+                for mismatch in mismatches {
+                    let expected = serde_json::to_string_pretty(&mismatch.expected).unwrap_or_default();
+                    let actual = serde_json::to_string_pretty(&mismatch.actual).unwrap_or_default();
+                    fmt.write_fmt(format_args!(
+                        "\n===== {}\n--- expected\n{}\n--- actual\n{}",
+                        mismatch.name, expected, actual
+                    ))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+///Regression suite, comparing responses of operations against stored golden files.
+///
+///Golden file of operation is `<dir>/<name>.json`.
+///Missing golden files are written, as well as all golden files when [UPDATE_GOLDEN_ENV] is set.
+pub struct GoldenSuite {
+    dir: PathBuf,
+    operations: Vec<(String, WarmupOperation)>,
+    masks: Vec<Mask>,
+    update: bool,
+}
+
+impl GoldenSuite {
+    #[inline]
+    ///Creates suite without operations, storing golden files in `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            operations: Vec::new(),
+            masks: Vec::new(),
+            update: std::env::var_os(UPDATE_GOLDEN_ENV).is_some(),
+        }
+    }
+
+    #[inline(always)]
+    ///Adds operation `name`.
+    pub fn operation(mut self, name: &str, operation: WarmupOperation) -> Self {
+        self.operations.push((name.to_owned(), operation));
+        self
+    }
+
+    ///Adds operations from `*.graphql` files of `dir`, named by file stem.
+    ///
+    ///Variables are read from `<name>.variables.json`, if present.
+    pub fn operations_from_dir<P: AsRef<Path>>(mut self, dir: P) -> Result<Self, GoldenError> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|error| GoldenError::Io(dir.to_owned(), error))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|error| GoldenError::Io(dir.to_owned(), error))?.path();
+            if path.extension().map_or(false, |extension| extension == "graphql") {
+                paths.push(path);
+            }
+        }
+        //Directory order is platform specific
+        paths.sort();
+
+        for path in paths {
+            let name = match path.file_stem().and_then(|name| name.to_str()) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            let query = std::fs::read_to_string(&path).map_err(|error| GoldenError::Io(path.clone(), error))?;
+            let mut operation = WarmupOperation::new(query);
+
+            let variables = dir.join(format!("{}.variables.json", name));
+            match std::fs::read(&variables) {
+                Ok(bytes) => {
+                    operation.variables =
+                        serde_json::from_slice(&bytes).map_err(|error| GoldenError::Json(variables, error))?;
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                Err(error) => return Err(GoldenError::Io(variables, error)),
+            }
+            self.operations.push((name, operation));
+        }
+        Ok(self)
+    }
+
+    #[inline]
+    ///Masks response values at `path`, such as `/data/me/id`, with `*` matching any key or index.
+    pub fn mask_path(mut self, path: &str) -> Self {
+        let path = path.split('/').filter(|segment| !segment.is_empty()).map(str::to_owned).collect();
+        self.masks.push(Mask::Path(path));
+        self
+    }
+
+    #[inline]
+    ///Masks response values of every field named `key`.
+    pub fn mask_key(mut self, key: &str) -> Self {
+        self.masks.push(Mask::Key(key.to_owned()));
+        self
+    }
+
+    #[inline(always)]
+    ///Sets whether golden files are overwritten with actual responses.
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    ///Returns `response` with masks applied and stable ordering.
+    pub fn normalize(&self, mut response: serde_json::Value) -> serde_json::Value {
+        for mask in self.masks.iter() {
+            mask.apply(&mut response);
+        }
+        stable_json(response)
+    }
+
+    async fn execute(
+        router: &mut GraphqlRouter,
+        name: &str,
+        operation: &WarmupOperation,
+    ) -> Result<serde_json::Value, GoldenError> {
+        let request = operation
+            .request()
+            .map_err(|error| GoldenError::Execute(name.to_owned(), Box::new(error)))?;
+        let response = router
+            .handle(from_graphql_request(request))
+            .await
+            .map_err(|error| GoldenError::Execute(name.to_owned(), error))?;
+        let response = GraphqlResponse::try_from(response.response.into_body())
+            .map_err(|error| GoldenError::Execute(name.to_owned(), error.to_string().into()))?;
+        Ok(serde_json::to_value(&response).expect("JSON serialization should not fail"))
+    }
+
+    ///Executes all operations against `router`, comparing responses with golden files.
+    pub async fn run(&self, router: &mut GraphqlRouter) -> Result<(), GoldenError> {
+        let mut mismatches = Vec::new();
+
+        for (name, operation) in self.operations.iter() {
+            let actual = self.normalize(Self::execute(router, name, operation).await?);
+            let path = self.dir.join(format!("{}.json", name));
+
+            let expected = match std::fs::read(&path) {
+                Ok(_) if self.update => None,
+                Ok(bytes) => {
+                    let expected = serde_json::from_slice(&bytes);
+                    Some(expected.map_err(|error| GoldenError::Json(path.clone(), error))?)
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return Err(GoldenError::Io(path, error)),
+            };
+
+            match expected {
+                Some(expected) => {
+                    let expected = stable_json(expected);
+                    if expected != actual {
+                        mismatches.push(GoldenMismatch {
+                            name: name.clone(),
+                            expected,
+                            actual,
+                        });
+                    }
+                }
+                None => {
+                    tracing::info!("{}: Writing golden file {}", name, path.display());
+                    let mut bytes = serde_json::to_vec_pretty(&actual).expect("JSON serialization should not fail");
+                    bytes.push(b'\n');
+                    std::fs::create_dir_all(&self.dir).map_err(|error| GoldenError::Io(self.dir.clone(), error))?;
+                    std::fs::write(&path, bytes).map_err(|error| GoldenError::Io(path, error))?;
+                }
+            }
+        }
+
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(GoldenError::Mismatch(mismatches)),
+        }
+    }
+
+    ///Runs suite, panicking on any failure.
+    pub async fn assert(&self, router: &mut GraphqlRouter) {
+        if let Err(error) = self.run(router).await {
+            panic!("Golden suite failed: {}", error);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use apollo_router_core::{ServiceBuildError, SubgraphRequest, SubgraphResponse};
+
+use crate::parser::from_graphql_request;
+use crate::{
+    BuildGraph, GraphqlRequest, GraphqlResponse, GraphqlRouter, GraphqlRouterBuilder, RemoteSettingsHandle, Schema,
+};
+
+use core::task;
+use std::sync::{Arc, Mutex};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Debug, Clone, PartialEq)]
+///Request received by subgraph
+pub struct SubgraphCall {
+    ///Subgraph name.
+    pub subgraph: String,
+    ///Query sent to subgraph.
+    pub query: Option<String>,
+    ///Operation name sent to subgraph.
+    pub operation_name: Option<String>,
+    ///Variables sent to subgraph.
+    pub variables: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SubgraphCall {
+    #[inline]
+    ///Returns entity representations, if it is entity query.
+    pub fn representations(&self) -> Option<&Vec<serde_json::Value>> {
+        match self.variables.get("representations") {
+            Some(serde_json::Value::Array(representations)) => Some(representations),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+///Log of subgraph calls, in order they were made.
+pub struct CallLog {
+    calls: Arc<Mutex<Vec<SubgraphCall>>>,
+}
+
+impl CallLog {
+    #[inline(always)]
+    ///Creates empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, call: SubgraphCall) {
+        match self.calls.lock() {
+            Ok(mut calls) => calls.push(call),
+            Err(error) => error.into_inner().push(call),
+        }
+    }
+
+    ///Returns all calls.
+    pub fn calls(&self) -> Vec<SubgraphCall> {
+        match self.calls.lock() {
+            Ok(calls) => calls.clone(),
+            Err(error) => error.into_inner().clone(),
+        }
+    }
+
+    ///Returns calls made to `subgraph`.
+    pub fn calls_to(&self, subgraph: &str) -> Vec<SubgraphCall> {
+        self.calls().into_iter().filter(|call| call.subgraph == subgraph).collect()
+    }
+
+    ///Returns names of called subgraphs, in order of calls.
+    pub fn order(&self) -> Vec<String> {
+        self.calls().into_iter().map(|call| call.subgraph).collect()
+    }
+
+    ///Removes all calls.
+    pub fn clear(&self) {
+        match self.calls.lock() {
+            Ok(mut calls) => calls.clear(),
+            Err(error) => error.into_inner().clear(),
+        }
+    }
+
+    #[track_caller]
+    ///Asserts that `subgraph` was called exactly `count` times.
+    pub fn assert_call_count(&self, subgraph: &str, count: usize) {
+        let actual = self.calls_to(subgraph).len();
+        assert_eq!(
+            actual,
+            count,
+            "Subgraph '{}' called {} times, expected {}. Calls: {:?}",
+            subgraph,
+            actual,
+            count,
+            self.order()
+        );
+    }
+
+    #[track_caller]
+    ///Asserts that `subgraph` was called at least once.
+    pub fn assert_called(&self, subgraph: &str) {
+        assert!(
+            !self.calls_to(subgraph).is_empty(),
+            "Subgraph '{}' was not called. Calls: {:?}",
+            subgraph,
+            self.order()
+        );
+    }
+
+    #[track_caller]
+    ///Asserts that `subgraph` was not called.
+    pub fn assert_not_called(&self, subgraph: &str) {
+        self.assert_call_count(subgraph, 0)
+    }
+
+    #[track_caller]
+    ///Asserts that subgraphs were called exactly in `order`.
+    pub fn assert_order(&self, order: &[&str]) {
+        assert_eq!(self.order(), order, "Unexpected order of subgraph calls");
+    }
+
+    #[track_caller]
+    ///Asserts that `subgraph` received `representations` in one of its calls.
+    pub fn assert_representations(&self, subgraph: &str, representations: &serde_json::Value) {
+        let calls = self.calls_to(subgraph);
+        let found = calls
+            .iter()
+            .any(|call| call.variables.get("representations") == Some(representations));
+        let actual = calls.iter().map(|call| call.representations()).collect::<Vec<_>>();
+        assert!(
+            found,
+            "Subgraph '{}' did not receive representations {}. Received: {:?}",
+            subgraph,
+            representations,
+            actual
+        );
+    }
+
+    #[track_caller]
+    ///Asserts that `subgraph` received variable `name` with `value` in one of its calls.
+    pub fn assert_variable(&self, subgraph: &str, name: &str, value: &serde_json::Value) {
+        let calls = self.calls_to(subgraph);
+        let found = calls.iter().any(|call| call.variables.get(name) == Some(value));
+        let actual = calls.iter().map(|call| call.variables.get(name)).collect::<Vec<_>>();
+        assert!(
+            found,
+            "Subgraph '{}' did not receive variable '{}' = {}. Received: {:?}",
+            subgraph,
+            name,
+            value,
+            actual
+        );
+    }
+}
+
+///Subgraph wrapper, which logs calls.
+pub struct LoggedGraph<T> {
+    inner: T,
+    log: CallLog,
+}
+
+impl<T: BuildGraph> LoggedGraph<T> {
+    #[inline(always)]
+    ///Wraps subgraph, logging its calls into `log`.
+    pub fn new(inner: T, log: CallLog) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl<T: BuildGraph> BuildGraph for LoggedGraph<T> {
+    type SubgraphSerivce = LoggedGraphService<T::SubgraphSerivce>;
+
+    #[inline(always)]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline(always)]
+    fn settings(&self) -> Option<RemoteSettingsHandle> {
+        self.inner.settings()
+    }
+
+    #[inline(always)]
+    fn build(self) -> Self::SubgraphSerivce {
+        LoggedGraphService {
+            name: self.inner.name().to_owned(),
+            inner: self.inner.build(),
+            log: self.log,
+        }
+    }
+}
+
+///Subgraph service, which logs calls.
+pub struct LoggedGraphService<S> {
+    name: String,
+    inner: S,
+    log: CallLog,
+}
+
+impl<S> tower_service::Service<SubgraphRequest> for LoggedGraphService<S>
+where
+    S: tower_service::Service<SubgraphRequest, Response = SubgraphResponse, Error = BoxError>,
+{
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = S::Future;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, ctx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(ctx)
+    }
+
+    fn call(&mut self, request: SubgraphRequest) -> Self::Future {
+        let body = request.subgraph_request.body();
+        let variables = body
+            .variables
+            .iter()
+            .filter_map(|(key, value)| {
+                let value = serde_json_bytes::from_value::<serde_json::Value>(value.clone()).ok()?;
+                Some((key.as_str().to_owned(), value))
+            })
+            .collect();
+        self.log.push(SubgraphCall {
+            subgraph: self.name.clone(),
+            query: body.query.clone(),
+            operation_name: body.operation_name.clone(),
+            variables,
+        });
+        self.inner.call(request)
+    }
+}
+
+///Router with subgraphs, which logs subgraph calls for assertions.
+pub struct RouterTestHarness {
+    router: GraphqlRouter,
+    log: CallLog,
+}
+
+impl RouterTestHarness {
+    #[inline]
+    ///Starts building harness with supergraph `schema`.
+    pub fn builder(schema: Arc<Schema>) -> RouterTestHarnessBuilder {
+        RouterTestHarnessBuilder {
+            builder: GraphqlRouter::build(schema),
+            log: CallLog::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns log of subgraph calls.
+    pub fn calls(&self) -> &CallLog {
+        &self.log
+    }
+
+    #[inline(always)]
+    ///Returns underlying router.
+    pub fn router(&mut self) -> &mut GraphqlRouter {
+        &mut self.router
+    }
+
+    ///Executes `request`, returning JSON of GraphQL response.
+    ///
+    ///Panics if router fails to produce response.
+    pub async fn execute(&mut self, request: GraphqlRequest) -> serde_json::Value {
+        let response = match self.router.handle(from_graphql_request(request)).await {
+            Ok(response) => response,
+            Err(error) => panic!("Router failed to handle request: {}", error),
+        };
+        let response = match GraphqlResponse::try_from(response.response.into_body()) {
+            Ok(response) => response,
+            Err(error) => panic!("Router returned invalid GraphQL response: {}", error),
+        };
+        serde_json::to_value(&response).expect("JSON serialization should not fail")
+    }
+
+    #[inline]
+    ///Executes `query` without variables, returning JSON of GraphQL response.
+    pub async fn query(&mut self, query: &str) -> serde_json::Value {
+        self.execute(GraphqlRequest::builder().query(query.to_owned()).build()).await
+    }
+}
+
+///Builder of [RouterTestHarness]
+pub struct RouterTestHarnessBuilder {
+    builder: GraphqlRouterBuilder,
+    log: CallLog,
+}
+
+impl RouterTestHarnessBuilder {
+    #[inline]
+    ///Adds subgraph, which calls are logged.
+    pub fn subgraph<T: BuildGraph>(self, graph: T) -> Self
+    where
+        <<T as BuildGraph>::SubgraphSerivce as tower_service::Service<SubgraphRequest>>::Future: Send,
+    {
+        let graph = LoggedGraph::new(graph, self.log.clone());
+        Self {
+            builder: self.builder.add_subgraph(graph),
+            ..self
+        }
+    }
+
+    #[inline]
+    ///Configures router, e.g. to add plugins.
+    pub fn configure<F: FnOnce(GraphqlRouterBuilder) -> GraphqlRouterBuilder>(self, cb: F) -> Self {
+        Self {
+            builder: cb(self.builder),
+            ..self
+        }
+    }
+
+    ///Builds harness.
+    pub async fn build(self) -> Result<RouterTestHarness, ServiceBuildError> {
+        Ok(RouterTestHarness {
+            router: self.builder.finish().await?,
+            log: self.log,
+        })
+    }
+}
+
@@ -0,0 +1,178 @@
+use apollo_router_core::{FetchError, SubgraphRequest, SubgraphResponse};
+
+use crate::{BuildGraph, GraphqlRequest};
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task;
+use std::sync::Arc;
+
+type Matcher = Box<dyn Fn(&GraphqlRequest) -> bool + Send + Sync>;
+type Responder = Box<dyn Fn(&GraphqlRequest) -> serde_json::Value + Send + Sync>;
+
+///Returns request's variable converted to plain JSON.
+fn variable(request: &GraphqlRequest, name: &str) -> Option<serde_json::Value> {
+    request
+        .variables
+        .get(name)
+        .and_then(|value| serde_json_bytes::from_value(value.clone()).ok())
+}
+
+///Builder of subgraph, which responds with predefined responses.
+///
+///Handlers are tried in order they are added, and first matching one produces response.
+///Request without matching handler fails as if subgraph is unreachable.
+pub struct MockGraphBuilder {
+    name: Arc<str>,
+    handlers: Vec<(Matcher, Responder)>,
+}
+
+impl MockGraphBuilder {
+    #[inline]
+    ///Starts building subgraph
+    pub fn new<N: Into<Arc<str>>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            handlers: Vec::new(),
+        }
+    }
+
+    #[inline]
+    ///Adds handler, which produces response using `responder` when `matcher` returns `true`.
+    pub fn on<M, R>(mut self, matcher: M, responder: R) -> Self
+    where
+        M: Fn(&GraphqlRequest) -> bool + Send + Sync + 'static,
+        R: Fn(&GraphqlRequest) -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.handlers.push((Box::new(matcher), Box::new(responder)));
+        self
+    }
+
+    #[inline]
+    ///Responds with `response` to queries containing `query`.
+    ///
+    ///Whitespace is ignored, as router re-formats queries it sends to subgraphs.
+    pub fn on_query(self, query: &str, response: serde_json::Value) -> Self {
+        let query = query.split_whitespace().collect::<String>();
+        self.on(
+            move |request| match request.query.as_ref() {
+                Some(actual) => actual.split_whitespace().collect::<String>().contains(&query),
+                None => false,
+            },
+            move |_| response.clone(),
+        )
+    }
+
+    #[inline]
+    ///Responds with `response` to entity queries with exactly `representations`.
+    pub fn on_representations(self, representations: serde_json::Value, response: serde_json::Value) -> Self {
+        self.on(
+            move |request| variable(request, "representations").as_ref() == Some(&representations),
+            move |_| response.clone(),
+        )
+    }
+
+    #[inline]
+    ///Responds to entity queries using `responder`, which receives representations.
+    pub fn on_entities<R>(self, responder: R) -> Self
+    where
+        R: Fn(&[serde_json::Value]) -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.on(
+            |request| request.variables.contains_key("representations"),
+            move |request| match variable(request, "representations") {
+                Some(serde_json::Value::Array(representations)) => responder(&representations),
+                _ => responder(&[]),
+            },
+        )
+    }
+
+    #[inline]
+    ///Responds with `response` to any request, not matched by previously added handlers.
+    pub fn fallback(self, response: serde_json::Value) -> Self {
+        self.on(|_| true, move |_| response.clone())
+    }
+
+    #[inline(always)]
+    ///Builds service
+    pub fn build(self) -> MockGraphService {
+        MockGraphService {
+            name: self.name,
+            handlers: Arc::new(self.handlers),
+        }
+    }
+}
+
+impl BuildGraph for MockGraphBuilder {
+    type SubgraphSerivce = MockGraphService;
+
+    #[inline(always)]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline(always)]
+    fn build(self) -> Self::SubgraphSerivce {
+        self.build()
+    }
+}
+
+#[derive(Clone)]
+///Mock subgraph service
+pub struct MockGraphService {
+    name: Arc<str>,
+    handlers: Arc<Vec<(Matcher, Responder)>>,
+}
+
+impl MockGraphService {
+    fn respond(&self, request: &GraphqlRequest) -> Result<serde_json::Value, FetchError> {
+        for (matcher, responder) in self.handlers.iter() {
+            if matcher(request) {
+                return Ok(responder(request));
+            }
+        }
+
+        Err(FetchError::SubrequestHttpError {
+            service: self.name.to_string(),
+            reason: format!("No mock response for query: {}", request.query.as_deref().unwrap_or_default()),
+        })
+    }
+}
+
+impl tower_service::Service<SubgraphRequest> for MockGraphService {
+    type Response = SubgraphResponse;
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: SubgraphRequest) -> Self::Future {
+        tracing::debug!("{}: Mock subgraph request", self.name);
+
+        let context = request.context;
+        let result = self.respond(request.subgraph_request.body()).and_then(|response| {
+            let bytes = serde_json::to_vec(&response).expect("JSON serialization should not fail");
+            apollo_router_core::Response::from_bytes(&self.name, bytes.into()).map_err(|error| {
+                FetchError::SubrequestMalformedResponse {
+                    service: self.name.to_string(),
+                    reason: error.to_string(),
+                }
+            })
+        });
+        let result = match result {
+            Ok(response) => Ok(SubgraphResponse {
+                response: http::Response::builder()
+                    .body(response)
+                    .expect("no argument can fail to parse or converted to the internal representation here")
+                    .into(),
+                context,
+            }),
+            Err(error) => Err(error.into()),
+        };
+
+        Box::pin(ready(result))
+    }
+}
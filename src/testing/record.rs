@@ -0,0 +1,151 @@
+use apollo_router_core::{SubgraphRequest, SubgraphResponse};
+use serde::{Deserialize, Serialize};
+
+use super::MockGraphBuilder;
+use crate::{BuildGraph, RemoteSettingsHandle};
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+///Recorded subgraph request with its response.
+pub struct Exchange {
+    ///GraphQL request sent to subgraph.
+    pub request: serde_json::Value,
+    ///GraphQL response of subgraph.
+    pub response: serde_json::Value,
+}
+
+///Reads exchanges from file, written by [RecordingGraphBuilder].
+pub fn read_recording<P: AsRef<Path>>(path: P) -> io::Result<Vec<Exchange>> {
+    let file = io::BufReader::new(File::open(path)?);
+    let mut exchanges = Vec::new();
+    for line in file.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        exchanges.push(serde_json::from_str(&line)?);
+    }
+    Ok(exchanges)
+}
+
+impl MockGraphBuilder {
+    ///Creates subgraph, which replays responses recorded in file by [RecordingGraphBuilder].
+    ///
+    ///Response is selected by exact match of request's query, operation name and variables.
+    pub fn from_recording<N: Into<Arc<str>>, P: AsRef<Path>>(name: N, path: P) -> io::Result<Self> {
+        let mut builder = Self::new(name);
+        for exchange in read_recording(path)? {
+            let Exchange { request, response } = exchange;
+            builder = builder.on(
+                move |actual| serde_json::to_value(actual).map_or(false, |actual| actual == request),
+                move |_| response.clone(),
+            );
+        }
+        Ok(builder)
+    }
+}
+
+///Wrapper over subgraph, which records all its successful exchanges to file.
+///
+///File contains one JSON encoded [Exchange] per line, and is appended to.
+pub struct RecordingGraphBuilder<T> {
+    inner: T,
+    file: Arc<Mutex<File>>,
+}
+
+impl<T: BuildGraph> RecordingGraphBuilder<T> {
+    ///Wraps `inner` subgraph, recording into file at `path`.
+    pub fn new<P: AsRef<Path>>(inner: T, path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            inner,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+}
+
+impl<T: BuildGraph> BuildGraph for RecordingGraphBuilder<T>
+where
+    <T::SubgraphSerivce as tower_service::Service<SubgraphRequest>>::Future: Send + 'static,
+{
+    type SubgraphSerivce = RecordingGraphService<T::SubgraphSerivce>;
+
+    #[inline(always)]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline(always)]
+    fn settings(&self) -> Option<RemoteSettingsHandle> {
+        self.inner.settings()
+    }
+
+    #[inline(always)]
+    fn build(self) -> Self::SubgraphSerivce {
+        RecordingGraphService {
+            inner: self.inner.build(),
+            file: self.file,
+        }
+    }
+}
+
+///Subgraph service, which records its exchanges.
+pub struct RecordingGraphService<S> {
+    inner: S,
+    file: Arc<Mutex<File>>,
+}
+
+fn record(file: &Mutex<File>, request: serde_json::Value, response: &SubgraphResponse) -> io::Result<()> {
+    let exchange = Exchange {
+        request,
+        response: serde_json::to_value(response.response.body())?,
+    };
+    let mut line = serde_json::to_vec(&exchange)?;
+    line.push(b'\n');
+
+    let mut file = match file.lock() {
+        Ok(file) => file,
+        Err(error) => error.into_inner(),
+    };
+    file.write_all(&line)
+}
+
+impl<S> tower_service::Service<SubgraphRequest> for RecordingGraphService<S>
+where
+    S: tower_service::Service<SubgraphRequest, Response = SubgraphResponse, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = SubgraphResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, ctx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(ctx)
+    }
+
+    fn call(&mut self, request: SubgraphRequest) -> Self::Future {
+        let recorded = serde_json::to_value(request.subgraph_request.body());
+        let file = self.file.clone();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = response.await;
+            if let (Ok(request), Ok(response)) = (recorded, result.as_ref()) {
+                if let Err(error) = record(&file, request, response) {
+                    tracing::warn!("Failed to record subgraph exchange: {}", error);
+                }
+            }
+            result
+        })
+    }
+}
@@ -0,0 +1,97 @@
+//! Time source and request id generation, injectable for deterministic tests
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+///Future returned by [Clock::sleep].
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+///Clock shared by router components.
+pub type SharedClock = Arc<dyn Clock>;
+///Request id generator shared by router components.
+pub type SharedRequestIds = Arc<dyn RequestIdGenerator>;
+
+///Source of time for retries, backoff, circuit breaking and caching.
+pub trait Clock: Send + Sync {
+    ///Returns current monotonic time.
+    fn now(&self) -> Instant;
+    ///Returns current wall clock time.
+    fn system_time(&self) -> SystemTime;
+    ///Returns future, which completes after `duration`.
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+///Clock of operating system, driven by tokio timers.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline(always)]
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    #[inline(always)]
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[inline(always)]
+///Returns shared [SystemClock].
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+///Generator of unique request ids.
+pub trait RequestIdGenerator: Send + Sync {
+    ///Returns new request id.
+    fn generate(&self) -> String;
+}
+
+#[derive(Debug)]
+///Generates ids in format `<prefix>-<counter>`, with counter starting from 0.
+pub struct SequentialIds {
+    prefix: String,
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    #[inline]
+    ///Creates generator with specified prefix.
+    pub fn new<T: Into<String>>(prefix: T) -> Self {
+        Self {
+            prefix: prefix.into(),
+            next: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for SequentialIds {
+    #[inline]
+    ///Creates generator, prefixed by process id.
+    fn default() -> Self {
+        Self::new(std::process::id().to_string())
+    }
+}
+
+impl RequestIdGenerator for SequentialIds {
+    #[inline]
+    fn generate(&self) -> String {
+        format!("{}-{}", self.prefix, self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[inline(always)]
+///Returns shared [SequentialIds], prefixed by process id.
+pub fn sequential_ids() -> SharedRequestIds {
+    Arc::new(SequentialIds::default())
+}
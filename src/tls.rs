@@ -0,0 +1,236 @@
+//! Common TLS utilities
+
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::sign::CertifiedKey;
+
+use crate::secret::Refresher;
+
+use core::time::Duration;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+#[derive(Clone)]
+///Source of PEM encoded data.
+pub enum PemSource {
+    ///Read from file on disk, and re-read when file is modified.
+    File(PathBuf),
+    ///Read from environment variable when configuration is loaded.
+    Env(String),
+    ///In-memory PEM content.
+    Memory(Vec<u8>),
+}
+
+impl PemSource {
+    fn read(&self) -> io::Result<Vec<u8>> {
+        match self {
+            PemSource::File(path) => std::fs::read(path),
+            PemSource::Env(name) => match std::env::var(name) {
+                Ok(data) => Ok(data.into_bytes()),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Environment variable '{}' is not set", name),
+                )),
+            },
+            PemSource::Memory(data) => Ok(data.clone()),
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        match self {
+            PemSource::File(path) => std::fs::metadata(path).and_then(|meta| meta.modified()).ok(),
+            PemSource::Env(_) | PemSource::Memory(_) => None,
+        }
+    }
+
+    pub(crate) fn certs(&self) -> io::Result<Vec<Vec<u8>>> {
+        let data = self.read()?;
+        let certs = rustls_pemfile::certs(&mut data.as_slice())?;
+        match certs.is_empty() {
+            true => Err(io::Error::new(io::ErrorKind::InvalidData, "No certificate found in PEM")),
+            false => Ok(certs),
+        }
+    }
+
+    pub(crate) fn private_key(&self) -> io::Result<rustls::PrivateKey> {
+        let data = self.read()?;
+        let mut reader = data.as_slice();
+        loop {
+            match rustls_pemfile::read_one(&mut reader)? {
+                Some(rustls_pemfile::Item::RSAKey(key))
+                | Some(rustls_pemfile::Item::PKCS8Key(key))
+                | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(rustls::PrivateKey(key)),
+                Some(_) => continue,
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, "No private key found in PEM")),
+            }
+        }
+    }
+
+    pub(crate) fn root_store(&self) -> io::Result<rustls::RootCertStore> {
+        let mut roots = rustls::RootCertStore::empty();
+        let (added, _) = roots.add_parsable_certificates(&self.certs()?);
+        match added {
+            0 => Err(io::Error::new(io::ErrorKind::InvalidData, "No valid CA certificate")),
+            _ => Ok(roots),
+        }
+    }
+}
+
+struct KeyState {
+    key: Arc<CertifiedKey>,
+    modified: (Option<SystemTime>, Option<SystemTime>),
+}
+
+struct KeySources {
+    cert: PemSource,
+    key: PemSource,
+    state: RwLock<KeyState>,
+}
+
+impl KeySources {
+    //Re-loads key once files are modified.
+    fn refresh(&self) {
+        let modified = (self.cert.modified(), self.key.modified());
+        let is_modified = match self.state.read() {
+            Ok(state) => state.modified != modified,
+            Err(error) => error.into_inner().modified != modified,
+        };
+        if !is_modified {
+            return;
+        }
+
+        //Files may be replaced one by one, so keep old key until both are valid.
+        match ReloadingCertifiedKey::load(&self.cert, &self.key) {
+            Ok(key) => {
+                tracing::info!("TLS certificate reloaded");
+                let mut state = match self.state.write() {
+                    Ok(state) => state,
+                    Err(error) => error.into_inner(),
+                };
+                state.key = key;
+                state.modified = modified;
+            }
+            Err(error) => tracing::warn!("Failed to reload TLS certificate: {}", error),
+        }
+    }
+}
+
+///Certificate chain with private key, re-loaded once files are modified.
+///
+///Files are checked in background, so that handshakes never touch file system.
+pub(crate) struct ReloadingCertifiedKey {
+    sources: Arc<KeySources>,
+    refresher: Refresher,
+}
+
+impl ReloadingCertifiedKey {
+    const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn load(cert: &PemSource, key: &PemSource) -> io::Result<Arc<CertifiedKey>> {
+        let certs = cert.certs()?.into_iter().map(rustls::Certificate).collect();
+        let key = rustls::sign::any_supported_type(&key.private_key()?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(Arc::new(CertifiedKey::new(certs, key)))
+    }
+
+    pub(crate) fn new(cert: PemSource, key: PemSource) -> io::Result<Self> {
+        let state = KeyState {
+            key: Self::load(&cert, &key)?,
+            modified: (cert.modified(), key.modified()),
+        };
+        let sources = KeySources {
+            cert,
+            key,
+            state: RwLock::new(state),
+        };
+        Ok(Self {
+            sources: Arc::new(sources),
+            refresher: Refresher::default(),
+        })
+    }
+
+    fn get(&self) -> Arc<CertifiedKey> {
+        if !self.refresher.is_started() {
+            self.refresher
+                .start(&self.sources, Self::CHECK_INTERVAL, |sources: &KeySources| sources.refresh());
+        }
+        match self.sources.state.read() {
+            Ok(state) => state.key.clone(),
+            Err(error) => error.into_inner().key.clone(),
+        }
+    }
+}
+
+impl rustls::server::ResolvesServerCert for ReloadingCertifiedKey {
+    #[inline(always)]
+    fn resolve(&self, _: rustls::server::ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.get())
+    }
+}
+
+impl rustls::client::ResolvesClientCert for ReloadingCertifiedKey {
+    #[inline(always)]
+    fn resolve(&self, _: &[&[u8]], _: &[rustls::SignatureScheme]) -> Option<Arc<CertifiedKey>> {
+        Some(self.get())
+    }
+
+    #[inline(always)]
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+///TLS config for outgoing connections
+pub struct ClientTlsConfig {
+    roots: Option<PemSource>,
+    identity: Option<(PemSource, PemSource)>,
+}
+
+impl ClientTlsConfig {
+    #[inline(always)]
+    ///Creates config which uses system's native roots and no client certificate.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    ///Uses specified CA certificates instead of native roots.
+    pub fn roots(mut self, roots: PemSource) -> Self {
+        self.roots = Some(roots);
+        self
+    }
+
+    #[inline(always)]
+    ///Presents client certificate chain with its private key.
+    pub fn identity(mut self, cert: PemSource, key: PemSource) -> Self {
+        self.identity = Some((cert, key));
+        self
+    }
+
+    pub(crate) fn client_config(&self) -> io::Result<rustls::ClientConfig> {
+        let roots = match &self.roots {
+            Some(roots) => roots.root_store()?,
+            None => {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in rustls_native_certs::load_native_certs()? {
+                    //Some system certificates may be not supported by webpki, so just skip them
+                    let _ = roots.add(&rustls::Certificate(cert.0));
+                }
+                roots
+            }
+        };
+
+        let builder = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots);
+        match &self.identity {
+            Some((cert, key)) => {
+                let resolver = ReloadingCertifiedKey::new(cert.clone(), key.clone())?;
+                Ok(builder.with_client_cert_resolver(Arc::new(resolver)))
+            }
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Warmup of router before it takes live traffic
+
+use apollo_router_core::ResponseBody;
+use serde::Deserialize;
+
+use crate::parser::{from_graphql_request, is_mutation};
+use crate::{GraphqlRequest, GraphqlRouter};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+///Representative operation, executed by router against itself on startup.
+///
+///Mutations are never executed, as they would change data on every start.
+pub struct WarmupOperation {
+    ///GraphQL query.
+    pub query: String,
+    #[serde(default)]
+    ///Name of operation to execute, if query has multiple.
+    pub operation_name: Option<String>,
+    #[serde(default)]
+    ///Variables of operation.
+    pub variables: serde_json::Map<String, serde_json::Value>,
+}
+
+impl WarmupOperation {
+    #[inline]
+    ///Creates operation without variables.
+    pub fn new<T: Into<String>>(query: T) -> Self {
+        Self {
+            query: query.into(),
+            operation_name: None,
+            variables: serde_json::Map::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Sets operation name.
+    pub fn operation_name<T: Into<String>>(mut self, operation_name: T) -> Self {
+        self.operation_name = Some(operation_name.into());
+        self
+    }
+
+    #[inline(always)]
+    ///Sets variable.
+    pub fn variable<T: Into<String>>(mut self, name: T, value: serde_json::Value) -> Self {
+        self.variables.insert(name.into(), value);
+        self
+    }
+
+    #[inline]
+    ///Returns whether operation is mutation.
+    pub fn is_mutation(&self) -> bool {
+        is_mutation(&self.query, self.operation_name.as_deref())
+    }
+
+    pub(crate) fn request(&self) -> Result<GraphqlRequest, serde_json::Error> {
+        let body = serde_json::json!({
+            "query": self.query,
+            "operationName": self.operation_name,
+            "variables": self.variables,
+        });
+        let body = serde_json::to_vec(&body)?;
+        GraphqlRequest::from_bytes(body.into())
+    }
+}
+
+///Executes `operations` one by one, returning number of successful ones.
+///
+///Failures are only logged, as router is still able to serve traffic.
+pub(crate) async fn run(router: &mut GraphqlRouter, operations: &[WarmupOperation]) -> usize {
+    let mut succeeded = 0;
+
+    for (idx, operation) in operations.iter().enumerate() {
+        let name = operation.operation_name.as_deref().unwrap_or("<anonymous>");
+        if operation.is_mutation() {
+            tracing::warn!("Warmup #{} '{}': Mutation is not executed", idx, name);
+            continue;
+        }
+        let body = match operation.request() {
+            Ok(body) => body,
+            Err(error) => {
+                tracing::warn!("Warmup #{} '{}': Invalid operation: {}", idx, name, error);
+                continue;
+            }
+        };
+        match router.handle(from_graphql_request(body)).await {
+            Ok(response) => match response.response.body() {
+                ResponseBody::GraphQL(body) if !body.errors.is_empty() => {
+                    tracing::warn!("Warmup #{} '{}': Response has errors: {:?}", idx, name, body.errors);
+                }
+                _ if !response.response.status().is_success() => {
+                    tracing::warn!("Warmup #{} '{}': Response status {}", idx, name, response.response.status());
+                }
+                _ => succeeded += 1,
+            },
+            Err(error) => tracing::warn!("Warmup #{} '{}': Failed: {}", idx, name, error),
+        }
+    }
+
+    tracing::info!("Warmup completed: {}/{} operations succeeded", succeeded, operations.len());
+    succeeded
+}
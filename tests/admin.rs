@@ -0,0 +1,270 @@
+use graphql_router::remote::{SubgraphTransport, TransportError, TransportFuture, TransportRequest};
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+use graphql_router::{GraphqlRequest, GraphqlRouter};
+
+use std::sync::Arc;
+
+mod common;
+
+#[tokio::test]
+async fn should_report_schema_and_purge_plans_via_admin() {
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::user_graph())
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = common::admin(harness.router(), "GET", "/schema", "").await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let schema = common::json_body(response).await;
+    assert_eq!(schema["subgraphs"]["user"], "http://127.0.0.1:9000/user");
+    assert_eq!(schema["sha256"].as_str().map(str::len), Some(64));
+
+    let response = common::admin(harness.router(), "POST", "/cache/purge", "").await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let response = harness.query("{ me { username } }").await;
+    assert_eq!(response, common::me());
+
+    let response = common::admin(harness.router(), "POST", "/config/reload", "").await;
+    assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn should_tune_subgraph_retries_at_runtime() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Unavailable(AtomicUsize);
+
+    impl SubgraphTransport for Unavailable {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Err(TransportError::Retry("Connection refused".to_owned())) })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let transport = Arc::new(Unavailable(AtomicUsize::new(0)));
+    let user = common::remote_graph("user", transport.clone());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    harness.query(query).await;
+    assert_eq!(transport.0.swap(0, Ordering::SeqCst), 2);
+
+    let update = r#"{ "max_retry_num": 4, "timeout_ms": 1000 }"#;
+    let response = common::admin(harness.router(), "PATCH", "/subgraphs", update).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let settings = common::json_body(response).await;
+    assert_eq!(settings["user"]["max_retry_num"], 4);
+    assert_eq!(settings["user"]["timeout_ms"], 1000);
+    harness.query(query).await;
+    assert_eq!(transport.0.swap(0, Ordering::SeqCst), 4);
+
+    assert!(harness.router().update_subgraph_settings("user", |settings| settings.max_retry_num = 1));
+    assert!(!harness.router().update_subgraph_settings("unknown", |settings| settings.max_retry_num = 1));
+    harness.query(query).await;
+    assert_eq!(transport.0.swap(0, Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn should_pause_request_processing_via_admin() {
+    use graphql_router::admin;
+    use graphql_router::server::ServerBuilder;
+
+    async fn post(body: &'static str) -> (hyper::StatusCode, Option<String>, serde_json::Value) {
+        let response = common::send(|| {
+            hyper::Request::post("http://127.0.0.1:9015/")
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(hyper::Body::from(body))
+                .expect("build request")
+        })
+        .await;
+        let status = response.status();
+        let retry_after = response.headers().get(http::header::RETRY_AFTER);
+        let retry_after = retry_after.and_then(|value| value.to_str().ok()).map(str::to_owned);
+        (status, retry_after, common::json_body(response).await)
+    }
+
+    const ME: &str = r#"{ "query": "query Me { me { username } }", "operationName": "Me" }"#;
+    const ID: &str = r#"{ "query": "query Id { me { id } }", "operationName": "Id" }"#;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user")
+        .on_query("username", common::me())
+        .on_query("id", serde_json::json!({ "data": { "me": { "id": "1" } } }));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| ServerBuilder::new(([127, 0, 0, 1], 9015).into()).serve(router, shutdown));
+
+    let response = common::admin(harness.router(), "PUT", "/pause", r#"{ "retry_after_secs": 5 }"#).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let mode = harness.router().pause_switch().current().expect("to be paused");
+    assert_eq!(mode.code, "MAINTENANCE");
+    assert_eq!(admin::health_json(harness.router())["ready"], false);
+
+    let (status, retry_after, body) = post(ME).await;
+    assert_eq!(status, hyper::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(retry_after.as_deref(), Some("5"));
+    assert_eq!(body["errors"][0]["extensions"]["code"], "MAINTENANCE");
+    assert_eq!(body["errors"][0]["extensions"]["retryAfterMs"], 5000);
+    harness.calls().assert_call_count("user", 0);
+
+    //Pause of selected operations lets others through.
+    let response = common::admin(harness.router(), "PUT", "/pause", r#"{ "operations": ["Me"] }"#).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let (status, _, _) = post(ME).await;
+    assert_eq!(status, hyper::StatusCode::SERVICE_UNAVAILABLE);
+    let (status, _, body) = post(ID).await;
+    assert_eq!(status, hyper::StatusCode::OK);
+    assert_eq!(body["data"]["me"]["id"], "1");
+
+    let response = common::admin(harness.router(), "DELETE", "/pause", "").await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    assert!(!harness.router().pause_switch().is_enabled());
+    assert_eq!(admin::health_json(harness.router())["ready"], true);
+    let (status, _, body) = post(ME).await;
+    assert_eq!(status, hyper::StatusCode::OK);
+    assert_eq!(body["data"]["me"]["username"], "Me");
+}
+
+#[tokio::test]
+async fn should_require_admin_token() {
+    use graphql_router::admin::AdminServer;
+    use graphql_router::server::ServerBuilder;
+
+    async fn status(uri: &str, token: Option<&str>) -> hyper::StatusCode {
+        let response = common::send(|| {
+            let mut request = hyper::Request::get(uri);
+            if let Some(token) = token {
+                request = request.header(http::header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            request.body(hyper::Body::empty()).expect("build request")
+        })
+        .await;
+        response.status()
+    }
+
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .build()
+        .await
+        .expect("to create harness");
+    let router = harness.router().clone();
+
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9011).into()).admin("/admin", "secret".to_owned());
+    let _server = common::spawn(|shutdown| server.serve(router.clone(), shutdown));
+    assert_eq!(status("http://127.0.0.1:9011/admin/schema", None).await, hyper::StatusCode::UNAUTHORIZED);
+    let status_code = status("http://127.0.0.1:9011/admin/schema", Some("secreT")).await;
+    assert_eq!(status_code, hyper::StatusCode::UNAUTHORIZED);
+    let status_code = status("http://127.0.0.1:9011/admin/schema", Some("secret-")).await;
+    assert_eq!(status_code, hyper::StatusCode::UNAUTHORIZED);
+    let status_code = status("http://127.0.0.1:9011/admin/schema", Some("secret")).await;
+    assert_eq!(status_code, hyper::StatusCode::OK);
+
+    //Without token admin server is reachable only from the same host.
+    let _admin = common::spawn(|shutdown| AdminServer::localhost(9012).serve(router, shutdown));
+    assert_eq!(status("http://127.0.0.1:9012/schema", None).await, hyper::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn should_toggle_dynamic_plugins_via_admin() {
+    use graphql_router::plugins::{OperationEvent, OperationEventSink, OperationEvents};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<OperationEvent>>>);
+
+    impl OperationEventSink for Collector {
+        fn publish(&self, event: &OperationEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    async fn plugins(router: &GraphqlRouter) -> serde_json::Value {
+        let response = common::admin(router, "GET", "/plugins", "").await;
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        common::json_body(response).await
+    }
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let collector = Collector::default();
+    let events = OperationEvents::with_sink(Box::new(collector.clone()));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| builder.with_dyn_plugin("operation_events".to_owned(), Box::new(events)))
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    let response = common::admin(harness.router(), "DELETE", "/plugins/operation_events", "").await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let status = plugins(harness.router()).await;
+    assert_eq!(status, serde_json::json!([{ "name": "operation_events", "enabled": false }]));
+    harness.query(query).await;
+    assert!(collector.0.lock().unwrap().is_empty(), "disabled plugin must not publish events");
+
+    let response = common::admin(harness.router(), "PUT", "/plugins/operation_events", "").await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let status = plugins(harness.router()).await;
+    assert_eq!(status, serde_json::json!([{ "name": "operation_events", "enabled": true }]));
+    harness.query(query).await;
+    assert_eq!(collector.0.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn should_toggle_maintenance_via_admin() {
+    use graphql_router::plugins::{Maintenance, MaintenanceMode};
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let maintenance = Maintenance::new();
+    let switch = maintenance.clone();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| builder.with_maintenance(maintenance))
+        .build()
+        .await
+        .expect("to create harness");
+    let expected = common::me();
+    let request = |name: &str| {
+        serde_json::from_value::<GraphqlRequest>(serde_json::json!({
+            "query": format!("query {} {{ me {{ username }} }}", name),
+            "operationName": name,
+        }))
+        .expect("valid request")
+    };
+
+    let mode = r#"{ "message": "Back soon", "retry_after_secs": 30, "operations": ["Me"] }"#;
+    let response = common::admin(harness.router(), "PUT", "/maintenance", mode).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    assert_eq!(switch.current().map(|mode| mode.operations), Some(vec!["Me".to_owned()]));
+
+    let response = harness.execute(request("Me")).await;
+    assert_eq!(response["errors"][0]["message"], "Back soon");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "MAINTENANCE");
+    assert_eq!(response["errors"][0]["extensions"]["retryAfterMs"], 30_000);
+    //Operations, which are not listed, are processed.
+    assert_eq!(harness.execute(request("Other")).await, expected);
+
+    let response = common::admin(harness.router(), "GET", "/maintenance", "").await;
+    let current = common::json_body(response).await;
+    let current = serde_json::from_value::<MaintenanceMode>(current).expect("maintenance mode");
+    assert_eq!(current.code, "MAINTENANCE");
+
+    let response = common::admin(harness.router(), "DELETE", "/maintenance", "").await;
+    assert_eq!(common::json_body(response).await, serde_json::Value::Null);
+    assert!(!switch.is_enabled());
+    assert_eq!(harness.execute(request("Me")).await, expected);
+}
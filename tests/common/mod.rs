@@ -0,0 +1,229 @@
+//!Fixtures shared by integration tests.
+#![allow(dead_code)]
+
+use graphql_router::plugins::{Delay, LatencyInjectionConfig, LatencyRule};
+use graphql_router::remote::{SubgraphTransport, TransportError, TransportFuture, TransportRequest};
+use graphql_router::testing::MockGraphBuilder;
+use graphql_router::{GraphqlRequest, GraphqlResponse, GraphqlRouter, RemoteGraphBuilder, RouterRequest, Schema};
+
+use core::future::Future;
+use core::pin::Pin;
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+
+///Body of `user` subgraph response to `{ me { username } }`.
+pub const ME: &str = r#"{ "data": { "me": { "username": "Me" } } }"#;
+
+///Future resolved once test server should shut down.
+pub type Shutdown = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+///Reads schema `tests/{name}.graphql`.
+pub fn schema(name: &str) -> Arc<Schema> {
+    let path = format!("tests/{}.graphql", name);
+    Arc::new(Schema::read(&path).expect("To read supergraph"))
+}
+
+#[inline]
+///Reads `tests/supergraph.graphql`.
+pub fn supergraph() -> Arc<Schema> {
+    schema("supergraph")
+}
+
+#[inline]
+///Router response to `{ me { username } }`.
+pub fn me() -> serde_json::Value {
+    serde_json::json!({ "data": { "me": { "username": "Me" } } })
+}
+
+///`user` subgraph, resolving `{ me { username } }`.
+pub fn user_graph() -> MockGraphBuilder {
+    MockGraphBuilder::new("user").on_query("me { username }", me())
+}
+
+///`product` subgraph, resolving `topProducts` to single `Trilby`.
+pub fn product_graph() -> MockGraphBuilder {
+    MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "__typename": "Product", "upc": "top-1", "name": "Trilby" }] }
+    }))
+}
+
+///`review` subgraph, resolving single review for every product.
+pub fn review_graph() -> MockGraphBuilder {
+    MockGraphBuilder::new("review").on_entities(|representations| {
+        let entities = representations
+            .iter()
+            .map(|_| serde_json::json!({ "reviews": [{ "body": "Great hat" }] }))
+            .collect::<Vec<_>>();
+        serde_json::json!({ "data": { "_entities": entities } })
+    })
+}
+
+///Creates router request with `query` and client `headers`.
+pub fn request(query: &str, headers: &[(&str, &str)]) -> RouterRequest {
+    let mut request = http::Request::post("/");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let (parts, _) = request.body(()).expect("build request").into_parts();
+    let body = GraphqlRequest::builder().query(query.to_owned()).build();
+    graphql_router::from_request_parts(parts, body)
+}
+
+///Handles `request`, returning response head and GraphQL response as JSON.
+pub async fn handle(router: &mut GraphqlRouter, request: RouterRequest) -> (http::response::Parts, serde_json::Value) {
+    let response = router.handle(request).await.expect("to handle request");
+    let (parts, body) = response.response.into_parts();
+    let body = GraphqlResponse::try_from(body).expect("GraphQL response");
+    (parts, serde_json::to_value(&body).expect("Serialize response"))
+}
+
+///Handles GraphQL `request`, returning serialized response.
+pub async fn execute(router: &mut GraphqlRouter, request: GraphqlRequest) -> String {
+    let (parts, _) = http::Request::post("/").body(()).expect("build request").into_parts();
+    let response = router
+        .handle(graphql_router::from_request_parts(parts, request))
+        .await
+        .expect("to handle request");
+    let response = GraphqlResponse::try_from(response.response.into_body()).expect("GraphQL response");
+    serde_json::to_string(&response).expect("Serialize response")
+}
+
+///Creates response of subgraph `service` with JSON `body`.
+pub fn respond(service: &str, body: &'static str) -> Result<http::Response<GraphqlResponse>, TransportError> {
+    let body = GraphqlResponse::from_bytes(service, body.into()).expect("valid response");
+    Ok(http::Response::new(body))
+}
+
+///Latency plugin config, delaying every subgraph request by `ms`.
+pub fn latency(ms: u64) -> LatencyInjectionConfig {
+    LatencyInjectionConfig {
+        rules: vec![LatencyRule {
+            delay: Delay::Fixed { ms },
+            rate: 1.0,
+            subgraphs: Vec::new(),
+            operations: Vec::new(),
+        }],
+    }
+}
+
+///Transport, which records requests and responds with `body` and `headers`.
+pub struct StubTransport {
+    body: &'static str,
+    headers: Vec<(&'static str, &'static str)>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl StubTransport {
+    pub fn new(body: &'static str) -> Arc<Self> {
+        Self::with_headers(body, Vec::new())
+    }
+
+    pub fn with_headers(body: &'static str, headers: Vec<(&'static str, &'static str)>) -> Arc<Self> {
+        Arc::new(Self {
+            body,
+            headers,
+            requests: Default::default(),
+        })
+    }
+
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl SubgraphTransport for StubTransport {
+    fn send(&self, request: TransportRequest) -> TransportFuture {
+        let body = GraphqlResponse::from_bytes(&request.service_name, self.body.into());
+        let mut response = http::Response::builder();
+        for (name, value) in self.headers.iter() {
+            response = response.header(*name, *value);
+        }
+        self.requests.lock().unwrap().push(request);
+        let response = body.map(|body| response.body(body).expect("build response"));
+        Box::pin(async move { response.map_err(|error| TransportError::Malformed(error.to_string())) })
+    }
+}
+
+///Creates remote subgraph `name`, which sends requests over `transport`.
+pub fn remote_graph(name: &str, transport: Arc<dyn SubgraphTransport>) -> RemoteGraphBuilder {
+    let url = format!("http://{}/graphql", name).parse().expect("valid url");
+    RemoteGraphBuilder::new(name, url).transport(transport)
+}
+
+///Server spawned by test, shut down once dropped.
+pub struct TestServer {
+    stop: tokio::sync::oneshot::Sender<()>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl TestServer {
+    ///Shuts server down, waiting for it to finish.
+    pub async fn stop(self) {
+        drop(self.stop);
+        self.handle.await.expect("to join server");
+    }
+}
+
+///Spawns server future, created by `serve` with shutdown signal.
+pub fn spawn<S, F, E>(serve: S) -> TestServer
+where
+    S: FnOnce(Shutdown) -> F,
+    F: Future<Output = Result<(), E>> + Send + 'static,
+    E: core::fmt::Debug,
+{
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = serve(Box::pin(async move {
+        let _ = stopped.await;
+    }));
+    let handle = tokio::spawn(async move {
+        if let Err(error) = server.await {
+            panic!("Server failed: {:?}", error);
+        }
+    });
+    TestServer { stop, handle }
+}
+
+///Sends request, created by `request`, retrying until spawned server starts listening.
+pub async fn send<F: Fn() -> hyper::Request<hyper::Body>>(request: F) -> hyper::Response<hyper::Body> {
+    let client = hyper::Client::new();
+    for _ in 0..50 {
+        match client.request(request()).await {
+            Ok(response) => return response,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    }
+    panic!("Server is not listening");
+}
+
+///Sends request with `method` and `body` to admin endpoint `path` of `router`.
+pub async fn admin(
+    router: &GraphqlRouter,
+    method: &str,
+    path: &str,
+    body: &'static str,
+) -> hyper::Response<hyper::Body> {
+    let request = hyper::Request::builder()
+        .method(method)
+        .uri(path)
+        .body(hyper::Body::from(body))
+        .expect("build request");
+    graphql_router::admin::handle(router, path, request).await
+}
+
+///Posts JSON `body` to `uri`, retrying until spawned server starts listening.
+pub async fn post_json(uri: &str, body: &serde_json::Value) -> hyper::Response<hyper::Body> {
+    let body = serde_json::to_vec(body).expect("Serialize body");
+    send(|| {
+        hyper::Request::post(uri)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(body.clone()))
+            .expect("build request")
+    })
+    .await
+}
+
+///Reads body of `response` as JSON.
+pub async fn json_body(response: hyper::Response<hyper::Body>) -> serde_json::Value {
+    let body = hyper::body::to_bytes(response.into_body()).await.expect("read body");
+    serde_json::from_slice(&body).expect("JSON body")
+}
@@ -0,0 +1,539 @@
+use graphql_router::config::{ConfigWatcher, SecretValue};
+use graphql_router::log::LogFilter;
+use graphql_router::secret::{Secret, SecretSource};
+use graphql_router::{GraphqlRouter, RouterConfig};
+
+use core::time::Duration;
+
+mod common;
+
+const CONFIG: &str = r#"
+supergraph: tests/supergraph.graphql
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+  review:
+    url: http://127.0.0.1:9000/review
+    max_retry_num: 5
+plugins:
+  propagate_headers: {}
+server:
+  listen: 127.0.0.1:9001
+"#;
+
+#[tokio::test]
+async fn should_parse_router_config() {
+    let config = RouterConfig::from_yaml(CONFIG).expect("to parse config");
+
+    assert_eq!(config.subgraphs.len(), 2);
+    assert_eq!(config.subgraphs["product"].url, "http://127.0.0.1:9000/product");
+    assert_eq!(config.subgraphs["product"].max_retry_num, None);
+    assert_eq!(config.subgraphs["review"].max_retry_num, Some(5));
+    assert!(config.plugins.contains_key("propagate_headers"));
+    assert_eq!(config.server.as_ref().expect("server config").listen.port(), 9001);
+
+    GraphqlRouter::from_config(&config).await.expect("to create router builder");
+}
+
+#[tokio::test]
+async fn should_reject_unknown_plugin() {
+    let config = "supergraph: tests/supergraph.graphql\nplugins:\n  unknown: {}";
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    match GraphqlRouter::from_config(&config).await {
+        Err(graphql_router::config::ConfigError::Validation(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].path, "plugins.unknown");
+        }
+        _ => panic!("Unknown plugin should be an error"),
+    }
+}
+
+#[tokio::test]
+async fn should_report_all_validation_errors() {
+    let config = r#"
+supergraph: tests/supergraph.graphql
+subgraphs:
+  inventory:
+    url: http://127.0.0.1:9000/inventory
+  product:
+    url: http://127.0.0.1:9000/product
+    timeout_ms: 0
+    max_retry_num: 2
+    max_concurrency: 0
+    circuit_breaker:
+      failure_threshold: 0
+      reset_timeout_ms: 0
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    match GraphqlRouter::from_config(&config).await {
+        Err(graphql_router::config::ConfigError::Validation(errors)) => {
+            let paths = errors.iter().map(|error| error.path.as_str()).collect::<Vec<_>>();
+            assert_eq!(
+                paths,
+                [
+                    "subgraphs.inventory",
+                    "subgraphs.product.timeout_ms",
+                    "subgraphs.product.max_concurrency",
+                    "subgraphs.product.circuit_breaker.failure_threshold",
+                    "subgraphs.product.circuit_breaker.reset_timeout_ms"
+                ]
+            );
+        }
+        _ => panic!("Invalid config should be an error"),
+    }
+}
+
+#[tokio::test]
+async fn should_require_admin_token_outside_loopback() {
+    let text = r#"
+supergraph: tests/supergraph.graphql
+server:
+  listen: 0.0.0.0:9001
+  admin_listener:
+    listen: 0.0.0.0:9002
+"#;
+    let config = RouterConfig::from_yaml(text).expect("to parse config");
+    assert!(config.server.as_ref().expect("server config").admin_server().is_none());
+    match GraphqlRouter::from_config(&config).await {
+        Err(graphql_router::config::ConfigError::Validation(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].path, "server.admin_listener.token");
+        }
+        _ => panic!("Admin server without token should be an error"),
+    }
+
+    let config = RouterConfig::from_yaml(&text.replace("0.0.0.0:9002", "127.0.0.1:9002")).expect("to parse config");
+    assert!(config.server.as_ref().expect("server config").admin_server().is_some());
+    GraphqlRouter::from_config(&config).await.expect("to create router builder");
+
+    //Admin endpoints on public listener always require token.
+    let text = text.replace("admin_listener:\n    listen: 0.0.0.0:9002", "admin:\n    prefix: /admin");
+    assert!(RouterConfig::from_yaml(&text).is_err());
+
+    //So does persisted queries registration, as registered operations bypass safelist.
+    let text = text.replace("admin:\n    prefix: /admin", "persisted_query_registration:\n    path: /persisted");
+    assert!(RouterConfig::from_yaml(&text).is_err());
+    let text = text.replace("path: /persisted", "path: /persisted\n    token: secret");
+    let config = RouterConfig::from_yaml(&text).expect("to parse config");
+    let registration = config.server.as_ref().and_then(|server| server.persisted_query_registration.as_ref());
+    assert_eq!(registration.map(|registration| registration.token.as_str()), Some("secret"));
+}
+
+#[test]
+fn should_reject_unknown_config_fields() {
+    let config = "supergraph: tests/supergraph.graphql\nunknown: true";
+    assert!(RouterConfig::from_yaml(config).is_err());
+}
+
+#[test]
+fn should_interpolate_env_variables() {
+    std::env::set_var("GRAPHQL_ROUTER_TEST_PRODUCT_URL", "http://127.0.0.1:9999/product");
+    std::env::set_var("GRAPHQL_ROUTER_TEST_RETRY", "3");
+    std::env::remove_var("GRAPHQL_ROUTER_TEST_MISSING");
+
+    let config = r#"
+supergraph: tests/supergraph.graphql
+subgraphs:
+  product:
+    url: ${GRAPHQL_ROUTER_TEST_PRODUCT_URL}
+    max_retry_num: ${GRAPHQL_ROUTER_TEST_RETRY}
+  review:
+    url: ${GRAPHQL_ROUTER_TEST_MISSING:-http://127.0.0.1:9000/review}
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    assert_eq!(config.subgraphs["product"].url, "http://127.0.0.1:9999/product");
+    assert_eq!(config.subgraphs["product"].max_retry_num, Some(3));
+    assert_eq!(config.subgraphs["review"].url, "http://127.0.0.1:9000/review");
+
+    let config = "supergraph: ${GRAPHQL_ROUTER_TEST_MISSING}";
+    match RouterConfig::from_yaml(config) {
+        Err(graphql_router::config::ConfigError::MissingEnv(name)) => assert_eq!(name, "GRAPHQL_ROUTER_TEST_MISSING"),
+        _ => panic!("Missing variable should be an error"),
+    }
+
+    assert_eq!(graphql_router::config::interpolate_env("$${literal}").expect("to escape"), "${literal}");
+}
+
+#[test]
+fn should_keep_interpolated_strings_as_strings() {
+    std::env::set_var("GRAPHQL_ROUTER_TEST_NUMERIC_KEY", "12345");
+    std::env::set_var("GRAPHQL_ROUTER_TEST_FLAG", "true");
+
+    let config = r#"
+supergraph: tests/supergraph.graphql
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+    max_retry_num: "${GRAPHQL_ROUTER_TEST_NUMERIC_KEY:-1}"
+    headers:
+      x-api-key: ${GRAPHQL_ROUTER_TEST_NUMERIC_KEY}
+      x-flag: ${GRAPHQL_ROUTER_TEST_FLAG}
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    let product = &config.subgraphs["product"];
+    assert_eq!(product.max_retry_num, Some(12345));
+    assert_eq!(product.headers["x-api-key"], SecretValue::Inline("12345".to_owned()));
+    assert_eq!(product.headers["x-flag"], SecretValue::Inline("true".to_owned()));
+}
+
+#[tokio::test]
+async fn should_merge_subgraph_defaults() {
+    let config = r#"
+supergraph: tests/supergraph.graphql
+subgraph_defaults:
+  timeout_ms: 1000
+  max_retry_num: 1
+  retry_backoff_ms: 100
+  headers:
+    x-router: graphql-router
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+  review:
+    url: http://127.0.0.1:9000/review
+    timeout_ms: 5000
+    max_concurrency: 8
+    circuit_breaker:
+      failure_threshold: 3
+      reset_timeout_ms: 2000
+    headers:
+      x-subgraph: review
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+
+    let product = config.subgraphs["product"].options(&config.subgraph_defaults);
+    assert_eq!(product.timeout_ms, Some(1000));
+    assert_eq!(product.max_retry_num, Some(1));
+    assert_eq!(product.max_concurrency, None);
+    assert_eq!(product.headers.len(), 1);
+
+    let review = config.subgraphs["review"].options(&config.subgraph_defaults);
+    assert_eq!(review.timeout_ms, Some(5000));
+    assert_eq!(review.max_retry_num, Some(1));
+    assert_eq!(review.max_concurrency, Some(8));
+    assert_eq!(review.headers["x-router"], SecretValue::Inline("graphql-router".to_owned()));
+    assert_eq!(review.headers["x-subgraph"], SecretValue::Inline("review".to_owned()));
+
+    let router = GraphqlRouter::from_config(&config).await.expect("to create router builder");
+    let router = router.finish().await.expect("to build router");
+    let product = router.subgraph_settings("product").expect("product settings").get();
+    assert_eq!(product.retry_backoff, Some(Duration::from_millis(100)));
+    assert!(product.circuit_breaker.is_none());
+    let review = router.subgraph_settings("review").expect("review settings").get();
+    assert_eq!(review.retry_backoff, Some(Duration::from_millis(100)));
+    let circuit_breaker = review.circuit_breaker.expect("circuit breaker");
+    assert_eq!(circuit_breaker.failure_threshold, 3);
+    assert_eq!(circuit_breaker.reset_timeout, Duration::from_millis(2000));
+}
+
+#[test]
+fn should_parse_secret_references() {
+    let config = r#"
+supergraph: tests/supergraph.graphql
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+    auth_token:
+      file: /run/secrets/product-token
+    headers:
+      x-api-key:
+        env: PRODUCT_API_KEY
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    let product = &config.subgraphs["product"];
+    assert_eq!(
+        product.auth_token,
+        Some(SecretValue::File {
+            file: "/run/secrets/product-token".into()
+        })
+    );
+    assert_eq!(
+        product.headers["x-api-key"],
+        SecretValue::Env {
+            env: "PRODUCT_API_KEY".to_owned()
+        }
+    );
+}
+
+#[test]
+fn should_parse_warmup_operations() {
+    let config = r#"
+supergraph: tests/supergraph.graphql
+warmup:
+  - query: "{ topProducts { name } }"
+  - query: "query Me($id: ID!) { me { id } }"
+    operation_name: Me
+    variables:
+      id: "1234"
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+
+    assert_eq!(config.warmup.len(), 2);
+    assert_eq!(config.warmup[0].operation_name, None);
+    assert_eq!(config.warmup[1].operation_name.as_deref(), Some("Me"));
+    assert_eq!(config.warmup[1].variables["id"], "1234");
+}
+
+#[tokio::test]
+async fn should_reload_subgraph_defaults_apart_from_overrides() {
+    let supergraph = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/supergraph.graphql");
+    let path = std::env::temp_dir().join(format!("graphql-router-reload-{}.yaml", std::process::id()));
+    let write_config = |timeout_ms: u64| {
+        let config = format!(
+            r#"
+supergraph: {}
+subgraph_defaults:
+  timeout_ms: {}
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+  review:
+    url: http://127.0.0.1:9000/review
+    timeout_ms: 5000
+"#,
+            supergraph, timeout_ms
+        );
+        std::fs::write(&path, config).expect("to write config");
+    };
+
+    write_config(1000);
+    let config = RouterConfig::from_file(&path).expect("to parse config");
+    let router = GraphqlRouter::from_config(&config).await.expect("to create router builder");
+    let router = router.finish().await.expect("to build router");
+    let mut watcher = ConfigWatcher::new(&path, config, &router);
+
+    write_config(2000);
+    let report = watcher.reload().await.expect("to reload config");
+    let expected = RouterConfig::from_file(&path).expect("to parse config");
+    std::fs::remove_file(&path).expect("to remove config");
+
+    assert_eq!(report.applied, ["subgraphs.product.timeout_ms"]);
+    assert!(report.rejected.is_empty());
+    assert_eq!(*watcher.current(), expected);
+    assert_eq!(watcher.current().subgraphs["product"].timeout_ms, None);
+
+    let product = router.subgraph_settings("product").expect("product settings").get();
+    assert_eq!(product.timeout, Some(Duration::from_millis(2000)));
+    let review = router.subgraph_settings("review").expect("review settings").get();
+    assert_eq!(review.timeout, Some(Duration::from_millis(5000)));
+}
+
+async fn execute(router: &mut GraphqlRouter, query: &str) -> serde_json::Value {
+    let (_, response) = common::handle(router, common::request(query, &[])).await;
+    response
+}
+
+#[tokio::test]
+async fn should_hot_reload_plugins_headers_and_log_levels() {
+    let supergraph = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/supergraph.graphql");
+    let path = std::env::temp_dir().join(format!("graphql-router-hot-reload-{}.yaml", std::process::id()));
+    let write_config = |max_query_bytes: usize, header: &str, level: &str| {
+        let config = format!(
+            r#"
+supergraph: {}
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+    headers:
+      x-router: {}
+plugins:
+  request_limits:
+    max_query_bytes: {}
+log:
+  modules:
+    graphql_router_reload_test: {}
+"#,
+            supergraph, header, max_query_bytes, level
+        );
+        std::fs::write(&path, config).expect("to write config");
+    };
+
+    write_config(1024, "v1", "warn");
+    let config = RouterConfig::from_file(&path).expect("to parse config");
+    let router = GraphqlRouter::from_config(&config).await.expect("to create router builder");
+    let mut router = router.finish().await.expect("to build router");
+    let mut watcher = ConfigWatcher::new(&path, config, &router);
+    assert_eq!(LogFilter::global().to_json()["modules"]["graphql_router_reload_test"], "warn");
+
+    write_config(8, "v2", "debug");
+    let report = watcher.reload().await.expect("to reload config");
+    let expected = RouterConfig::from_file(&path).expect("to parse config");
+    std::fs::remove_file(&path).expect("to remove config");
+
+    assert!(report.rejected.is_empty(), "{}", report);
+    assert_eq!(report.applied, ["log", "subgraphs.product.headers", "plugins.request_limits"]);
+    assert_eq!(*watcher.current(), expected);
+    assert_eq!(LogFilter::global().to_json()["modules"]["graphql_router_reload_test"], "debug");
+
+    let response = execute(&mut router, "{ topProducts { name } }").await;
+    assert_eq!(response["errors"][0]["extensions"]["code"], "QUERY_TOO_LARGE");
+}
+
+#[tokio::test]
+async fn should_reject_invalid_config_on_reload() {
+    let supergraph = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/supergraph.graphql");
+    let path = std::env::temp_dir().join(format!("graphql-router-invalid-reload-{}.yaml", std::process::id()));
+    let config = format!("supergraph: {}\nlog:\n  default: info\n", supergraph);
+    std::fs::write(&path, config).expect("to write config");
+
+    let config = RouterConfig::from_file(&path).expect("to parse config");
+    let router = GraphqlRouter::from_config(&config).await.expect("to create router builder");
+    let router = router.finish().await.expect("to build router");
+    let mut watcher = ConfigWatcher::new(&path, config.clone(), &router);
+
+    let invalid = format!("supergraph: {}\nlog:\n  default: loud\n", supergraph);
+    std::fs::write(&path, invalid).expect("to write config");
+    let result = watcher.reload().await;
+    std::fs::remove_file(&path).expect("to remove config");
+
+    match result {
+        Err(graphql_router::config::ConfigError::Validation(errors)) => assert_eq!(errors[0].path, "log"),
+        _ => panic!("Invalid log level should be an error"),
+    }
+    assert_eq!(*watcher.current(), config);
+}
+
+#[tokio::test]
+async fn should_create_plugins_in_config_order() {
+    let config = r#"
+supergraph: tests/supergraph.graphql
+plugins:
+  request_limits:
+    max_query_bytes: 1024
+  propagate_headers: {}
+  hide_suggestions: {}
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    let expected = ["request_limits", "propagate_headers", "hide_suggestions"];
+    assert!(config.plugins.keys().map(String::as_str).eq(expected.iter().copied()));
+
+    let router = GraphqlRouter::from_config(&config).await.expect("to create router builder");
+    let router = router.finish().await.expect("to build router");
+    let plugins = router.plugins().await;
+    assert!(plugins.iter().map(|(name, _)| name.as_str()).eq(expected.iter().copied()));
+}
+
+#[tokio::test]
+async fn should_add_plugins_by_builder_hooks() {
+    use graphql_router::plugins::PluginRegistry;
+
+    let config = r#"
+supergraph: tests/supergraph.graphql
+plugins:
+  metrics: null
+  quiet: {}
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    let mut registry = PluginRegistry::new();
+    registry.register_builder("quiet", |_, builder| Ok(builder.hide_suggestions()));
+
+    let router = GraphqlRouter::from_config_with_registry(&config, &registry).await.expect("to create builder");
+    let router = router.finish().await.expect("to build router");
+    assert!(router.metrics().is_some());
+    let plugins = router.plugins().await;
+    assert!(plugins.iter().map(|(name, _)| name.as_str()).eq(["metrics", "hide_suggestions"].iter().copied()));
+}
+
+#[tokio::test]
+async fn should_report_subgraph_defaults_once() {
+    let config = r#"
+supergraph: tests/supergraph.graphql
+subgraph_defaults:
+  timeout_ms: 0
+  max_concurrency: 0
+subgraphs:
+  product:
+    url: http://127.0.0.1:9000/product
+  review:
+    url: http://127.0.0.1:9000/review
+    max_retry_num: 0
+  user:
+    url: http://127.0.0.1:9000/user
+    timeout_ms: 0
+"#;
+    let config = RouterConfig::from_yaml(config).expect("to parse config");
+    match GraphqlRouter::from_config(&config).await {
+        Err(graphql_router::config::ConfigError::Validation(errors)) => {
+            let paths = errors.iter().map(|error| error.path.as_str()).collect::<Vec<_>>();
+            //Retries are 2 by default, so zero timeout makes them impossible, while zero attempts never send request.
+            assert_eq!(
+                paths,
+                [
+                    "subgraph_defaults.timeout_ms",
+                    "subgraph_defaults.max_concurrency",
+                    "subgraphs.review.max_retry_num",
+                    "subgraphs.user.timeout_ms"
+                ]
+            );
+            let error = errors.iter().find(|error| error.path == "subgraphs.review.max_retry_num");
+            let error = error.expect("zero retries to be rejected");
+            assert_eq!(error.message, "Number of attempts must be at least 1, as it includes the first one");
+        }
+        _ => panic!("Invalid config should be an error"),
+    }
+}
+
+#[tokio::test]
+async fn should_rotate_file_secret_in_background() {
+    let path = std::env::temp_dir().join(format!("graphql-router-secret-{}", std::process::id()));
+    std::fs::write(&path, "first\n").expect("to write secret");
+    let secret = Secret::new(SecretSource::File(path.clone())).refresh_interval(Duration::from_millis(10));
+    assert_eq!(&*secret.get().expect("to read secret"), "first");
+
+    //Let modification time differ even on file systems with coarse timestamps.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    std::fs::write(&path, "second\n").expect("to write secret");
+    let mut rotated = false;
+    for _ in 0..200 {
+        if &*secret.get().expect("to read secret") == "second" {
+            rotated = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(rotated, "Secret should be re-read once file is modified");
+
+    //Previous value is kept, while file cannot be read.
+    std::fs::remove_file(&path).expect("to remove secret");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(&*secret.get().expect("to read secret"), "second");
+}
+
+#[test]
+fn should_patch_log_filter() {
+    use graphql_router::log::LogPatch;
+
+    let filter = LogFilter::new();
+    let patch = serde_json::json!({
+        "default": "warn",
+        "modules": { "graphql_router::remote": "debug" },
+        "debug_subgraphs": ["user"],
+        "trace_sampling": 0.5,
+    });
+    let patch = serde_json::from_value::<LogPatch>(patch).expect("valid patch");
+    patch.apply(&filter).expect("to apply patch");
+    assert!(filter.is_subgraph_debug("user"));
+    assert!(!filter.is_subgraph_debug("product"));
+    let sampled = (0..10).filter_map(|_| filter.sample_trace()).filter(|sampled| *sampled).count();
+    assert_eq!(sampled, 5);
+    assert_eq!(
+        filter.to_json(),
+        serde_json::json!({
+            "default": "warn",
+            "modules": { "graphql_router::remote": "debug" },
+            "debug_subgraphs": ["user"],
+            "trace_sampling": 0.5,
+        })
+    );
+
+    let patch = serde_json::json!({ "default": "loud", "trace_sampling": null });
+    let patch = serde_json::from_value::<LogPatch>(patch).expect("valid patch");
+    assert!(patch.apply(&filter).is_err());
+    assert_eq!(filter.to_json()["trace_sampling"], 0.5);
+
+    let patch = serde_json::json!({ "modules": { "graphql_router::remote": null }, "trace_sampling": null });
+    let patch = serde_json::from_value::<LogPatch>(patch).expect("valid patch");
+    patch.apply(&filter).expect("to apply patch");
+    assert_eq!(filter.sample_trace(), None);
+    assert_eq!(filter.to_json()["modules"], serde_json::json!({}));
+}
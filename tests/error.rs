@@ -0,0 +1,307 @@
+use graphql_router::remote::{SubgraphTransport, TransportError, TransportFuture, TransportRequest};
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+use graphql_router::{GraphqlResponse, RemoteGraphBuilder};
+
+use core::time::Duration;
+use std::sync::Arc;
+
+mod common;
+
+#[tokio::test]
+async fn should_rewrite_entity_error_paths() {
+    let supergraph = common::supergraph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [
+            { "__typename": "Product", "upc": "top-1", "name": "Trilby" },
+            { "__typename": "Product", "upc": "top-2", "name": "Fedora" },
+        ] }
+    }));
+    let review = MockGraphBuilder::new("review").on_entities(|representations| {
+        let entities = representations
+            .iter()
+            .enumerate()
+            .map(|(idx, _)| {
+                let reviews = (0..=idx)
+                    .map(|author| {
+                        serde_json::json!({
+                            "body": "Great hat",
+                            "author": { "__typename": "User", "id": format!("{}-{}", idx, author) },
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!({ "reviews": reviews })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "data": { "_entities": entities },
+            "errors": [{ "message": "Review is hidden", "path": ["_entities", 1, "reviews", 0, "body"] }],
+        })
+    });
+    let user = MockGraphBuilder::new("user").on_entities(|representations| {
+        let entities = representations
+            .iter()
+            .map(|_| serde_json::json!({ "username": "Me" }))
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "data": { "_entities": entities },
+            "errors": [{ "message": "Forbidden", "path": ["_entities", 2, "username"] }],
+        })
+    });
+
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(product)
+        .subgraph(review)
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness
+        .query("query Query { topProducts { name, reviews { body, author { username } } } }")
+        .await;
+    let mut paths = response["errors"]
+        .as_array()
+        .expect("to have errors")
+        .iter()
+        .map(|error| error["path"].clone())
+        .collect::<Vec<_>>();
+    paths.sort_by_key(|path| path.to_string());
+
+    assert_eq!(
+        paths,
+        [
+            serde_json::json!(["topProducts", 1, "reviews", 0, "body"]),
+            serde_json::json!(["topProducts", 1, "reviews", 1, "author", "username"]),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_add_fetch_failure_details_to_errors() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    //Fails with status and whether it is retried, or with connection failure.
+    struct Failing {
+        status: Mutex<Option<(u16, bool)>>,
+        sent: AtomicUsize,
+    }
+
+    impl SubgraphTransport for Failing {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            let error = match *self.status.lock().unwrap() {
+                Some((status, retry)) => TransportError::Status {
+                    status,
+                    retry,
+                    retry_after: None,
+                },
+                None => TransportError::Failed("Connection refused".to_owned()),
+            };
+            Box::pin(async move { Err(error) })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let transport = Arc::new(Failing {
+        status: Mutex::new(Some((503, true))),
+        sent: AtomicUsize::new(0),
+    });
+    let user = RemoteGraphBuilder::new("user", "http://users.internal:4001/graphql".parse().expect("valid url"))
+        .transport(transport.clone())
+        .max_retry_num(3);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    let response = harness.query(query).await;
+    let extensions = &response["errors"][0]["extensions"];
+    assert_eq!(extensions["type"], "SubrequestHttpError");
+    assert_eq!(extensions["service"], "user");
+    assert_eq!(extensions["status"], 503);
+    assert_eq!(extensions["attempts"], 3);
+    assert_eq!(extensions["host"], "users.internal");
+    assert!(extensions.get("retryAfterMs").is_none());
+    assert_eq!(transport.sent.load(Ordering::SeqCst), 3);
+
+    //Permanent failure is not retried.
+    *transport.status.lock().unwrap() = Some((401, false));
+    let response = harness.query(query).await;
+    let extensions = &response["errors"][0]["extensions"];
+    assert_eq!(extensions["status"], 401);
+    assert_eq!(extensions["attempts"], 1);
+    assert_eq!(transport.sent.load(Ordering::SeqCst), 4);
+
+    *transport.status.lock().unwrap() = None;
+    let response = harness.query(query).await;
+    let extensions = &response["errors"][0]["extensions"];
+    assert_eq!(extensions["status"], serde_json::Value::Null);
+    assert_eq!(extensions["attempts"], 1);
+    assert_eq!(extensions["host"], "users.internal");
+}
+
+#[tokio::test]
+async fn should_expose_subgraph_errors_per_subgraph() {
+    use graphql_router::plugins::{ErrorExposure, SubgraphErrors, SubgraphErrorsConfig};
+
+    fn messages(response: &serde_json::Value) -> Vec<(String, serde_json::Value)> {
+        let mut messages = response["errors"]
+            .as_array()
+            .expect("errors")
+            .iter()
+            .map(|error| (error["message"].as_str().expect("message").to_owned(), error["extensions"]["code"].clone()))
+            .collect::<Vec<_>>();
+        messages.sort_by(|left, right| left.0.cmp(&right.0));
+        messages
+    }
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").on_query("me", serde_json::json!({
+        "data": { "me": { "username": "Me" } },
+        "errors": [{ "message": "Users table is locked", "extensions": { "code": "DB_LOCKED" } }],
+    }));
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "name": "Trilby" }] },
+        "errors": [{ "message": "Inventory is down", "extensions": { "code": "INVENTORY" } }],
+    }));
+    let config = SubgraphErrorsConfig {
+        subgraphs: [
+            ("user".to_owned(), ErrorExposure::Debug),
+            ("product".to_owned(), ErrorExposure::Redact),
+        ]
+        .into_iter()
+        .collect(),
+        message: "Hidden".to_owned(),
+        ..SubgraphErrorsConfig::default()
+    };
+    let errors = SubgraphErrors::with_config(config);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(move |builder| builder.with_dyn_plugin("subgraph_errors".to_owned(), Box::new(errors)))
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } topProducts { name } }";
+
+    let response = harness.query(query).await;
+    assert_eq!(response["data"]["me"]["username"], "Me");
+    assert_eq!(response["data"]["topProducts"][0]["name"], "Trilby");
+    let hidden = ("Hidden".to_owned(), serde_json::Value::Null);
+    assert_eq!(messages(&response), [hidden.clone(), hidden.clone()]);
+
+    //Debug header reveals errors of `debug` subgraphs only.
+    let request = common::request(query, &[("x-debug-errors", "1")]);
+    let (_, response) = common::handle(harness.router(), request).await;
+    let locked = ("Users table is locked".to_owned(), serde_json::json!("DB_LOCKED"));
+    assert_eq!(messages(&response), [hidden, locked]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn should_report_timeouts_as_timeout_errors() {
+    //Responds after delay, unless fetch is cancelled before.
+    struct Slow;
+
+    impl SubgraphTransport for Slow {
+        fn send(&self, request: TransportRequest) -> TransportFuture {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                common::respond(&request.service_name, common::ME)
+            })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let remote = || common::remote_graph("user", Arc::new(Slow));
+    let query = "{ me { username } }";
+
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(remote().timeout(Duration::from_millis(50)))
+        .build()
+        .await
+        .expect("to create harness");
+    let response = harness.query(query).await;
+    let extensions = &response["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "TIMEOUT");
+    assert_eq!(extensions["subgraph"], "user");
+    assert_eq!(extensions["budgetMs"], 50);
+    assert_eq!(extensions["type"], "SubrequestHttpError");
+
+    //Budget of the whole request is reported without subgraph.
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(remote())
+        .configure(|builder| builder.timeout(Duration::from_millis(100)))
+        .build()
+        .await
+        .expect("to create harness");
+    let started = tokio::time::Instant::now();
+    let response = harness.query(query).await;
+    assert!(started.elapsed() < Duration::from_secs(1), "request must be cancelled: {:?}", started.elapsed());
+    assert_eq!(
+        response,
+        serde_json::json!({ "errors": [{
+            "message": "Request timed out after 100ms",
+            "extensions": { "code": "TIMEOUT", "budgetMs": 100 },
+        }] })
+    );
+}
+
+#[tokio::test]
+async fn should_add_retry_hints_to_transient_failures() {
+    use graphql_router::error::{router_error, set_retry_hint};
+
+    //Always responds as temporarily unavailable.
+    struct Unavailable(Option<Duration>);
+
+    impl SubgraphTransport for Unavailable {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            let error = TransportError::Status {
+                status: 503,
+                retry: true,
+                retry_after: self.0,
+            };
+            Box::pin(async move { Err(error) })
+        }
+    }
+
+    async fn handle(harness: &mut RouterTestHarness) -> (Option<http::HeaderValue>, serde_json::Value) {
+        let request = common::request("{ me { username } }", &[]);
+        let (parts, body) = common::handle(harness.router(), request).await;
+        (parts.headers.get(http::header::RETRY_AFTER).cloned(), body)
+    }
+
+    let supergraph = common::supergraph();
+    let remote = |retry_after| common::remote_graph("user", Arc::new(Unavailable(retry_after))).max_retry_num(1);
+
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(remote(Some(Duration::from_millis(1500))))
+        .build()
+        .await
+        .expect("to create harness");
+    let (retry_after, body) = handle(&mut harness).await;
+    //Header is rounded up to whole seconds.
+    assert_eq!(retry_after.as_ref().map(|value| value.to_str().expect("valid header")), Some("2"));
+    assert_eq!(body["errors"][0]["extensions"]["status"], 503);
+    assert_eq!(body["errors"][0]["extensions"]["retryAfterMs"], 1500);
+
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(remote(None))
+        .build()
+        .await
+        .expect("to create harness");
+    let (retry_after, body) = handle(&mut harness).await;
+    assert_eq!(retry_after, None);
+    assert!(body["errors"][0]["extensions"].get("retryAfterMs").is_none());
+
+    let context = apollo_router_core::Context::new();
+    let mut response = router_error(http::StatusCode::SERVICE_UNAVAILABLE, "Busy", "BUSY", context);
+    set_retry_hint(&mut response, Duration::from_millis(250));
+    assert_eq!(response.response.headers()[http::header::RETRY_AFTER], "1");
+    let body = GraphqlResponse::try_from(response.response.into_body()).expect("GraphQL response");
+    let body = serde_json::to_value(&body).expect("Serialize response");
+    assert_eq!(body["errors"][0]["extensions"], serde_json::json!({ "code": "BUSY", "retryAfterMs": 250 }));
+}
@@ -0,0 +1,448 @@
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+
+mod common;
+
+#[tokio::test]
+async fn should_forward_cookies_per_subgraph() {
+    use graphql_router::plugins::{CookiePolicy, CookiePolicyConfig};
+
+    let supergraph = common::supergraph();
+    let user = common::StubTransport::new(common::ME);
+    let product = common::StubTransport::new(r#"{ "data": { "topProducts": [{ "name": "Trilby" }] } }"#);
+    let config = CookiePolicyConfig::default().forward("user", "session").forward("product", "pref_*");
+    let policy = CookiePolicy::with_config(config);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("user", user.clone()))
+        .subgraph(common::remote_graph("product", product.clone()))
+        .subgraph(MockGraphBuilder::new("review"))
+        //Propagated `Cookie` header is replaced by policy.
+        .configure(move |builder| {
+            builder
+                .propagate_headers()
+                .with_dyn_plugin("cookie_policy".to_owned(), Box::new(policy))
+        })
+        .build()
+        .await
+        .expect("to create harness");
+    let request = |cookies: &[&str]| {
+        let headers = cookies.iter().map(|cookie| ("cookie", *cookie)).collect::<Vec<_>>();
+        common::request("{ me { username } topProducts { name } }", &headers)
+    };
+    let cookie = |transport: &common::StubTransport, idx: usize| {
+        let requests = transport.requests();
+        let cookie = requests[idx].headers.get(http::header::COOKIE);
+        cookie.map(|cookie| cookie.to_str().expect("valid cookie").to_owned())
+    };
+
+    let cookies = ["session=abc; pref_theme=dark", "tracking=1;pref_lang=en"];
+    harness.router().handle(request(&cookies)).await.expect("to handle request");
+    assert_eq!(cookie(&user, 0).as_deref(), Some("session=abc"));
+    assert_eq!(cookie(&product, 0).as_deref(), Some("pref_theme=dark; pref_lang=en"));
+
+    harness.router().handle(request(&["tracking=1"])).await.expect("to handle request");
+    assert_eq!(cookie(&user, 1), None);
+    assert_eq!(cookie(&product, 1), None);
+}
+
+#[tokio::test]
+async fn should_propagate_baggage() {
+    use graphql_router::plugins::{Baggage, BaggageConfig};
+
+    let supergraph = common::supergraph();
+    let transport = common::StubTransport::new(common::ME);
+    let config = BaggageConfig {
+        allow: vec!["tenant".to_owned(), "exp-*".to_owned(), "router".to_owned()],
+        append: [("router".to_owned(), "edge 1".to_owned())].into_iter().collect(),
+        from_headers: [("x-user-region".to_owned(), "region".to_owned())].into_iter().collect(),
+        max_bytes: 48,
+        ..BaggageConfig::default()
+    };
+    let baggage = Baggage::with_config(config);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("user", transport.clone()))
+        .configure(move |builder| builder.with_dyn_plugin("baggage".to_owned(), Box::new(baggage)))
+        .build()
+        .await
+        .expect("to create harness");
+    let request = |headers: &[(&str, &str)]| common::request("{ me { username } }", headers);
+    let baggage = |idx: usize| {
+        let requests = transport.requests();
+        let baggage = requests[idx].headers.get("baggage");
+        baggage.map(|baggage| baggage.to_str().expect("valid baggage").to_owned())
+    };
+
+    //Later entry replaces earlier one with the same key, while appended entries replace client's.
+    let headers = [
+        ("baggage", "tenant=acme;prop=1, secret=x, exp-a=on, router=client"),
+        ("baggage", "tenant=other"),
+        ("x-user-region", "eu"),
+    ];
+    harness.router().handle(request(&headers)).await.expect("to handle request");
+    assert_eq!(baggage(0).as_deref(), Some("exp-a=on,tenant=other,router=edge%201,region=eu"));
+
+    //Entries, which do not fit, are dropped.
+    let long = format!("exp-long={}", "x".repeat(30));
+    harness.router().handle(request(&[("baggage", &long)])).await.expect("to handle request");
+    assert_eq!(baggage(1), Some(long));
+
+    harness.router().handle(request(&[])).await.expect("to handle request");
+    assert_eq!(baggage(2).as_deref(), Some("router=edge%201"));
+}
+
+#[tokio::test]
+async fn should_propagate_trace_context_across_formats() {
+    use graphql_router::plugins::{TraceFormat, TracePropagation, TracePropagationConfig};
+
+    async fn send(format: TraceFormat, headers: &[(&str, &str)]) -> http::HeaderMap {
+        let supergraph = common::supergraph();
+        let transport = common::StubTransport::new(common::ME);
+        let trace = TracePropagation::with_config(TracePropagationConfig { format });
+        let mut harness = RouterTestHarness::builder(supergraph)
+            .subgraph(common::remote_graph("user", transport.clone()))
+            //Client's trace headers are not propagated as is.
+            .configure(move |builder| {
+                builder
+                    .propagate_headers()
+                    .with_dyn_plugin("trace_propagation".to_owned(), Box::new(trace))
+            })
+            .build()
+            .await
+            .expect("to create harness");
+        let request = common::request("{ me { username } }", headers);
+        harness.router().handle(request).await.expect("to handle request");
+        transport.requests().pop().expect("subgraph request").headers
+    }
+
+    fn trace_headers(headers: &http::HeaderMap) -> Vec<(String, String)> {
+        let mut trace = headers
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.as_str();
+                name == "traceparent" || name == "tracestate" || name == "b3" || name.starts_with("x-b3-")
+            })
+            .map(|(name, value)| (name.to_string(), value.to_str().expect("valid header").to_owned()))
+            .collect::<Vec<_>>();
+        trace.sort();
+        trace
+    }
+
+    let pair = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+
+    //64-bit B3 trace id is padded for W3C.
+    let b3_multi = [
+        ("x-b3-traceid", "463AC35C9F6413AD"),
+        ("x-b3-spanid", "a2fb4a1d1a96d312"),
+        ("x-b3-sampled", "1"),
+    ];
+    let headers = send(TraceFormat::W3c, &b3_multi).await;
+    let expected = "00-0000000000000000463ac35c9f6413ad-a2fb4a1d1a96d312-01";
+    assert_eq!(trace_headers(&headers), [pair("traceparent", expected)]);
+
+    let w3c = [
+        ("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        ("tracestate", "vendor=value"),
+    ];
+    let headers = send(TraceFormat::W3c, &w3c).await;
+    assert_eq!(
+        trace_headers(&headers),
+        [
+            pair("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            pair("tracestate", "vendor=value"),
+        ]
+    );
+    let headers = send(TraceFormat::B3Single, &w3c).await;
+    let expected = "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1";
+    assert_eq!(trace_headers(&headers), [pair("b3", expected)]);
+
+    let b3_single = [("b3", "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-0")];
+    let headers = send(TraceFormat::B3Multi, &b3_single).await;
+    assert_eq!(
+        trace_headers(&headers),
+        [
+            pair("x-b3-sampled", "0"),
+            pair("x-b3-spanid", "e457b5a2e4d86bd1"),
+            pair("x-b3-traceid", "80f198ee56343ba864fe8b2a57d3eff7"),
+        ]
+    );
+
+    //Invalid context is dropped rather than propagated.
+    let invalid = [("traceparent", "00-00000000000000000000000000000000-00f067aa0ba902b7-01")];
+    let headers = send(TraceFormat::W3c, &invalid).await;
+    assert!(trace_headers(&headers).is_empty());
+}
+
+#[tokio::test]
+async fn should_aggregate_subgraph_response_headers() {
+    use graphql_router::plugins::{HeaderMerge, ResponseHeaders, ResponseHeadersConfig};
+
+    let supergraph = common::supergraph();
+    let headers = |name: &'static str| {
+        vec![
+            ("x-version", name),
+            ("x-cache-status", name),
+            ("x-warn", name),
+            ("x-internal", name),
+            ("set-cookie", name),
+            ("content-type", "application/json; subgraph=1"),
+        ]
+    };
+    //Review is fetched after product, so order of responses is fixed.
+    let product = common::StubTransport::with_headers(
+        r#"{ "data": { "topProducts": [{ "__typename": "Product", "upc": "1", "name": "Trilby" }] } }"#,
+        headers("product"),
+    );
+    let review = common::StubTransport::with_headers(
+        r#"{ "data": { "_entities": [{ "reviews": [{ "body": "Great hat" }] }] } }"#,
+        headers("review"),
+    );
+    let config = ResponseHeadersConfig {
+        headers: [
+            ("x-version", HeaderMerge::FirstWins),
+            ("x-cache-*", HeaderMerge::LastWins),
+            ("x-warn", HeaderMerge::Append),
+            ("set-cookie", HeaderMerge::Append),
+            ("content-*", HeaderMerge::Append),
+        ]
+        .into_iter()
+        .map(|(pattern, merge)| (pattern.to_owned(), merge))
+        .collect(),
+    };
+    let response_headers = ResponseHeaders::with_config(config);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("product", product))
+        .subgraph(common::remote_graph("review", review))
+        .subgraph(MockGraphBuilder::new("user"))
+        .configure(move |builder| builder.with_dyn_plugin("response_headers".to_owned(), Box::new(response_headers)))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let request = common::request("{ topProducts { name reviews { body } } }", &[]);
+    let (parts, body) = common::handle(harness.router(), request).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "data": { "topProducts": [{ "name": "Trilby", "reviews": [{ "body": "Great hat" }] }] } })
+    );
+
+    let values = |name: &str| {
+        let values = parts.headers.get_all(name).iter();
+        values.map(|value| value.to_str().expect("valid header").to_owned()).collect::<Vec<_>>()
+    };
+    assert_eq!(values("x-version"), ["product"]);
+    assert_eq!(values("x-cache-status"), ["review"]);
+    assert_eq!(values("x-warn"), ["product", "review"]);
+    //Unlisted, content and cookie headers are never aggregated.
+    assert!(values("x-internal").is_empty());
+    assert!(values("content-type").is_empty());
+    assert!(values("set-cookie").is_empty());
+}
+
+#[tokio::test]
+async fn should_pass_set_cookie_from_single_subgraph() {
+    use graphql_router::plugins::{CookiePolicy, CookiePolicyConfig, CookieRule};
+
+    let supergraph = common::supergraph();
+    let user = common::StubTransport::with_headers(
+        common::ME,
+        vec![("set-cookie", "session=abc; HttpOnly"), ("set-cookie", "csrf=1")],
+    );
+    let product = common::StubTransport::with_headers(
+        r#"{ "data": { "topProducts": [{ "name": "Trilby" }] } }"#,
+        vec![("set-cookie", "tracking=1")],
+    );
+    //Previously allowed subgraph is stripped in favour of single one.
+    let mut config = CookiePolicyConfig::default();
+    config.subgraphs.insert(
+        "product".to_owned(),
+        CookieRule {
+            set_cookie: true,
+            ..CookieRule::default()
+        },
+    );
+    let policy = CookiePolicy::with_config(config.set_cookie_from("user"));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("user", user))
+        .subgraph(common::remote_graph("product", product))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(move |builder| builder.with_dyn_plugin("cookie_policy".to_owned(), Box::new(policy)))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let request = common::request("{ me { username } topProducts { name } }", &[]);
+    let response = harness.router().handle(request).await.expect("to handle request");
+    let set_cookies = response.response.headers().get_all(http::header::SET_COOKIE).iter();
+    let set_cookies = set_cookies.map(|value| value.to_str().expect("valid header")).collect::<Vec<_>>();
+    assert_eq!(set_cookies, ["session=abc; HttpOnly", "csrf=1"]);
+}
+
+#[tokio::test]
+async fn should_strip_headers_per_subgraph() {
+    use graphql_router::plugins::PropagateHeadersConfig;
+
+    let supergraph = common::supergraph();
+    let user = common::StubTransport::new(common::ME);
+    let product = common::StubTransport::new(r#"{ "data": { "topProducts": [{ "name": "Trilby" }] } }"#);
+    let config = PropagateHeadersConfig::default().strip_credentials("product").strip("user", "X-Secret");
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("user", user.clone()))
+        .subgraph(common::remote_graph("product", product.clone()))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(move |builder| builder.propagate_headers_with(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let headers = [("authorization", "Bearer token"), ("cookie", "session=abc"), ("x-secret", "42")];
+    let request = common::request("{ me { username } topProducts { name } }", &headers);
+    harness.router().handle(request).await.expect("to handle request");
+
+    let user = user.requests().remove(0).headers;
+    assert_eq!(user.get(http::header::AUTHORIZATION).expect("authorization"), "Bearer token");
+    assert_eq!(user.get(http::header::COOKIE).expect("cookie"), "session=abc");
+    assert!(user.get("x-secret").is_none());
+
+    let product = product.requests().remove(0).headers;
+    assert!(product.get(http::header::AUTHORIZATION).is_none());
+    assert!(product.get(http::header::COOKIE).is_none());
+    assert_eq!(product.get("x-secret").expect("x-secret"), "42");
+}
+
+#[tokio::test]
+async fn should_control_user_agent_and_client_hints() {
+    use graphql_router::plugins::{UserAgent, UserAgentConfig, UserAgentMode};
+
+    async fn send(config: UserAgentConfig, client: &[(&str, &str)]) -> http::HeaderMap {
+        let supergraph = common::supergraph();
+        let transport = common::StubTransport::new(common::ME);
+        let user_agent = UserAgent::with_config(config);
+        //Propagated headers are overridden by plugin.
+        let mut harness = RouterTestHarness::builder(supergraph)
+            .subgraph(common::remote_graph("user", transport.clone()))
+            .configure(move |builder| {
+                builder
+                    .propagate_headers()
+                    .with_dyn_plugin("user_agent".to_owned(), Box::new(user_agent))
+            })
+            .build()
+            .await
+            .expect("to create harness");
+        let request = common::request("{ me { username } }", client);
+        harness.router().handle(request).await.expect("to handle request");
+        transport.requests().remove(0).headers
+    }
+    let config = |mode: UserAgentMode, forward_client_hints: bool| UserAgentConfig {
+        mode,
+        identity: "edge/1".to_owned(),
+        forward_client_hints,
+    };
+    let client = [
+        ("user-agent", "Browser/2"),
+        ("sec-ch-ua-mobile", "?0"),
+        ("viewport-width", "1280"),
+    ];
+    let user_agent = |headers: &http::HeaderMap| {
+        let user_agent = headers.get(http::header::USER_AGENT);
+        user_agent.map(|value| value.to_str().expect("valid header").to_owned())
+    };
+
+    let headers = send(config(UserAgentMode::Forward, true), &client).await;
+    assert_eq!(user_agent(&headers).as_deref(), Some("Browser/2"));
+    assert_eq!(headers.get("sec-ch-ua-mobile").expect("client hint"), "?0");
+    assert_eq!(headers.get("viewport-width").expect("client hint"), "1280");
+
+    let headers = send(config(UserAgentMode::Append, false), &client).await;
+    assert_eq!(user_agent(&headers).as_deref(), Some("Browser/2 edge/1"));
+    assert!(headers.get("sec-ch-ua-mobile").is_none());
+    assert!(headers.get("viewport-width").is_none());
+
+    let headers = send(config(UserAgentMode::Append, true), &[]).await;
+    assert_eq!(user_agent(&headers).as_deref(), Some("edge/1"));
+
+    let headers = send(config(UserAgentMode::Replace, true), &client).await;
+    assert_eq!(user_agent(&headers).as_deref(), Some("edge/1"));
+
+    let headers = send(config(UserAgentMode::Forward, true), &[]).await;
+    assert_eq!(user_agent(&headers), None);
+}
+
+#[tokio::test]
+async fn should_propagate_headers_except_reserved() {
+    use graphql_router::plugins::PropagateHeadersConfig;
+
+    let supergraph = common::supergraph();
+    let transport = common::StubTransport::new(common::ME);
+    let config = PropagateHeadersConfig::default().reserve("X-Forwarded-For").unreserve("TE");
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("user", transport.clone()))
+        .configure(move |builder| builder.propagate_headers_with(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let headers = [
+        ("x-forwarded-for", "10.0.0.1"),
+        ("te", "trailers"),
+        ("proxy-authorization", "Basic secret"),
+        ("connection", "x-hop"),
+        ("x-hop", "1"),
+        ("x-tenant", "acme"),
+    ];
+    let request = common::request("{ me { username } }", &headers);
+    harness.router().handle(request).await.expect("to handle request");
+
+    let headers = transport.requests().remove(0).headers;
+    assert_eq!(headers.get("x-tenant").expect("x-tenant"), "acme");
+    assert_eq!(headers.get("te").expect("te"), "trailers");
+    assert!(headers.get("x-forwarded-for").is_none());
+    assert!(headers.get("proxy-authorization").is_none());
+    //Headers listed in `Connection` are hop-by-hop as well.
+    assert!(headers.get("connection").is_none());
+    assert!(headers.get("x-hop").is_none());
+}
+
+#[tokio::test]
+async fn should_set_templated_headers() {
+    use graphql_router::context::{JwtClaims, RequestId, TypedContext};
+    use graphql_router::plugins::PropagateHeadersConfig;
+
+    let supergraph = common::supergraph();
+    let transport = common::StubTransport::new(common::ME);
+    let config = PropagateHeadersConfig::default()
+        .template("x-request-id", "{request_id}")
+        .template("X-User", "user-{jwt.sub}")
+        .template("x-tenant", "{context.tenant}")
+        .template("x-client", "{client}");
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::remote_graph("user", transport.clone()))
+        .configure(move |builder| builder.propagate_headers_with(config))
+        .build()
+        .await
+        .expect("to create harness");
+    let request = || common::request("{ me { username } }", &[("x-user", "user-admin"), ("x-client", "spoofed")]);
+    let header = |idx: usize, name: &str| {
+        let requests = transport.requests();
+        let value = requests[idx].headers.get(name);
+        value.map(|value| value.to_str().expect("valid header").to_owned())
+    };
+
+    let request_with_context = request();
+    let typed = TypedContext::new(&request_with_context.context);
+    typed.insert(RequestId("req-1".to_owned())).expect("to store request id");
+    let claims = serde_json::json!({ "sub": 42 });
+    let claims = claims.as_object().expect("object").clone();
+    typed.insert(JwtClaims(claims)).expect("to store claims");
+    request_with_context.context.insert("tenant", "acme").expect("to store tenant");
+    harness.router().handle(request_with_context).await.expect("to handle request");
+    assert_eq!(header(0, "x-request-id").as_deref(), Some("req-1"));
+    assert_eq!(header(0, "x-user").as_deref(), Some("user-42"));
+    assert_eq!(header(0, "x-tenant").as_deref(), Some("acme"));
+    //Client's value is dropped, even if template has no value.
+    assert_eq!(header(0, "x-client"), None);
+
+    harness.router().handle(request()).await.expect("to handle request");
+    assert_eq!(header(1, "x-request-id"), None);
+    assert_eq!(header(1, "x-user"), None);
+    assert_eq!(header(1, "x-tenant"), None);
+    assert_eq!(header(1, "x-client"), None);
+}
@@ -0,0 +1,221 @@
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+use graphql_router::GraphqlRequest;
+
+mod common;
+
+#[tokio::test]
+async fn should_limit_tokens_and_definitions() {
+    use graphql_router::plugins::RequestLimitsConfig;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").on_query("username", common::me());
+    let limits = RequestLimitsConfig {
+        max_tokens: Some(24),
+        max_definitions: Some(2),
+        ..RequestLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.request_limits(limits))
+        .build()
+        .await
+        .expect("to create harness");
+    let expected = common::me();
+
+    assert_eq!(harness.query("{ me { username } }").await, expected);
+    //Comments and commas are not tokens, while string is single token regardless of its content.
+    let query = "# Comment { with } many ( tokens )\n{ me,,, { username @include(if: true) } }";
+    assert_eq!(harness.query(query).await, expected);
+    harness.calls().assert_call_count("user", 2);
+
+    let query = format!("{{ me {{ {} }} }}", "username ".repeat(21));
+    let response = harness.query(&query).await;
+    assert_eq!(response["errors"][0]["message"], "Query exceeds limit of 24 tokens");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_TOKENS");
+
+    //Braces within arguments do not start definitions.
+    let response = harness.query("query($u: I = {}) { me { id } } { me { id } }").await;
+    let message = response["errors"][0]["message"].as_str().expect("error message");
+    assert!(!message.starts_with("Query exceeds limit"), "unexpected error: {}", message);
+    let response = harness.query("{ me { id } } { me { id } } { me { id } }").await;
+    assert_eq!(response["errors"][0]["message"], "Query exceeds limit of 2 definitions");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_DEFINITIONS");
+    harness.calls().assert_call_count("user", 2);
+}
+
+#[tokio::test]
+async fn should_limit_aliases_and_duplicate_fields() {
+    use graphql_router::plugins::OperationLimitsConfig;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").fallback(serde_json::json!({
+        "data": { "me": { "id": "1", "username": "Me", "first": "Me", "second": "Me", "third": "Me" } }
+    }));
+    let limits = OperationLimitsConfig {
+        max_aliases: Some(2),
+        max_duplicate_fields: Some(1),
+        ..OperationLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.operation_limits(limits))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("{ me { first: username second: username username } }").await;
+    assert!(response.get("errors").is_none(), "unexpected errors: {}", response);
+    let response = harness.query("{ me { first: username second: username third: username } }").await;
+    assert_eq!(response["errors"][0]["message"], "Operation exceeds limit of 2 aliases");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_ALIASES");
+
+    //Aliases within fragments are counted as well.
+    let query = "{ me { ...Names } } fragment Names on User { first: username second: username third: username }";
+    let response = harness.query(query).await;
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_ALIASES");
+
+    let response = harness.query("{ me { username username } }").await;
+    assert!(response.get("errors").is_none(), "unexpected errors: {}", response);
+    let response = harness.query("{ me { username username id username } }").await;
+    assert_eq!(response["errors"][0]["message"], "Operation exceeds limit of 1 duplicate fields");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_DUPLICATE_FIELDS");
+    harness.calls().assert_call_count("user", 2);
+}
+
+#[tokio::test]
+async fn should_limit_directives() {
+    use graphql_router::plugins::OperationLimitsConfig;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user")
+        .fallback(serde_json::json!({ "data": { "me": { "id": "1", "username": "Me" } } }));
+    let limits = OperationLimitsConfig {
+        max_directives: Some(3),
+        max_directives_per_location: Some(2),
+        max_directive_depth: Some(2),
+        ..OperationLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(MockGraphBuilder::new("product"))
+        .configure(|builder| builder.operation_limits(limits))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("{ me @include(if: true) { username @include(if: true) @skip(if: false) } }").await;
+    assert_eq!(response, common::me());
+    harness.calls().assert_call_count("user", 1);
+
+    let query = "{ me { username @include(if: true) @skip(if: false) @include(if: true) } }";
+    let response = harness.query(query).await;
+    assert_eq!(response["errors"][0]["message"], "Operation exceeds limit of 2 directives per location");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_DIRECTIVES");
+
+    let query = concat!(
+        "{ me { id @skip(if: false) username @skip(if: false) } ",
+        "topProducts @skip(if: true) { name @skip(if: false) } }",
+    );
+    let response = harness.query(query).await;
+    assert_eq!(response["errors"][0]["message"], "Operation exceeds limit of 3 directives");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TOO_MANY_DIRECTIVES");
+
+    let query = "{ me @include(if: true) { ... on User @include(if: true) { username @include(if: true) } } }";
+    let response = harness.query(query).await;
+    assert_eq!(response["errors"][0]["message"], "Operation exceeds limit of 2 nested directives");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "DIRECTIVES_TOO_DEEP");
+    //Fragment is counted once, regardless of number of its spreads.
+    let query = concat!(
+        "{ me { ...Name ...Name } } ",
+        "fragment Name on User { username @include(if: true) @skip(if: false) }",
+    );
+    let response = harness.query(query).await;
+    assert_ne!(response["errors"][0]["extensions"]["code"], "TOO_MANY_DIRECTIVES");
+    harness.calls().assert_call_count("user", 2);
+}
+
+#[tokio::test]
+async fn should_limit_variables() {
+    use graphql_router::plugins::RequestLimitsConfig;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user")
+        .fallback(common::me());
+    let limits = RequestLimitsConfig {
+        max_variables: Some(2),
+        max_variables_bytes: Some(32),
+        ..RequestLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.request_limits(limits))
+        .build()
+        .await
+        .expect("to create harness");
+    let request = |variables: serde_json::Value| {
+        serde_json::from_value::<GraphqlRequest>(serde_json::json!({
+            "query": "query Me($a: Boolean!, $b: Boolean!) { me { username @include(if: $a) @skip(if: $b) } }",
+            "variables": variables,
+        }))
+        .expect("valid request")
+    };
+
+    let response = harness.execute(request(serde_json::json!({ "a": true, "b": false }))).await;
+    assert_eq!(response, common::me());
+
+    let response = harness.execute(request(serde_json::json!({ "a": true, "b": false, "c": 1 }))).await;
+    assert_eq!(response["errors"][0]["message"], "Request exceeds limit of 2 variables");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "BAD_USER_INPUT");
+
+    //Size counts names along with serialized values.
+    let response = harness.execute(request(serde_json::json!({ "a": true, "b": "x".repeat(30) }))).await;
+    assert_eq!(response["errors"][0]["message"], "Variables exceed limit of 32 bytes");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "BAD_USER_INPUT");
+    harness.calls().assert_call_count("user", 1);
+}
+
+#[tokio::test]
+async fn should_require_named_operations() {
+    use graphql_router::plugins::OperationLimitsConfig;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user")
+        .on_query("username", common::me())
+        .on_query("id", serde_json::json!({ "data": { "me": { "id": "1" } } }));
+    let limits = OperationLimitsConfig {
+        require_named_operations: true,
+        require_operation_name: true,
+        ..OperationLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.operation_limits(limits))
+        .build()
+        .await
+        .expect("to create harness");
+    let request = |query: &str, operation_name: Option<&str>| {
+        serde_json::from_value::<GraphqlRequest>(serde_json::json!({
+            "query": query,
+            "operationName": operation_name,
+        }))
+        .expect("valid request")
+    };
+
+    let response = harness.query("{ me { username } }").await;
+    assert_eq!(response["errors"][0]["message"], "Anonymous operations are not allowed");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "OPERATION_NAME_REQUIRED");
+    //Single named operation can be executed without operationName.
+    let response = harness.query("query Name { me { username } }").await;
+    assert_eq!(response, common::me());
+
+    const DOCUMENT: &str = "query Name { me { username } } query Id { me { id } }";
+    let response = harness.execute(request(DOCUMENT, None)).await;
+    assert_eq!(
+        response["errors"][0]["message"],
+        "operationName is required for document with multiple operations"
+    );
+    assert_eq!(response["errors"][0]["extensions"]["code"], "OPERATION_NAME_REQUIRED");
+    let response = harness.execute(request(DOCUMENT, Some("Id"))).await;
+    assert_eq!(response, serde_json::json!({ "data": { "me": { "id": "1" } } }));
+    harness.calls().assert_call_count("user", 2);
+}
@@ -0,0 +1,1154 @@
+use graphql_router::remote::{SubgraphTransport, TransportFuture, TransportRequest};
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+use graphql_router::{GraphqlRequest, GraphqlResponse, GraphqlRouter};
+
+use core::time::Duration;
+use std::sync::Arc;
+
+mod common;
+
+#[tokio::test]
+async fn should_fail_request_on_critical_subgraph_errors() {
+    use graphql_router::plugins::PartialResultsConfig;
+
+    let supergraph = common::supergraph();
+    let review = || {
+        MockGraphBuilder::new("review").fallback(serde_json::json!({
+            "data": null,
+            "errors": [{ "message": "Reviews are unavailable" }],
+        }))
+    };
+    let query = "query Query { topProducts { name, reviews { body } } }";
+
+    let config = PartialResultsConfig::default().optional("review");
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(common::product_graph())
+        .subgraph(review())
+        .configure(|builder| builder.partial_results(config))
+        .build()
+        .await
+        .expect("to create harness");
+    let response = harness.query(query).await;
+    assert_eq!(response["errors"][0]["message"], "Reviews are unavailable");
+
+    let config = PartialResultsConfig::default().critical("review");
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(common::product_graph())
+        .subgraph(review())
+        .configure(|builder| builder.partial_results(config))
+        .build()
+        .await
+        .expect("to create harness");
+    let response = harness.query(query).await;
+    assert_eq!(response["errors"][0]["extensions"]["code"], "SUBGRAPH_FAILED");
+}
+
+#[tokio::test]
+async fn should_omit_failed_subgraph_without_error() {
+    use graphql_router::plugins::{PartialResultsConfig, SubgraphFailurePolicy};
+
+    let supergraph = common::supergraph();
+    let query = "query Query { topProducts { name, reviews { body } } }";
+
+    let config = PartialResultsConfig {
+        default: SubgraphFailurePolicy::Omit,
+        ..PartialResultsConfig::default()
+    };
+    assert_eq!(config.policy("review"), SubgraphFailurePolicy::Omit);
+    //Subgraph without handlers fails as if it is unreachable.
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(common::product_graph())
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.partial_results(config))
+        .build()
+        .await
+        .expect("to create harness");
+    let response = harness.query(query).await;
+    assert!(response.get("errors").is_none(), "unexpected response: {}", response);
+    harness.calls().assert_call_count("review", 1);
+
+    //Errors alongside data are not failure, even for critical subgraph.
+    let review = MockGraphBuilder::new("review").on_entities(|representations| {
+        let entities = representations
+            .iter()
+            .map(|_| serde_json::json!({ "reviews": [{ "body": "Great hat" }] }))
+            .collect::<Vec<_>>();
+        serde_json::json!({ "data": { "_entities": entities }, "errors": [{ "message": "Partial reviews" }] })
+    });
+    let config = PartialResultsConfig::default().critical("review");
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(common::product_graph())
+        .subgraph(review)
+        .configure(|builder| builder.partial_results(config))
+        .build()
+        .await
+        .expect("to create harness");
+    let response = harness.query(query).await;
+    assert_eq!(response["data"]["topProducts"][0]["reviews"][0]["body"], "Great hat");
+    assert_ne!(response["errors"][0]["extensions"]["code"], "SUBGRAPH_FAILED");
+}
+
+#[tokio::test]
+async fn should_replace_non_null_violations_with_placeholders() {
+    use graphql_router::plugins::{NullabilityConfig, NullabilityPolicy};
+
+    let supergraph = common::supergraph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "name": null, "price": null }] }
+    }));
+    let mut config = NullabilityConfig {
+        policy: NullabilityPolicy::Placeholder,
+        ..NullabilityConfig::default()
+    };
+    config.placeholders.insert("String".to_owned(), serde_json::json!("N/A"));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.nullability(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name, price } }").await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "topProducts": [{ "name": "N/A", "price": 0 }] } })
+    );
+}
+
+#[tokio::test]
+async fn should_route_fetches_by_plan_overrides() {
+    use graphql_router::plugins::PlanOverridesConfig;
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "name": "Fedora" }] }
+    }));
+    let config = PlanOverridesConfig::default().route("product", "user", &["Query.topProducts"]);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(MockGraphBuilder::new("product"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.plan_overrides(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name } }").await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "topProducts": [{ "name": "Fedora" }] } })
+    );
+    harness.calls().assert_called("user");
+    harness.calls().assert_not_called("product");
+}
+
+#[tokio::test]
+async fn should_roll_out_plan_overrides_by_percentage() {
+    use apollo_router_core::Plugin;
+    use graphql_router::plugins::{PlanOverrides, PlanOverridesConfig};
+
+    //Migration of `topProducts` from `user`, that previously resolved it, to `product`.
+    async fn harness(percentage: f64) -> RouterTestHarness {
+        let supergraph = common::supergraph();
+        let user = MockGraphBuilder::new("user").on_query("topProducts", serde_json::json!({
+            "data": { "topProducts": [{ "name": "Fedora" }] }
+        }));
+        let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+            "data": { "topProducts": [{ "name": "Trilby" }] }
+        }));
+        let config = PlanOverridesConfig::default().rollout(
+            "user",
+            "product",
+            &["Query.topProducts"],
+            percentage,
+            Some("x-user-id"),
+        );
+        RouterTestHarness::builder(supergraph)
+            .subgraph(user)
+            .subgraph(product)
+            .subgraph(MockGraphBuilder::new("review"))
+            .configure(|builder| builder.plan_overrides(config))
+            .build()
+            .await
+            .expect("to create harness")
+    }
+    async fn top_product(harness: &mut RouterTestHarness, user_id: &str) -> serde_json::Value {
+        let request = common::request("{ topProducts { name } }", &[("x-user-id", user_id)]);
+        let (_, response) = common::handle(harness.router(), request).await;
+        response["data"]["topProducts"][0]["name"].clone()
+    }
+
+    let mut none = harness(0.0).await;
+    assert_eq!(top_product(&mut none, "user-1").await, "Fedora");
+    none.calls().assert_not_called("product");
+
+    let mut all = harness(100.0).await;
+    assert_eq!(top_product(&mut all, "user-1").await, "Trilby");
+    all.calls().assert_not_called("user");
+
+    //Each user is consistently routed one way, with users split between both.
+    let mut half = harness(50.0).await;
+    let mut names = Vec::new();
+    for idx in 0..20 {
+        let user_id = format!("user-{}", idx);
+        let name = top_product(&mut half, &user_id).await;
+        assert_eq!(top_product(&mut half, &user_id).await, name, "{} must be sticky", user_id);
+        names.push(name);
+    }
+    assert!(names.contains(&serde_json::json!("Fedora")), "{:?}", names);
+    assert!(names.contains(&serde_json::json!("Trilby")), "{:?}", names);
+
+    let mut config = PlanOverridesConfig::default().route("product", "user", &["Query.topProducts"]);
+    config.routes[0].percentage = 150.0;
+    assert!(PlanOverrides::new(config).await.is_err());
+}
+
+#[tokio::test]
+async fn should_split_entities_by_batch_size() {
+    use graphql_router::plugins::EntityBatchingConfig;
+
+    let supergraph = common::supergraph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [
+            { "__typename": "Product", "upc": "top-1", "name": "Trilby" },
+            { "__typename": "Product", "upc": "top-2", "name": "Fedora" },
+            { "__typename": "Product", "upc": "top-3", "name": "Boater" },
+        ] }
+    }));
+    let review = MockGraphBuilder::new("review").on_entities(|representations| {
+        let entities = representations
+            .iter()
+            .map(|representation| serde_json::json!({ "reviews": [{ "body": representation["upc"] }] }))
+            .collect::<Vec<_>>();
+        serde_json::json!({ "data": { "_entities": entities } })
+    });
+    let config = EntityBatchingConfig::default().max_representations("review", 2);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(product)
+        .subgraph(review)
+        .subgraph(MockGraphBuilder::new("user"))
+        .configure(|builder| builder.entity_batching(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name, reviews { body } } }").await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "topProducts": [
+            { "name": "Trilby", "reviews": [{ "body": "top-1" }] },
+            { "name": "Fedora", "reviews": [{ "body": "top-2" }] },
+            { "name": "Boater", "reviews": [{ "body": "top-3" }] },
+        ] } })
+    );
+    harness.calls().assert_call_count("review", 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn should_apply_operation_policy_timeout() {
+    use graphql_router::plugins::{OperationPoliciesConfig, OperationPolicy};
+
+    let supergraph = common::supergraph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "name": "Fedora" }] }
+    }));
+    let latency = common::latency(1000);
+    let policy = OperationPolicy {
+        timeout_ms: Some(50),
+        ..OperationPolicy::default()
+    };
+    let policies = OperationPoliciesConfig::default().operation("Critical", policy);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.latency_injection(latency).operation_policies(policies))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let started = tokio::time::Instant::now();
+    let response = harness.query("query Critical { topProducts { name } }").await;
+    assert_eq!(response["errors"][0]["extensions"]["code"], "TIMEOUT");
+    assert_eq!(response["errors"][0]["extensions"]["budgetMs"], 50);
+    //Injected latency is not awaited past budget.
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn should_skip_low_priority_fetches_under_load() {
+    use graphql_router::plugins::FetchPriorityConfig;
+
+    let supergraph = common::supergraph();
+    let product = common::product_graph();
+    let config = FetchPriorityConfig::default().low("review").shed_above(0);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("review"))
+        .subgraph(MockGraphBuilder::new("user"))
+        .configure(|builder| builder.fetch_priority(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name, reviews { body } } }").await;
+    assert_eq!(response["data"]["topProducts"][0]["name"], "Trilby");
+    harness.calls().assert_called("product");
+    harness.calls().assert_not_called("review");
+}
+
+const MUTATION: &str = r#"mutation {
+    setUsername(username: "hatter")
+    addReview(body: "Great hat")
+    setPrice(upc: "1", price: 10)
+}"#;
+
+#[tokio::test]
+async fn should_execute_mutation_fields_serially() {
+    use graphql_router::plugins::MutationOrderingConfig;
+
+    let supergraph = common::schema("mutation_supergraph");
+    let user = MockGraphBuilder::new("user").on_query("setUsername", serde_json::json!({
+        "data": { "setUsername": "hatter" }
+    }));
+    let review = MockGraphBuilder::new("review").on_query("addReview", serde_json::json!({
+        "data": { "addReview": "Great hat" }
+    }));
+    let product = MockGraphBuilder::new("product").on_query("setPrice", serde_json::json!({
+        "data": { "setPrice": 10 }
+    }));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(review)
+        .subgraph(product)
+        .configure(|builder| builder.mutation_ordering(MutationOrderingConfig::default()))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query(MUTATION).await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "setUsername": "hatter", "addReview": "Great hat", "setPrice": 10 } })
+    );
+    harness.calls().assert_order(&["user", "review", "product"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn should_execute_mutation_fields_in_parallel() {
+    use graphql_router::plugins::{MutationMode, MutationOrderingConfig};
+
+    let supergraph = common::schema("mutation_supergraph");
+    let user = MockGraphBuilder::new("user").on_query("setUsername", serde_json::json!({
+        "data": { "setUsername": "hatter" }
+    }));
+    let review = MockGraphBuilder::new("review").on_query("addReview", serde_json::json!({
+        "data": { "addReview": "Great hat" }
+    }));
+    let product = MockGraphBuilder::new("product").on_query("setPrice", serde_json::json!({
+        "data": { "setPrice": 10 }
+    }));
+    let latency = common::latency(300);
+    let config = MutationOrderingConfig { mode: MutationMode::Parallel };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(review)
+        .subgraph(product)
+        //Latency is injected within mutation ordering, so that fetches started early are delayed as well.
+        .configure(|builder| builder.mutation_ordering(config).latency_injection(latency))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let started = tokio::time::Instant::now();
+    let response = harness.query(MUTATION).await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "setUsername": "hatter", "addReview": "Great hat", "setPrice": 10 } })
+    );
+    //Serial execution takes 900ms.
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(300) && elapsed < Duration::from_millis(900), "{:?}", elapsed);
+    harness.calls().assert_call_count("user", 1);
+    harness.calls().assert_call_count("review", 1);
+    harness.calls().assert_call_count("product", 1);
+}
+
+#[tokio::test]
+async fn should_inject_distinct_faults() {
+    use graphql_router::plugins::{Fault, FaultInjectionConfig, FaultRule};
+
+    async fn inject(fault: Fault) -> serde_json::Value {
+        let supergraph = common::supergraph();
+        let user = common::user_graph();
+        let faults = FaultInjectionConfig {
+            rules: vec![FaultRule {
+                fault,
+                rate: 1.0,
+                subgraphs: vec!["user".to_owned()],
+                operations: Vec::new(),
+            }],
+        };
+        let mut harness = RouterTestHarness::builder(supergraph)
+            .subgraph(user)
+            .configure(|builder| builder.fault_injection(faults))
+            .build()
+            .await
+            .expect("to create harness");
+        let response = harness.query("{ me { username } }").await;
+        response["errors"][0].clone()
+    }
+
+    let error = inject(Fault::Error).await;
+    assert_eq!(error["extensions"]["type"], "SubrequestHttpError");
+    assert_eq!(error["extensions"]["status"], 500);
+    assert_eq!(error["extensions"]["attempts"], 1);
+
+    let error = inject(Fault::DroppedConnection).await;
+    assert_eq!(error["extensions"]["type"], "SubrequestHttpError");
+    assert_eq!(error["extensions"]["status"], serde_json::Value::Null);
+    assert!(error.to_string().contains("connection closed"), "{}", error);
+
+    let error = inject(Fault::MalformedResponse).await;
+    assert_eq!(error["extensions"]["type"], "SubrequestMalformedResponse");
+    assert!(error["extensions"].get("status").is_none(), "{}", error);
+}
+
+#[tokio::test(start_paused = true)]
+async fn should_delay_subgraph_request_before_sending() {
+    use graphql_router::plugins::{Delay, LatencyInjectionConfig, LatencyRule};
+    use std::sync::Mutex;
+
+    struct Recording(Mutex<Vec<tokio::time::Instant>>);
+
+    impl SubgraphTransport for Recording {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            self.0.lock().unwrap().push(tokio::time::Instant::now());
+            Box::pin(async { common::respond("user", common::ME) })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let transport = Arc::new(Recording(Mutex::new(Vec::new())));
+    let user = common::remote_graph("user", transport.clone())
+        .timeout(Duration::from_millis(100));
+    let latency = LatencyInjectionConfig {
+        rules: vec![LatencyRule {
+            delay: Delay::Fixed { ms: 500 },
+            rate: 1.0,
+            subgraphs: vec!["user".to_owned()],
+            operations: Vec::new(),
+        }],
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.latency_injection(latency))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let started = tokio::time::Instant::now();
+    let response = harness.query("{ me { username } }").await;
+    //Request is sent only after delay, so delay does not exhaust subgraph timeout.
+    assert_eq!(response, common::me());
+    let sent = transport.0.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0] - started >= Duration::from_millis(500), "sent after {:?}", sent[0] - started);
+}
+
+#[tokio::test]
+async fn should_resolve_persisted_queries_shared_between_routers() {
+    use graphql_router::plugins::{PersistedQueries, PersistedQueryStore, StoreFuture, StoredQuery};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        documents: Mutex<HashMap<String, String>>,
+        expires_in: Mutex<Option<Duration>>,
+        lookups: Mutex<usize>,
+    }
+
+    impl PersistedQueryStore for MemoryStore {
+        fn get<'a>(&'a self, namespace: &'a str, id: &'a str) -> StoreFuture<'a, Option<StoredQuery>> {
+            *self.lookups.lock().unwrap() += 1;
+            let document = self.documents.lock().unwrap().get(&format!("{}:{}", namespace, id)).cloned();
+            let expires_in = *self.expires_in.lock().unwrap();
+            Box::pin(async move { Ok(document.map(|document| StoredQuery { document, expires_in })) })
+        }
+
+        fn register<'a>(&'a self, namespace: &'a str, id: &'a str, document: &'a str) -> StoreFuture<'a, ()> {
+            let key = format!("{}:{}", namespace, id);
+            self.documents.lock().unwrap().insert(key, document.to_owned());
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let store = Arc::new(MemoryStore::default());
+    let mut routers = Vec::new();
+    for _ in 0..2 {
+        let user = common::user_graph();
+        let persisted = PersistedQueries::new(true).with_store(store.clone());
+        let harness = RouterTestHarness::builder(supergraph.clone())
+            .subgraph(user)
+            .configure(move |builder| builder.with_persisted_queries(persisted))
+            .build()
+            .await
+            .expect("to create harness");
+        routers.push(harness);
+    }
+    let request = |id: &str| {
+        serde_json::from_value::<GraphqlRequest>(serde_json::json!({
+            "extensions": { "persistedQuery": { "version": 1, "sha256Hash": id } }
+        }))
+        .expect("valid request")
+    };
+
+    let response = common::execute(routers[1].router(), request("me-hash")).await;
+    assert!(response.contains("PERSISTED_QUERY_NOT_FOUND"), "unexpected response: {}", response);
+
+    let persisted = routers[0].router().persisted_queries().cloned().expect("persisted queries");
+    persisted
+        .share(&supergraph, "me-hash", "{ me { username } }")
+        .await
+        .expect("to share operation");
+    let response = common::execute(routers[1].router(), request("me-hash")).await;
+    assert_eq!(response, r#"{"data":{"me":{"username":"Me"}}}"#);
+    assert_eq!(store.documents.lock().unwrap().len(), 1);
+
+    //Operation resolved from shared store is executed by id only.
+    let response = routers[1].query("{ me { username } }").await;
+    assert_eq!(response["errors"][0]["extensions"]["code"], "OPERATION_NOT_IN_SAFELIST");
+
+    //Cached operation is used until it expires in shared store.
+    let lookups = *store.lookups.lock().unwrap();
+    common::execute(routers[1].router(), request("me-hash")).await;
+    assert_eq!(*store.lookups.lock().unwrap(), lookups);
+
+    *store.expires_in.lock().unwrap() = Some(Duration::ZERO);
+    persisted
+        .share(&supergraph, "expiring-hash", "{ me { username } }")
+        .await
+        .expect("to share operation");
+    for idx in 1..=2 {
+        let response = common::execute(routers[1].router(), request("expiring-hash")).await;
+        assert_eq!(response, r#"{"data":{"me":{"username":"Me"}}}"#);
+        assert_eq!(*store.lookups.lock().unwrap(), lookups + idx);
+    }
+}
+
+#[tokio::test]
+async fn should_chain_audit_records_across_restarts() {
+    use graphql_router::plugins::{Audit, AuditConfig, AuditSinkConfig, Redaction};
+    use sha2::{Digest, Sha256};
+
+    //Records are written by background thread.
+    async fn read_records(path: &std::path::Path, expected: usize) -> Vec<serde_json::Value> {
+        for _ in 0..100 {
+            let content = std::fs::read_to_string(path).unwrap_or_default();
+            if content.lines().count() >= expected {
+                let records = content.lines().map(|line| serde_json::from_str(line).expect("JSON record"));
+                return records.collect();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} audit records are not written", expected);
+    }
+
+    fn hash(record: &serde_json::Value) -> String {
+        let mut record = record.clone();
+        record["hash"] = "".into();
+        format!("{:x}", Sha256::digest(serde_json::to_vec(&record).expect("serialize record")))
+    }
+
+    let supergraph = common::schema("mutation_supergraph");
+    let path = std::env::temp_dir().join(format!("graphql-router-audit-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let rename = serde_json::json!({
+        "query": "mutation Rename($username: String!) { setUsername(username: $username) }",
+        "operationName": "Rename",
+        "variables": { "username": "hatter" }
+    });
+
+    //Router is restarted, continuing chain of existing file.
+    for (mutations, expected) in [(2, 2), (1, 3)] {
+        let config = AuditConfig {
+            sink: AuditSinkConfig::File { path: path.clone() },
+            queue_capacity: 16,
+            client_header: None,
+            redact_variables: vec!["username".to_owned()],
+            redaction: Redaction::Mask,
+        };
+        let audit = Audit::with_config(config).expect("to open audit log");
+        let user = MockGraphBuilder::new("user")
+            .on_query("setUsername", serde_json::json!({ "data": { "setUsername": "hatter" } }))
+            .on_query("me", common::me());
+        let mut harness = RouterTestHarness::builder(supergraph.clone())
+            .subgraph(user)
+            .configure(move |builder| builder.with_dyn_plugin("audit".to_owned(), Box::new(audit)))
+            .build()
+            .await
+            .expect("to create harness");
+        harness.query("{ me { username } }").await;
+        for _ in 0..mutations {
+            let request = serde_json::from_value::<GraphqlRequest>(rename.clone()).expect("valid request");
+            harness.execute(request).await;
+        }
+        assert_eq!(read_records(&path, expected).await.len(), expected, "queries must not be audited");
+    }
+
+    let records = read_records(&path, 3).await;
+    let _ = std::fs::remove_file(&path);
+    let mut previous_hash = String::new();
+    for (idx, record) in records.iter().enumerate() {
+        assert_eq!(record["sequence"], idx + 1);
+        assert_eq!(record["operation_name"], "Rename");
+        assert_eq!(record["outcome"], "success");
+        assert_eq!(record["variables"]["username"], "<redacted>");
+        assert_eq!(record["previous_hash"], previous_hash.as_str());
+        assert_eq!(record["hash"], hash(record).as_str());
+        previous_hash = hash(record);
+    }
+
+    let mut tampered = records[1].clone();
+    tampered["outcome"] = "error".into();
+    assert_ne!(hash(&tampered), records[1]["hash"].as_str().unwrap_or_default());
+}
+
+#[tokio::test]
+async fn should_publish_operation_events() {
+    use graphql_router::plugins::{OperationEvent, OperationEventSink, OperationEvents};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<OperationEvent>>>);
+
+    impl OperationEventSink for Collector {
+        fn publish(&self, event: &OperationEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let collector = Collector::default();
+    let events = OperationEvents::with_sink(Box::new(collector.clone()));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| builder.with_dyn_plugin("operation_events".to_owned(), Box::new(events)))
+        .build()
+        .await
+        .expect("to create harness");
+
+    harness.query("{ me { username } }").await;
+    harness.query("{ me { unknown } }").await;
+    harness.query("# Current user\n{\n  me, { username }\n}").await;
+
+    let events = collector.0.lock().unwrap().clone();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].signature.as_ref().map(String::len), Some(64));
+    assert_eq!(events[0].status, Some(200));
+    assert_eq!(events[0].error_class, None);
+    assert_ne!(events[0].signature, events[1].signature);
+    assert!(events[1].error_class.is_some(), "invalid query must be classified as error");
+    assert_eq!(events[0].signature, events[2].signature, "formatting must not change signature");
+}
+
+#[tokio::test]
+async fn should_authenticate_api_keys_across_rebuilds() {
+    use graphql_router::plugins::{ApiKeyConfig, ApiKeyStore, ApiKeys, StoreFuture};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        keys: Mutex<HashMap<String, ApiKeyConfig>>,
+        lookups: Mutex<usize>,
+    }
+
+    impl ApiKeyStore for MemoryStore {
+        fn get<'a>(&'a self, key: &'a str) -> StoreFuture<'a, Option<ApiKeyConfig>> {
+            *self.lookups.lock().unwrap() += 1;
+            let settings = self.keys.lock().unwrap().get(key).cloned();
+            Box::pin(async move { Ok(settings) })
+        }
+    }
+
+    async fn execute_with_key(router: &mut GraphqlRouter, key: Option<&str>) -> String {
+        let headers = key.map(|key| vec![("x-api-key", key)]).unwrap_or_default();
+        let request = common::request("{ me { username } }", &headers);
+        let (_, response) = common::handle(router, request).await;
+        response.to_string()
+    }
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let store = Arc::new(MemoryStore::default());
+    store.keys.lock().unwrap().insert("stored-key".to_owned(), ApiKeyConfig {
+        client: "stored".to_owned(),
+        enabled: true,
+    });
+    let keys = ApiKeys::new("x-api-key").with_store(store.clone(), Duration::from_secs(60));
+    keys.insert("config-key".to_owned(), "config".to_owned());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| builder.with_api_keys(keys))
+        .build()
+        .await
+        .expect("to create harness");
+    let router = harness.router();
+    let expected = r#"{"data":{"me":{"username":"Me"}}}"#;
+
+    let response = execute_with_key(router, None).await;
+    assert!(response.contains("UNAUTHENTICATED"), "unexpected response: {}", response);
+    assert_eq!(execute_with_key(router, Some("config-key")).await, expected);
+
+    //Keys are managed via router and their state is kept when router is rebuilt.
+    let keys = router.api_keys().cloned().expect("api keys");
+    assert!(keys.set_enabled("config-key", false));
+    router.purge_plan_cache().await.expect("to rebuild router");
+    let response = execute_with_key(router, Some("config-key")).await;
+    assert!(response.contains("UNAUTHENTICATED"), "unexpected response: {}", response);
+    assert!(router.api_keys().expect("api keys").set_enabled("config-key", true));
+    assert_eq!(execute_with_key(router, Some("config-key")).await, expected);
+    assert!(keys.info("config-key").expect("key info").last_used.is_some());
+
+    //Stored key is looked up once and cached, while unknown key is looked up every time.
+    assert_eq!(execute_with_key(router, Some("stored-key")).await, expected);
+    assert_eq!(execute_with_key(router, Some("stored-key")).await, expected);
+    assert_eq!(*store.lookups.lock().unwrap(), 1);
+    for _ in 0..2 {
+        let response = execute_with_key(router, Some("unknown-key")).await;
+        assert!(response.contains("UNAUTHENTICATED"), "unexpected response: {}", response);
+    }
+    assert_eq!(*store.lookups.lock().unwrap(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn should_shed_requests_above_limit() {
+    use graphql_router::plugins::LoadShedConfig;
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let latency = common::latency(100);
+    let config = LoadShedConfig {
+        max_in_flight: 1,
+        queue_timeout_ms: 0,
+        retry_after_ms: 1500,
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.load_shed(config).latency_injection(latency))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let request = || common::request("{ me { username } }", &[]);
+    let in_flight = tokio::spawn(harness.router().handle(request()));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let response = harness.router().handle(request()).await.expect("to handle request");
+    assert_eq!(response.response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.response.headers()[http::header::RETRY_AFTER], "2");
+    let response = GraphqlResponse::try_from(response.response.into_body()).expect("GraphQL response");
+    let response = serde_json::to_value(&response).expect("Serialize response");
+    assert_eq!(response["errors"][0]["extensions"]["code"], "SERVER_OVERLOADED");
+    assert_eq!(response["errors"][0]["extensions"]["retryAfterMs"], 1500);
+
+    //Slot is released once request in flight completes.
+    let response = in_flight.await.expect("to join").expect("to handle request");
+    assert_eq!(response.response.status(), http::StatusCode::OK);
+    let response = harness.query("{ me { username } }").await;
+    assert_eq!(response, common::me());
+}
+
+#[tokio::test]
+async fn should_capture_subgraph_exchanges_on_request() {
+    use graphql_router::plugins::{CaptureFormat, DebugCaptureConfig};
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let dir = std::env::temp_dir().join(format!("graphql-router-capture-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("create capture directory");
+    let config = DebugCaptureConfig {
+        format: CaptureFormat::Har,
+        extensions: true,
+        directory: Some(dir.clone()),
+        ..DebugCaptureConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.debug_capture(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("{ me { username } }").await;
+    assert!(response.get("extensions").is_none(), "capture must be requested: {}", response);
+
+    let request = common::request("{ me { username } }", &[("x-debug-capture", "1"), ("x-request-id", "../req-1")]);
+    let (_, response) = common::handle(harness.router(), request).await;
+    let capture = &response["extensions"]["debugCapture"]["log"];
+    assert_eq!(capture["comment"], "../req-1");
+    assert_eq!(capture["entries"].as_array().map(Vec::len), Some(1));
+    assert_eq!(capture["entries"][0]["comment"], "user");
+    assert_eq!(capture["entries"][0]["response"]["status"], 200);
+
+    //Request id cannot escape capture directory.
+    let written = std::fs::read(dir.join("___req-1.har")).expect("read capture");
+    let _ = std::fs::remove_dir_all(&dir);
+    let written = serde_json::from_slice::<serde_json::Value>(&written).expect("JSON capture");
+    assert_eq!(&written["log"], capture);
+}
+
+#[tokio::test]
+async fn should_expose_query_plan_and_fetch_timings() {
+    use graphql_router::plugins::ExposeQueryPlanConfig;
+
+    let supergraph = common::supergraph();
+    let product = common::product_graph();
+    let review = common::review_graph();
+    let config = ExposeQueryPlanConfig {
+        always: true,
+        ..ExposeQueryPlanConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(product)
+        .subgraph(review)
+        .configure(|builder| builder.expose_query_plan(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name, reviews { body } } }").await;
+    assert_eq!(response["data"]["topProducts"][0]["reviews"][0]["body"], "Great hat");
+    let exposed = &response["extensions"]["queryPlan"];
+    assert!(exposed["plan"].is_object(), "plan must be exposed: {}", exposed);
+    assert!(exposed["durationMs"].is_number());
+    let fetches = exposed["fetches"].as_array().expect("fetches");
+    let subgraphs = fetches.iter().map(|fetch| fetch["subgraph"].clone()).collect::<Vec<_>>();
+    assert_eq!(subgraphs, ["product", "review"]);
+    //Entity fetch depends on fetch of products.
+    assert!(fetches[1]["start_ms"].as_f64() > fetches[0]["start_ms"].as_f64(), "{}", exposed);
+}
+
+#[tokio::test]
+async fn should_log_subgraph_requests_with_redaction() {
+    use graphql_router::plugins::{Redaction, SubgraphLoggingConfig};
+    use std::sync::Mutex;
+
+    //Collects messages of events, emitted on current thread.
+    struct LogCapture(Arc<Mutex<Vec<String>>>);
+
+    struct Message(String);
+
+    impl tracing::field::Visit for Message {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn core::fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl tracing::Subscriber for LogCapture {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0.lock().unwrap().push(message.0);
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    let supergraph = common::supergraph();
+    let product = common::product_graph();
+    let review = common::review_graph();
+    let config = SubgraphLoggingConfig {
+        subgraphs: vec!["review".to_owned()],
+        redact_variables: vec!["representations".to_owned()],
+        redaction: Redaction::Hash,
+        ..SubgraphLoggingConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(product)
+        .subgraph(review)
+        .configure(|builder| builder.subgraph_logging(config))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let guard = tracing::subscriber::set_default(LogCapture(logs.clone()));
+    let response = harness.query("query Query { topProducts { name, reviews { body } } }").await;
+    drop(guard);
+    assert_eq!(response["data"]["topProducts"][0]["reviews"][0]["body"], "Great hat");
+
+    let logs = logs.lock().unwrap().clone();
+    let requests = logs.iter().filter(|log| log.contains(": Request '")).collect::<Vec<_>>();
+    assert_eq!(requests.len(), 1, "only review must be logged: {:?}", logs);
+    assert!(requests[0].starts_with("review: Request"), "{}", requests[0]);
+    assert!(requests[0].contains(r#"{"representations":"hash:"#), "{}", requests[0]);
+    assert!(!requests[0].contains("top-1"), "variables must be redacted: {}", requests[0]);
+    assert!(logs.iter().any(|log| log.starts_with("review: Response in ")), "{:?}", logs);
+}
+
+#[tokio::test(start_paused = true)]
+async fn should_limit_parallel_fetches_per_request() {
+    use graphql_router::plugins::FetchParallelismConfig;
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "name": "Trilby" }] }
+    }));
+    let latency = common::latency(100);
+    let config = FetchParallelismConfig {
+        max_per_request: 2,
+        max_total: None,
+        header: Some("x-fetch-parallelism".to_owned()),
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("review"))
+        //Latency is injected within limit, so that delayed fetch holds its permit.
+        .configure(|builder| builder.fetch_parallelism(config).latency_injection(latency))
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } topProducts { name } }";
+    let expected = serde_json::json!({ "data": { "me": { "username": "Me" }, "topProducts": [{ "name": "Trilby" }] } });
+
+    let started = tokio::time::Instant::now();
+    assert_eq!(harness.query(query).await, expected);
+    let elapsed = started.elapsed();
+    assert!(elapsed < Duration::from_millis(200), "fetches must run in parallel: {:?}", elapsed);
+
+    let request = common::request(query, &[("x-fetch-parallelism", "1")]);
+    let started = tokio::time::Instant::now();
+    let (_, response) = common::handle(harness.router(), request).await;
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(200), "fetches must run one by one: {:?}", elapsed);
+    assert_eq!(response, expected);
+}
+
+#[tokio::test]
+async fn should_report_server_timing() {
+    use graphql_router::plugins::ServerTimingConfig;
+
+    async fn handle(router: &mut GraphqlRouter, header: Option<&str>) -> (Option<String>, serde_json::Value) {
+        let headers = header.map(|header| vec![(header, "1")]).unwrap_or_default();
+        let request = common::request("{ me { username } }", &headers);
+        let (parts, body) = common::handle(router, request).await;
+        let server_timing = parts.headers.get("server-timing");
+        let server_timing = server_timing.map(|value| value.to_str().expect("valid header").to_owned());
+        (server_timing, body)
+    }
+
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(common::user_graph())
+        .configure(|builder| builder.server_timing(ServerTimingConfig::default()))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let (server_timing, body) = handle(harness.router(), Some("x-server-timing")).await;
+    let server_timing = server_timing.expect("Server-Timing header");
+    let metrics = server_timing
+        .split(", ")
+        .map(|metric| metric.split(";dur=").next().expect("metric name"))
+        .collect::<Vec<_>>();
+    //Request is not parsed by server, so there is no parse phase.
+    assert_eq!(metrics, ["plan", "fetch-user", "total"]);
+    let timing = &body["extensions"]["serverTiming"];
+    assert_eq!(timing["parseMs"], serde_json::Value::Null);
+    assert!(timing["planMs"].is_f64());
+    assert_eq!(timing["fetches"].as_array().expect("fetches").len(), 1);
+    assert_eq!(timing["fetches"][0]["subgraph"], "user");
+    let fetch_ms = timing["fetches"][0]["durationMs"].as_f64().expect("fetch duration");
+    assert!(timing["totalMs"].as_f64().expect("total duration") >= fetch_ms);
+
+    let (server_timing, body) = handle(harness.router(), None).await;
+    assert_eq!(server_timing, None);
+    assert_eq!(body, common::me());
+
+    let config = ServerTimingConfig {
+        always: true,
+        extensions: false,
+        ..ServerTimingConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(common::user_graph())
+        .configure(|builder| builder.server_timing(config))
+        .build()
+        .await
+        .expect("to create harness");
+    let (server_timing, body) = handle(harness.router(), None).await;
+    assert!(server_timing.expect("Server-Timing header").ends_with(|ch: char| ch.is_ascii_digit()));
+    assert_eq!(body, common::me());
+}
+
+#[test]
+fn should_store_typed_context_entries() {
+    use graphql_router::context::{ContextEntry, Priority, RequestId, TypedContext};
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Tenant {
+        id: u32,
+        region: String,
+    }
+
+    impl ContextEntry for Tenant {
+        const KEY: &'static str = "tests::tenant";
+    }
+
+    let context = apollo_router_core::Context::new();
+    let typed = TypedContext::new(&context);
+    assert!(!typed.contains::<Tenant>());
+    assert_eq!(typed.get::<Tenant>().expect("to read tenant"), None);
+
+    let tenant = Tenant {
+        id: 1,
+        region: "eu".to_owned(),
+    };
+    typed.insert(tenant).expect("to store tenant");
+    typed.insert(Priority::High).expect("to store priority");
+    typed.insert(RequestId("req-1".to_owned())).expect("to store request id");
+    typed.insert(RequestId("req-2".to_owned())).expect("to replace request id");
+    let tenant = typed.get::<Tenant>().expect("to read tenant");
+    assert_eq!(tenant, Some(Tenant { id: 1, region: "eu".to_owned() }));
+    assert_eq!(typed.get::<Priority>().expect("to read priority"), Some(Priority::High));
+    assert_eq!(typed.get::<RequestId>().expect("to read request id"), Some(RequestId("req-2".to_owned())));
+
+    //Entries are plain context values under their keys.
+    let value = context.get::<_, serde_json::Value>(Tenant::KEY).expect("to read value");
+    assert_eq!(value, Some(serde_json::json!({ "id": 1, "region": "eu" })));
+    assert_eq!(context.get::<_, String>(Priority::KEY).expect("to read value").as_deref(), Some("high"));
+    context.insert(Tenant::KEY, "not a tenant").expect("to store value");
+    assert!(typed.get::<Tenant>().is_err());
+    assert!(!typed.contains::<Tenant>());
+}
+
+#[tokio::test]
+async fn should_expire_values_of_memory_cache_store() {
+    use graphql_router::plugins::{CacheStore, MemoryCacheStore};
+
+    let store = MemoryCacheStore::new();
+    assert_eq!(store.get("me").await.expect("to get value"), None);
+
+    store.set("me", "user".into(), None).await.expect("to set value");
+    store.set("temp", "user".into(), Some(Duration::from_millis(10))).await.expect("to set value");
+    assert_eq!(store.get("me").await.expect("to get value").as_deref(), Some(&b"user"[..]));
+    assert_eq!(store.get("temp").await.expect("to get value").as_deref(), Some(&b"user"[..]));
+    assert_eq!(store.len(), 2);
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(store.get("temp").await.expect("to get value"), None);
+    assert_eq!(store.len(), 1);
+    store.remove("me").await.expect("to remove value");
+    assert_eq!(store.get("me").await.expect("to get value"), None);
+    assert!(store.is_empty());
+}
+
+#[cfg(feature = "memcached-store")]
+#[tokio::test]
+async fn should_cache_values_in_memcached() {
+    use graphql_router::plugins::{CacheStore, MemcachedStore, MemcachedStoreConfig};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+
+    //Minimal memcached, which ignores flags and expiration.
+    let values = Arc::new(Mutex::new(HashMap::<String, Vec<u8>>::new()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:9026").await.expect("to bind");
+    let server_values = values.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let values = server_values.clone();
+            tokio::spawn(async move {
+                let mut stream = BufStream::new(stream);
+                let mut line = String::new();
+                while stream.read_line(&mut line).await.unwrap_or(0) > 0 {
+                    let parts = line.split_whitespace().map(str::to_owned).collect::<Vec<_>>();
+                    line.clear();
+                    let reply = match parts.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+                        ["get", key] => match values.lock().expect("lock").get(*key) {
+                            Some(value) => {
+                                let mut reply = format!("VALUE {} 0 {}\r\n", key, value.len()).into_bytes();
+                                reply.extend_from_slice(value);
+                                reply.extend_from_slice(b"\r\nEND\r\n");
+                                reply
+                            }
+                            None => b"END\r\n".to_vec(),
+                        },
+                        ["set", key, _, _, size] => {
+                            let mut value = vec![0; size.parse::<usize>().expect("size") + 2];
+                            stream.read_exact(&mut value).await.expect("to read value");
+                            value.truncate(value.len() - 2);
+                            values.lock().expect("lock").insert(key.to_string(), value);
+                            b"STORED\r\n".to_vec()
+                        }
+                        ["delete", key] => match values.lock().expect("lock").remove(*key) {
+                            Some(_) => b"DELETED\r\n".to_vec(),
+                            None => b"NOT_FOUND\r\n".to_vec(),
+                        },
+                        _ => b"ERROR\r\n".to_vec(),
+                    };
+                    stream.write_all(&reply).await.expect("to write reply");
+                    stream.flush().await.expect("to flush");
+                }
+            });
+        }
+    });
+
+    let config = MemcachedStoreConfig {
+        address: "127.0.0.1:9026".to_owned(),
+        pool_size: 1,
+        prefix: "test".to_owned(),
+    };
+    let store = MemcachedStore::with_config(config).expect("to create store");
+    assert_eq!(store.get("query { me }").await.expect("to get value"), None);
+    store.set("query { me }", "user".into(), Some(Duration::from_secs(60))).await.expect("to set value");
+    assert_eq!(store.get("query { me }").await.expect("to get value").as_deref(), Some(&b"user"[..]));
+
+    //Keys are hashed, as memcached keys cannot contain whitespaces.
+    let keys = values.lock().expect("lock").keys().cloned().collect::<Vec<_>>();
+    assert_eq!(keys.len(), 1);
+    assert!(keys[0].starts_with("test:") && keys[0].len() == "test:".len() + 64);
+
+    store.remove("query { me }").await.expect("to remove value");
+    store.remove("query { me }").await.expect("to remove missing value");
+    assert_eq!(store.get("query { me }").await.expect("to get value"), None);
+
+    let config = MemcachedStoreConfig {
+        address: "127.0.0.1:9026".to_owned(),
+        pool_size: 1,
+        prefix: "with space".to_owned(),
+    };
+    assert!(MemcachedStore::with_config(config).is_err());
+}
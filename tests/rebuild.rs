@@ -0,0 +1,344 @@
+use graphql_router::plugins::{Fault, FaultInjectionConfig, FaultRule};
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+use graphql_router::{GraphqlRouter, WarmupOperation};
+
+use core::time::Duration;
+use std::sync::Arc;
+
+mod common;
+
+//Faults every subgraph request.
+fn faults() -> FaultInjectionConfig {
+    FaultInjectionConfig {
+        rules: vec![FaultRule {
+            fault: Fault::Error,
+            rate: 1.0,
+            subgraphs: Vec::new(),
+            operations: Vec::new(),
+        }],
+    }
+}
+
+#[tokio::test]
+async fn should_add_and_remove_subgraph_at_runtime() {
+    use graphql_router::testing::LoggedGraph;
+
+    let catalog = common::schema("catalog_supergraph");
+    let supergraph = common::supergraph();
+    let product = common::product_graph();
+    let mut harness = RouterTestHarness::builder(catalog.clone())
+        .subgraph(product)
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ topProducts { name reviews { body } } }";
+
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "reviews must be unknown: {}", response);
+
+    let review_graph = LoggedGraph::new(common::review_graph(), harness.calls().clone());
+    let error = harness.router().add_subgraph(catalog.clone(), review_graph).await.expect_err("not in schema");
+    assert!(matches!(error, graphql_router::RebuildError::NotInSchema(_)));
+
+    let review_graph = LoggedGraph::new(common::review_graph(), harness.calls().clone());
+    harness.router().add_subgraph(supergraph, review_graph).await.expect("to add subgraph");
+    let response = harness.query(query).await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "topProducts": [{ "name": "Trilby", "reviews": [{ "body": "Great hat" }] }] } })
+    );
+    harness.calls().assert_call_count("review", 1);
+
+    harness.router().remove_subgraph(catalog, "review").await.expect("to remove subgraph");
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "reviews must be unknown: {}", response);
+    harness.calls().assert_call_count("review", 1);
+}
+
+#[tokio::test]
+async fn should_share_added_subgraph_with_clones() {
+    use graphql_router::remote::PRIMARY_VARIANT;
+
+    let catalog = common::schema("catalog_supergraph");
+    let mut router = GraphqlRouter::build(catalog.clone())
+        .add_subgraph(common::product_graph())
+        .finish()
+        .await
+        .expect("to create router");
+    let clone = router.clone();
+
+    let review = common::remote_graph("review", common::StubTransport::new(common::ME));
+    router.add_subgraph(common::supergraph(), review).await.expect("to add subgraph");
+
+    let subgraphs = common::json_body(common::admin(&clone, "GET", "/subgraphs", "").await).await;
+    assert!(subgraphs.get("review").is_some(), "review must be known to clone: {}", subgraphs);
+    let health = graphql_router::admin::health_json(&clone);
+    assert!(health["subgraphs"].get("review").is_some(), "review health must be known to clone: {}", health);
+    assert!(clone.is_ready());
+
+    let path = format!("/subgraphs/review/drain/{}", PRIMARY_VARIANT);
+    let response = common::admin(&clone, "PUT", &path, "").await;
+    assert_eq!(response.status(), http::StatusCode::OK);
+    let settings = router.subgraph_settings("review").expect("review settings");
+    assert_eq!(settings.draining(), vec![PRIMARY_VARIANT.to_owned()]);
+
+    router.remove_subgraph(catalog, "review").await.expect("to remove subgraph");
+    assert!(clone.subgraph_settings("review").is_none());
+    let response = common::admin(&clone, "GET", "/subgraphs/review", "").await;
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn should_diff_schema_with_candidate() {
+    use graphql_router::diff::Severity;
+
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .build()
+        .await
+        .expect("to create harness");
+    let current = std::fs::read_to_string("tests/supergraph.graphql").expect("To read supergraph");
+
+    let diff = harness.router().diff_schema(&current).expect("valid schema");
+    assert!(diff.is_empty());
+
+    let candidate = std::fs::read_to_string("tests/catalog_supergraph.graphql").expect("To read supergraph");
+    let diff = harness.router().diff_schema(&candidate).expect("valid schema");
+    assert!(diff.is_breaking());
+    let paths = diff.changes.iter().map(|change| change.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(paths, ["Product.reviews", "Review", "User.reviews"]);
+
+    let candidate = current.replace("  price: Int! @join__field(graph: PRODUCT)", "  price(currency: String): Int!");
+    let diff = harness.router().diff_schema(&candidate).expect("valid schema");
+    assert!(!diff.is_breaking());
+    assert_eq!(diff.changes.len(), 1);
+    assert_eq!(diff.changes[0].path, "Product.price(currency:)");
+    assert_eq!(diff.changes[0].severity, Severity::Safe);
+
+    let response = common::admin(harness.router(), "POST", "/schema/diff", "type {").await;
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn should_promote_and_roll_back_staged_schema() {
+    use graphql_router::RebuildError;
+
+    let catalog = common::schema("catalog_supergraph");
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(common::user_graph())
+        .subgraph(common::product_graph())
+        .subgraph(common::review_graph())
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ topProducts { name reviews { body } } }";
+
+    let error = harness.router().promote_standby().await.expect_err("nothing staged");
+    assert!(matches!(error, RebuildError::NoStandby));
+    harness.router().stage_schema(catalog.clone()).await.expect("to stage schema");
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_null(), "staged schema must not take traffic: {}", response);
+
+    let error = harness
+        .router()
+        .smoke_test_standby(&[WarmupOperation::new(query)])
+        .await
+        .expect_err("reviews are not in staged schema");
+    assert!(matches!(error, RebuildError::SmokeTest { failed: 1, total: 1 }));
+    let smoke = [WarmupOperation::new("{ me { username } }")];
+    harness.router().smoke_test_standby(&smoke).await.expect("to pass smoke test");
+
+    harness.router().promote_standby().await.expect("to promote");
+    assert_eq!(harness.router().schema().as_str(), catalog.as_str());
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "reviews must be unknown: {}", response);
+
+    harness.router().rollback().await.expect("to roll back");
+    assert_eq!(harness.router().schema().as_str(), supergraph.as_str());
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_null(), "reviews must be restored: {}", response);
+    let standby = harness.router().standby_schema().await.expect("rolled back schema is kept in standby");
+    assert_eq!(standby.as_str(), catalog.as_str());
+    let error = harness.router().rollback().await.expect_err("nothing to roll back to");
+    assert!(matches!(error, RebuildError::NoPrevious));
+}
+
+#[tokio::test]
+async fn should_toggle_plugins_at_runtime() {
+    use graphql_router::plugins::RequestLimitsConfig;
+    use graphql_router::RebuildError;
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.request_limits(RequestLimitsConfig::default()).fault_injection(faults()))
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "fault must be injected: {}", response);
+
+    harness.router().set_plugin_enabled("fault_injection", false).await.expect("to disable plugin");
+    let response = harness.query(query).await;
+    assert_eq!(response, common::me());
+    let plugins = harness.router().plugins().await;
+    assert!(plugins.contains(&("request_limits".to_owned(), true)));
+    assert!(plugins.contains(&("fault_injection".to_owned(), false)));
+
+    harness.router().set_plugin_enabled("fault_injection", true).await.expect("to enable plugin");
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "fault must be injected again: {}", response);
+
+    let error = harness.router().set_plugin_enabled("request_limits", false).await.expect_err("pinned");
+    assert!(matches!(error, RebuildError::PluginPinned(_)));
+    let error = harness.router().set_plugin_enabled("unknown", false).await.expect_err("not added");
+    assert!(matches!(error, RebuildError::UnknownPlugin(_)));
+}
+
+#[tokio::test]
+async fn should_toggle_plugins_by_flags() {
+    use graphql_router::plugins::RequestLimitsConfig;
+    use graphql_router::RebuildError;
+    use std::collections::BTreeMap;
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.request_limits(RequestLimitsConfig::default()).fault_injection(faults()))
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    //Pushed flags: unknown plugins are ignored, while disabling pinned ones fails whole update.
+    let mut flags = BTreeMap::new();
+    flags.insert("fault_injection".to_owned(), false);
+    flags.insert("request_limits".to_owned(), false);
+    let error = harness.router().update_plugins(&flags).await.expect_err("pinned");
+    assert!(matches!(error, RebuildError::PluginPinned(_)));
+    assert!(harness.router().plugins().await.iter().all(|(_, is_enabled)| *is_enabled));
+
+    //Pinned plugins are always enabled, so flag enabling them is accepted.
+    flags.insert("request_limits".to_owned(), true);
+    flags.insert("response_cache".to_owned(), false);
+    harness.router().update_plugins(&flags).await.expect("to apply flags");
+    let plugins = harness.router().plugins().await;
+    assert!(plugins.contains(&("request_limits".to_owned(), true)));
+    assert!(plugins.contains(&("fault_injection".to_owned(), false)));
+    let response = harness.query(query).await;
+    assert_eq!(response, common::me());
+
+    //Polled flags are applied until shutdown.
+    let (done, finished) = tokio::sync::oneshot::channel::<()>();
+    let mut done = Some(done);
+    let mut fetched = 0;
+    let fetch = || {
+        fetched += 1;
+        if fetched == 2 {
+            if let Some(done) = done.take() {
+                let _ = done.send(());
+            }
+        }
+        let mut flags = BTreeMap::new();
+        flags.insert("fault_injection".to_owned(), true);
+        core::future::ready(Ok(flags))
+    };
+    let shutdown = async move {
+        let _ = finished.await;
+    };
+    harness
+        .router()
+        .poll_plugin_flags(Duration::from_millis(1), fetch, shutdown)
+        .await;
+    let plugins = harness.router().plugins().await;
+    assert_eq!(plugins, [("request_limits".to_owned(), true), ("fault_injection".to_owned(), true)]);
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "fault must be injected again: {}", response);
+}
+
+#[tokio::test]
+async fn should_keep_plugin_state_across_rebuilds() {
+    use apollo_router_core::Plugin;
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use graphql_router::{RouterRequest, RouterResponse};
+    use tower::util::BoxService;
+    use tower::{BoxError, ServiceExt};
+
+    //Counts requests and builds of router it was used by.
+    struct Counter {
+        builds: usize,
+        requests: Arc<AtomicUsize>,
+        last_build: Arc<AtomicUsize>,
+    }
+
+    impl Plugin for Counter {
+        type Config = ();
+
+        fn new<'a>(_: Self::Config) -> Pin<Box<dyn Future<Output = Result<Self, BoxError>> + Send + 'a>>
+        where
+            Self: 'a,
+        {
+            Box::pin(core::future::ready(Err("Created by test only".into())))
+        }
+
+        fn router_service(
+            &mut self,
+            service: BoxService<RouterRequest, RouterResponse, BoxError>,
+        ) -> BoxService<RouterRequest, RouterResponse, BoxError> {
+            self.builds += 1;
+            self.last_build.store(self.builds, Ordering::SeqCst);
+            let requests = self.requests.clone();
+            service
+                .map_request(move |req| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    req
+                })
+                .boxed()
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let last_build = Arc::new(AtomicUsize::new(0));
+    let counter = Counter {
+        builds: 0,
+        requests: requests.clone(),
+        last_build: last_build.clone(),
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| builder.with_dyn_plugin("counter".to_owned(), Box::new(counter)))
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+    let clone = harness.router().clone();
+
+    harness.query(query).await;
+    harness.router().purge_plan_cache().await.expect("to rebuild with dynamic plugin");
+    //Plugin instance is reused by new build, keeping its state.
+    assert_eq!(last_build.load(Ordering::SeqCst), 2);
+    harness.query(query).await;
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    harness.router().set_plugin_enabled("counter", false).await.expect("to disable dynamic plugin");
+    harness.query(query).await;
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(harness.router().plugins().await, [("counter".to_owned(), false)]);
+
+    harness.router().set_plugin_enabled("counter", true).await.expect("to enable dynamic plugin");
+    assert_eq!(last_build.load(Ordering::SeqCst), 3);
+    harness.query(query).await;
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    //Clones made before rebuild use current service and schema.
+    assert_eq!(clone.schema().as_str(), harness.router().schema().as_str());
+}
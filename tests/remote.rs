@@ -0,0 +1,572 @@
+use graphql_router::remote::{SubgraphTransport, TransportError, TransportFuture, TransportRequest};
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness, TestClock};
+use graphql_router::{GraphqlRequest, RemoteGraphBuilder};
+
+use core::time::Duration;
+use std::sync::Arc;
+
+mod common;
+
+#[tokio::test]
+async fn should_drain_subgraph_endpoints() {
+    use graphql_router::remote::PRIMARY_VARIANT;
+    use std::sync::Mutex;
+
+    struct UrlLog(Mutex<Vec<String>>);
+
+    impl SubgraphTransport for UrlLog {
+        fn send(&self, request: TransportRequest) -> TransportFuture {
+            self.0.lock().expect("lock").push(request.url.to_string());
+            Box::pin(async { common::respond("user", common::ME) })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let log = Arc::new(UrlLog(Mutex::new(Vec::new())));
+    let user = RemoteGraphBuilder::new("user", "http://primary/user".parse().expect("valid url"))
+        .variant("canary", "http://canary/user".parse().expect("valid url"), 0.0)
+        .transport(log.clone());
+    let settings = user.settings();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    assert!(settings.drain(PRIMARY_VARIANT));
+    assert!(!settings.drain("unknown"));
+    let response = harness.query(query).await;
+    assert_eq!(response, common::me());
+
+    assert!(settings.drain("canary"));
+    let response = harness.query(query).await;
+    assert!(response["errors"].is_array(), "fetch must fail: {}", response);
+
+    assert!(settings.resume(PRIMARY_VARIANT));
+    harness.query(query).await;
+    assert_eq!(settings.draining(), vec!["canary".to_owned()]);
+    assert_eq!(settings.in_flight(), 0);
+    let urls = log.0.lock().expect("lock").clone();
+    assert_eq!(urls, vec!["http://canary/user".to_owned(), "http://primary/user".to_owned()]);
+}
+
+#[tokio::test]
+async fn should_track_subgraph_health() {
+    use graphql_router::remote::HealthStatus;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    struct Flaky(AtomicBool);
+
+    impl SubgraphTransport for Flaky {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            let is_down = self.0.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if is_down {
+                    return Err(TransportError::Failed("Connection refused".to_owned()));
+                }
+                common::respond("user", common::ME)
+            })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let transport = Arc::new(Flaky(AtomicBool::new(false)));
+    let user = common::remote_graph("user", transport.clone());
+    let settings = user.settings();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let query = "{ me { username } }";
+
+    harness.query(query).await;
+    assert_eq!(settings.health().status, HealthStatus::Healthy);
+    assert!(harness.router().is_ready());
+
+    transport.0.store(true, Ordering::SeqCst);
+    harness.query(query).await;
+    harness.query(query).await;
+    let health = settings.health();
+    assert_eq!(health.status, HealthStatus::Down);
+    assert_eq!(health.samples, 3);
+    assert!(health.last_error.expect("last error").contains("Connection refused"));
+
+    let response = common::admin(harness.router(), "GET", "/health", "").await;
+    let health = common::json_body(response).await;
+    assert_eq!(health["ready"], false);
+    assert_eq!(health["subgraphs"]["user"]["status"], "down");
+}
+
+#[tokio::test]
+async fn should_open_and_close_circuit_breaker() {
+    use graphql_router::remote::CircuitState;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct Flaky {
+        is_down: AtomicBool,
+        sent: AtomicUsize,
+    }
+
+    impl SubgraphTransport for Flaky {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            let is_down = self.is_down.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if is_down {
+                    return Err(TransportError::Failed("Connection refused".to_owned()));
+                }
+                common::respond("user", common::ME)
+            })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let clock = TestClock::new();
+    let transport = Arc::new(Flaky {
+        is_down: AtomicBool::new(true),
+        sent: AtomicUsize::new(0),
+    });
+    let user = common::remote_graph("user", transport.clone())
+        .clock(Arc::new(clock.clone()))
+        .max_retry_num(0)
+        .circuit_breaker(2, Duration::from_secs(10));
+    let settings = user.settings();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let breaker = settings.get().circuit_breaker.expect("circuit breaker settings");
+    let query = "{ me { username } }";
+
+    harness.query(query).await;
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::Closed);
+    harness.query(query).await;
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::Open);
+    let response = harness.query(query).await;
+    assert!(response.to_string().contains("Circuit breaker is open"), "{}", response);
+    assert_eq!(transport.sent.load(Ordering::SeqCst), 2);
+
+    //Failed probe opens circuit again.
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::HalfOpen);
+    harness.query(query).await;
+    assert_eq!(transport.sent.load(Ordering::SeqCst), 3);
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::Open);
+
+    //Successful probe closes circuit.
+    clock.advance(Duration::from_secs(10));
+    transport.is_down.store(false, Ordering::SeqCst);
+    let response = harness.query(query).await;
+    assert_eq!(response, common::me());
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::Closed);
+
+    transport.is_down.store(true, Ordering::SeqCst);
+    harness.query(query).await;
+    harness.query(query).await;
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::Open);
+    settings.circuit_breaker().reset();
+    assert_eq!(settings.circuit_breaker().state(&breaker), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn should_mirror_sampled_requests_to_shadow() {
+    let (sender, mut shadowed) = tokio::sync::mpsc::unbounded_channel();
+    let shadow = axum::Router::new().route(
+        "/shadow",
+        axum::routing::post(move |headers: http::HeaderMap, body: hyper::body::Bytes| async move {
+            sender.send((headers, body)).ok();
+            r#"{ "data": { "me": { "username": "Shadow" } } }"#
+        }),
+    );
+    let server = common::spawn(|shutdown| {
+        axum::Server::bind(&([127, 0, 0, 1], 9016).into())
+            .serve(shadow.into_make_service())
+            .with_graceful_shutdown(shutdown)
+    });
+
+    let supergraph = common::supergraph();
+    let transport = common::StubTransport::new(common::ME);
+    let shadow = "http://127.0.0.1:9016/shadow".parse().expect("valid url");
+    let user = common::remote_graph("user", transport.clone()).mirror(shadow, 0.5, true);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+
+    for _ in 0..4 {
+        //Shadow response is only compared, while client receives primary response.
+        let response = harness.query("{ me { username } }").await;
+        assert_eq!(response, common::me());
+    }
+    assert_eq!(transport.requests().len(), 4);
+
+    for _ in 0..2 {
+        let received = tokio::time::timeout(Duration::from_secs(5), shadowed.recv()).await;
+        let (headers, body) = received.expect("shadow request").expect("shadow server running");
+        assert_eq!(headers[http::header::CONTENT_TYPE], "application/json");
+        assert_eq!(body, transport.requests()[0].body);
+    }
+    let unsampled = tokio::time::timeout(Duration::from_millis(100), shadowed.recv()).await;
+    assert!(unsampled.is_err(), "only half of requests must be mirrored");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn should_route_between_variants_by_weight_and_header() {
+    let supergraph = common::supergraph();
+    let transport = common::StubTransport::new(common::ME);
+    let user = RemoteGraphBuilder::new("user", "http://primary/user".parse().expect("valid url"))
+        .variant("canary", "http://canary/user".parse().expect("valid url"), 0.5)
+        .variant_header(http::header::HeaderName::from_static("x-variant"))
+        .transport(transport.clone());
+    let settings = user.settings();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let request = |variant: &str| common::request("{ me { username } }", &[("x-variant", variant)]);
+    let canary_count = || {
+        let requests = transport.requests();
+        requests.iter().filter(|request| request.url == "http://canary/user").count()
+    };
+
+    for _ in 0..10 {
+        harness.query("{ me { username } }").await;
+    }
+    assert_eq!(canary_count(), 5);
+
+    //Header overrides weighted selection, while unknown variant falls back to it.
+    for variant in ["canary", "canary", "primary", "unknown"] {
+        harness.router().handle(request(variant)).await.expect("to handle request");
+    }
+    let requests = transport.requests();
+    let urls = requests[10..13].iter().map(|request| request.url.to_string()).collect::<Vec<_>>();
+    assert_eq!(urls, ["http://canary/user", "http://canary/user", "http://primary/user"]);
+
+    let metrics = settings.variant_metrics();
+    assert_eq!(metrics["canary"].requests + metrics["primary"].requests, 14);
+    assert_eq!(metrics["canary"].requests as usize, canary_count());
+}
+
+#[tokio::test]
+async fn should_share_serialized_body_between_retries() {
+    use std::sync::Mutex;
+
+    struct FailingOnce(Mutex<Vec<hyper::body::Bytes>>);
+
+    impl SubgraphTransport for FailingOnce {
+        fn send(&self, request: TransportRequest) -> TransportFuture {
+            let mut bodies = self.0.lock().unwrap();
+            bodies.push(request.body);
+            let attempt = bodies.len();
+            Box::pin(async move {
+                if attempt == 1 {
+                    return Err(TransportError::Retry("Connection reset".to_owned()));
+                }
+                common::respond("user", common::ME)
+            })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let transport = Arc::new(FailingOnce(Mutex::new(Vec::new())));
+    let user = common::remote_graph("user", transport.clone());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("{ me { username } }").await;
+    assert_eq!(response, common::me());
+
+    let bodies = transport.0.lock().unwrap().clone();
+    assert_eq!(bodies.len(), 2);
+    //Retry sends the same buffer, instead of serializing request again.
+    assert_eq!(bodies[0].as_ptr(), bodies[1].as_ptr());
+    let body = serde_json::from_slice::<serde_json::Value>(&bodies[0]).expect("JSON body");
+    assert!(body["query"].as_str().unwrap_or_default().contains("username"), "{}", body);
+}
+
+#[tokio::test]
+async fn should_share_http_client_between_subgraphs() {
+    use axum::extract::ConnectInfo;
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+
+    let peers = Arc::new(Mutex::new(Vec::new()));
+    let (user_peers, product_peers) = (peers.clone(), peers.clone());
+    let app = axum::Router::new()
+        .route(
+            "/user",
+            axum::routing::post(move |ConnectInfo(peer): ConnectInfo<SocketAddr>| async move {
+                user_peers.lock().unwrap().push(peer);
+                axum::Json(common::me())
+            }),
+        )
+        .route(
+            "/product",
+            axum::routing::post(move |ConnectInfo(peer): ConnectInfo<SocketAddr>| async move {
+                product_peers.lock().unwrap().push(peer);
+                axum::Json(serde_json::json!({ "data": { "topProducts": [{ "name": "Trilby" }] } }))
+            }),
+        );
+    let server = common::spawn(|shutdown| {
+        axum::Server::bind(&([127, 0, 0, 1], 9017).into())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown)
+    });
+
+    let supergraph = common::supergraph();
+    let client = Arc::new(graphql_router::remote::http_client());
+    let user = RemoteGraphBuilder::new("user", "http://127.0.0.1:9017/user".parse().expect("valid url"))
+        .client(client.clone());
+    let product = RemoteGraphBuilder::new("product", "http://127.0.0.1:9017/product".parse().expect("valid url"))
+        .client(client);
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("review"))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("{ me { username } }").await;
+    assert_eq!(response, common::me());
+    //Let connection return to pool.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let response = harness.query("{ topProducts { name } }").await;
+    assert_eq!(response, serde_json::json!({ "data": { "topProducts": [{ "name": "Trilby" }] } }));
+
+    let peers = peers.lock().unwrap().clone();
+    assert_eq!(peers.len(), 2);
+    assert_eq!(peers[0], peers[1], "subgraphs must share pooled connection");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn should_negotiate_subgraph_encoding() {
+    use graphql_router::encoding::{body_encoding, negotiate, Encoding, EncodingError};
+
+    let header = |value: &'static str| http::HeaderValue::from_static(value);
+    assert_eq!(negotiate(None), Encoding::Json);
+    assert_eq!(negotiate(Some(&header("text/html, application/json;q=0.9"))), Encoding::Json);
+    assert_eq!(body_encoding(None).expect("JSON by default"), Encoding::Json);
+    match body_encoding(Some(&header("text/plain"))) {
+        Err(EncodingError::Unsupported(content_type)) => assert_eq!(content_type, "text/plain"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    #[cfg(feature = "msgpack")]
+    {
+        assert_eq!(negotiate(Some(&header(Encoding::MessagePack.accept()))), Encoding::MessagePack);
+        assert_eq!(body_encoding(Some(&header("application/x-msgpack"))).ok(), Some(Encoding::MessagePack));
+
+        let supergraph = common::supergraph();
+        let transport = common::StubTransport::new(common::ME);
+        let user = common::remote_graph("user", transport.clone()).encoding(Encoding::MessagePack);
+        let mut harness = RouterTestHarness::builder(supergraph)
+            .subgraph(user)
+            .build()
+            .await
+            .expect("to create harness");
+
+        let response = harness.query("{ me { username } }").await;
+        assert_eq!(response, common::me());
+        let request = transport.requests().remove(0);
+        assert_eq!(request.encoding, Encoding::MessagePack);
+        let body = Encoding::MessagePack.decode::<serde_json::Value>(&request.body).expect("MessagePack body");
+        assert!(body["query"].as_str().unwrap_or_default().contains("username"), "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn should_send_requests_over_http_transport() {
+    use graphql_router::encoding::Encoding;
+    use graphql_router::remote::HttpTransport;
+
+    let redirect = |location: &'static str| {
+        move || async move {
+            let mut response = http::Response::new(hyper::Body::empty());
+            *response.status_mut() = http::StatusCode::TEMPORARY_REDIRECT;
+            response.headers_mut().insert(http::header::LOCATION, http::HeaderValue::from_static(location));
+            response
+        }
+    };
+    let app = axum::Router::new()
+        .route("/moved", axum::routing::post(redirect("/graphql")))
+        .route("/elsewhere", axum::routing::post(redirect("http://example.com/graphql")))
+        .route(
+            "/graphql",
+            axum::routing::post(|headers: http::HeaderMap| async move {
+                assert_eq!(headers[http::header::CONTENT_TYPE], "application/json");
+                assert_eq!(headers["x-subgraph"], "user");
+                axum::Json(common::me())
+            }),
+        )
+        .route(
+            "/busy",
+            axum::routing::post(|| async {
+                (http::StatusCode::SERVICE_UNAVAILABLE, [(http::header::RETRY_AFTER, "3")], "Busy")
+            }),
+        )
+        .route(
+            "/gateway",
+            axum::routing::post(|| async { (http::StatusCode::BAD_GATEWAY, "<html>Bad gateway</html>") }),
+        );
+    let server = common::spawn(|shutdown| {
+        axum::Server::bind(&([127, 0, 0, 1], 9019).into())
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(shutdown)
+    });
+
+    let transport = HttpTransport::new(graphql_router::remote::http_client());
+    let graphql = GraphqlRequest::builder().query("{ me { username } }".to_owned()).build();
+    let request = |path: &str| {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-subgraph", http::HeaderValue::from_static("user"));
+        TransportRequest {
+            service_name: "user".into(),
+            url: format!("http://127.0.0.1:9019{}", path).parse().expect("valid url"),
+            headers,
+            graphql: Arc::new(graphql.clone()),
+            body: serde_json::to_vec(&graphql).expect("Serialize request").into(),
+            encoding: Encoding::Json,
+            max_redirect_num: 1,
+        }
+    };
+
+    let response = transport.send(request("/moved")).await.expect("to follow redirect");
+    assert_eq!(response.status(), http::StatusCode::OK);
+    let body = serde_json::to_value(response.body()).expect("Serialize response");
+    assert_eq!(body, common::me());
+
+    match transport.send(request("/elsewhere")).await {
+        Err(TransportError::Failed(reason)) => assert_eq!(reason, "Redirect points to different host"),
+        result => panic!("unexpected result: {:?}", result.map(|response| response.status())),
+    }
+    match transport.send(request("/busy")).await {
+        Err(TransportError::Status { status, retry, retry_after }) => {
+            assert_eq!((status, retry, retry_after), (503, true, Some(Duration::from_secs(3))));
+        }
+        result => panic!("unexpected result: {:?}", result.map(|response| response.status())),
+    }
+    //Error page, which is not GraphQL response, is reported by its status.
+    match transport.send(request("/gateway")).await {
+        Err(TransportError::Status { status, retry, .. }) => assert_eq!((status, retry), (502, false)),
+        result => panic!("unexpected result: {:?}", result.map(|response| response.status())),
+    }
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn should_resolve_entities_from_rest_datasource() {
+    use graphql_router::rest::{RestEntity, RestGraphBuilder};
+
+    let app = axum::Router::new().route(
+        "/products/:upc/reviews",
+        axum::routing::get(
+            |axum::extract::Path(upc): axum::extract::Path<String>, headers: http::HeaderMap| async move {
+                assert_eq!(headers[http::header::ACCEPT], "application/json");
+                assert_eq!(headers["x-api-key"], "secret");
+                axum::Json(serde_json::json!({
+                    "upc": upc,
+                    "page": { "items": [{ "body": format!("Review of {}", upc) }] },
+                }))
+            },
+        ),
+    );
+    let server = common::spawn(|shutdown| {
+        axum::Server::bind(&([127, 0, 0, 1], 9020).into())
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(shutdown)
+    });
+
+    let supergraph = common::supergraph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [
+            { "__typename": "Product", "upc": "top-1", "name": "Trilby" },
+            { "__typename": "Product", "upc": "top 2", "name": "Fedora" },
+        ] }
+    }));
+    let reviews = RestEntity::new("http://127.0.0.1:9020/products/{upc}/reviews").field("reviews", "/page/items");
+    let review = RestGraphBuilder::new("review")
+        .entity("Product", reviews)
+        .header(http::HeaderName::from_static("x-api-key"), http::HeaderValue::from_static("secret"))
+        .timeout(Duration::from_secs(5));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(product)
+        .subgraph(review)
+        .subgraph(MockGraphBuilder::new("user"))
+        .build()
+        .await
+        .expect("to create harness");
+
+    //Key field is percent encoded within URL and list is taken by JSON pointer.
+    let response = harness.query("query Query { topProducts { name, reviews { body } } }").await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "topProducts": [
+            { "name": "Trilby", "reviews": [{ "body": "Review of top-1" }] },
+            { "name": "Fedora", "reviews": [{ "body": "Review of top 2" }] },
+        ] } })
+    );
+    harness.calls().assert_call_count("review", 1);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn should_proxy_requests_to_single_subgraph() {
+    use graphql_router::proxy::{Proxy, ProxyError};
+
+    let supergraph = common::supergraph();
+    match Proxy::from_schema(&supergraph) {
+        Err(ProxyError::SubgraphCount(count)) => assert_eq!(count, 3),
+        Ok(_) => panic!("proxy requires single subgraph"),
+    }
+
+    let app = axum::Router::new().route(
+        "/user",
+        axum::routing::post(|headers: http::HeaderMap, body: hyper::body::Bytes| async move {
+            let forwarded = headers.contains_key("x-client") && !headers.contains_key("proxy-authorization");
+            let mut response = http::Response::new(hyper::Body::from(body));
+            response.headers_mut().insert("x-forwarded-headers", forwarded.to_string().parse().unwrap());
+            response
+        }),
+    );
+    let server = common::spawn(|shutdown| {
+        axum::Server::bind(&([127, 0, 0, 1], 9018).into())
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(shutdown)
+    });
+
+    let proxy = Proxy::new("user", "http://127.0.0.1:9018/user".parse().expect("valid url"));
+    assert_eq!(proxy.name(), "user");
+    //Body is forwarded without being parsed, so formatting is preserved.
+    let body = "{ \"query\" :  \"{ me { username } }\" }";
+    let request = hyper::Request::post("/graphql")
+        .header("x-client", "test")
+        .header("proxy-authorization", "Basic cHJveHk6c2VjcmV0")
+        .body(hyper::Body::from(body))
+        .expect("build request");
+    let response = proxy.forward(request).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    assert_eq!(response.headers()["x-forwarded-headers"], "true");
+    let received = hyper::body::to_bytes(response.into_body()).await.expect("read body");
+    assert_eq!(&received[..], body.as_bytes());
+
+    server.stop().await;
+
+    let request = hyper::Request::post("/graphql").body(hyper::Body::from(body)).expect("build request");
+    let response = proxy.forward(request).await;
+    assert_eq!(response.status(), hyper::StatusCode::BAD_GATEWAY);
+}
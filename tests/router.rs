@@ -1,5 +1,10 @@
-use graphql_router::{GraphqlRequest, GraphqlResponse, GraphqlRouter, LocalGraphBuilder, RemoteGraphBuilder};
+use graphql_router::remote::{SubgraphTransport, TransportError, TransportFuture, TransportRequest};
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+use graphql_router::{
+    GraphqlRequest, GraphqlResponse, GraphqlRouter, LocalGraphBuilder, RemoteGraphBuilder, WarmupOperation,
+};
 
+use core::time::Duration;
 use std::sync::Arc;
 
 use async_graphql::{Interface, Context, EmptyMutation, EmptySubscription, Object, SimpleObject, ID};
@@ -7,6 +12,8 @@ use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::Extension;
 use hyper::Uri;
 
+mod common;
+
 const API_PORT: u16 = 9000;
 
 mod user {
@@ -383,3 +390,464 @@ async fn should_handle_local_and_remote_graphql() {
         .expect("Successfully finish server task")
         .expect("Successfully finish server");
 }
+
+#[tokio::test]
+async fn should_handle_spawned_federation() {
+    use graphql_router::testing::{spawn_federation, FederatedSubgraph};
+
+    let supergraph = graphql_router::Schema::read("tests/supergraph.graphql").expect("To read supergraph");
+    let mut federation = spawn_federation(
+        Arc::new(supergraph),
+        vec![
+            FederatedSubgraph::new("user", user::schema()),
+            FederatedSubgraph::new("product", product::schema()),
+            FederatedSubgraph::new("review", review::schema()),
+        ],
+    )
+    .await
+    .expect("to spawn federation");
+
+    let req = GraphqlRequest::builder()
+        .query(r#"query Query { topProducts { name, reviews { author { username } } } }"#.to_owned())
+        .build();
+    let (parts, _) = http::Request::post("/").body(()).expect("build request").into_parts();
+    let response = federation
+        .router()
+        .handle(graphql_router::from_request_parts(parts, req))
+        .await
+        .expect("to handle request");
+    let response = GraphqlResponse::try_from(response.response.into_body()).expect("GraphQL response");
+    let response = serde_json::to_string(&response).expect("Serialize response");
+    assert_eq!(
+        response,
+        r#"{"data":{"topProducts":[{"name":"Trilby","reviews":[{"author":{"username":"Me"}},{"author":{"username":"Me"}},{"author":{"username":"User ID(\"7777\")"}}]},{"name":"Fedora","reviews":[]},{"name":"Boater","reviews":[]}]}}"#
+    );
+
+    federation.shutdown().await;
+}
+
+#[tokio::test]
+async fn should_cache_documents_of_local_subgraphs() {
+    let supergraph = graphql_router::Schema::read("tests/supergraph.graphql").expect("To read supergraph");
+    //Single cached document is evicted by each other operation.
+    let mut user = LocalGraphBuilder::new("user", user::schema());
+    user.document_cache(1);
+    let mut product = LocalGraphBuilder::new("product", product::schema());
+    product.document_cache(1);
+    let mut review = LocalGraphBuilder::new("review", review::schema());
+    review.document_cache(0);
+    let mut router = GraphqlRouter::build(Arc::new(supergraph))
+        .add_subgraph(user)
+        .add_subgraph(product)
+        .add_subgraph(review)
+        .finish()
+        .await
+        .expect("to create router");
+
+    let operations = [
+        (r#"query Me { me { username } }"#, r#"{"data":{"me":{"username":"Me"}}}"#),
+        (
+            r#"query Query { topProducts { name, reviews { author { username } } } }"#,
+            concat!(
+                r#"{"data":{"topProducts":[{"name":"Trilby","reviews":[{"author":{"username":"Me"}},"#,
+                r#"{"author":{"username":"Me"}},{"author":{"username":"User ID(\"7777\")"}}]},"#,
+                r#"{"name":"Fedora","reviews":[]},{"name":"Boater","reviews":[]}]}}"#
+            ),
+        ),
+    ];
+    for _ in 0..2 {
+        for (query, expected) in operations.iter() {
+            let req = GraphqlRequest::builder().query((*query).to_owned()).build();
+            let (parts, _) = http::Request::post("/").body(()).expect("build request").into_parts();
+            let response = router
+                .handle(graphql_router::from_request_parts(parts, req))
+                .await
+                .expect("to handle request");
+            let response = GraphqlResponse::try_from(response.response.into_body()).expect("GraphQL response");
+            assert_eq!(serde_json::to_string(&response).expect("Serialize response"), *expected);
+        }
+    }
+}
+
+#[test]
+fn should_reuse_serialization_buffers() {
+    let pool = graphql_router::pool::BufferPool::new();
+    let value = serde_json::json!({"data": {"me": {"id": "1"}}});
+
+    let bytes = pool.serialize(&value).expect("to serialize");
+    assert_eq!(bytes, serde_json::to_vec(&value).expect("to serialize"));
+    drop(bytes);
+    let bytes = pool.serialize(&value).expect("to serialize");
+    assert_eq!(bytes, serde_json::to_vec(&value).expect("to serialize"));
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.misses, 1);
+    assert_eq!(metrics.hits, 1);
+    assert_eq!(metrics.dropped, 0);
+    assert_eq!(metrics.pooled, 1);
+
+    //Pooled buffer keeps its allocation, even while serialized bytes are alive.
+    let capacity = metrics.pooled_capacity;
+    assert!(capacity >= 4 * 1024);
+    let kept = (0..4).map(|_| pool.serialize(&value).expect("to serialize")).collect::<Vec<_>>();
+    let metrics = pool.metrics();
+    assert_eq!(metrics.misses, 1);
+    assert_eq!(metrics.hits, 5);
+    assert_eq!(metrics.pooled_capacity, capacity);
+    drop(kept);
+}
+
+#[tokio::test]
+async fn should_check_request_before_parsing_variables() {
+    let body = r#"{"query":"query Me { me { id } }","operationName":"Me","variables":{"id":"1"}}"#;
+    let check = |_: &http::request::Parts, head: &graphql_router::RequestHead<'_>| {
+        let variables_len = head.variables.map_or(0, |variables| variables.get().len());
+        match head.operation_name.as_deref() {
+            Some("Me") if variables_len <= 8 => Ok(()),
+            _ => Err("Rejected".to_owned()),
+        }
+    };
+
+    let req = http::Request::post("/").body(hyper::Body::from(body)).expect("build request");
+    let error = graphql_router::parse_http_request_with(req, check).await.expect_err("to reject");
+    assert_eq!(error.to_string(), "Rejected");
+
+    let body = r#"{"query":"query Me { me { id } }","operationName":"Me","variables":{}}"#;
+    let req = http::Request::post("/").body(hyper::Body::from(body)).expect("build request");
+    let req = graphql_router::parse_http_request_with(req, check).await.expect("to accept");
+    assert_eq!(req.originating_request.body().operation_name.as_deref(), Some("Me"));
+}
+
+#[tokio::test]
+async fn should_parse_request_body_regardless_of_json_backend() {
+    let body = concat!(
+        r#"{"query":"query Me($name: String) { me { id } }","#,
+        r#""variables":{"name":"Caf\u00e9 \"Me\"","ids":[1,2.5]}}"#
+    );
+    let req = http::Request::post("/").body(hyper::Body::from(body)).expect("build request");
+    let req = graphql_router::parse_http_request(req).await.expect("to parse");
+    let variables = serde_json::to_value(&req.originating_request.body().variables).expect("Serialize variables");
+    assert_eq!(variables, serde_json::json!({ "name": "Café \"Me\"", "ids": [1, 2.5] }));
+
+    //Errors are reported by serde_json, even if other parser is used.
+    let req = http::Request::post("/").body(hyper::Body::from(r#"{"query":"#)).expect("build request");
+    match graphql_router::parse_http_request(req).await {
+        Err(graphql_router::ParseHttpError::Invalid(error)) => assert!(error.is_eof(), "{}", error),
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("invalid body must be rejected"),
+    }
+}
+
+#[test]
+fn should_strip_field_suggestions() {
+    use graphql_router::plugins::strip_suggestions;
+
+    let message = r#"Cannot query field "nam" on type "User". Did you mean "name" or "username"?"#;
+    assert_eq!(
+        strip_suggestions(message).as_deref(),
+        Some(r#"Cannot query field "nam" on type "User"."#)
+    );
+    assert_eq!(strip_suggestions("Unknown operation"), None);
+}
+
+#[test]
+fn should_filter_clients_behind_trusted_proxy() {
+    use graphql_router::server::IpFilter;
+
+    let filter = IpFilter::new()
+        .allow("192.168.0.0/16".parse().expect("valid net"))
+        .trusted_proxy("10.0.0.0/8".parse().expect("valid net"));
+    let proxy = "10.0.0.1:443".parse().expect("valid addr");
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-forwarded-for", "1.1.1.1, 192.168.1.1, 10.0.0.2".parse().expect("valid header"));
+    let client = filter.client_addr(proxy, &headers);
+    assert_eq!(client, "192.168.1.1".parse::<std::net::IpAddr>().expect("valid addr"));
+    assert!(filter.is_allowed(&client));
+
+    headers.insert("forwarded", r#"for="[2001:db8::1]:80""#.parse().expect("valid header"));
+    let client = filter.client_addr(proxy, &headers);
+    assert_eq!(client, "2001:db8::1".parse::<std::net::IpAddr>().expect("valid addr"));
+    assert!(!filter.is_allowed(&client));
+
+    let direct: std::net::SocketAddr = "172.16.0.1:1234".parse().expect("valid addr");
+    assert_eq!(filter.client_addr(direct, &headers), direct.ip());
+}
+
+#[tokio::test]
+async fn should_format_router_errors() {
+    use graphql_router::error::RouterError;
+    use graphql_router::plugins::RequestLimitsConfig;
+    fn format(error: &RouterError, _: &apollo_router_core::Context) -> graphql_router::GraphqlError {
+        serde_json::from_value(serde_json::json!({
+            "message": format!("Rejected: {}", error.message),
+            "extensions": { "code": error.code, "status": error.status },
+        }))
+        .expect("valid error")
+    }
+
+    let supergraph = common::supergraph();
+    let limits = RequestLimitsConfig {
+        max_query_bytes: Some(8),
+        ..RequestLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(MockGraphBuilder::new("product"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.request_limits(limits).error_formatter(format))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Me { me { username } }").await;
+    let error = &response["errors"][0];
+    assert!(error["message"].as_str().expect("message").starts_with("Rejected: "));
+    assert_eq!(error["extensions"]["status"], 413);
+    assert_eq!(error["extensions"]["code"], "QUERY_TOO_LARGE");
+}
+
+#[test]
+fn should_create_timed_handler_outside_runtime() {
+    struct Hanging;
+
+    impl SubgraphTransport for Hanging {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            Box::pin(core::future::pending())
+        }
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("to create runtime");
+    let supergraph = common::supergraph();
+    let user = common::remote_graph("user", Arc::new(Hanging));
+    let harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(|builder| builder.timeout(Duration::from_millis(50)));
+    let mut harness = runtime.block_on(harness.build()).expect("to create harness");
+
+    let handler = harness.router().handle(common::request("{ me { username } }", &[]));
+    let response = runtime.block_on(handler).expect("to handle request");
+    assert_eq!(response.response.status(), http::StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test]
+async fn should_apply_response_hook() {
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").on_query("me", serde_json::json!({
+        "data": { "me": { "id": "1234", "username": "Me" } }
+    }));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(MockGraphBuilder::new("product"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| {
+            builder.response_hook(|response, _| {
+                let mut data = serde_json::to_value(&response.data).expect("valid data");
+                if let Some(me) = data["me"].as_object_mut() {
+                    me.remove("id");
+                    me.insert("masked".to_owned(), true.into());
+                }
+                response.data = serde_json::from_value(data).expect("valid data");
+            })
+        })
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Me { me { username id } }").await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "me": { "username": "Me", "masked": true } } })
+    );
+}
+
+#[tokio::test]
+async fn should_wrap_query_planner() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    let supergraph = common::supergraph();
+    let product = MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+        "data": { "topProducts": [{ "name": "Fedora" }] }
+    }));
+    let planned = Arc::new(AtomicUsize::new(0));
+    let counter = planned.clone();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(product)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(move |builder| {
+            builder.query_planner(move |service| {
+                let counter = counter.clone();
+                service
+                    .map_request(move |req| {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                        req
+                    })
+                    .boxed()
+            })
+        })
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name } }").await;
+    assert_eq!(response, serde_json::json!({ "data": { "topProducts": [{ "name": "Fedora" }] } }));
+    assert_eq!(planned.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn should_save_and_load_plan_manifest() {
+    use graphql_router::PlanManifest;
+
+    let supergraph = common::supergraph();
+    let path = std::env::temp_dir().join(format!("graphql-router-plan-manifest-{}.json", std::process::id()));
+    let product = || {
+        MockGraphBuilder::new("product").on_query("topProducts", serde_json::json!({
+            "data": { "topProducts": [{ "name": "Fedora" }] }
+        }))
+    };
+
+    let manifest = PlanManifest::new(16);
+    let mut harness = RouterTestHarness::builder(supergraph.clone())
+        .subgraph(product())
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.with_plan_manifest(manifest.clone()))
+        .build()
+        .await
+        .expect("to create harness");
+    harness.query("query Top { topProducts { name } }").await;
+    harness.query("query Top { topProducts { name } }").await;
+    assert_eq!(manifest.len(), 1);
+    manifest.save(&path).expect("to save manifest");
+
+    let loaded = PlanManifest::load(&path, 16).expect("to load manifest");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded.operations(), manifest.operations());
+    assert_eq!(loaded.operations()[0].hits, 2);
+
+    let harness = RouterTestHarness::builder(supergraph)
+        .subgraph(product())
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .configure(|builder| builder.with_plan_manifest(loaded.clone()))
+        .build()
+        .await
+        .expect("to create harness");
+    //Operations are only planned on startup, not executed nor counted.
+    harness.calls().assert_not_called("product");
+    assert_eq!(loaded.operations()[0].hits, 2);
+}
+
+#[tokio::test]
+async fn should_execute_warmup_operations_except_mutations() {
+    let supergraph = common::schema("mutation_supergraph");
+    let user = MockGraphBuilder::new("user")
+        .on_query("me", common::me())
+        .on_query("setUsername", serde_json::json!({ "data": { "setUsername": "hatter" } }));
+    let operations = vec![
+        WarmupOperation::new("{ me { username } }"),
+        WarmupOperation::new("mutation { setUsername(username: \"hatter\") }"),
+        WarmupOperation::new("query Me { me { username } } mutation Rename { setUsername(username: \"hatter\") }")
+            .operation_name("Rename"),
+    ];
+    assert!(!operations[0].is_mutation());
+    assert!(operations[1].is_mutation());
+    assert!(operations[2].is_mutation());
+    let harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| operations.into_iter().fold(builder, |builder, operation| builder.warmup(operation)))
+        .build()
+        .await
+        .expect("to create harness");
+
+    harness.calls().assert_call_count("user", 1);
+    let call = harness.calls().calls_to("user").remove(0);
+    assert!(!call.query.as_deref().unwrap_or_default().contains("setUsername"));
+}
+
+#[tokio::test]
+async fn should_apply_recommended_defaults() {
+    use graphql_router::{RECOMMENDED_RETRY_BACKOFF, RECOMMENDED_SUBGRAPH_TIMEOUT};
+
+    struct Unavailable;
+
+    impl SubgraphTransport for Unavailable {
+        fn send(&self, _: TransportRequest) -> TransportFuture {
+            Box::pin(async { Err(TransportError::Failed("Connection refused".to_owned())) })
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let product = common::remote_graph("product", Arc::new(Unavailable));
+    let review = common::remote_graph("review", Arc::new(Unavailable))
+        .timeout(Duration::from_secs(1));
+    let (product_settings, review_settings) = (product.settings(), review.settings());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(product)
+        .subgraph(review)
+        .configure(|builder| builder.with_recommended_defaults())
+        .build()
+        .await
+        .expect("to create harness");
+
+    let plugins = harness.router().plugins().await;
+    let plugins = plugins.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(plugins, ["metrics", "propagate_headers", "request_limits"]);
+
+    //Subgraph's own settings take precedence over defaults.
+    let settings = product_settings.get();
+    assert_eq!(settings.timeout, Some(RECOMMENDED_SUBGRAPH_TIMEOUT));
+    assert_eq!(settings.retry_backoff, Some(RECOMMENDED_RETRY_BACKOFF));
+    let settings = review_settings.get();
+    assert_eq!(settings.timeout, Some(Duration::from_secs(1)));
+    assert_eq!(settings.retry_backoff, Some(RECOMMENDED_RETRY_BACKOFF));
+
+    let response = harness.query("{ me { username } }").await;
+    assert_eq!(response, common::me());
+    let query = format!("{{ me {{ username }} }} # {}", "x".repeat(64 * 1024));
+    let response = harness.query(&query).await;
+    assert_eq!(response["errors"][0]["extensions"]["code"], "QUERY_TOO_LARGE");
+
+    let metrics = harness.router().metrics().expect("metrics to be collected").router();
+    assert_eq!(metrics.requests, 2);
+    assert_eq!(metrics.errors, 1);
+}
+
+#[tokio::test]
+async fn should_handle_with_service_of_pinned_future() {
+    use graphql_router::GraphqlRouterHandler;
+
+    let router = GraphqlRouter::build(common::supergraph())
+        .add_subgraph(common::user_graph())
+        .finish()
+        .await
+        .expect("to create router");
+    let inner = router.service();
+    //Future of async block is not Unpin, so handler must poll it pinned in place.
+    let service = tower::service_fn(move |req| {
+        let inner = inner.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            tower::ServiceExt::oneshot(inner, req).await
+        }
+    });
+
+    let request = common::request("{ me { username } }", &[]);
+    let response = GraphqlRouterHandler::new(service.clone(), request)
+        .with_timeout(Duration::from_millis(10))
+        .await
+        .expect("to handle request");
+    assert_eq!(response.response.status(), http::StatusCode::GATEWAY_TIMEOUT);
+
+    let request = common::request("{ me { username } }", &[]);
+    let response = GraphqlRouterHandler::new(service, request).await.expect("to handle request");
+    let body = GraphqlResponse::try_from(response.response.into_body()).expect("GraphQL response");
+    assert_eq!(serde_json::to_value(&body).expect("Serialize response"), common::me());
+}
@@ -0,0 +1,442 @@
+use graphql_router::server::ServerBuilder;
+use graphql_router::testing::{MockGraphBuilder, RouterTestHarness};
+
+use core::time::Duration;
+
+mod common;
+
+//User subgraph, resolving either username or id.
+fn user_graph() -> MockGraphBuilder {
+    MockGraphBuilder::new("user")
+        .on_query("username", common::me())
+        .on_query("id", serde_json::json!({ "data": { "me": { "id": "1" } } }))
+}
+
+#[tokio::test]
+async fn should_execute_batch_in_order() {
+
+    async fn post(body: serde_json::Value) -> (hyper::StatusCode, serde_json::Value) {
+        let response = common::post_json("http://127.0.0.1:9013/", &body).await;
+        (response.status(), common::json_body(response).await)
+    }
+
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user_graph())
+        .build()
+        .await
+        .expect("to create harness");
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9013).into())
+        .batching(3)
+        .request_check(|_, head| match head.operation_name.as_deref() {
+            Some("Forbidden") => Err("Operation is forbidden".to_owned()),
+            _ => Ok(()),
+        });
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| server.serve(router, shutdown));
+
+    let (status, body) = post(serde_json::json!([
+        { "query": "query Name { me { username } }", "operationName": "Name" },
+        { "query": "query Forbidden { me { username } }", "operationName": "Forbidden" },
+        { "query": "query Id { me { id } }", "operationName": "Id" },
+    ]))
+    .await;
+    assert_eq!(status, hyper::StatusCode::OK);
+    assert_eq!(
+        body,
+        serde_json::json!([
+            { "data": { "me": { "username": "Me" } } },
+            { "errors": [{ "message": "Operation is forbidden" }] },
+            { "data": { "me": { "id": "1" } } },
+        ])
+    );
+    harness.calls().assert_call_count("user", 2);
+
+    let operation = serde_json::json!({ "query": "{ me { id } }" });
+    let (status, body) = post(serde_json::json!([operation, operation, operation, operation])).await;
+    assert_eq!(status, hyper::StatusCode::BAD_REQUEST);
+    assert_eq!(body["errors"][0]["message"], "Batch of 4 operations exceeds limit of 3");
+    harness.calls().assert_call_count("user", 2);
+}
+
+#[tokio::test]
+async fn should_negotiate_response_compression() {
+    use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, ETAG, IF_NONE_MATCH};
+
+    async fn get(query: &str, headers: &[(http::header::HeaderName, &str)]) -> hyper::Response<hyper::Body> {
+        let query = form_urlencoded::Serializer::new(String::new()).append_pair("query", query).finish();
+        common::send(|| {
+            let mut request = hyper::Request::get(format!("http://127.0.0.1:9014/?{}", query));
+            for (name, value) in headers {
+                request = request.header(name, *value);
+            }
+            request.body(hyper::Body::empty()).expect("build request")
+        })
+        .await
+    }
+
+    fn header<'a>(response: &'a hyper::Response<hyper::Body>, name: &http::header::HeaderName) -> Option<&'a str> {
+        response.headers().get(name).and_then(|value| value.to_str().ok())
+    }
+
+    let supergraph = common::supergraph();
+    let username = "Mad Hatter, who is quite fond of riddles without answers";
+    let user = MockGraphBuilder::new("user")
+        .on_query("username", serde_json::json!({ "data": { "me": { "username": username } } }))
+        .on_query("id", serde_json::json!({ "data": { "me": { "id": "1" } } }));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9014).into()).etag().compression(48);
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| server.serve(router, shutdown));
+
+    const QUERY: &str = "{ me { username } }";
+    let response = get(QUERY, &[(ACCEPT_ENCODING, "gzip;q=0.5, br;q=0.8")]).await;
+    assert_eq!(header(&response, &CONTENT_ENCODING), Some("br"));
+    let br = header(&response, &ETAG).expect("ETag").to_owned();
+    let response = get(QUERY, &[(ACCEPT_ENCODING, "br;q=0.5, gzip")]).await;
+    assert_eq!(header(&response, &CONTENT_ENCODING), Some("gzip"));
+    let gzip = header(&response, &ETAG).expect("ETag").to_owned();
+    let response = get(QUERY, &[(ACCEPT_ENCODING, "br;q=0, gzip;q=0")]).await;
+    assert_eq!(header(&response, &CONTENT_ENCODING), None);
+    let identity = header(&response, &ETAG).expect("ETag").to_owned();
+    assert_ne!(br, gzip);
+    assert_ne!(br, identity);
+    assert_ne!(gzip, identity);
+
+    let response = get(QUERY, &[(ACCEPT_ENCODING, "gzip"), (IF_NONE_MATCH, &gzip)]).await;
+    assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+    assert_eq!(header(&response, &ETAG), Some(gzip.as_str()));
+
+    //Response is smaller than threshold.
+    let response = get("{ me { id } }", &[(ACCEPT_ENCODING, "br, gzip")]).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    assert_eq!(header(&response, &CONTENT_ENCODING), None);
+}
+
+#[tokio::test]
+async fn should_execute_operations_over_websocket() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
+
+    type Socket = WebSocketStream<hyper::upgrade::Upgraded>;
+
+    async fn handshake(protocol: &str) -> Result<Socket, hyper::StatusCode> {
+        let response = common::send(|| {
+            hyper::Request::get("http://127.0.0.1:9021/ws")
+                .header(http::header::CONNECTION, "Upgrade")
+                .header(http::header::UPGRADE, "websocket")
+                .header(http::header::SEC_WEBSOCKET_VERSION, "13")
+                .header(http::header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+                .header(http::header::SEC_WEBSOCKET_PROTOCOL, protocol)
+                .body(hyper::Body::empty())
+                .expect("build request")
+        })
+        .await;
+        if response.status() != hyper::StatusCode::SWITCHING_PROTOCOLS {
+            return Err(response.status());
+        }
+        let headers = response.headers();
+        assert_eq!(headers[http::header::SEC_WEBSOCKET_ACCEPT], "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(headers[http::header::SEC_WEBSOCKET_PROTOCOL], "graphql-transport-ws");
+        let upgraded = hyper::upgrade::on(response).await.expect("to upgrade");
+        Ok(WebSocketStream::from_raw_socket(upgraded, Role::Client, None).await)
+    }
+
+    async fn send(socket: &mut Socket, message: serde_json::Value) {
+        socket.send(Message::Text(message.to_string())).await.expect("to send message");
+    }
+
+    async fn receive(socket: &mut Socket) -> Message {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await;
+        message.expect("to receive in time").expect("open socket").expect("valid message")
+    }
+
+    async fn receive_json(socket: &mut Socket) -> serde_json::Value {
+        match receive(socket).await {
+            Message::Text(text) => serde_json::from_str(&text).expect("JSON message"),
+            message => panic!("unexpected message: {:?}", message),
+        }
+    }
+
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user_graph())
+        .build()
+        .await
+        .expect("to create harness");
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9021).into()).websocket("/ws");
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| server.serve(router, shutdown));
+
+    assert_eq!(handshake("graphql-ws").await.err(), Some(hyper::StatusCode::BAD_REQUEST));
+
+    let mut socket = handshake("graphql-transport-ws").await.expect("to connect");
+    send(&mut socket, serde_json::json!({ "type": "connection_init" })).await;
+    assert_eq!(receive_json(&mut socket).await, serde_json::json!({ "type": "connection_ack" }));
+    send(&mut socket, serde_json::json!({ "type": "ping" })).await;
+    assert_eq!(receive_json(&mut socket).await, serde_json::json!({ "type": "pong" }));
+
+    let subscribe = |id: &str, query: &str| {
+        serde_json::json!({ "type": "subscribe", "id": id, "payload": { "query": query } })
+    };
+    send(&mut socket, subscribe("1", "{ me { username } }")).await;
+    assert_eq!(
+        receive_json(&mut socket).await,
+        serde_json::json!({ "type": "next", "id": "1", "payload": { "data": { "me": { "username": "Me" } } } })
+    );
+    assert_eq!(receive_json(&mut socket).await, serde_json::json!({ "type": "complete", "id": "1" }));
+    //Id of completed operation can be re-used.
+    send(&mut socket, subscribe("1", "{ me { id } }")).await;
+    assert_eq!(
+        receive_json(&mut socket).await,
+        serde_json::json!({ "type": "next", "id": "1", "payload": { "data": { "me": { "id": "1" } } } })
+    );
+    assert_eq!(receive_json(&mut socket).await, serde_json::json!({ "type": "complete", "id": "1" }));
+    harness.calls().assert_call_count("user", 2);
+
+    //Operations are accepted only after connection is acknowledged.
+    let mut socket = handshake("graphql-transport-ws").await.expect("to connect");
+    send(&mut socket, subscribe("1", "{ me { username } }")).await;
+    match receive(&mut socket).await {
+        Message::Close(Some(frame)) => assert_eq!(u16::from(frame.code), 4401),
+        message => panic!("unexpected message: {:?}", message),
+    }
+    harness.calls().assert_call_count("user", 2);
+}
+
+#[tokio::test]
+async fn should_register_persisted_queries_via_endpoint() {
+    use graphql_router::plugins::PersistedQueries;
+
+    async fn post(path: &str, token: Option<&str>, body: serde_json::Value) -> (hyper::StatusCode, serde_json::Value) {
+        let body = serde_json::to_vec(&body).expect("Serialize body");
+        let response = common::send(|| {
+            let mut request = hyper::Request::post(format!("http://127.0.0.1:9022{}", path))
+                .header(http::header::CONTENT_TYPE, "application/json");
+            if let Some(token) = token {
+                request = request.header(http::header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            request.body(hyper::Body::from(body.clone())).expect("build request")
+        })
+        .await;
+        (response.status(), common::json_body(response).await)
+    }
+
+    let supergraph = common::supergraph();
+    let user = user_graph();
+    let persisted = PersistedQueries::new(true);
+    persisted.register("me-id".to_owned(), "{ me { id } }".to_owned());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .configure(move |builder| builder.with_persisted_queries(persisted))
+        .build()
+        .await
+        .expect("to create harness");
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9022).into())
+        .persisted_query_registration("/persisted", "secret".to_owned());
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| server.serve(router, shutdown));
+    let by_id = |id: &str| {
+        serde_json::json!({ "extensions": { "persistedQuery": { "version": 1, "sha256Hash": id } } })
+    };
+
+    let (_, body) = post("/", None, serde_json::json!({ "query": "{ me { username } }" })).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "OPERATION_NOT_IN_SAFELIST");
+    let (_, body) = post("/", None, by_id("me-username")).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+
+    let operations = serde_json::json!({ "me-username": "{ me { username } }", "me-id": "{ me { id } }" });
+    let (status, _) = post("/persisted", None, operations.clone()).await;
+    assert_eq!(status, hyper::StatusCode::UNAUTHORIZED);
+    let (status, _) = post("/persisted", Some("secreT"), operations.clone()).await;
+    assert_eq!(status, hyper::StatusCode::UNAUTHORIZED);
+    assert_eq!(harness.router().persisted_queries().expect("persisted queries").len(), 1);
+    let (status, body) = post("/persisted", Some("secret"), operations).await;
+    assert_eq!(status, hyper::StatusCode::OK);
+    assert_eq!(body, serde_json::json!({ "registered": 2, "total": 2 }));
+
+    let (status, body) = post("/", None, by_id("me-username")).await;
+    assert_eq!(status, hyper::StatusCode::OK);
+    assert_eq!(body, common::me());
+    let (_, body) = post("/", None, serde_json::json!({ "query": "{ me { username } }" })).await;
+    assert_eq!(body, common::me());
+
+    //Conflicting id leaves the whole registration out.
+    let operations = serde_json::json!({ "me-both": "{ me { id username } }", "me-id": "{ me { username } }" });
+    let (status, body) = post("/persisted", Some("secret"), operations).await;
+    assert_eq!(status, hyper::StatusCode::CONFLICT);
+    assert_eq!(body["errors"][0]["message"], "Operation ids are already registered: me-id");
+    let persisted = harness.router().persisted_queries().expect("persisted queries");
+    assert_eq!(persisted.len(), 2);
+    assert_eq!(persisted.get("me-id").as_deref(), Some("{ me { id } }"));
+}
+
+#[tokio::test]
+async fn should_execute_get_requests_with_etag() {
+    use http::header::{ETAG, IF_NONE_MATCH};
+
+    async fn get(params: &[(&str, &str)], if_none_match: Option<&str>) -> hyper::Response<hyper::Body> {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in params {
+            query.append_pair(key, value);
+        }
+        let query = query.finish();
+        common::send(|| {
+            let mut request = hyper::Request::get(format!("http://127.0.0.1:9023/?{}", query));
+            if let Some(if_none_match) = if_none_match {
+                request = request.header(IF_NONE_MATCH, if_none_match);
+            }
+            request.body(hyper::Body::empty()).expect("build request")
+        })
+        .await
+    }
+
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").on_query("username", common::me());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9023).into()).etag();
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| server.serve(router, shutdown));
+
+    let params = [
+        ("query", "query Id { me { id } } query Name { me { username } }"),
+        ("operationName", "Name"),
+        ("variables", "{}"),
+    ];
+    let response = get(&params, None).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let etag = response.headers().get(ETAG).expect("ETag").to_str().expect("valid ETag").to_owned();
+    assert_eq!(common::json_body(response).await, common::me());
+
+    //Operation is executed, but its response is not sent again.
+    let response = get(&params, Some(&etag)).await;
+    assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[ETAG], etag.as_str());
+    let response = get(&params, Some("\"other\", *")).await;
+    assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+    harness.calls().assert_call_count("user", 3);
+
+    let response = get(&[("query", "{ me { nickname } }")], None).await;
+    assert!(response.headers().get(ETAG).is_none(), "response with errors must not be tagged");
+    assert!(!common::json_body(response).await["errors"].as_array().expect("errors").is_empty());
+
+    let response = get(&[("query", "mutation { me { id } }")], None).await;
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    assert_eq!(common::json_body(response).await["errors"][0]["message"], "Mutations are not allowed over GET");
+    let response = get(&[("query", "{ me { username } }"), ("variables", "{")], None).await;
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    harness.calls().assert_call_count("user", 3);
+}
+
+#[tokio::test]
+async fn should_set_security_headers() {
+    use graphql_router::server::SecurityHeaders;
+    use http::header::{
+        CACHE_CONTROL, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    };
+
+    async fn post(body: serde_json::Value) -> hyper::Response<hyper::Body> {
+        common::post_json("http://127.0.0.1:9024/", &body).await
+    }
+
+    let supergraph = common::schema("mutation_supergraph");
+    let user = MockGraphBuilder::new("user")
+        .on_query("setUsername", serde_json::json!({ "data": { "setUsername": "hatter" } }))
+        .on_query("me", common::me());
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .build()
+        .await
+        .expect("to create harness");
+    let headers = SecurityHeaders::new()
+        .hsts(600)
+        .without(&X_FRAME_OPTIONS)
+        .header(REFERRER_POLICY, http::HeaderValue::from_static("same-origin"));
+    let server = ServerBuilder::new(([127, 0, 0, 1], 9024).into()).security_headers(headers);
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| server.serve(router, shutdown));
+
+    let response = post(serde_json::json!({ "query": "{ me { username } }" })).await;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[REFERRER_POLICY], "same-origin");
+    assert_eq!(headers[STRICT_TRANSPORT_SECURITY], "max-age=600");
+    assert!(headers.get(X_FRAME_OPTIONS).is_none());
+    assert!(headers.get(CACHE_CONTROL).is_none());
+
+    let response = post(serde_json::json!({ "query": "mutation { setUsername(username: \"hatter\") }" })).await;
+    assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+    assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+    //Selected operation decides, whether response is of mutation.
+    let response = post(serde_json::json!({
+        "query": "query Me { me { username } } mutation Rename { setUsername(username: \"hatter\") }",
+        "operationName": "Me",
+    }))
+    .await;
+    assert!(response.headers().get(CACHE_CONTROL).is_none());
+    harness.calls().assert_call_count("user", 2);
+
+    //Rejected requests have them too.
+    let response = post(serde_json::json!([])).await;
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+}
+
+#[tokio::test]
+async fn should_check_router_limits_before_parsing_variables() {
+    use graphql_router::plugins::{PersistedQueries, RequestLimitsConfig};
+
+    async fn post(body: serde_json::Value) -> (hyper::StatusCode, serde_json::Value) {
+        let response = common::post_json("http://127.0.0.1:9025/", &body).await;
+        (response.status(), common::json_body(response).await)
+    }
+
+    let persisted = PersistedQueries::new(true);
+    persisted.register("me-username".to_owned(), "{ me { username } }".to_owned());
+    let limits = RequestLimitsConfig {
+        max_query_bytes: Some(32),
+        ..RequestLimitsConfig::default()
+    };
+    let mut harness = RouterTestHarness::builder(common::supergraph())
+        .subgraph(user_graph())
+        .configure(move |builder| builder.with_persisted_queries(persisted).request_limits(limits))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let head = serde_json::from_str::<graphql_router::RequestHead>(r#"{"query":"{ me { id } }"}"#).expect("head");
+    let response = harness.router().check_request_head(&head).expect_err("not in safelist");
+    assert_eq!(response.response.status(), hyper::StatusCode::FORBIDDEN);
+    let head = serde_json::from_str::<graphql_router::RequestHead>(r#"{"query":"{ me { username } }"}"#).expect("head");
+    harness.router().check_request_head(&head).expect("to pass checks");
+
+    let router = harness.router().clone();
+    let _server = common::spawn(|shutdown| ServerBuilder::new(([127, 0, 0, 1], 9025).into()).serve(router, shutdown));
+
+    //Variables are invalid, so request is rejected before they are parsed.
+    let query = format!("{{ me {{ {} }} }}", "username ".repeat(8));
+    let (status, body) = post(serde_json::json!({ "query": query, "variables": [1] })).await;
+    assert_eq!(status, hyper::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "QUERY_TOO_LARGE");
+    let (status, body) = post(serde_json::json!({ "query": "{ me { id } }", "variables": [1] })).await;
+    assert_eq!(status, hyper::StatusCode::FORBIDDEN);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "OPERATION_NOT_IN_SAFELIST");
+
+    let (status, _) = post(serde_json::json!({ "query": "{ me { username } }", "variables": [1] })).await;
+    assert_eq!(status, hyper::StatusCode::BAD_REQUEST);
+    let (status, body) = post(serde_json::json!({ "query": "{ me { username } }", "variables": null })).await;
+    assert_eq!(status, hyper::StatusCode::OK);
+    assert_eq!(body, common::me());
+    harness.calls().assert_call_count("user", 1);
+}
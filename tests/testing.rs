@@ -0,0 +1,170 @@
+use graphql_router::testing::{
+    GoldenError, GoldenSuite, MockGraphBuilder, RecordingGraphBuilder, RouterTestHarness, TestClock,
+};
+use graphql_router::time::{Clock, RequestIdGenerator, SequentialIds};
+use graphql_router::{GraphqlRequest, GraphqlRouter, WarmupOperation};
+
+use core::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn should_handle_mock_subgraphs() {
+    let supergraph = common::supergraph();
+    let user = common::user_graph();
+    let product = common::product_graph();
+    let review = common::review_graph();
+
+    let mut router = GraphqlRouter::build(supergraph)
+        .add_subgraph(user)
+        .add_subgraph(product)
+        .add_subgraph(review)
+        .finish()
+        .await
+        .expect("to create router");
+
+    let request = GraphqlRequest::builder()
+        .query("query Query { topProducts { name, reviews { body } } }".to_owned())
+        .build();
+    let response = common::execute(&mut router, request).await;
+
+    assert_eq!(
+        response,
+        r#"{"data":{"topProducts":[{"name":"Trilby","reviews":[{"body":"Great hat"}]}]}}"#
+    );
+}
+
+#[tokio::test]
+async fn should_replay_recorded_subgraph() {
+    let path = std::env::temp_dir().join(format!("graphql-router-recording-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let query = "query Query { me { username } }";
+
+    let user = common::user_graph();
+    let user = RecordingGraphBuilder::new(user, &path).expect("to open recording");
+    let supergraph = common::supergraph();
+    let mut router = GraphqlRouter::build(supergraph.clone())
+        .add_subgraph(user)
+        .finish()
+        .await
+        .expect("to create router");
+    let recorded = common::execute(&mut router, GraphqlRequest::builder().query(query.to_owned()).build()).await;
+
+    let user = MockGraphBuilder::from_recording("user", &path).expect("to read recording");
+    let mut router = GraphqlRouter::build(supergraph)
+        .add_subgraph(user)
+        .finish()
+        .await
+        .expect("to create router");
+    let replayed = common::execute(&mut router, GraphqlRequest::builder().query(query.to_owned()).build()).await;
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(recorded, r#"{"data":{"me":{"username":"Me"}}}"#);
+    assert_eq!(replayed, recorded);
+}
+
+#[tokio::test]
+async fn should_assert_subgraph_calls() {
+    let supergraph = common::supergraph();
+    let product = common::product_graph();
+    let review = MockGraphBuilder::new("review").fallback(serde_json::json!({
+        "data": { "_entities": [{ "reviews": [] }] }
+    }));
+
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(product)
+        .subgraph(review)
+        .build()
+        .await
+        .expect("to create harness");
+
+    let response = harness.query("query Query { topProducts { name, reviews { body } } }").await;
+    assert_eq!(
+        response,
+        serde_json::json!({ "data": { "topProducts": [{ "name": "Trilby", "reviews": [] }] } })
+    );
+
+    let calls = harness.calls();
+    calls.assert_order(&["product", "review"]);
+    calls.assert_not_called("user");
+    calls.assert_representations(
+        "review",
+        &serde_json::json!([{ "__typename": "Product", "upc": "top-1" }]),
+    );
+}
+
+#[tokio::test]
+async fn should_plan_without_fetching() {
+    let supergraph = common::supergraph();
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(MockGraphBuilder::new("user"))
+        .subgraph(MockGraphBuilder::new("product"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let request = common::request("query Query { topProducts { name, reviews { body } } }", &[]);
+    let plan = harness.router().plan(request).await.expect("to plan request");
+
+    assert_eq!(plan.fetches(), ["product", "review"]);
+    harness.calls().assert_call_count("product", 0);
+    harness.calls().assert_call_count("review", 0);
+}
+
+#[tokio::test]
+async fn should_advance_test_clock() {
+    let clock = TestClock::new();
+    let started = clock.now();
+    let mut sleep = tokio::spawn(clock.sleep(Duration::from_secs(10)));
+
+    clock.advance(Duration::from_secs(5));
+    let pending = tokio::time::timeout(Duration::from_millis(10), &mut sleep).await;
+    assert!(pending.is_err());
+
+    clock.advance(Duration::from_secs(5));
+    sleep.await.expect("sleep to complete");
+    assert_eq!(clock.now() - started, Duration::from_secs(10));
+    assert_eq!(clock.system_time(), std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(10));
+
+    let ids = SequentialIds::new("test");
+    assert_eq!(ids.generate(), "test-0");
+    assert_eq!(ids.generate(), "test-1");
+}
+
+#[tokio::test]
+async fn should_compare_golden_responses() {
+    let supergraph = common::supergraph();
+    let user = MockGraphBuilder::new("user").on_query("me", serde_json::json!({
+        "data": { "me": { "id": "1234", "username": "Me" } }
+    }));
+    let mut harness = RouterTestHarness::builder(supergraph)
+        .subgraph(user)
+        .subgraph(MockGraphBuilder::new("product"))
+        .subgraph(MockGraphBuilder::new("review"))
+        .build()
+        .await
+        .expect("to create harness");
+
+    let dir = std::env::temp_dir().join(format!("graphql-router-golden-{}", std::process::id()));
+    let suite = GoldenSuite::new(&dir)
+        .update(false)
+        .operation("me", WarmupOperation::new("query Me { me { username id } }"))
+        .mask_path("/data/me/id");
+
+    suite.run(harness.router()).await.expect("to write golden files");
+    let golden = std::fs::read_to_string(dir.join("me.json")).expect("to read golden file");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&golden).expect("valid golden file"),
+        serde_json::json!({ "data": { "me": { "id": "<masked>", "username": "Me" } } })
+    );
+    suite.run(harness.router()).await.expect("to match golden files");
+
+    std::fs::write(dir.join("me.json"), r#"{"data":{"me":{"id":"<masked>","username":"You"}}}"#).expect("to write");
+    match suite.run(harness.router()).await {
+        Err(GoldenError::Mismatch(mismatches)) => assert_eq!(mismatches[0].name, "me"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}